@@ -0,0 +1,9 @@
+fn main() {
+  // Generates the gRPC service from its published definition. The output is not formatted, so
+  // that building does not depend on rustfmt being installed.
+  tonic_build::configure()
+    .format(false)
+    .build_client(false)
+    .compile(&["proto/crawler.proto"], &["proto"])
+    .unwrap();
+}
@@ -0,0 +1,87 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// The window the budget of pages is counted over.
+const WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Limits how many pages the crawler fetches from a single domain over the last 24 hours,
+/// however many crawls of the domain are submitted meanwhile.
+pub struct Budget {
+  /// How many pages of a domain may be fetched within the window, zero for no limit.
+  pages_per_day: usize,
+  /// When the pages within the window were fetched, from the oldest one, keyed by the domain.
+  fetches: HashMap<String, VecDeque<Instant>>,
+}
+
+impl Budget {
+  pub fn new(pages_per_day: usize) -> Budget {
+    Budget { pages_per_day, fetches: HashMap::new() }
+  }
+
+  /// How many more pages of given domain may be fetched now. None if there is no limit.
+  pub fn remaining(&mut self, domain: &str, now: Instant) -> Option<usize> {
+    if self.pages_per_day == 0 {
+      return None;
+    }
+
+    let is_expired = |fetched_at: &Instant| now.duration_since(*fetched_at) >= WINDOW;
+    let fetched = match self.fetches.get_mut(domain) {
+      Some(fetches) => {
+        while fetches.front().is_some_and(is_expired) {
+          fetches.pop_front();
+        }
+
+        fetches.len()
+      },
+      None => 0,
+    };
+
+    // Domains with nothing left in the window are forgotten so that the map does not grow with
+    // every domain ever crawled.
+    if fetched == 0 {
+      self.fetches.remove(domain);
+    }
+
+    Some(self.pages_per_day.saturating_sub(fetched))
+  }
+
+  /// Records that given number of pages of given domain are being fetched.
+  pub fn spend(&mut self, domain: &str, pages: usize, now: Instant) {
+    if self.pages_per_day == 0 || pages == 0 {
+      return;
+    }
+
+    let fetches = self.fetches.entry(domain.to_string()).or_default();
+    fetches.extend((0..pages).map(|_| now));
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Budget;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn test_remaining() {
+    let now = Instant::now();
+    let mut budget = Budget::new(10);
+
+    assert_eq!(budget.remaining("github.com", now), Some(10));
+    budget.spend("github.com", 4, now);
+    budget.spend("github.com", 6, now + Duration::from_secs(3600));
+    assert_eq!(budget.remaining("github.com", now + Duration::from_secs(3600)), Some(0));
+    // Other domains have budgets of their own.
+    assert_eq!(budget.remaining("gitlab.com", now), Some(10));
+    // Pages leave the window a day after they were fetched.
+    assert_eq!(budget.remaining("github.com", now + Duration::from_secs(24 * 3600)), Some(4));
+    assert_eq!(budget.remaining("github.com", now + Duration::from_secs(25 * 3600)), Some(10));
+  }
+
+  #[test]
+  fn test_unlimited() {
+    let mut budget = Budget::new(0);
+    budget.spend("github.com", 1_000_000, Instant::now());
+
+    assert_eq!(budget.remaining("github.com", Instant::now()), None);
+  }
+}
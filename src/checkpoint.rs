@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use super::routes;
+use super::jobs::{Job, StopConditions};
+use super::frontier::CrawlOrder;
+
+/// How often a running crawl writes its checkpoint.
+pub const INTERVAL: Duration = Duration::from_secs(10);
+
+/// What is left of a running crawl. Crawls write their checkpoints to disk as they go, so that
+/// the crawls which were interrupted by a restart of the service are resumed once it starts
+/// again, rather than started over or lost.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Checkpoint {
+  /// The url the crawl started from.
+  pub url: String,
+  /// Either `bfs` or `dfs`.
+  pub order: String,
+  pub max_pages: Option<usize>,
+  pub max_seconds: Option<u64>,
+  pub max_consecutive_errors: Option<usize>,
+  /// The selector the links of the crawl are scoped to. Checkpoints written before crawls could
+  /// be scoped have none.
+  #[serde(default)]
+  pub link_scope: Option<String>,
+  /// The urls the crawl is yet to fetch.
+  pub frontier: Vec<String>,
+}
+
+impl Checkpoint {
+  pub fn new(
+    url: &str,
+    order: CrawlOrder,
+    stop: StopConditions,
+    link_scope: Option<String>,
+    frontier: Vec<String>,
+  ) -> Self {
+    Checkpoint {
+      url: url.to_string(),
+      order: order.as_str().to_string(),
+      max_pages: stop.max_pages,
+      max_seconds: stop.max_duration.map(|duration| duration.as_secs()),
+      max_consecutive_errors: stop.max_consecutive_errors,
+      link_scope,
+      frontier,
+    }
+  }
+
+  /// The job which resumes the crawl. Its stop conditions apply to the resumed crawl afresh.
+  pub fn into_job(self) -> Job {
+    let mut job = Job::resuming(self.url, self.frontier);
+    job.order = routes::parse_order(&self.order).unwrap_or_default();
+    job.stop = StopConditions {
+      max_pages: self.max_pages,
+      max_duration: self.max_seconds.map(Duration::from_secs),
+      max_consecutive_errors: self.max_consecutive_errors,
+    };
+    job.link_scope = self.link_scope;
+
+    job
+  }
+}
+
+/// Replaces the checkpoint of given job in given directory. The checkpoint is written next to
+/// the previous one first, so that an interrupted write does not lose the previous one.
+pub fn save(directory: &str, id: usize, checkpoint: &Checkpoint) -> io::Result<()> {
+  fs::create_dir_all(directory)?;
+
+  let path = Path::new(directory).join(format!("{}.json", id));
+  let partial = Path::new(directory).join(format!("{}.json.partial", id));
+  fs::write(&partial, serde_json::to_vec(checkpoint)?)?;
+
+  fs::rename(partial, path)
+}
+
+/// Removes the checkpoint of given job once its crawl stopped.
+pub fn remove(directory: &str, id: usize) -> io::Result<()> {
+  match fs::remove_file(Path::new(directory).join(format!("{}.json", id))) {
+    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+    _ => Ok(()),
+  }
+}
+
+/// Reads and removes every checkpoint left in given directory by the crawls which did not stop,
+/// ordered by the ids of their jobs. Ids start from zero again when the service starts, so the
+/// checkpoints are removed to make room for the ones of the new jobs. Checkpoints which can't be
+/// read are set aside as `<id>.json.corrupt` rather than resumed, and nothing is removed until
+/// all of them have been read.
+pub fn take(directory: &str) -> io::Result<Vec<Checkpoint>> {
+  let entries = match fs::read_dir(directory) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec!()),
+    Err(e) => return Err(e),
+  };
+
+  let mut checkpoints: Vec<(usize, Checkpoint)> = vec!();
+  let mut corrupt: Vec<PathBuf> = vec!();
+  let mut taken: Vec<PathBuf> = vec!();
+
+  for entry in entries {
+    let path = entry?.path();
+    let id: usize = match path.file_name().and_then(|name| name.to_str()) {
+      Some(name) if name.ends_with(".json") => match name.trim_end_matches(".json").parse() {
+        Ok(id) => id,
+        Err(_) => continue,
+      },
+      // Writes interrupted by the restart never replaced their checkpoints.
+      Some(name) if name.ends_with(".json.partial") => {
+        taken.push(path);
+
+        continue;
+      },
+      _ => continue,
+    };
+
+    let checkpoint = fs::read(&path)
+      .and_then(|bytes| serde_json::from_slice(&bytes).map_err(io::Error::from));
+
+    match checkpoint {
+      Ok(checkpoint) => {
+        checkpoints.push((id, checkpoint));
+        taken.push(path);
+      },
+      Err(e) => {
+        println!("[Crawler] Skipping the checkpoint {} as it can't be read: {}", path.display(), e);
+        corrupt.push(path);
+      },
+    }
+  }
+
+  for path in corrupt {
+    let mut aside = path.clone().into_os_string();
+    aside.push(".corrupt");
+    fs::rename(path, aside)?;
+  }
+
+  for path in taken {
+    fs::remove_file(path)?;
+  }
+
+  checkpoints.sort_by_key(|(id, _)| *id);
+
+  Ok(checkpoints.into_iter().map(|(_, checkpoint)| checkpoint).collect())
+}
+
+#[cfg(test)]
+mod test {
+  use super::{remove, save, take, Checkpoint};
+  use super::super::frontier::CrawlOrder;
+  use super::super::jobs::StopConditions;
+  use std::env;
+  use std::fs;
+  use std::path::Path;
+  use std::time::Duration;
+
+  #[test]
+  fn test_take() {
+    let directory = env::temp_dir().join("link-crawler-test-checkpoint");
+    let directory = directory.to_str().unwrap();
+    let stop = StopConditions { max_duration: Some(Duration::from_secs(60)), ..Default::default() };
+    let scope = Some(String::from("main"));
+    let checkpoint = |url: &str| {
+      Checkpoint::new(url, CrawlOrder::DepthFirst, stop, scope.clone(), vec!(format!("{}/a", url)))
+    };
+
+    save(directory, 10, &checkpoint("https://gitlab.com")).unwrap();
+    save(directory, 2, &checkpoint("https://github.com")).unwrap();
+    save(directory, 2, &checkpoint("https://github.com")).unwrap();
+    save(directory, 3, &checkpoint("https://bitbucket.org")).unwrap();
+    fs::write(Path::new(directory).join("5.json"), "{\"url\":").unwrap();
+    // Crawls which stopped leave no checkpoint behind.
+    remove(directory, 3).unwrap();
+    remove(directory, 4).unwrap();
+
+    let checkpoints = take(directory).unwrap();
+    let left: Vec<_> = fs::read_dir(directory).unwrap()
+      .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+      .collect();
+    fs::remove_dir_all(directory).unwrap();
+
+    // The unreadable checkpoint does not hold up the others.
+    assert_eq!(checkpoints, vec!(
+      checkpoint("https://github.com"), checkpoint("https://gitlab.com"),
+    ));
+    assert_eq!(left, vec!("5.json.corrupt"));
+
+    let job = checkpoint("https://github.com").into_job();
+    assert_eq!(job.retried, vec!("https://github.com/a"));
+    assert_eq!(job.order, CrawlOrder::DepthFirst);
+    assert_eq!(job.stop, stop);
+    assert_eq!(job.link_scope, scope);
+  }
+}
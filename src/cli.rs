@@ -0,0 +1,150 @@
+use std::env;
+use serde::Deserialize;
+use serde_json::json;
+use reqwest::{Client, Response};
+
+/// Where the client commands send their requests unless told otherwise.
+const DEFAULT_SERVER: &str = "http://localhost:8000";
+
+/// Describes the commands, printed when the arguments are not understood.
+pub const USAGE: &str = "\
+Usage:
+  link-crawler [serve]
+      Runs the service.
+  link-crawler crawl <url> [--server <url>]
+      Submits a crawl of the url to a running service and prints the id of its job.
+  link-crawler list <domain> [--server <url>]
+      Prints the urls crawled on the domain by a running service in alphabetical order.
+
+The server defaults to the CRAWLER_SERVER environment variable or http://localhost:8000.";
+
+/// What the binary was asked to do on the command line.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+  /// Runs the service.
+  Serve,
+  /// Submits a crawl of given url to the service at given address.
+  Crawl { server: String, url: String },
+  /// Lists the urls of given domain crawled by the service at given address.
+  List { server: String, domain: String },
+}
+
+#[derive(Deserialize)]
+struct JobCreated {
+  id: usize,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+  message: String,
+}
+
+/// Reads the command from the arguments of the binary, without the name of the binary.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+  let mut server = None;
+  let mut positional = vec!();
+  let mut args = args.into_iter();
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--server" => server = Some(args.next().ok_or("The --server option needs a value")?),
+      // Asking for help is reported the same way as invalid arguments, with the usage.
+      "-h" | "--help" => return Err(String::new()),
+      _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+      _ => positional.push(arg),
+    }
+  }
+
+  let server = server
+    .or_else(|| env::var("CRAWLER_SERVER").ok())
+    .unwrap_or_else(|| String::from(DEFAULT_SERVER))
+    .trim_end_matches('/')
+    .to_string();
+
+  let (command, args) = match positional.split_first() {
+    Some((command, args)) => (command.as_str(), args),
+    None => return Ok(Command::Serve),
+  };
+
+  match (command, args) {
+    ("serve", []) => Ok(Command::Serve),
+    ("crawl", [url]) => Ok(Command::Crawl { server, url: url.clone() }),
+    ("list", [domain]) => Ok(Command::List { server, domain: domain.clone() }),
+    ("serve", _) | ("crawl", _) | ("list", _) => {
+      Err(format!("Wrong number of arguments of the {} command", command))
+    },
+    _ => Err(format!("Unknown command {}", command)),
+  }
+}
+
+/// Carries out a client command against a running service and prints its result.
+pub fn run(command: Command) -> Result<(), String> {
+  let client = Client::new();
+
+  match command {
+    Command::Serve => Ok(()),
+    Command::Crawl { server, url } => {
+      let response = client.post(&format!("{}/host", server))
+        .json(&json!({ "url": url }))
+        .send();
+      let job: JobCreated = read(response)?;
+      println!("Submitted job {}, see {}/jobs/{}", job.id, server, job.id);
+
+      Ok(())
+    },
+    Command::List { server, domain } => {
+      let response = client.get(&format!("{}/host/{}/url", server, domain))
+        .header("Accept", "application/json")
+        .send();
+      let mut urls: Vec<String> = read(response)?;
+      urls.sort();
+
+      for url in urls {
+        println!("{}", url);
+      }
+
+      Ok(())
+    },
+  }
+}
+
+/// Parses the body of a successful response, or describes why the request failed.
+fn read<T: for<'de> Deserialize<'de>>(response: reqwest::Result<Response>) -> Result<T, String> {
+  let mut response = response.map_err(|e| format!("The service is not reachable: {}", e))?;
+
+  if !response.status().is_success() {
+    let reason = response.json::<ErrorBody>()
+      .map(|body| body.message)
+      .unwrap_or_else(|_| response.status().to_string());
+
+    return Err(format!("The request failed: {}", reason));
+  }
+
+  response.json().map_err(|e| format!("The response is not valid: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+  use super::{parse, Command};
+
+  fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(parse(args(&[])), Ok(Command::Serve));
+    assert_eq!(parse(args(&["serve"])), Ok(Command::Serve));
+    let crawl = parse(args(&["crawl", "https://github.com", "--server", "http://crawler:80/"]));
+    assert_eq!(crawl, Ok(
+      Command::Crawl { server: "http://crawler:80".into(), url: "https://github.com".into() },
+    ));
+    assert_eq!(parse(args(&["--server", "http://crawler", "list", "github.com"])), Ok(
+      Command::List { server: "http://crawler".into(), domain: "github.com".into() },
+    ));
+    assert!(parse(args(&["crawl"])).is_err());
+    assert!(parse(args(&["stop"])).is_err());
+    assert!(parse(args(&["list", "github.com", "--verbose"])).is_err());
+    assert!(parse(args(&["list", "github.com", "--server"])).is_err());
+  }
+}
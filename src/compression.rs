@@ -0,0 +1,73 @@
+use std::io::{Cursor, Write};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use rocket::{Request, Response};
+use rocket::http::{ContentType, Status};
+use rocket::fairing::{Fairing, Info, Kind};
+
+/// Bodies smaller than this are sent as they are, as compressing them saves next to nothing.
+const MIN_COMPRESSED_SIZE: usize = 1024;
+
+/// Compresses text responses, such as url listings, exports and reports, with gzip for clients
+/// which accept it.
+pub struct Gzip;
+
+impl Fairing for Gzip {
+  fn info(&self) -> Info {
+    Info { name: "Gzip compression", kind: Kind::Response }
+  }
+
+  fn on_response(&self, request: &Request, response: &mut Response) {
+    // Whether the response is compressed depends on the request, which caches must know about.
+    response.set_raw_header("Vary", "Accept-Encoding");
+
+    let is_compressible = response.content_type().map(|kind| is_text(&kind)).unwrap_or(false);
+
+    if response.status() != Status::Ok || !is_compressible || !accepts_gzip(request) {
+      return;
+    }
+
+    let body = match response.body_bytes() {
+      Some(body) => body,
+      None => return,
+    };
+
+    if body.len() < MIN_COMPRESSED_SIZE {
+      response.set_sized_body(Cursor::new(body));
+
+      return;
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+    // Writing into memory can't fail, but the original body is sent if it ever did.
+    match encoder.write_all(&body).and_then(|_| encoder.finish()) {
+      Ok(compressed) => {
+        response.set_raw_header("Content-Encoding", "gzip");
+        response.set_sized_body(Cursor::new(compressed));
+      },
+      Err(_) => response.set_sized_body(Cursor::new(body)),
+    }
+  }
+}
+
+/// Checks whether the content type is one of the text formats the service responds with.
+fn is_text(kind: &ContentType) -> bool {
+  kind.is_json() || kind.is_csv() || kind.is_plain() || kind.is_xml() || kind.is_html()
+}
+
+/// Checks whether the Accept-Encoding header of the request lists gzip without ruling it out
+/// with a zero quality.
+fn accepts_gzip(request: &Request) -> bool {
+  request.headers().get("Accept-Encoding")
+    .flat_map(|header| header.split(','))
+    .any(|encoding| {
+      let mut parts = encoding.split(';').map(str::trim);
+      let name = parts.next().unwrap_or_default();
+      let is_excluded = parts.any(|param| {
+        param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0)
+      });
+
+      (name.eq_ignore_ascii_case("gzip") || name == "*") && !is_excluded
+    })
+}
@@ -0,0 +1,284 @@
+use std::net::SocketAddr;
+use super::window::CrawlWindow;
+
+/// Redirect chains with more hops than this are flagged in the redirect report.
+const DEFAULT_MAX_REDIRECT_HOPS: usize = 3;
+
+/// Certificates expiring in fewer days than this are reported as expiring soon.
+const DEFAULT_CERT_EXPIRY_WARNING_DAYS: usize = 30;
+
+/// A single crawl stops once it has downloaded more bytes of page bodies than this.
+const DEFAULT_MAX_BYTES_PER_JOB: usize = 50 * 1024 * 1024;
+
+/// A single crawl may be asked to fetch at most this many pages.
+const DEFAULT_MAX_PAGES_PER_JOB: usize = 10_000;
+
+/// A single crawl fetches this many pages in parallel.
+const DEFAULT_CRAWL_CONCURRENCY: usize = 4;
+
+/// How many connections to the PostgreSQL storage are kept open at most.
+const DEFAULT_POSTGRES_POOL_SIZE: usize = 4;
+
+/// Service settings. They are read from the extras of the Rocket configuration, therefore can be
+/// set either in `Rocket.toml` or with `ROCKET_` prefixed environment variables.
+#[derive(Clone)]
+pub struct Config {
+  /// How many hops can a redirect chain have before it is reported as too long.
+  pub max_redirect_hops: usize,
+  /// Whether the crawler checks that links pointing to other hosts are not dead.
+  pub check_outbound_links: bool,
+  /// Whether the crawler records images without alt text.
+  pub check_image_alt: bool,
+  /// Whether the crawler leaves out the AMP variants pages declare from the links it follows.
+  pub skip_amp_variants: bool,
+  /// Whether the crawler checks how the sitemap urls it did not reach by following links respond.
+  pub check_sitemap_urls: bool,
+  /// Whether the crawler collects email addresses and phone numbers published on pages.
+  pub extract_contacts: bool,
+  /// The url the service is publicly reachable at, used when linking back to the service. If
+  /// not set, the url is derived from the Host header of the request.
+  pub public_url: Option<String>,
+  /// Hosts or IP addresses the crawler may fetch even though they resolve to loopback, private
+  /// or link-local addresses.
+  pub allowed_internal_hosts: Vec<String>,
+  /// If not empty, only these domains and their subdomains may be crawled.
+  pub allowed_domains: Vec<String>,
+  /// Domains which, together with their subdomains, must never be crawled.
+  pub denied_domains: Vec<String>,
+  /// If not empty, only links matching one of these regular expressions are followed.
+  pub url_include_patterns: Vec<String>,
+  /// Links matching any of these regular expressions are never followed.
+  pub url_exclude_patterns: Vec<String>,
+  /// Directories whose files may be crawled as `file://` urls, see `LocalFetcher`.
+  pub local_directories: Vec<String>,
+  /// Whether the crawler proceeds with crawling hosts presenting invalid TLS certificates.
+  pub accept_invalid_certs: bool,
+  /// Certificates expiring in fewer days than this are reported as expiring soon.
+  pub cert_expiry_warning_days: usize,
+  /// A single crawl stops and is marked as truncated once it has downloaded more bytes of page
+  /// bodies than this.
+  pub max_bytes_per_job: usize,
+  /// Crawls asking for more pages than this are refused.
+  pub max_pages_per_job: usize,
+  /// How many bytes of page bodies per second the crawler may download on average, zero for no
+  /// limit.
+  pub max_bytes_per_second: usize,
+  /// How many pages of a single domain the crawler may fetch within 24 hours across all crawls,
+  /// zero for no limit.
+  pub max_pages_per_domain_per_day: usize,
+  /// Hours of the day domains may be crawled at. Domains without a window may be crawled any
+  /// time.
+  pub crawl_windows: Vec<CrawlWindow>,
+  /// Whether the crawler negotiates HTTP/2 with servers which support it.
+  pub http2: bool,
+  /// How many pages of a single crawl are fetched in parallel.
+  pub crawl_concurrency: usize,
+  /// Every page waits a random number of milliseconds up to this before it is fetched, zero for
+  /// no delay.
+  pub request_jitter_ms: usize,
+  /// Headers sent with every page of every crawl, such as credentials of a site behind a login,
+  /// as pairs of the name and the value.
+  pub request_headers: Vec<(String, String)>,
+  /// Whether the crawler only looks for links on pages, skipping all other details about them.
+  /// Pages are then tokenized instead of building their DOM, which is much cheaper.
+  pub links_only: bool,
+  /// How links are found on pages.
+  pub link_extractor: LinkExtraction,
+  /// The path of the Lua script run with every successfully loaded page of the crawled host to
+  /// decide which of its links are followed and what data is extracted from it, see
+  /// `script::PageScript`.
+  pub page_script: Option<String>,
+  /// Whether links have to point to the same port as the crawled url to be followed, rather
+  /// than just to the same host.
+  pub match_port: bool,
+  /// Which scheme urls of the crawled host are stored with, if http and https variants of a url
+  /// are considered the same page.
+  pub fold_schemes: SchemeFolding,
+  /// Where the urls of each finished crawl are uploaded to, if anywhere.
+  pub export: Option<S3Export>,
+  /// The directory the pages fetched by each crawl are archived in as WARC files, if any.
+  pub warc_directory: Option<String>,
+  /// The directory running crawls write their checkpoints to, if any, see `Checkpoint`.
+  pub checkpoint_directory: Option<String>,
+  /// The directory every response the crawler gets is recorded in, if any, see `Recorder`.
+  pub record_directory: Option<String>,
+  /// The directory the crawler is served the recorded responses from instead of fetching urls,
+  /// if any, see `Replayer`.
+  pub replay_directory: Option<String>,
+  /// The Elasticsearch or OpenSearch index the urls of each finished crawl are indexed in, if
+  /// any.
+  pub elasticsearch: Option<Elasticsearch>,
+  /// The address the gRPC api listens on, e.g. `127.0.0.1:50051`. The gRPC api is disabled
+  /// unless set.
+  pub grpc_address: Option<SocketAddr>,
+  /// The PostgreSQL database the crawled domains are stored in, e.g.
+  /// `postgres://crawler@localhost/crawler`. The domains are kept in memory only unless set.
+  pub postgres_url: Option<String>,
+  /// How many connections to the PostgreSQL database are kept open at most.
+  pub postgres_pool_size: usize,
+}
+
+/// An S3 compatible bucket the results of crawls are exported to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct S3Export {
+  /// The url of the storage service, e.g. `https://s3.eu-west-1.amazonaws.com`.
+  pub endpoint: String,
+  pub bucket: String,
+  pub region: String,
+  pub access_key: String,
+  pub secret_key: String,
+  /// Prepended to the keys of the uploaded objects.
+  pub prefix: String,
+  /// Whether the urls are uploaded as CSV rather than JSON.
+  pub csv: bool,
+}
+
+/// An Elasticsearch or OpenSearch cluster the results of crawls are indexed in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Elasticsearch {
+  /// The url of the cluster, e.g. `http://localhost:9200`.
+  pub url: String,
+  pub index: String,
+  /// The user requests are authenticated as with basic authentication, if any.
+  pub username: Option<String>,
+  pub password: Option<String>,
+}
+
+/// How http and https variants of urls of the crawled host are treated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchemeFolding {
+  /// Both variants are stored and crawled as different urls.
+  Off,
+  /// Both variants are stored and crawled as the https one.
+  Https,
+  /// Both variants are stored and crawled as the http one.
+  Http,
+}
+
+/// Which of the built in extractors finds the links on pages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkExtraction {
+  /// The links are selected from the DOM of the page.
+  Dom,
+  /// The page is tokenized, which finds the same links without building the DOM.
+  Tokenizer,
+}
+
+impl Config {
+  /// Reads the settings from the Rocket configuration, falling back to defaults for missing or
+  /// invalid values.
+  pub fn from_rocket(config: &rocket::Config) -> Config {
+    let links_only = config.get_bool("links_only").unwrap_or(false);
+
+    Config {
+      max_redirect_hops: get_usize(config, "max_redirect_hops", DEFAULT_MAX_REDIRECT_HOPS),
+      check_outbound_links: config.get_bool("check_outbound_links").unwrap_or(false),
+      check_image_alt: config.get_bool("check_image_alt").unwrap_or(false),
+      skip_amp_variants: config.get_bool("skip_amp_variants").unwrap_or(false),
+      check_sitemap_urls: config.get_bool("check_sitemap_urls").unwrap_or(false),
+      extract_contacts: config.get_bool("extract_contacts").unwrap_or(false),
+      public_url: config.get_str("public_url").ok().map(|url| url.trim_end_matches('/').to_string()),
+      allowed_internal_hosts: get_strings(config, "allowed_internal_hosts"),
+      allowed_domains: get_strings(config, "allowed_domains"),
+      denied_domains: get_strings(config, "denied_domains"),
+      // The service does not start with invalid patterns, see `serve`.
+      url_include_patterns: get_strings(config, "url_include_patterns"),
+      url_exclude_patterns: get_strings(config, "url_exclude_patterns"),
+      local_directories: get_strings(config, "local_directories"),
+      accept_invalid_certs: config.get_bool("accept_invalid_certs").unwrap_or(false),
+      cert_expiry_warning_days: get_usize(
+        config, "cert_expiry_warning_days", DEFAULT_CERT_EXPIRY_WARNING_DAYS,
+      ),
+      max_bytes_per_job: get_usize(config, "max_bytes_per_job", DEFAULT_MAX_BYTES_PER_JOB),
+      max_pages_per_job: get_usize(config, "max_pages_per_job", DEFAULT_MAX_PAGES_PER_JOB),
+      max_bytes_per_second: get_usize(config, "max_bytes_per_second", 0),
+      max_pages_per_domain_per_day: get_usize(config, "max_pages_per_domain_per_day", 0),
+      // The service does not start with invalid windows, see `serve`.
+      crawl_windows: CrawlWindow::from_rocket(config).unwrap_or_default(),
+      http2: config.get_bool("http2").unwrap_or(true),
+      crawl_concurrency: get_usize(config, "crawl_concurrency", DEFAULT_CRAWL_CONCURRENCY),
+      request_jitter_ms: get_usize(config, "request_jitter_ms", 0),
+      request_headers: match config.get_table("request_headers") {
+        Ok(headers) => headers.iter()
+          .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.to_string())))
+          .collect(),
+        Err(_) => vec!(),
+      },
+      links_only,
+      // Pages are tokenized in the links only mode unless configured otherwise, as their DOM is
+      // not needed for anything else.
+      link_extractor: match config.get_str("link_extractor") {
+        Ok(extractor) if extractor.eq_ignore_ascii_case("dom") => LinkExtraction::Dom,
+        Ok(extractor) if extractor.eq_ignore_ascii_case("tokenizer") => LinkExtraction::Tokenizer,
+        _ if links_only => LinkExtraction::Tokenizer,
+        _ => LinkExtraction::Dom,
+      },
+      page_script: config.get_str("page_script").ok().map(String::from),
+      match_port: config.get_bool("match_port").unwrap_or(true),
+      fold_schemes: match config.get_str("fold_schemes") {
+        Ok(scheme) if scheme.eq_ignore_ascii_case("https") => SchemeFolding::Https,
+        Ok(scheme) if scheme.eq_ignore_ascii_case("http") => SchemeFolding::Http,
+        _ => SchemeFolding::Off,
+      },
+      export: S3Export::from_rocket(config),
+      warc_directory: config.get_str("warc_directory").ok().map(String::from),
+      checkpoint_directory: config.get_str("checkpoint_directory").ok().map(String::from),
+      record_directory: config.get_str("record_directory").ok().map(String::from),
+      replay_directory: config.get_str("replay_directory").ok().map(String::from),
+      elasticsearch: Elasticsearch::from_rocket(config),
+      grpc_address: config.get_str("grpc_address").ok().and_then(|address| address.parse().ok()),
+      postgres_url: config.get_str("postgres_url").ok().map(String::from),
+      postgres_pool_size: get_usize(config, "postgres_pool_size", DEFAULT_POSTGRES_POOL_SIZE),
+    }
+  }
+}
+
+impl S3Export {
+  /// Reads the bucket settings from the Rocket configuration. Exporting is disabled unless the
+  /// endpoint, the bucket and both keys are set.
+  fn from_rocket(config: &rocket::Config) -> Option<S3Export> {
+    let get = |name: &str| config.get_str(name).ok().map(String::from);
+
+    Some(S3Export {
+      endpoint: get("export_s3_endpoint")?.trim_end_matches('/').to_string(),
+      bucket: get("export_s3_bucket")?,
+      region: get("export_s3_region").unwrap_or_else(|| String::from("us-east-1")),
+      access_key: get("export_s3_access_key")?,
+      secret_key: get("export_s3_secret_key")?,
+      prefix: get("export_s3_prefix").unwrap_or_default(),
+      csv: config.get_str("export_format").map(|format| format.eq_ignore_ascii_case("csv"))
+        .unwrap_or(false),
+    })
+  }
+}
+
+impl Elasticsearch {
+  /// Reads the cluster settings from the Rocket configuration. Indexing is disabled unless the
+  /// url of the cluster is set.
+  fn from_rocket(config: &rocket::Config) -> Option<Elasticsearch> {
+    let get = |name: &str| config.get_str(name).ok().map(String::from);
+
+    Some(Elasticsearch {
+      url: get("elasticsearch_url")?.trim_end_matches('/').to_string(),
+      index: get("elasticsearch_index").unwrap_or_else(|| String::from("crawler")),
+      username: get("elasticsearch_username"),
+      password: get("elasticsearch_password"),
+    })
+  }
+}
+
+/// Reads a non-negative integer from the configuration extras.
+fn get_usize(config: &rocket::Config, name: &str, default: usize) -> usize {
+  match config.get_int(name) {
+    Ok(value) if value >= 0 => value as usize,
+    _ => default,
+  }
+}
+
+/// Reads an array of strings from the configuration extras, skipping values of other types.
+fn get_strings(config: &rocket::Config, name: &str) -> Vec<String> {
+  match config.get_slice(name) {
+    Ok(values) => values.iter().filter_map(|value| value.as_str()).map(String::from).collect(),
+    Err(_) => vec!(),
+  }
+}
@@ -1,20 +1,191 @@
 use url::Url;
+use chrono::{DateTime, Utc};
+use super::jobs;
+use super::fetch::{self, Fetcher, HttpFetcher};
+use super::extract::{self, DomExtractor, LinkExtractor, TokenExtractor};
+use super::export;
+use super::warc::{self, Warc};
+use super::elasticsearch;
+use super::webhooks::{Notifier, Webhooks};
+use super::storage::{self, Changes};
+use super::sitemap;
+use super::robots;
+use super::window;
 use super::Database;
+use super::config::{Config, LinkExtraction, SchemeFolding};
+use super::budget::Budget;
+use super::throttle::{self, AdaptiveRate, Throttle, Throttled};
+use super::frontier::{CrawlOrder, Frontier};
+use super::checkpoint::{self, Checkpoint};
+use super::replay::{Recorder, Replayer};
+use super::local::{self, LocalFetcher};
+use super::hooks::{Hooks, PageRequest};
+use super::traps::Traps;
+use super::script::{PageScript, ScriptInput};
+use super::tls::Certificate;
+use super::jobs::{JobState, Jobs, StopConditions, StopReason, Timing};
+use super::database::{Page, Session, SitemapCheck, UrlRecord, UrlState};
+use reqwest::Response;
 use scraper::{Html, Selector};
-use std::sync::mpsc::Receiver;
-use std::collections::HashSet;
+use std::thread;
+use std::sync::Mutex;
+use reqwest::{Client, StatusCode};
+use std::time::{Duration, Instant};
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER};
+use std::io::{self, Read};
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError};
+use std::collections::{HashMap, HashSet};
 
 /// Per one request, the crawler will visit at most N websites.
 const MAX_LINKS_CRAWLED_PER_REQUEST: u32 = 16;
 
-/// Crawler assumes its own thread as it blocks. It listen to consumer channel for url.
+/// A url the host responds to with 429 Too Many Requests is retried at most this many times.
+const MAX_RATE_LIMITED_RETRIES: usize = 3;
+
+/// How long the crawl pauses after a 429 response without a usable Retry-After header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// The crawl never pauses for longer than this, whatever the Retry-After header asks for, as
+/// other jobs wait for it meanwhile.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Per one request, the crawler will check at most N sitemap urls it did not fetch.
+const MAX_SITEMAP_CHECKS: usize = 100;
+
+/// The parts of the service a crawl works with.
+#[derive(Clone, Copy)]
+struct Context<'a> {
+  db: &'a Database,
+  jobs: &'a Jobs,
+  config: &'a Config,
+  fetcher: &'a dyn Fetcher,
+  extractor: &'a dyn LinkExtractor,
+  notifier: &'a Notifier,
+  hooks: &'a Hooks,
+  script: Option<&'a PageScript>,
+}
+
+/// What the parallel fetches of the urls of a crawl share, see `crawl`.
+#[derive(Clone, Copy)]
+struct Shared<'a> {
+  throttle: &'a Mutex<Throttle>,
+  /// Outbound links already checked by the crawl and whether they responded successfully.
+  checked_links: &'a Mutex<HashMap<String, bool>>,
+  warc: Option<&'a Mutex<Warc>>,
+  /// The extraction rules of the host as the names of the fields and their selectors.
+  rules: &'a [(String, Selector)],
+  /// Only the links within the elements it selects are followed, if set.
+  link_scope: Option<&'a Selector>,
+  /// The url the crawl started from.
+  root: &'a Url,
+}
+
+/// What fetching a single url of a crawl yielded.
+enum Fetched {
+  /// The page with the unique urls of the host found on it and the time spent on it.
+  Page(Page, HashSet<String>, Timing),
+  /// The host asked the crawler to slow down. Holds the page with the 429 status, the time
+  /// spent on it and how long the host asked the crawler to wait.
+  RateLimited(Page, Timing, Duration),
+  /// A hook vetoed the url, so it was not fetched.
+  Vetoed,
+}
+
+/// Crawler assumes its own thread as it blocks. It listen to consumer channel for ids of jobs.
 /// It checks the domain and makes a request to it. It collects recursivelly all urls it can
 /// find that belong to the same hostname. These urls are then stored in a HashSet and commited
-/// to the database.
-pub fn listen(db: Database, consumer: Receiver<String>) {
+/// to the database. Progress of the crawl is recorded in the job. Given hooks run around every
+/// page of every crawl.
+pub fn listen(
+  db: Database,
+  jobs: Jobs,
+  webhooks: Webhooks,
+  hooks: Hooks,
+  changes: Changes,
+  config: Config,
+  consumer: Receiver<usize>,
+) {
+  // Jobs are crawled one after another, so a single throttle shared by the parallel fetches of
+  // their rounds limits the bandwidth of the whole service.
+  let throttle = Mutex::new(Throttle::new(config.max_bytes_per_second));
+  // For the same reason, a single budget limits the pages fetched from each domain.
+  let mut budget = Budget::new(config.max_pages_per_domain_per_day);
+  // The client is shared by all jobs so that connections are reused.
+  let http = match HttpFetcher::new(&config) {
+    Ok(fetcher) => LocalFetcher::new(fetcher, &config),
+    Err(e) => {
+      println!("[Crawler] Error during http client creation: {:?}", e);
+
+      return;
+    },
+  };
+  // Replayed crawls don't touch the network, while recorded ones go through the client as usual.
+  let fetcher: Box<dyn Fetcher> = match (&config.replay_directory, &config.record_directory) {
+    (Some(directory), _) => Box::new(Replayer::new(directory)),
+    (None, Some(directory)) => Box::new(Recorder::new(http, directory)),
+    (None, None) => Box::new(http),
+  };
+
+  let notifier = match Notifier::new(&config, webhooks) {
+    Ok(notifier) => notifier,
+    Err(e) => {
+      println!("[Crawler] Error during webhook client creation: {:?}", e);
+
+      return;
+    },
+  };
+
+  // The script was checked when the service started, but could have changed since.
+  let script = match config.page_script.as_ref().map(|path| PageScript::load(path)) {
+    Some(Ok(script)) => Some(script),
+    Some(Err(e)) => {
+      println!("[Crawler] {}", e);
+
+      return;
+    },
+    None => None,
+  };
+
+  // Uploads and indexing go to services the operator configured, so they are not subject to the
+  // restrictions of the fetcher.
+  let client = Client::new();
+  let context = Context {
+    db: &db,
+    jobs: &jobs,
+    config: &config,
+    fetcher: &*fetcher,
+    extractor: match config.link_extractor {
+      LinkExtraction::Dom => &DomExtractor,
+      LinkExtraction::Tokenizer => &TokenExtractor,
+    },
+    notifier: &notifier,
+    hooks: &hooks,
+    script: script.as_ref(),
+  };
+
+  // Jobs of domains outside of their crawl windows wait here until the windows open, so that they
+  // don't hold up the jobs of other domains.
+  let mut waiting: Vec<(usize, DateTime<Utc>)> = vec!();
+
   loop {
-    // Waits for a message to be sent down the channel.
-    let message = consumer.recv();
+    let now = Utc::now();
+    let message = match waiting.iter().position(|(_, opens_at)| *opens_at <= now) {
+      // Jobs whose window opened go before the new ones.
+      Some(index) => Ok(waiting.remove(index).0),
+      // Waits for a message to be sent down the channel, but only until the next window opens.
+      None => match waiting.iter().map(|(_, opens_at)| *opens_at).min() {
+        Some(opens_at) => {
+          let timeout = (opens_at - now).to_std().unwrap_or_default();
+
+          match consumer.recv_timeout(timeout) {
+            Ok(id) => Ok(id),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => Err(RecvError),
+          }
+        },
+        None => consumer.recv(),
+      },
+    };
 
     if message.is_err() {
       // Prints out the error and awaits next message.
@@ -23,124 +194,1669 @@ pub fn listen(db: Database, consumer: Receiver<String>) {
       continue;
     }
 
-    // Unwrap here is safe as we have just checked for error. We can ignore the error given
-    // by parsing as a malformed url is a user, not server error.
-    match Url::parse(&message.unwrap()).ok().filter(|url| url.has_host()) {
-      Some(url) => {
-        // Unwrap here is safe as we have filtered `has_host` in match statement.
-        crawl_urls(&db, url.as_str().to_string(), url.host_str().unwrap());
+    // Unwrap here is safe as we have just checked for error.
+    let id = message.unwrap();
+
+    // TODO: Error handling the mutex.
+    let host = jobs.lock().unwrap().get(id)
+      .and_then(|job| Url::parse(&job.url).ok())
+      .and_then(|url| local::host(&config, &url));
+    let opens_at = host.and_then(|host| window::opens_at(&config.crawl_windows, &host, Utc::now()));
+
+    if let Some(opens_at) = opens_at {
+      println!(
+        "[Crawler] Job {} waits for the crawl window opening at {}", id, opens_at.to_rfc3339(),
+      );
+      waiting.push((id, opens_at));
+
+      continue;
+    }
+    let mut url = String::new();
+    let mut guessed_scheme = false;
+    let mut dry_run = false;
+    jobs::update(&jobs, id, |job| {
+      job.state = JobState::Running;
+      job.started_at = Some(Utc::now());
+      url = job.url.clone();
+      guessed_scheme = job.guessed_scheme;
+      dry_run = job.dry_run;
+    });
+
+    // A malformed url is a user, not server error, therefore the job is just marked as failed.
+    let url = Url::parse(&url).ok().filter(|url| local::host(&config, url).is_some());
+    // Only crawls which got to a host are kept in its history.
+    let host = url.as_ref().and_then(|url| local::host(&config, url));
+    // Dry runs work with a copy of what is known about the host, so that they skip the same urls
+    // a crawl would, and the copy is thrown away once they stop.
+    let scratch: Database = Default::default();
+    let context = match (&host, dry_run) {
+      (Some(host), true) => {
+        // TODO: Error handling the mutex.
+        if let Some(domain) = db.lock().unwrap().get(host) {
+          scratch.lock().unwrap().insert(host.clone(), domain.clone());
+        }
+
+        Context { db: &scratch, ..context }
+      },
+      _ => context,
+    };
+    let (found, error) = match url {
+      Some(mut url) => {
+        // Bare domains were assumed to be served over https, which not every host does.
+        if guessed_scheme && fetcher.status(url.as_str()).is_none() {
+          println!("[Crawler] Falling back to http as {} does not respond over https", url);
+          // Switching between the two special schemes always succeeds.
+          let _ = url.set_scheme("http");
+          jobs::update(&jobs, id, |job| job.url = url.to_string());
+        }
+
+        match run(context, &throttle, &mut budget, id, url) {
+          Ok(found) => (found, None),
+          Err(error) => (HashMap::new(), Some(error)),
+        }
       },
-      None => continue,
+      None => (HashMap::new(), Some(String::from("The url is malformed"))),
+    };
+
+    let is_finished = error.is_none();
+    let finished_at = Utc::now();
+    jobs::update(&jobs, id, |job| {
+      job.state = if is_finished { JobState::Finished } else { JobState::Failed };
+      job.finished_at = Some(finished_at);
+      job.error = error;
+    });
+
+    if let (Some(host), false) = (host, dry_run) {
+      insert_session(&db, &jobs, id, &host, found);
+      storage::changed(&changes, &host);
+
+      // Only crawls which finished have results worth exporting.
+      if let (Some(export), true) = (&config.export, is_finished) {
+        if let Err(e) = export::upload(&client, export, &db, &host, id, finished_at) {
+          println!("[Crawler] Error during export of {}: {}", host, e);
+        }
+      }
+
+      if let (Some(search), true) = (&config.elasticsearch, is_finished) {
+        if let Err(e) = elasticsearch::index(&client, search, &db, &host, id) {
+          println!("[Crawler] Error during indexing of {}: {}", host, e);
+        }
+      }
     }
   }
 }
 
+/// Carries out the crawl of a job. Returns the urls the crawl found, see `crawl_urls`, or an
+/// error if the host could not be crawled at all.
+fn run(
+  context: Context,
+  throttle: &Mutex<Throttle>,
+  budget: &mut Budget,
+  id: usize,
+  url: Url,
+) -> Result<HashMap<String, Option<u16>>, String> {
+  let Context { db, jobs, config, fetcher, .. } = context;
+  // Unwrap here is safe as the url was checked to have a host.
+  let domain = local::host(config, &url).unwrap();
+  let host = domain.as_str();
+  let mut timing = Timing::default();
+
+  // Inspects the certificate of https hosts. Unless configured otherwise, hosts with invalid
+  // certificates are not crawled.
+  let started_at = Instant::now();
+  let certificate = fetcher.certificate(&url);
+  timing.fetch += started_at.elapsed();
+
+  if let Some(certificate) = certificate {
+    let is_valid = certificate.is_valid();
+    insert_certificate(db, host, certificate);
+
+    if !is_valid && !config.accept_invalid_certs {
+      jobs::update(jobs, id, |job| job.timing.add(timing));
+      println!("[Crawler] Not crawling {} as it presents an invalid certificate", host);
+
+      return Err(String::from("The host presents an invalid certificate"));
+    }
+  }
+
+  // Remembers the urls the host lists in its sitemap so that they can be later compared against
+  // the urls reachable by following links.
+  let started_at = Instant::now();
+  let sitemap = sitemap::fetch(fetcher, &url);
+  timing.fetch += started_at.elapsed();
+
+  if let Some(sitemap) = sitemap {
+    // Sitemap urls are folded the same way as the crawled ones so that the two can be compared.
+    let sitemap = sitemap.iter()
+      .filter_map(|url| Url::parse(url).ok())
+      .map(|url| fold_scheme(config, url).as_str().to_string())
+      .collect();
+    let started_at = Instant::now();
+    insert_sitemap(db, host, sitemap);
+    timing.insert += started_at.elapsed();
+  }
+
+  jobs::update(jobs, id, |job| job.timing.add(timing));
+
+  let found = crawl_urls(context, throttle, budget, id, &url);
+
+  if config.check_sitemap_urls {
+    let started_at = Instant::now();
+    check_sitemap(context, host);
+    let timing = Timing { fetch: started_at.elapsed(), ..Default::default() };
+    jobs::update(jobs, id, |job| job.timing.add(timing));
+  }
+
+  insert_crawled_at(db, host, Utc::now());
+
+  Ok(found)
+}
+
 /// Crawls given url and finds all link that are of the same hostname. It then visits the links
-/// looking for move unique links. Once it drains all usable links on given hostname, it stops
-/// crawling.
-fn crawl_urls(master: &Database, url: String, host: &str) {
+/// looking for move unique links. Once it drains all usable links on given hostname or runs out
+/// of the job's byte budget, it stops crawling. Urls are fetched in rounds of several parallel
+/// requests, the most valuable urls first, see `Frontier`.
+///
+/// When the host responds with 429 Too Many Requests, the url is put back into the queue, the
+/// crawl pauses for as long as the host asks and the following rounds fetch half as many urls
+/// in parallel. The rounds also slow down when the host responds with server errors or much
+/// slower than before, and speed back up once it recovers, see `AdaptiveRate`.
+///
+/// The crawl also stops once the domain runs out of its daily budget of pages, which is shared
+/// by all crawls of the domain, once its crawl window closes or once it meets one of the stop
+/// conditions of its job. Why it stopped is recorded in the job.
+///
+/// Returns every url the crawl found with the status of its page if the crawl fetched it.
+fn crawl_urls(
+  context: Context,
+  throttle: &Mutex<Throttle>,
+  budget: &mut Budget,
+  id: usize,
+  url: &Url,
+) -> HashMap<String, Option<u16>> {
+  let Context { db: master, jobs, config, fetcher, .. } = context;
+  // Unwrap here is safe as the url was checked to have a host before the job was run.
+  let domain = local::host(config, url).unwrap();
+  let host = domain.as_str();
+  let root = url;
   let mut counter: u32 = 0;
-  let mut queue: Vec<String> = vec!(url);
+  let mut bytes_downloaded: usize = 0;
+  // Retried urls are fetched again on their own, the url is then just one of them.
+  // TODO: Error handling the mutex.
+  let (retried, stale_after_hours, order, stop, dry_run, link_scope) = jobs.lock().unwrap().get(id)
+    .map(|job| {
+      let link_scope = job.link_scope.clone();
+
+      (job.retried.clone(), job.stale_after_hours, job.order, job.stop, job.dry_run, link_scope)
+    })
+    .unwrap_or((vec!(), None, CrawlOrder::BreadthFirst, StopConditions::default(), false, None));
+  // The scope was checked when the job was submitted.
+  let scope_selector = link_scope.as_deref().and_then(|scope| Selector::parse(scope).ok());
+  // The extraction rules of the host are compiled once for all of its pages. Their selectors were
+  // checked when they were set.
+  // TODO: Error handling the mutex.
+  let rules: Vec<(String, Selector)> = master.lock().unwrap().get(host)
+    .map(|domain| domain.extraction_rules.iter()
+      .filter_map(|(selector, field)| Some((field.clone(), Selector::parse(selector).ok()?)))
+      .collect())
+    .unwrap_or_default();
+  let max_pages = stop.max_pages
+    .map_or(MAX_LINKS_CRAWLED_PER_REQUEST, |pages| pages.min(u32::MAX as usize) as u32);
+  let crawl_started_at = Instant::now();
+  let seed = if retried.is_empty() {
+    Some(fold_scheme(config, url.clone()).as_str().to_string())
+  } else {
+    None
+  };
+  let queued: Vec<String> = match stale_after_hours {
+    // Recrawls fetch the known urls which are not fresh, skipping the fresh ones. Urls found for
+    // the first time are fetched as always.
+    Some(hours) => {
+      let fresh_since = Utc::now()
+        .checked_sub_signed(chrono::Duration::hours(hours.min(i32::MAX as u64) as i64));
+
+      stale_urls(master, host, seed.as_deref(), fresh_since)
+    },
+    None => seed.iter().cloned().chain(retried).collect(),
+  };
+  let mut queue = Frontier::new(order);
+  for (url, depth) in depths(master, host, seed.as_deref(), queued) {
+    queue.push(url, depth);
+  }
+  // Outbound links are often repeated on every page, e.g. in footers, so each of them is
+  // checked only once per crawl.
+  let checked_links: Mutex<HashMap<String, bool>> = Mutex::new(HashMap::new());
+  // Fetched pages are archived if enabled, except by dry runs. The crawl goes on without archiving
+  // if the archive can't be created.
+  let warc = config.warc_directory.as_ref().filter(|_| !dry_run).and_then(|directory| {
+    match Warc::create(directory, host, id, Utc::now()) {
+      Ok(warc) => {
+        println!("[Crawler] Archiving crawl of {} to {}", host, warc.path().display());
+
+        Some(Mutex::new(warc))
+      },
+      Err(e) => {
+        println!("[Crawler] Error during creation of the WARC file of {}: {}", host, e);
+
+        None
+      },
+    }
+  });
+  // Hosts which ask crawlers to wait between requests in their robots.txt get one page at a time,
+  // at least that long after the previous one, in place of the parallel rounds.
+  let crawl_delay = if url.scheme() == "file" {
+    None
+  } else {
+    robots::fetch_crawl_delay(fetcher, root)
+  };
+  if let Some(delay) = crawl_delay {
+    println!("[Crawler] Waiting {:?} between pages of {} as its robots.txt asks", delay, host);
+    jobs::update(jobs, id, |job| job.crawl_delay = Some(delay));
+  }
+  // Fetches as many pages in parallel as configured, unless the host struggles.
+  let mut rate = AdaptiveRate::new(crawl_delay.map_or(config.crawl_concurrency, |_| 1));
+  // How many times each url was rate limited.
+  let mut retries: HashMap<String, usize> = HashMap::new();
+  let mut paused_until: Option<Instant> = None;
+  let mut found: HashMap<String, Option<u16>> = HashMap::new();
+  // How many pages in a row did not respond or responded with a server error.
+  let mut consecutive_errors = 0;
+  let mut stop_reason = StopReason::FrontierExhausted;
+  let mut checkpointed_at: Option<Instant> = None;
+  // Branches which seem to generate urls without end stop being expanded.
+  let mut traps = Traps::default();
 
   loop {
-    counter += 1;
+    let round_size = (rate.concurrency() as u32).min(max_pages - counter);
+    // Dry runs don't spend the budget, so they count the pages they would have spent themselves.
+    let remaining = budget.remaining(host, Instant::now())
+      .map(|left| if dry_run { left.saturating_sub(counter as usize) } else { left });
 
-    if queue.len() == 0 || counter > MAX_LINKS_CRAWLED_PER_REQUEST {
+    if queue.is_empty() {
       break;
     }
 
-    // Unwrap is safe here as we just checked for the length.
-    if let Some(crawled_urls) = crawl(host, queue.pop().unwrap()) {
-      // Appends all unique urls found on given site.
-      queue.append(
-        &mut insert_unique_urls(master, crawled_urls, host)
-      );
+    if round_size == 0 {
+      stop_reason = StopReason::MaxPages;
+
+      break;
     }
 
-  }
-}
+    if remaining == Some(0) {
+      println!("[Crawler] Stopping crawl of {} as it used up the daily budget of pages", host);
+      jobs::update(jobs, id, |job| job.truncated = true);
+      stop_reason = StopReason::DailyBudget;
 
-/// Crawls all urls on given website and filters out the ones not belonging to given
-/// host name. HashSet also makes sure all returned urls are unique.
-fn crawl(host: &str, url: String) -> Option<HashSet<String>> {
-  let mut req = reqwest::get(&url).ok()?;
-  // Used to fiddle around with the paths. If a relative path is identified, this
-  // struct gets updated to contain that path and then inserted into the set.
-  let mut url_parsed = Url::parse(&url).ok()?;
+      break;
+    }
 
-  if !req.status().is_success() {
-    return None;
-  }
+    if window::opens_at(&config.crawl_windows, host, Utc::now()).is_some() {
+      println!("[Crawler] Stopping crawl of {} as its crawl window closed", host);
+      jobs::update(jobs, id, |job| job.truncated = true);
+      stop_reason = StopReason::CrawlWindow;
+
+      break;
+    }
+
+    if stop.max_duration.is_some_and(|max_duration| crawl_started_at.elapsed() >= max_duration) {
+      println!("[Crawler] Stopping crawl of {} as it ran out of time", host);
+      stop_reason = StopReason::MaxDuration;
+
+      break;
+    }
+
+    let round_size = remaining.map_or(round_size, |remaining| round_size.min(remaining as u32));
+
+    if let Some(paused_until) = paused_until.take() {
+      let now = Instant::now();
+
+      if paused_until > now {
+        println!("[Crawler] Pausing crawl of {} as it is rate limited", host);
+        thread::sleep(paused_until - now);
+      }
+    }
+
+    // The crawl delay only applies between pages, and the host may still slow the crawl down more.
+    let delay = match crawl_delay {
+      Some(crawl_delay) if counter > 0 => crawl_delay.max(rate.delay()),
+      _ => rate.delay(),
+    };
+    if delay > Duration::from_secs(0) {
+      thread::sleep(delay);
+    }
+
+    let round = queue.pop(round_size as usize);
+    counter += round.len() as u32;
+    if !dry_run {
+      budget.spend(host, round.len(), Instant::now());
+    }
 
-  let body = req.text().ok()?;
-  let dom = Html::parse_document(&body);
-  let link_selector = Selector::parse("a").ok()?;
+    // The bodies of a round are downloaded at the same time, so each of them may use only its
+    // share of what is left of the budget.
+    let bytes_left = config.max_bytes_per_job.saturating_sub(bytes_downloaded);
+    let bytes_per_page = bytes_left / round.len().max(1);
+    let shared = Shared {
+      throttle,
+      checked_links: &checked_links,
+      warc: warc.as_ref(),
+      rules: &rules,
+      link_scope: scope_selector.as_ref(),
+      root,
+    };
+    let results = thread::scope(|scope| {
+      let handles: Vec<_> = round.into_iter()
+        .map(|(url, depth)| scope.spawn(move || {
+          // The pages of a round don't go out at the same time, nor do the rounds come at
+          // regular intervals.
+          let jitter = throttle::jitter(Duration::from_millis(config.request_jitter_ms as u64));
+          if jitter > Duration::from_secs(0) {
+            thread::sleep(jitter);
+          }
+
+          let result = crawl(context, shared, url.clone(), bytes_per_page);
+
+          (url, depth, result)
+        }))
+        .collect();
+
+      // A panicking fetch loses only its own page.
+      handles.into_iter().filter_map(|handle| handle.join().ok()).collect::<Vec<_>>()
+    });
 
-  // Finds all links in the DOM and filters them based on host name.
-  let mut urls: HashSet<String> = dom.select(&link_selector)
-    .filter_map(|node| {
-      let link = node.value().attr("href")?.to_string();
+    let mut crawled: Vec<(String, Page, HashSet<String>)> = vec!();
+    let mut timing = Timing::default();
+    let mut is_truncated = false;
+    let mut pages_failed = 0;
+    let mut unreachable: Vec<String> = vec!();
+    let mut vetoed: Vec<String> = vec!();
+    // How the host coped with the round, see `AdaptiveRate::record`.
+    let mut latencies: Vec<u64> = vec!();
+    let mut errors = 0;
+    let mut is_rate_limited = false;
 
-      // If the URL couldn't be parsed, it was most likely a relative href.
-      match Url::parse(&link) {
-        Ok(link_parsed) => {
-          // Checks the hostname to ensure the links are from a single domain.
-          if *host != link_parsed.host_str()?.to_string() {
-            None
+    for (url, depth, result) in results {
+      let (page, crawled_urls, page_timing) = match result {
+        Some(Fetched::Page(page, crawled_urls, page_timing)) => (page, crawled_urls, page_timing),
+        Some(Fetched::RateLimited(page, page_timing, retry_after)) => {
+          let attempts = retries.entry(url.clone()).or_insert(0);
+          *attempts += 1;
+
+          // Once out of retries, the page is stored with its 429 status.
+          if *attempts > MAX_RATE_LIMITED_RETRIES {
+            (page, HashSet::new(), page_timing)
           } else {
-            Some(link_parsed.as_str().to_string())
+            let resume_at = Instant::now() + retry_after.min(MAX_RETRY_AFTER);
+            paused_until = Some(paused_until.map_or(resume_at, |paused| paused.max(resume_at)));
+            rate.slow_down();
+            is_rate_limited = true;
+            timing.add(page_timing);
+            // Retrying the url does not count against the limit of crawled urls.
+            counter -= 1;
+            queue.push(url, depth);
+
+            continue;
           }
         },
-        Err(_) => {
-          // The link does not contain hostname (relative href) points to the same
-          // domain and we don't have to check for origin.
-          url_parsed.set_path(&link);
+        // Urls which were never fetched don't count against the limit of crawled urls.
+        Some(Fetched::Vetoed) => {
+          counter -= 1;
+          vetoed.push(url);
+
+          continue;
+        },
+        // The url did not respond at all.
+        None => {
+          pages_failed += 1;
+          errors += 1;
+          consecutive_errors += 1;
+          unreachable.push(url);
+
+          continue;
+        },
+      };
+
+      if page.status.is_some_and(|status| status >= 400) {
+        pages_failed += 1;
+      }
+
+      if page.status.is_some_and(|status| status >= 500) {
+        errors += 1;
+        consecutive_errors += 1;
+      } else {
+        consecutive_errors = 0;
+        latencies.extend(page.ttfb_ms);
+      }
+
+      bytes_downloaded += page.size.unwrap_or(0);
+      // The page which did not fit in the budget is still stored, only without its links.
+      is_truncated |= page.truncated;
+      timing.add(page_timing);
+      crawled.push((url, page, crawled_urls));
+    }
+
+    // Rate limited rounds already slowed the crawl down as the host asked.
+    if !is_rate_limited {
+      rate.record(&latencies, errors);
+    }
+
+    // Urls which did not respond were fetched too, as far as dry runs are concerned.
+    let fetched: Vec<String> = if dry_run {
+      crawled.iter().map(|(url, _, _)| url.clone()).chain(unreachable.iter().cloned()).collect()
+    } else {
+      vec!()
+    };
+
+    for (url, page, crawled_urls) in &crawled {
+      found.insert(url.clone(), page.status);
 
-          Some(url_parsed.as_str().to_string())
+      for url in crawled_urls {
+        found.entry(url.clone()).or_insert(None);
+      }
+    }
+
+    // Pages of paginated listings go before the other urls, so that the listings are traversed
+    // to the end even if the crawl can't fetch every url it finds.
+    let pagination: HashSet<String> = crawled.iter()
+      .flat_map(|(_, page, _)| page.pagination.iter().cloned())
+      .collect();
+
+    // Appends all unique urls found on the pages of the round. The other pages of the round are
+    // stored even if one of them ran out of the budget, the crawl stops after them.
+    let pages_crawled = crawled.len();
+    let started_at = Instant::now();
+    let unique_urls = insert_unique_urls(master, crawled, host, seed.as_deref());
+
+    if !unreachable.is_empty() {
+      insert_states(master, host, unreachable, UrlState::Failed);
+    }
+
+    if !vetoed.is_empty() {
+      insert_states(master, host, vetoed, UrlState::Skipped);
+    }
+
+    timing.insert += started_at.elapsed();
+
+    if !unique_urls.is_empty() && !dry_run {
+      let urls: Vec<String> = unique_urls.iter().map(|(url, _)| url.clone()).collect();
+      context.notifier.notify(host, id, &urls);
+    }
+
+    let mut trapped: Vec<String> = vec!();
+    for (url, depth) in unique_urls {
+      if !traps.admit(&url) {
+        trapped.push(url);
+      } else if pagination.contains(&url) {
+        queue.push_pagination(url, depth);
+      } else {
+        queue.push(url, depth);
+      }
+    }
+
+    if !trapped.is_empty() {
+      insert_states(master, host, trapped, UrlState::Skipped);
+    }
+
+    jobs::update(jobs, id, |job| {
+      job.timing.add(timing);
+      job.pages_crawled += pages_crawled;
+      job.pages_failed += pages_failed;
+      job.bytes_downloaded = bytes_downloaded;
+      job.truncated = is_truncated;
+      job.would_crawl.extend(fetched);
+      job.traps = traps.suspected();
+    });
+
+    if is_truncated {
+      println!("[Crawler] Stopping crawl of {} as it downloaded {} bytes", host, bytes_downloaded);
+      stop_reason = StopReason::ByteBudget;
+
+      break;
+    }
+
+    if stop.max_consecutive_errors.is_some_and(|max_errors| consecutive_errors >= max_errors) {
+      println!("[Crawler] Stopping crawl of {} as its last pages failed", host);
+      stop_reason = StopReason::MaxConsecutiveErrors;
+
+      break;
+    }
+
+    // Dry runs are not worth resuming when the service restarts.
+    if let (Some(directory), false) = (&config.checkpoint_directory, dry_run) {
+      if checkpointed_at.map_or(true, |at| at.elapsed() >= checkpoint::INTERVAL) {
+        let checkpoint = Checkpoint::new(
+          root.as_str(), order, stop, link_scope.clone(), queue.urls(),
+        );
+
+        if let Err(e) = checkpoint::save(directory, id, &checkpoint) {
+          println!("[Crawler] Error during writing of the checkpoint of {}: {}", host, e);
         }
+
+        checkpointed_at = Some(Instant::now());
       }
-    })
+    }
+  }
+
+  if let Some(directory) = &config.checkpoint_directory {
+    if let Err(e) = checkpoint::remove(directory, id) {
+      println!("[Crawler] Error during removing of the checkpoint of {}: {}", host, e);
+    }
+  }
+
+  // Whatever is left in the queue was found but never fetched. The crawl can be resumed from it.
+  let left = queue.into_urls();
+  jobs::update(jobs, id, |job| {
+    job.stop_reason = Some(stop_reason);
+    job.frontier = left.clone();
+  });
+
+  if !left.is_empty() {
+    insert_states(master, host, left, UrlState::Skipped);
+  }
+
+  found
+}
+
+/// Crawls all urls on given website and filters out the ones not belonging to the host of the
+/// root url the crawl started from. HashSet also makes sure all returned urls are unique.
+/// Details recorded while fetching the url and the time spent fetching and parsing it are
+/// returned alongside the links, unless the host rate limited the crawler. At most one byte
+/// more than the budget is downloaded of the body, at the pace the throttle allows. Fields of
+/// the extraction rules are extracted from the page unless only links are crawled. Only the
+/// links within the link scope are followed if it is set.
+///
+/// The hooks may change the request of the url or veto it before it is sent, and see the links
+/// found on the page once it is parsed.
+fn crawl(context: Context, shared: Shared, url: String, budget: usize) -> Option<Fetched> {
+  let Context { config, fetcher, extractor, hooks, script, .. } = context;
+  let Shared { throttle, checked_links, warc, rules, link_scope, root } = shared;
+  let mut request = PageRequest { url: Url::parse(&url).ok()?, headers: Default::default() };
+
+  if !hooks.before_fetch(&mut request) {
+    return Some(Fetched::Vetoed);
+  }
+
+  let started_at = Instant::now();
+  let mut timing = Timing::default();
+  let (mut req, redirects) = fetcher.get_with(&request.url, &request.headers)?;
+  // The response is returned once its headers arrive, the body is read later.
+  let ttfb = started_at.elapsed();
+  // The url the redirects ended at, which is where the page was actually loaded from.
+  let url_parsed = match redirects.last() {
+    Some(last) => Url::parse(last).ok()?,
+    None => request.url,
+  };
+  let is_same_host = is_same_host(config, root, &url_parsed);
+
+  let mut page = Page {
+    fetched_at: Some(Utc::now()),
+    status: Some(req.status().as_u16()),
+    redirects,
+    ttfb_ms: Some(ttfb.as_millis() as u64),
+    duration_ms: Some(ttfb.as_millis() as u64),
+    ..Default::default()
+  };
+
+  // Urls of the host the page redirected to are discovered even if no page links to them, so
+  // that the destinations of entry points which only redirect are crawled too.
+  let redirects = page.redirects.clone();
+  let (redirect_targets, _) = resolve_links(config, hooks, root, &url_parsed, redirects);
+
+  if req.status() == StatusCode::TOO_MANY_REQUESTS {
+    timing.fetch = started_at.elapsed();
+    let retry_after = req.headers().get(RETRY_AFTER)
+      .and_then(|header| header.to_str().ok())
+      .and_then(|header| fetch::retry_after(header, Utc::now()))
+      .unwrap_or(DEFAULT_RETRY_AFTER);
+
+    return Some(Fetched::RateLimited(page, timing, retry_after));
+  }
+
+  // Pages which failed to load or redirected away from the host have no links to follow.
+  if !req.status().is_success() || !is_same_host {
+    timing.fetch = started_at.elapsed();
+
+    return Some(Fetched::Page(page, redirect_targets, timing));
+  }
+
+  page.noindex_header = req.headers().get_all("x-robots-tag").iter()
+    .filter_map(|header| header.to_str().ok())
+    .any(extract::is_noindex);
+  page.security_headers = extract::SECURITY_HEADERS.iter()
+    .filter(|name| req.headers().contains_key(**name))
+    .map(|name| name.to_string())
     .collect();
 
+  // Media and downloads have no links to follow, they would only use up the byte budget.
+  if !is_document(&req) {
+    timing.fetch = started_at.elapsed();
+
+    return Some(Fetched::Page(page, HashSet::new(), timing));
+  }
+
+  // Bodies which are known not to fit in the budget are not downloaded at all.
+  if req.content_length().is_some_and(|length| length > budget as u64) {
+    page.truncated = true;
+    timing.fetch = started_at.elapsed();
+
+    return Some(Fetched::Page(page, HashSet::new(), timing));
+  }
+
+  let bytes = read_within(Throttled::new(&mut req, throttle), budget).ok()?;
+  page.size = Some(bytes.len());
+  page.duration_ms = Some(started_at.elapsed().as_millis() as u64);
+  timing.fetch = started_at.elapsed();
+
+  if bytes.len() > budget {
+    page.truncated = true;
+
+    return Some(Fetched::Page(page, HashSet::new(), timing));
+  }
+
+  // Archived pages are kept exactly as they were received.
+  if let Some(warc) = warc {
+    // TODO: Error handling the mutex.
+    if let Err(e) = warc.lock().unwrap().write_response(&url_parsed, &req, &bytes) {
+      println!("[Crawler] Error during archiving of {}: {}", url_parsed, e);
+    }
+  }
+
+  let body = warc::decode(&req, &bytes);
+
+  // Building the DOM is the most expensive part of crawling a page. In the links only mode, the
+  // page is just tokenized to find its links and no other details about it are recorded, unless
+  // the links are scoped, which needs the DOM.
+  let mut pagination: Vec<String> = vec!();
+  let (base_href, hrefs): (Option<String>, Vec<String>) = if config.links_only && link_scope.is_none() {
+    extractor.extract(&body, None)?
+  } else {
+    let dom = Html::parse_document(&body);
+
+    if !config.links_only {
+      analyze(config, fetcher, &mut page, &dom, &url_parsed, &mut timing);
+      page.fields = extract::fields(&dom, rules);
+
+      // Pagination hints are links too, so scoped crawls leave them out.
+      if link_scope.is_none() {
+        pagination = extract::pagination(&dom);
+      }
+    }
+
+    match link_scope {
+      // Scoped links are found in the DOM rather than by the extractor.
+      Some(scope) => (extract::base_href(&dom), extract::scoped_links(&dom, scope)?),
+      None => extractor.extract(&body, Some(&dom))?,
+    }
+  };
+
+  // Relative links are resolved against the url the redirects ended at, unless the page declares
+  // another base url.
+  let base = base_href
+    .and_then(|href| url_parsed.join(href.trim()).ok())
+    .unwrap_or_else(|| url_parsed.clone());
+  let (mut urls, outbound) = resolve_links(config, hooks, root, &base, hrefs);
+
+  // The script decides which of the links are followed, or all of them are if it fails.
+  if let Some(script) = script {
+    let mut links: Vec<&str> = urls.iter().map(String::as_str).collect();
+    links.sort();
+    let status = page.status.unwrap_or_default();
+    let input = ScriptInput { url: url_parsed.as_str(), status, body: &body, links };
+
+    if let Some(output) = script.run(&input) {
+      output.filter(&mut urls);
+      page.script_data = output.data;
+    }
+  }
+
+  // AMP variants show the same content as their canonical pages, so they are not worth a fetch of
+  // their own if the crawl skips them.
+  if config.skip_amp_variants {
+    let amp = page.amp.iter().cloned().collect();
+    let (amp, _) = resolve_links(config, hooks, root, &url_parsed, amp);
+
+    for url in amp {
+      urls.remove(&url);
+    }
+  }
+
+  hooks.after_parse(&url_parsed, &urls, &outbound);
+
+  // Pagination links are usually also anchors, but listings which only declare them in the head
+  // are traversed too.
+  let (pagination, _) = resolve_links(config, hooks, root, &base, pagination);
+  page.pagination = pagination.iter().cloned().collect();
+  page.pagination.sort();
+  urls.extend(pagination);
   urls.insert(url);
+  urls.extend(redirect_targets);
+
+  if config.check_outbound_links {
+    let checked_at = Instant::now();
+    page.broken_links = outbound.into_iter()
+      // Links to domains the service must not touch can't be checked.
+      .filter(|link| Url::parse(link).map(|link| !fetch::is_denied(config, &link)).unwrap_or(false))
+      .filter(|link| {
+        // The lock is not held while checking so that other pages can be crawled meanwhile.
+        let checked = checked_links.lock().ok().and_then(|links| links.get(link).cloned());
+        let is_alive = checked.unwrap_or_else(|| fetcher.is_alive(link));
+
+        if let Ok(mut links) = checked_links.lock() {
+          links.insert(link.clone(), is_alive);
+        }
+
+        !is_alive
+      })
+      .collect();
+    page.broken_links.sort();
+    timing.fetch += checked_at.elapsed();
+  }
+
+  // Whatever time was not spent waiting for the network was spent parsing the page.
+  timing.parse = started_at.elapsed() - timing.fetch;
+
+  Some(Fetched::Page(page, urls, timing))
+}
+
+/// Checks whether the response is an html or text document. Responses which don't say what they
+/// are are assumed to be html.
+fn is_document(res: &Response) -> bool {
+  let content_type = match res.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()) {
+    Some(content_type) => content_type.trim().to_lowercase(),
+    None => return true,
+  };
+
+  content_type.starts_with("text/") || content_type.starts_with("application/xhtml+xml")
+}
 
-  Some(urls)
+/// Reads the body up to one byte more than given budget, so that bodies which don't fit in the
+/// budget are told apart from bodies which fill it exactly.
+fn read_within(body: impl Read, budget: usize) -> io::Result<Vec<u8>> {
+  let mut bytes = vec!();
+  body.take(budget as u64 + 1).read_to_end(&mut bytes)?;
+
+  Ok(bytes)
 }
 
-/// Compares the set of crawled urls against the database, inserts the new ones and returns them.
-fn insert_unique_urls(master: &Database, mut crawled_urls: HashSet<String>, host: &str) -> Vec<String> {
+/// Resolves the hrefs found on a page against its base url and splits them into the links of
+/// the host of the root url and the http links to other hosts. Both sets are unique. Links which
+/// can't be resolved or don't point to a host, such as `mailto:` links, are dropped.
+///
+/// Hosts of resolved links are always in punycode, so internationalized domains match whether
+/// the page spelled them in Unicode or not. Links the url filters of the hooks reject are
+/// dropped too.
+fn resolve_links(
+  config: &Config,
+  hooks: &Hooks,
+  root: &Url,
+  base: &Url,
+  hrefs: Vec<String>,
+) -> (HashSet<String>, HashSet<String>) {
+  let mut urls: HashSet<String> = HashSet::new();
+  let mut outbound: HashSet<String> = HashSet::new();
+
+  for href in hrefs {
+    let mut link = match base.join(href.trim()) {
+      Ok(link) => link,
+      Err(_) => continue,
+    };
+
+    // Fragments are never sent to the server, so a link to a part of a page is a link to the
+    // page itself.
+    link.set_fragment(None);
+
+    if !hooks.allows(&link) {
+      continue;
+    }
+
+    match link.host_str() {
+      // Checks the hostname to ensure the links are from a single domain.
+      _ if is_same_host(config, root, &link) => {
+        urls.insert(fold_scheme(config, link).as_str().to_string());
+      },
+      Some(_) if link.scheme() == "http" || link.scheme() == "https" => {
+        outbound.insert(link.as_str().to_string());
+      },
+      _ => (),
+    }
+  }
+
+  (urls, outbound)
+}
+
+/// Checks whether given url belongs to the host the crawl started from. Unless the `match_port`
+/// setting is disabled, the port has to match as well, since different ports of a host are
+/// usually different sites. Ports are compared only when given explicitly, so that the same
+/// host over http and https still matches.
+///
+/// Local trees have no host, their files belong to the crawl if they are in the directory the
+/// crawl started from or below it.
+fn is_same_host(config: &Config, root: &Url, url: &Url) -> bool {
+  if root.scheme() == "file" {
+    let directory = &root.path()[..root.path().rfind('/').map_or(0, |slash| slash + 1)];
+
+    return url.scheme() == "file" && url.path().starts_with(directory);
+  }
+
+  url.host_str() == root.host_str() && (!config.match_port || url.port() == root.port())
+}
+
+/// Changes the scheme of given url of the crawled host according to the `fold_schemes` setting,
+/// so that http and https variants of a url are stored and crawled only once. Urls with other
+/// schemes are left alone.
+fn fold_scheme(config: &Config, mut url: Url) -> Url {
+  let scheme = match config.fold_schemes {
+    SchemeFolding::Off => return url,
+    SchemeFolding::Https => "https",
+    SchemeFolding::Http => "http",
+  };
+
+  if url.scheme() == "http" || url.scheme() == "https" {
+    // Switching between the two special schemes always succeeds.
+    let _ = url.set_scheme(scheme);
+  }
+
+  url
+}
+
+/// Records the details of a successfully loaded page found in its DOM. Time spent checking the
+/// canonical url is added to the fetch time of the page.
+fn analyze(
+  config: &Config,
+  fetcher: &dyn Fetcher,
+  page: &mut Page,
+  dom: &Html,
+  url: &Url,
+  timing: &mut Timing,
+) {
+  page.title = extract::title(dom);
+  page.description = extract::description(dom);
+  page.headings = extract::headings(dom);
+  page.alternates = extract::alternates(dom, url);
+  page.noindex_meta = extract::has_noindex_meta(dom);
+  page.schema_types = extract::schema_types(dom);
+  page.forms = extract::forms(dom, url);
+  page.trackers = extract::trackers(dom);
+  let (asset_hosts, linked_hosts) = extract::third_party_hosts(dom, url);
+  page.asset_hosts = asset_hosts;
+  page.linked_hosts = linked_hosts;
+
+  // Plain http references only weaken pages which were served securely.
+  if url.scheme() == "https" {
+    let (assets, links) = extract::insecure_references(dom);
+    page.insecure_assets = assets;
+    page.insecure_links = links;
+  }
+
+  // A canonical url pointing elsewhere is checked to be alive, as pointing search engines to a
+  // missing page is a common misconfiguration.
+  if let Some(canonical) = extract::canonical(dom, url) {
+    if canonical != *url {
+      let checked_at = Instant::now();
+      page.canonical_status = fetcher.status(canonical.as_str()).map(|status| status.as_u16());
+      timing.fetch += checked_at.elapsed();
+    }
+
+    page.canonical = Some(canonical.as_str().to_string());
+  }
+
+  page.amp = extract::amphtml(dom, url).map(|amp| amp.as_str().to_string());
+  page.is_amp = extract::is_amp(dom);
+
+  if config.check_image_alt {
+    page.images_missing_alt = extract::images_missing_alt(dom);
+  }
+
+  // Contact details are personal data, so they are only collected when explicitly enabled.
+  if config.extract_contacts {
+    let (emails, phones) = extract::contacts(dom);
+    page.emails = emails;
+    page.phones = phones;
+  }
+}
+
+/// Stores details about the crawled pages, compares the sets of urls found on them against the
+/// database, inserts the new ones and returns them with their depths. All pages are stored
+/// under a single lock acquisition so that the crawler contends with reads of the API less often.
+///
+/// The new urls are queued to be fetched and the fetched pages record whether they loaded.
+///
+/// Urls found on a page are one link deeper than the page, counting from the seed url the crawl
+/// started at, if it did not just retry urls. If a url is found at several depths, the shallowest
+/// one is kept.
+fn insert_unique_urls(
+  master: &Database,
+  pages: Vec<(String, Page, HashSet<String>)>,
+  host: &str,
+  seed: Option<&str>,
+) -> Vec<(String, usize)> {
   // Acquires the database lock.
   // TODO: Error handling the mutex.
   let mut map = master.lock().unwrap();
 
-  // Gets the HashSet associated with given domain.
-  match map.get_mut(host) {
-    // If the domain has been already crawled, adds new items to the set.
-    Some(set) => {
-      let mut unique_urls: Vec<String> = Vec::new();
-
-      // For each newly crawled url, tries to insert it into the HashSet.
-      // If the url hasn't been in the set prior, pushes it into unique
-      // urls collections to be crawled in next cycle.
-      for url in crawled_urls.drain() {
-        if (*set).insert(url.clone()) {
-          unique_urls.push(url);
-        }
+  // Gets the record associated with given domain, creating it if the domain hasn't been crawled
+  // yet.
+  let domain = map.entry(host.to_string()).or_default();
+  let mut unique_urls: Vec<String> = Vec::new();
+  let seen_at = Utc::now();
+
+  for (page_url, page, crawled_urls) in pages {
+    let page_depth = if seed == Some(page_url.as_str()) {
+      0
+    } else {
+      domain.records.get(&page_url).map_or(0, |record| record.depth)
+    };
+
+    // Moves the urls which haven't been in the set prior into unique urls collection to be
+    // crawled in next cycle. Most links on a page are already known, so checking before
+    // inserting means only the new urls have to be copied into the set.
+    for url in crawled_urls {
+      // The page itself is among the urls found on it, but it does not refer to itself.
+      let (depth, referrer) = if url == page_url {
+        (page_depth, None)
+      } else {
+        (page_depth + 1, Some(&page_url))
+      };
+
+      domain.records.entry(url.clone())
+        .or_insert_with(|| UrlRecord::new(seen_at, depth))
+        .found(seen_at, depth, referrer);
+
+      // The page has just been fetched, so it is known but not queued again.
+      if url == page_url {
+        domain.urls.insert(url);
+      } else if !domain.urls.contains(&url) {
+        domain.urls.insert(url.clone());
+        // Unwrap here is safe as the record was inserted above.
+        domain.records.get_mut(&url).unwrap().state = UrlState::Queued;
+        unique_urls.push(url);
       }
+    }
 
-      unique_urls
-    },
-    // If the domain hasn't been crawled yet, all crawled urls are unique.
-    None => {
-      // Copies the urls so that they can be crawled in next cycle.
-      let items: Vec<String> = crawled_urls.iter().cloned().collect();
+    // Pages which failed to load have no urls, not even their own, so their record may be
+    // missing if they were not found on another page, e.g. the seed.
+    domain.records.entry(page_url.clone())
+      .or_insert_with(|| UrlRecord::new(seen_at, page_depth))
+      .state = UrlState::of_page(&page);
+    domain.pages.insert(page_url, page);
+  }
+
+  if !unique_urls.is_empty() {
+    domain.version += 1;
+  }
+
+  // Urls can be found deeper first and shallower later in the same round.
+  unique_urls.into_iter()
+    .map(|url| {
+      let depth = domain.records.get(&url).map_or(0, |record| record.depth);
+
+      (url, depth)
+    })
+    .collect()
+}
+
+/// Pairs given urls of given host with their depths as the database knows them. The seed and
+/// the urls the crawler has no record of are at depth zero.
+fn depths(
+  master: &Database,
+  host: &str,
+  seed: Option<&str>,
+  urls: Vec<String>,
+) -> Vec<(String, usize)> {
+  // TODO: Error handling the mutex.
+  let map = master.lock().unwrap();
+  let record = map.get(host);
+
+  urls.into_iter()
+    .map(|url| {
+      let depth = match record.and_then(|record| record.records.get(&url)) {
+        Some(history) if seed != Some(url.as_str()) => history.depth,
+        _ => 0,
+      };
+
+      (url, depth)
+    })
+    .collect()
+}
+
+/// Moves given urls of given host into given state. Urls the crawler has no record of, which can
+/// only be the seed of the crawl, get one at depth zero.
+fn insert_states(master: &Database, host: &str, urls: Vec<String>, state: UrlState) {
+  // TODO: Error handling the mutex.
+  let mut map = master.lock().unwrap();
+  let domain = map.entry(host.to_string()).or_default();
+  let seen_at = Utc::now();
+
+  for url in urls {
+    domain.records.entry(url).or_insert_with(|| UrlRecord::new(seen_at, 0)).state = state;
+  }
+}
 
-      // Inserts the HashSet into the database.
-      map.insert(host.to_string(), crawled_urls);
+/// Lists the urls of given host, together with the seed, which were never fetched or were last
+/// fetched before given time. The seed goes first, then the urls from the least recently fetched
+/// one, which is the order the urls equally valuable for the frontier are fetched in.
+fn stale_urls(
+  master: &Database,
+  host: &str,
+  seed: Option<&str>,
+  fresh_since: Option<DateTime<Utc>>,
+) -> Vec<String> {
+  // TODO: Error handling the mutex.
+  let map = master.lock().unwrap();
+  let record = map.get(host);
+  let fetched_at = |url: &str| record.and_then(|record| record.pages.get(url)?.fetched_at);
+  let is_stale = |url: &str| match fetched_at(url) {
+    Some(fetched_at) => fresh_since.is_some_and(|fresh_since| fetched_at < fresh_since),
+    None => true,
+  };
+
+  let mut urls: Vec<(Option<DateTime<Utc>>, String)> = record
+    .map(|record| record.urls.iter()
+      .filter(|url| Some(url.as_str()) != seed && is_stale(url))
+      .map(|url| (fetched_at(url), url.clone()))
+      .collect())
+    .unwrap_or_default();
+  urls.sort();
+
+  seed.filter(|seed| is_stale(seed))
+    .map(String::from)
+    .into_iter()
+    .chain(urls.into_iter().map(|(_, url)| url))
+    .collect()
+}
 
-      items
+/// Checks how the sitemap urls of given host which were never fetched respond, at most
+/// `MAX_SITEMAP_CHECKS` of them in the alphabetical order, several in parallel. The checks replace
+/// the ones of the previous crawl.
+fn check_sitemap(context: Context, host: &str) {
+  let Context { db, config, fetcher, .. } = context;
+  let mut urls: Vec<String> = {
+    // TODO: Error handling the mutex.
+    let db = db.lock().unwrap();
+
+    match db.get(host) {
+      Some(record) => record.sitemap.iter()
+        .filter(|url| !record.pages.contains_key(*url))
+        .cloned()
+        .collect(),
+      None => return,
     }
+  };
+  urls.sort();
+  urls.truncate(MAX_SITEMAP_CHECKS);
+
+  // The lock is not held while checking so that the api can read meanwhile.
+  let mut checks: HashMap<String, SitemapCheck> = HashMap::new();
+  for round in urls.chunks(config.crawl_concurrency.max(1)) {
+    let results = thread::scope(|scope| {
+      let handles: Vec<_> = round.iter()
+        .map(|url| scope.spawn(move || {
+          let response = Url::parse(url).ok().and_then(|url| fetcher.head(&url));
+          let check = match response {
+            Some((status, redirects)) => SitemapCheck { status: Some(status.as_u16()), redirects },
+            None => SitemapCheck::default(),
+          };
+
+          (url.clone(), check)
+        }))
+        .collect();
+
+      handles.into_iter().filter_map(|handle| handle.join().ok()).collect::<Vec<_>>()
+    });
+
+    checks.extend(results);
+  }
+
+  // TODO: Error handling the mutex.
+  let mut map = db.lock().unwrap();
+
+  if let Some(record) = map.get_mut(host) {
+    record.sitemap_checks = checks;
   }
 }
 
+/// Replaces the sitemap urls stored for given host with the freshly fetched ones.
+fn insert_sitemap(master: &Database, host: &str, sitemap: HashSet<String>) {
+  // TODO: Error handling the mutex.
+  let mut map = master.lock().unwrap();
+
+  map.entry(host.to_string()).or_default().sitemap = sitemap;
+}
+
+/// Records when a crawl of given host finished.
+fn insert_crawled_at(master: &Database, host: &str, crawled_at: DateTime<Utc>) {
+  // TODO: Error handling the mutex.
+  let mut map = master.lock().unwrap();
+
+  map.entry(host.to_string()).or_default().crawled_at = Some(crawled_at);
+}
+
+/// Adds the crawl carried out by given job, which found given urls, to the history of given host.
+fn insert_session(
+  master: &Database,
+  jobs: &Jobs,
+  id: usize,
+  host: &str,
+  urls: HashMap<String, Option<u16>>,
+) {
+  // TODO: Error handling the mutex.
+  let session = match jobs.lock().unwrap().get(id) {
+    Some(job) => Session {
+      job: id,
+      url: job.url.clone(),
+      started_at: job.started_at.unwrap_or(job.submitted_at),
+      finished_at: job.finished_at.unwrap_or_else(Utc::now),
+      pages_crawled: job.pages_crawled,
+      pages_failed: job.pages_failed,
+      bytes_downloaded: job.bytes_downloaded,
+      truncated: job.truncated,
+      error: job.error.clone(),
+      urls,
+    },
+    None => return,
+  };
+
+  // TODO: Error handling the mutex.
+  let mut map = master.lock().unwrap();
+
+  map.entry(host.to_string()).or_default().sessions.push(session);
+}
+
+/// Replaces the certificate stored for given host with the freshly inspected one.
+fn insert_certificate(master: &Database, host: &str, certificate: Certificate) {
+  // TODO: Error handling the mutex.
+  let mut map = master.lock().unwrap();
+
+  map.entry(host.to_string()).or_default().certificate = Some(certificate);
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use super::{crawl_urls, read_within, resolve_links, stale_urls, Context};
+  use super::super::Database;
+  use super::super::budget::Budget;
+  use super::super::config::{Config, SchemeFolding};
+  use super::super::database::{Domain, Page, UrlState};
+  use super::super::fetch::MockFetcher;
+  use super::super::extract::{DomExtractor, LinkExtractor};
+  use super::super::hooks::{AfterParse, BeforeFetch, Hooks, PageRequest};
+  use scraper::Html;
+  use super::super::jobs::{Job, Jobs, StopConditions, StopReason};
+  use super::super::throttle::Throttle;
+  use super::super::webhooks::{Notifier, Webhooks};
+  use chrono::{Duration, Utc};
+  use std::collections::{HashMap, HashSet};
+  use std::sync::{Arc, Mutex};
+
+  /// Finds links in the text of pages as if they were written out by scripts.
+  struct TextExtractor;
+
+  impl LinkExtractor for TextExtractor {
+    fn extract(&self, body: &str, _: Option<&Html>) -> Option<(Option<String>, Vec<String>)> {
+      let paths = body.split_whitespace().filter(|word| word.starts_with('/'));
+
+      Some((None, paths.map(String::from).collect()))
+    }
+  }
+
+  /// Crawls given made up site from its root as the job of given stop conditions.
+  fn crawl_site(
+    db: &Database,
+    fetcher: &MockFetcher,
+    stop: StopConditions,
+  ) -> (HashMap<String, Option<u16>>, Job) {
+    crawl_site_with(db, fetcher, &DomExtractor, &Hooks::default(), stop)
+  }
+
+  fn crawl_site_with(
+    db: &Database,
+    fetcher: &MockFetcher,
+    extractor: &dyn LinkExtractor,
+    hooks: &Hooks,
+    stop: StopConditions,
+  ) -> (HashMap<String, Option<u16>>, Job) {
+    let config = Config::from_rocket(&rocket::Config::development());
+    let jobs = Jobs::default();
+    let mut job = Job::new(String::from("https://github.com/"));
+    job.stop = stop;
+    jobs.lock().unwrap().push(job);
+
+    let context = Context {
+      db,
+      jobs: &jobs,
+      config: &config,
+      fetcher,
+      extractor,
+      notifier: &Notifier::new(&config, Webhooks::default()).unwrap(),
+      hooks,
+      script: None,
+    };
+    let throttle = Mutex::new(Throttle::new(0));
+    let mut budget = Budget::new(0);
+    let url = Url::parse("https://github.com/").unwrap();
+    let found = crawl_urls(context, &throttle, &mut budget, 0, &url);
+    let job = jobs.lock().unwrap()[0].clone();
+
+    (found, job)
+  }
+
+  #[test]
+  fn test_crawl_urls() {
+    let db: Database = Default::default();
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/", r#"
+        <a href="/about">About</a> <a href="/pricing">Pricing</a> <a href="https://gitlab.com">x</a>
+      "#)
+      .page("https://github.com/about", r#"<a href="/team">Team</a> <a href="/">Home</a>"#)
+      .response("https://github.com/pricing", 500, &[], "")
+      .unreachable("https://github.com/team");
+
+    let (found, job) = crawl_site(&db, &fetcher, StopConditions::default());
+
+    let mut found: Vec<(String, Option<u16>)> = found.into_iter().collect();
+    found.sort();
+    assert_eq!(found, vec!(
+      (String::from("https://github.com/"), Some(200)),
+      (String::from("https://github.com/about"), Some(200)),
+      (String::from("https://github.com/pricing"), Some(500)),
+      (String::from("https://github.com/team"), None),
+    ));
+    // Other hosts are never crawled.
+    assert!(fetcher.requested().iter().all(|url| url.starts_with("https://github.com/")));
+    assert_eq!(job.pages_failed, 2);
+    assert_eq!(job.stop_reason, Some(StopReason::FrontierExhausted));
+    assert!(job.frontier.is_empty());
+
+    let db = db.lock().unwrap();
+    let domain = &db["github.com"];
+    assert_eq!(domain.records["https://github.com/team"].depth, 2);
+    assert_eq!(domain.records["https://github.com/team"].state, UrlState::Failed);
+    assert_eq!(domain.records["https://github.com/about"].state, UrlState::Fetched);
+  }
+
+  #[test]
+  fn test_dry_run_leaves_budget() {
+    let db: Database = Default::default();
+    // The host was crawled before, so the root is not queued again as one of its own links.
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/"));
+    db.lock().unwrap().insert(String::from("github.com"), domain);
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/", r#"<a href="/about">About</a> <a href="/pricing">Pricing</a>"#);
+    let config = Config::from_rocket(&rocket::Config::development());
+    let jobs = Jobs::default();
+    let mut job = Job::new(String::from("https://github.com/"));
+    job.dry_run = true;
+    jobs.lock().unwrap().push(job);
+
+    let context = Context {
+      db: &db,
+      jobs: &jobs,
+      config: &config,
+      fetcher: &fetcher,
+      extractor: &DomExtractor,
+      notifier: &Notifier::new(&config, Webhooks::default()).unwrap(),
+      hooks: &Hooks::default(),
+      script: None,
+    };
+    let mut budget = Budget::new(2);
+    let url = Url::parse("https://github.com/").unwrap();
+    let found = crawl_urls(context, &Mutex::new(Throttle::new(0)), &mut budget, 0, &url);
+
+    // The budget still stops the dry run, but none of it is spent.
+    assert_eq!(found.values().filter(|status| status.is_some()).count(), 2);
+    assert_eq!(budget.remaining("github.com", std::time::Instant::now()), Some(2));
+  }
+
+  #[test]
+  fn test_crawl_urls_with_crawl_delay() {
+    let db: Database = Default::default();
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/robots.txt", "User-agent: *\nCrawl-delay: 0.01\n")
+      .page("https://github.com/", r#"<a href="/about">About</a> <a href="/pricing">Pricing</a>"#)
+      .page("https://github.com/about", "")
+      .page("https://github.com/pricing", "");
+
+    let (found, job) = crawl_site(&db, &fetcher, StopConditions::default());
+
+    assert_eq!(found.len(), 3);
+    assert_eq!(job.crawl_delay, Some(std::time::Duration::from_millis(10)));
+    // The robots.txt is only read for the delay, it is not crawled as a page.
+    assert!(!found.contains_key("https://github.com/robots.txt"));
+  }
+
+  #[test]
+  fn test_crawl_urls_follows_pagination() {
+    let db: Database = Default::default();
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/", concat!(
+        r#"<link rel="next" href="/blog?page=2"><a href="/about">About</a>"#,
+        r#"<a href="/pricing">Pricing</a> <a href="/team">Team</a>"#,
+      ))
+      .page("https://github.com/blog?page=2", r#"<link rel="next" href="/blog?page=3">"#)
+      .page("https://github.com/blog?page=3", "");
+    let stop = StopConditions { max_pages: Some(3), ..Default::default() };
+
+    let (found, _) = crawl_site(&db, &fetcher, stop);
+
+    // The listing is traversed before the shorter urls without a query.
+    assert_eq!(found["https://github.com/blog?page=2"], Some(200));
+    assert_eq!(found["https://github.com/blog?page=3"], Some(200));
+    let db = db.lock().unwrap();
+    assert_eq!(db["github.com"].pages["https://github.com/"].pagination, vec!(
+      String::from("https://github.com/blog?page=2"),
+    ));
+  }
+
+  #[test]
+  fn test_crawl_urls_skips_traps() {
+    let db: Database = Default::default();
+    let calendar = (0..70)
+      .map(|month| format!("<a href=\"/calendar/{}/{}\">x</a>", 2000 + month / 12, month % 12 + 1))
+      .collect::<Vec<_>>()
+      .concat();
+    let fetcher = MockFetcher::default().page("https://github.com/", &calendar);
+    let stop = StopConditions { max_pages: Some(1), ..Default::default() };
+
+    let (_, job) = crawl_site(&db, &fetcher, stop);
+
+    assert_eq!(job.traps.len(), 1);
+    assert_eq!(job.traps[0].pattern, "https://github.com/calendar/{year}/{month}");
+    assert_eq!(job.traps[0].skipped, 10);
+    // The urls of the trap are not left to a resumed crawl either.
+    assert_eq!(job.frontier.len(), 60);
+  }
+
+  #[test]
+  fn test_crawl_urls_with_extractor() {
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/", "<a href=\"/about\">About</a> Pricing is at /pricing")
+      .page("https://github.com/about", "<a href=\"/team\">Team</a>");
+
+    let db = Database::default();
+    let hooks = Hooks::default();
+    let (found, _) = crawl_site_with(&db, &fetcher, &TextExtractor, &hooks, Default::default());
+
+    let mut found: Vec<String> = found.into_keys().collect();
+    found.sort();
+    // The crawler follows only the links the extractor finds, not the ones it would find.
+    assert_eq!(found, vec!("https://github.com/", "https://github.com/pricing"));
+  }
+
+  #[test]
+  fn test_crawl_urls_with_hooks() {
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/", r#"<a href="/about">About</a> <a href="/admin">Admin</a>"#)
+      .page("https://github.com/about?lang=en", r#"<a href="/team">Team</a>"#);
+    let observed = Arc::new(Mutex::new(vec!()));
+    let links = Arc::clone(&observed);
+
+    let mut hooks = Hooks::default();
+    hooks.register(BeforeFetch(|request: &mut PageRequest| {
+      request.headers.insert("authorization", "Bearer token".parse().unwrap());
+
+      request.url.path() != "/admin"
+    }));
+    hooks.register(BeforeFetch(|request: &mut PageRequest| {
+      if request.url.path() == "/about" {
+        request.url.set_query(Some("lang=en"));
+      }
 
+      true
+    }));
+    hooks.register(AfterParse(move |url: &Url, urls: &HashSet<String>, _: &HashSet<String>| {
+      let mut urls: Vec<String> = urls.iter().cloned().collect();
+      urls.sort();
+      links.lock().unwrap().push((url.to_string(), urls));
+    }));
+
+    let db = Database::default();
+    let (found, _) = crawl_site_with(&db, &fetcher, &DomExtractor, &hooks, Default::default());
+
+    let requested = fetcher.requested();
+    assert!(requested.contains(&String::from("https://github.com/about?lang=en")));
+    assert!(requested.contains(&String::from("https://github.com/team")));
+    // Vetoed urls are never requested.
+    assert!(!requested.contains(&String::from("https://github.com/admin")));
+    assert_eq!(fetcher.headers("https://github.com/").unwrap()["authorization"], "Bearer token");
+    // Changed requests are stored under the urls the crawl found.
+    assert_eq!(found["https://github.com/about"], Some(200));
+    assert_eq!(found["https://github.com/admin"], None);
+    assert_eq!(db.lock().unwrap()["github.com"].records["https://github.com/admin"].state,
+      UrlState::Skipped);
+    let observed = observed.lock().unwrap();
+    assert!(observed.contains(&(String::from("https://github.com/"), vec!(
+      String::from("https://github.com/about"), String::from("https://github.com/admin"),
+    ))));
+    assert!(observed.contains(&(String::from("https://github.com/about?lang=en"), vec!(
+      String::from("https://github.com/team"),
+    ))));
+  }
+
+  #[test]
+  fn test_crawl_urls_stops() {
+    let site = || MockFetcher::default()
+      .page("https://github.com/", r#"<a href="/a">A</a> <a href="/b">B</a> <a href="/c">C</a>"#)
+      .unreachable("https://github.com/a")
+      .unreachable("https://github.com/b")
+      .unreachable("https://github.com/c");
+
+    let stop = StopConditions { max_pages: Some(2), ..Default::default() };
+    let (_, job) = crawl_site(&Database::default(), &site(), stop);
+    assert_eq!(job.stop_reason, Some(StopReason::MaxPages));
+    // The crawl can be resumed from the urls it did not get to.
+    assert!(!job.frontier.is_empty());
+
+    let stop = StopConditions { max_consecutive_errors: Some(2), ..Default::default() };
+    let (_, job) = crawl_site(&Database::default(), &site(), stop);
+    assert_eq!(job.stop_reason, Some(StopReason::MaxConsecutiveErrors));
+  }
+
+  #[test]
+  fn test_stale_urls() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let now = Utc::now();
+    for (url, hours) in &[("/", Some(30)), ("/a", Some(1)), ("/b", Some(48)), ("/c", None)] {
+      let url = format!("https://github.com{}", url);
+      domain.urls.insert(url.clone());
+
+      if let Some(hours) = hours {
+        let page = Page { fetched_at: Some(now - Duration::hours(*hours)), ..Default::default() };
+        domain.pages.insert(url, page);
+      }
+    }
+    db.lock().unwrap().insert(String::from("github.com"), domain);
+
+    let seed = Some("https://github.com/");
+    // The seed goes first, then the urls never fetched and the urls fetched longest ago.
+    assert_eq!(stale_urls(&db, "github.com", seed, Some(now - Duration::hours(24))), vec!(
+      "https://github.com/", "https://github.com/c", "https://github.com/b",
+    ));
+    assert_eq!(stale_urls(&db, "github.com", seed, Some(now - Duration::hours(72))), vec!(
+      "https://github.com/c",
+    ));
+    // Domains which were not crawled yet start from the seed.
+    assert_eq!(stale_urls(&db, "gitlab.com", Some("https://gitlab.com/"), None), vec!(
+      "https://gitlab.com/",
+    ));
+  }
+
+  #[test]
+  fn test_resolve_links_against_base() {
+    let base = Url::parse("https://github.com/docs/").unwrap();
+    let hrefs = vec!(
+      "guide", "../about", "/pricing#plans", "#top", "https://gitlab.com/x", "mailto:a@github.com",
+      "HTTPS://GitHub.COM/Team",
+    )
+      .into_iter()
+      .map(String::from)
+      .collect();
+
+    let config = Config::from_rocket(&rocket::Config::development());
+    let (mut urls, outbound) = resolve_links(&config, &Hooks::default(), &base, &base, hrefs);
+    let mut urls: Vec<String> = urls.drain().collect();
+    urls.sort();
+
+    assert_eq!(urls, vec!(
+      // Scheme and host are case insensitive, unlike the path.
+      "https://github.com/Team".to_string(),
+      "https://github.com/about".to_string(),
+      "https://github.com/docs/".to_string(),
+      "https://github.com/docs/guide".to_string(),
+      "https://github.com/pricing".to_string(),
+    ));
+    assert_eq!(outbound.into_iter().collect::<Vec<_>>(), vec!("https://gitlab.com/x".to_string()));
+  }
+
+  #[test]
+  fn test_resolve_filtered_links() {
+    let base = Url::parse("https://github.com/").unwrap();
+    let hrefs = vec!("/about", "/about?sessionid=4f2a", "https://gitlab.com/?sessionid=1", "/team")
+      .into_iter()
+      .map(String::from)
+      .collect();
+
+    let mut hooks = Hooks::default();
+    hooks.filter(|url: &Url| !url.query_pairs().any(|(name, _)| name == "sessionid"));
+    let config = Config::from_rocket(&rocket::Config::development());
+    let (mut urls, outbound) = resolve_links(&config, &hooks, &base, &base, hrefs);
+    let mut urls: Vec<String> = urls.drain().collect();
+    urls.sort();
+
+    // Filtered links are neither followed nor checked.
+    assert_eq!(urls, vec!("https://github.com/about", "https://github.com/team"));
+    assert!(outbound.is_empty());
+  }
+
+  #[test]
+  fn test_resolve_internationalized_links() {
+    let base = Url::parse("https://bücher.de/").unwrap();
+    let hrefs = vec!("https://Bücher.de/a", "https://xn--bcher-kva.de/b", "https://bücher.com/")
+      .into_iter()
+      .map(String::from)
+      .collect();
+
+    let config = Config::from_rocket(&rocket::Config::development());
+    let (urls, outbound) = resolve_links(&config, &Hooks::default(), &base, &base, hrefs);
+
+    assert_eq!(urls.len(), 2);
+    assert!(urls.contains("https://xn--bcher-kva.de/a"));
+    assert!(urls.contains("https://xn--bcher-kva.de/b"));
+    assert!(outbound.contains("https://xn--bcher-kva.com/"));
+  }
+
+  #[test]
+  fn test_resolve_links_with_ports() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    let root = Url::parse("https://github.com/").unwrap();
+    let hrefs = || {
+      vec!("http://github.com/a", "https://github.com:443/b", "https://github.com:8080/c")
+        .into_iter()
+        .map(String::from)
+        .collect()
+    };
+
+    let (urls, outbound) = resolve_links(&config, &Hooks::default(), &root, &root, hrefs());
+
+    assert_eq!(urls.len(), 2);
+    assert!(urls.contains("http://github.com/a"));
+    assert!(urls.contains("https://github.com/b"));
+    assert!(outbound.contains("https://github.com:8080/c"));
+
+    config.match_port = false;
+    let (urls, outbound) = resolve_links(&config, &Hooks::default(), &root, &root, hrefs());
+
+    assert_eq!(urls.len(), 3);
+    assert!(outbound.is_empty());
+  }
+
+  #[test]
+  fn test_resolve_local_links() {
+    let root = Url::parse("file:///srv/site/index.html").unwrap();
+    let base = Url::parse("file:///srv/site/docs/").unwrap();
+    let hrefs = vec!("guide.html", "../about.html", "../../secret.txt", "https://github.com/")
+      .into_iter()
+      .map(String::from)
+      .collect();
+
+    let config = Config::from_rocket(&rocket::Config::development());
+    let (urls, outbound) = resolve_links(&config, &Hooks::default(), &root, &base, hrefs);
+
+    // Only the files of the tree the crawl started in are followed.
+    assert_eq!(urls.len(), 2);
+    assert!(urls.contains("file:///srv/site/docs/guide.html"));
+    assert!(urls.contains("file:///srv/site/about.html"));
+    assert!(outbound.contains("https://github.com/"));
+  }
+
+  #[test]
+  fn test_fold_schemes() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    let root = Url::parse("https://github.com/").unwrap();
+    let hrefs = || {
+      vec!("http://github.com/a", "https://github.com/a", "http://gitlab.com/", "ftp://github.com/")
+        .into_iter()
+        .map(String::from)
+        .collect()
+    };
+
+    let (urls, _) = resolve_links(&config, &Hooks::default(), &root, &root, hrefs());
+    assert_eq!(urls.len(), 3);
+
+    config.fold_schemes = SchemeFolding::Https;
+    let (urls, outbound) = resolve_links(&config, &Hooks::default(), &root, &root, hrefs());
+
+    assert_eq!(urls.len(), 2);
+    assert!(urls.contains("https://github.com/a"));
+    assert!(urls.contains("ftp://github.com/"));
+    // Links to other hosts are left as they are.
+    assert!(outbound.contains("http://gitlab.com/"));
+
+    config.fold_schemes = SchemeFolding::Http;
+    let (urls, _) = resolve_links(&config, &Hooks::default(), &root, &root, hrefs());
+
+    assert!(urls.contains("http://github.com/a"));
+    assert!(!urls.contains("https://github.com/a"));
+  }
+
+  #[test]
+  fn test_read_within() {
+    let body = [b'a'; 100];
+
+    assert_eq!(read_within(&body[..], 10).unwrap().len(), 11);
+    assert_eq!(read_within(&body[..], 100).unwrap().len(), 100);
+    assert_eq!(read_within(&body[..], 0).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn test_crawl_urls_cuts_off_large_bodies() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.max_bytes_per_job = 100;
+    let body = format!(r#"<a href="/about">About</a>{}"#, "a".repeat(200));
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/", &body)
+      .page("https://github.com/about", "About");
+
+    let db: Database = Default::default();
+    let jobs = Jobs::default();
+    jobs.lock().unwrap().push(Job::new(String::from("https://github.com/")));
+    let context = Context {
+      db: &db,
+      jobs: &jobs,
+      config: &config,
+      fetcher: &fetcher,
+      extractor: &DomExtractor,
+      notifier: &Notifier::new(&config, Webhooks::default()).unwrap(),
+      hooks: &Hooks::default(),
+      script: None,
+    };
+    let url = Url::parse("https://github.com/").unwrap();
+    let found = crawl_urls(context, &Mutex::new(Throttle::new(0)), &mut Budget::new(0), 0, &url);
+
+    // The links of a body which was cut off are not followed.
+    assert_eq!(found.keys().collect::<Vec<_>>(), vec!("https://github.com/"));
+    assert!(!fetcher.requested().contains(&String::from("https://github.com/about")));
+    assert!(jobs.lock().unwrap()[0].truncated);
+  }
+}
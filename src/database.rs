@@ -0,0 +1,370 @@
+use std::mem;
+use serde::Serialize;
+use super::tls::Certificate;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Everything the crawler has learned about a single host name.
+#[derive(Clone, Default)]
+pub struct Domain {
+  /// Unique urls the crawler has found on the host.
+  pub urls: HashSet<String>,
+  /// What the crawler knows about each of the urls it has found, keyed by the url.
+  pub records: HashMap<String, UrlRecord>,
+  /// Urls the host lists in its sitemap. Empty if the host has no sitemap.
+  pub sitemap: HashSet<String>,
+  /// How the sitemap urls the last crawl did not fetch responded, keyed by the url. Only checked
+  /// if the `check_sitemap_urls` setting is enabled.
+  pub sitemap_checks: HashMap<String, SitemapCheck>,
+  /// Details about the urls the crawler has fetched, keyed by the fetched url.
+  pub pages: HashMap<String, Page>,
+  /// The TLS certificate the host presented when it was last crawled over https.
+  pub certificate: Option<Certificate>,
+  /// Bumped whenever new urls are found on the host, so that clients can tell whether the urls
+  /// changed since they last listed them.
+  pub version: u64,
+  /// When the last crawl of the host finished.
+  pub crawled_at: Option<DateTime<Utc>>,
+  /// Every crawl of the host which has finished, in the order they finished.
+  pub sessions: Vec<Session>,
+  /// Labels the users attached to the host to organize the crawled hosts.
+  pub tags: BTreeSet<String>,
+  /// Field names the crawler extracts from every page of the host, keyed by the CSS selectors
+  /// they are extracted with, see `extract::fields`.
+  pub extraction_rules: BTreeMap<String, String>,
+}
+
+impl Domain {
+  /// Estimates how many bytes the record takes up in memory. Only the strings and the items of
+  /// collections are counted, not the spare capacity or the internals of the collections.
+  pub fn approximate_size(&self) -> usize {
+    let urls: usize = self.urls.iter()
+      .chain(&self.sitemap)
+      .chain(&self.tags)
+      .chain(self.extraction_rules.iter().flat_map(|(selector, field)| vec!(selector, field)))
+      .map(|url| string_size(url))
+      .sum();
+    let records: usize = self.records.iter()
+      .map(|(url, record)| string_size(url) + record.approximate_size())
+      .sum();
+    let pages: usize = self.pages.iter()
+      .map(|(url, page)| string_size(url) + page.approximate_size())
+      .sum();
+
+    let checks: usize = self.sitemap_checks.iter()
+      .map(|(url, check)| string_size(url) + check.approximate_size())
+      .sum();
+
+    let sessions: usize = self.sessions.iter().map(|session| session.approximate_size()).sum();
+
+    mem::size_of::<Domain>() + urls + records + pages + checks + sessions
+  }
+}
+
+/// A single finished crawl of the host. Sessions are identified by their position in the
+/// history of the host.
+#[derive(Clone)]
+pub struct Session {
+  /// The id of the job which carried out the crawl.
+  pub job: usize,
+  /// The url the crawl started from.
+  pub url: String,
+  pub started_at: DateTime<Utc>,
+  pub finished_at: DateTime<Utc>,
+  pub pages_crawled: usize,
+  /// How many pages did not respond or responded with an error status.
+  pub pages_failed: usize,
+  pub bytes_downloaded: usize,
+  /// Whether the crawl was stopped early because it ran out of its byte budget, the domain ran
+  /// out of its daily budget of pages or its crawl window closed.
+  pub truncated: bool,
+  /// Why the crawl could not be carried out, if it failed.
+  pub error: Option<String>,
+  /// Every url the crawl found, with the status of its page if the crawl fetched it.
+  pub urls: HashMap<String, Option<u16>>,
+}
+
+impl Session {
+  /// Estimates how many bytes the session takes up in memory, see `Domain::approximate_size`.
+  pub fn approximate_size(&self) -> usize {
+    let strings: usize = Some(&self.url).into_iter()
+      .chain(&self.error)
+      .chain(self.urls.keys())
+      .map(|string| string_size(string))
+      .sum();
+    let statuses = self.urls.len() * mem::size_of::<Option<u16>>();
+
+    mem::size_of::<Session>() + strings + statuses
+  }
+}
+
+/// How many referring pages are remembered for a url. Navigation links appear on every page of a
+/// host, so remembering all of their referrers would take memory quadratic in the size of the host.
+pub const MAX_REFERRERS: usize = 100;
+
+/// The history of a single url of the host.
+#[derive(Clone)]
+pub struct UrlRecord {
+  /// When the url was first found.
+  pub first_seen: DateTime<Utc>,
+  /// When the url was last found, either linked from a page or fetched itself.
+  pub last_seen: DateTime<Utc>,
+  /// The fewest links the crawler followed from the url a crawl started at to find the url.
+  /// The starting url itself has depth zero.
+  pub depth: usize,
+  /// Pages of the host the url was found on, up to `MAX_REFERRERS` of them. Empty for urls the
+  /// crawl started at.
+  pub referrers: BTreeSet<String>,
+  /// A note the users attached to the url, e.g. why it is broken.
+  pub annotation: Option<String>,
+  /// How far the crawler got with the url.
+  pub state: UrlState,
+}
+
+/// The lifecycle of a url. Urls are discovered on pages and queued to be fetched. A queued url
+/// is either fetched, fails to load, or is skipped if the crawl ends before getting to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UrlState {
+  Discovered,
+  Queued,
+  /// The page of the url loaded with a successful or redirect status.
+  Fetched,
+  /// The url did not respond or responded with an error status.
+  Failed,
+  /// The crawl ran out of its page limit or byte budget before fetching the url.
+  Skipped,
+}
+
+impl UrlState {
+  pub const ALL: [UrlState; 5] = [
+    UrlState::Discovered, UrlState::Queued, UrlState::Fetched, UrlState::Failed, UrlState::Skipped,
+  ];
+
+  /// The name of the state as used in requests and responses.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      UrlState::Discovered => "discovered",
+      UrlState::Queued => "queued",
+      UrlState::Fetched => "fetched",
+      UrlState::Failed => "failed",
+      UrlState::Skipped => "skipped",
+    }
+  }
+
+  /// The state of a url once its page was fetched.
+  pub fn of_page(page: &Page) -> UrlState {
+    if page.status.is_some_and(|status| status < 400) {
+      UrlState::Fetched
+    } else {
+      UrlState::Failed
+    }
+  }
+}
+
+impl UrlRecord {
+  /// Creates the record of a url found just now at given depth.
+  pub fn new(seen_at: DateTime<Utc>, depth: usize) -> UrlRecord {
+    UrlRecord {
+      first_seen: seen_at,
+      last_seen: seen_at,
+      depth,
+      referrers: BTreeSet::new(),
+      annotation: None,
+      state: UrlState::Discovered,
+    }
+  }
+
+  /// Records that the url was found again at given depth, linked from the referring page if any.
+  pub fn found(&mut self, seen_at: DateTime<Utc>, depth: usize, referrer: Option<&String>) {
+    self.last_seen = seen_at;
+    self.depth = self.depth.min(depth);
+
+    if let Some(referrer) = referrer {
+      if self.referrers.len() < MAX_REFERRERS && !self.referrers.contains(referrer) {
+        self.referrers.insert(referrer.clone());
+      }
+    }
+  }
+
+  /// Estimates how many bytes the record takes up in memory, see `Domain::approximate_size`.
+  pub fn approximate_size(&self) -> usize {
+    let referrers: usize = self.referrers.iter()
+      .chain(&self.annotation)
+      .map(|string| string_size(string))
+      .sum();
+
+    mem::size_of::<UrlRecord>() + referrers
+  }
+}
+
+/// How a url listed in the sitemap responded to a HEAD request.
+#[derive(Clone, Default)]
+pub struct SitemapCheck {
+  /// The status code of the response the redirects ended at. None if the url did not respond.
+  pub status: Option<u16>,
+  /// Urls the request was redirected through, in order. Empty if the url did not redirect.
+  pub redirects: Vec<String>,
+}
+
+impl SitemapCheck {
+  /// Estimates how many bytes the check takes up in memory, see `Domain::approximate_size`.
+  pub fn approximate_size(&self) -> usize {
+    let redirects: usize = self.redirects.iter().map(|url| string_size(url)).sum();
+
+    mem::size_of::<SitemapCheck>() + redirects
+  }
+}
+
+/// Details the crawler recorded while fetching a single url.
+#[derive(Clone, Default)]
+pub struct Page {
+  /// When the url was last fetched.
+  pub fetched_at: Option<DateTime<Utc>>,
+  /// The status code of the response the redirects ended at.
+  pub status: Option<u16>,
+  /// Urls the request was redirected through, in order. The last one is where the chain ended.
+  /// Empty if the url did not redirect.
+  pub redirects: Vec<String>,
+  /// Links to other hosts found on the page which did not respond successfully. Only checked
+  /// if the `check_outbound_links` setting is enabled.
+  pub broken_links: Vec<String>,
+  /// The title of the page.
+  pub title: Option<String>,
+  /// The content of the description meta tag of the page.
+  pub description: Option<String>,
+  /// Sources of the images on the page without alt text. Only recorded if the `check_image_alt`
+  /// setting is enabled.
+  pub images_missing_alt: Vec<String>,
+  /// The h1, h2 and h3 headings of the page in the document order.
+  pub headings: Vec<Heading>,
+  /// The canonical url the page declares.
+  pub canonical: Option<String>,
+  /// The status code the canonical url responds with. Only checked if the canonical url is not
+  /// the url the page was loaded from.
+  pub canonical_status: Option<u16>,
+  /// The url of the AMP variant of the page it declares with an amphtml link.
+  pub amp: Option<String>,
+  /// Whether the page is itself an AMP variant, whose canonical url is the regular page.
+  pub is_amp: bool,
+  /// Language alternates of the page declared with hreflang links.
+  pub alternates: Vec<Alternate>,
+  /// Whether a robots meta tag excludes the page from search engine indexes.
+  pub noindex_meta: bool,
+  /// Whether the X-Robots-Tag header excludes the page from search engine indexes.
+  pub noindex_header: bool,
+  /// Size of the response body in bytes. Only recorded for pages which loaded successfully.
+  pub size: Option<usize>,
+  /// Milliseconds it took until the headers of the final response arrived, including redirects.
+  pub ttfb_ms: Option<u64>,
+  /// Milliseconds it took to fetch the page including its body.
+  pub duration_ms: Option<u64>,
+  /// Plain http assets an https page loads. Always empty for http pages.
+  pub insecure_assets: Vec<String>,
+  /// Plain http links and form actions of an https page. Always empty for http pages.
+  pub insecure_links: Vec<String>,
+  /// Schema.org types the page declares with JSON-LD or microdata, in alphabetical order.
+  pub schema_types: Vec<String>,
+  /// Whether the body was cut off or not downloaded at all as it did not fit in the byte budget
+  /// of the job. The links of such pages are not followed.
+  pub truncated: bool,
+  /// Which of the security headers listed in `extract::SECURITY_HEADERS` the page was served
+  /// with.
+  pub security_headers: Vec<String>,
+  /// Hosts of other parties the page loads assets from, in alphabetical order.
+  pub asset_hosts: Vec<String>,
+  /// Hosts of other parties the page links to, in alphabetical order.
+  pub linked_hosts: Vec<String>,
+  /// Email addresses published on the page, if contact extraction is enabled.
+  pub emails: Vec<String>,
+  /// Phone numbers published on the page, if contact extraction is enabled.
+  pub phones: Vec<String>,
+  /// Forms on the page in the document order.
+  pub forms: Vec<Form>,
+  /// Names of the well-known analytics and tracking services the page embeds, in alphabetical
+  /// order.
+  pub trackers: Vec<String>,
+  /// Data the page script extracted from the page, if a script is configured.
+  pub script_data: BTreeMap<String, String>,
+  /// Fields extracted from the page by the extraction rules of its host.
+  pub fields: BTreeMap<String, String>,
+  /// Urls of the host the page declares to be the next or the previous page of its listing,
+  /// which the crawl fetches before other urls.
+  pub pagination: Vec<String>,
+}
+
+impl Page {
+  /// The url the page was loaded from, i.e. where its redirects ended.
+  pub fn final_url<'a>(&'a self, url: &'a str) -> &'a str {
+    self.redirects.last().map(|url| url.as_str()).unwrap_or(url)
+  }
+
+  /// Estimates how many bytes the page takes up in memory, see `Domain::approximate_size`.
+  pub fn approximate_size(&self) -> usize {
+    let strings = self.redirects.iter()
+      .chain(&self.broken_links)
+      .chain(&self.images_missing_alt)
+      .chain(&self.insecure_assets)
+      .chain(&self.insecure_links)
+      .chain(&self.schema_types)
+      .chain(&self.security_headers)
+      .chain(&self.asset_hosts)
+      .chain(&self.linked_hosts)
+      .chain(&self.emails)
+      .chain(&self.phones)
+      .chain(&self.trackers)
+      .chain(&self.title)
+      .chain(&self.description)
+      .chain(&self.canonical)
+      .chain(&self.amp)
+      .chain(self.headings.iter().map(|heading| &heading.text))
+      .chain(self.alternates.iter().flat_map(|alternate| vec!(&alternate.lang, &alternate.url)))
+      .chain(self.forms.iter().flat_map(|form| vec!(&form.action, &form.method)))
+      .chain(self.script_data.iter().flat_map(|(name, value)| vec!(name, value)))
+      .chain(self.fields.iter().flat_map(|(name, value)| vec!(name, value)))
+      .chain(&self.pagination)
+      .map(|string| string_size(string))
+      .sum::<usize>();
+
+    mem::size_of::<Page>() + strings
+  }
+
+  /// Whether the page loaded successfully, i.e. it has content to be analyzed.
+  pub fn is_success(&self) -> bool {
+    match self.status {
+      Some(status) => (200..300).contains(&status),
+      None => false,
+    }
+  }
+}
+
+#[derive(Clone, Serialize)]
+pub struct Heading {
+  /// The level of the heading, from 1 to 3.
+  pub level: u8,
+  /// The text of the heading.
+  pub text: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Form {
+  /// The url the form submits to, resolved against the url of the page. Forms without an action
+  /// submit to the page itself.
+  pub action: String,
+  /// The HTTP method of the form, uppercased.
+  pub method: String,
+  /// Whether the form submits over plain http, exposing what users type into it.
+  pub insecure: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Alternate {
+  /// The language code of the alternate, lowercased.
+  pub lang: String,
+  /// The url of the alternate.
+  pub url: String,
+}
+
+/// The size of a string including its heap allocation.
+fn string_size(string: &str) -> usize {
+  mem::size_of::<String>() + string.len()
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use super::export;
+use super::Database;
+use super::config::Elasticsearch;
+use super::database::Domain;
+use reqwest::Client;
+use openssl::sha::sha256;
+
+/// How many documents are sent in a single bulk request.
+const BULK_SIZE: usize = 500;
+
+/// A url of a crawled domain as it is indexed.
+#[derive(Serialize)]
+struct Document<'a> {
+  domain: &'a str,
+  url: &'a str,
+  /// The id of the job which last found the url.
+  job: usize,
+  crawled_at: String,
+  status: Option<u16>,
+  state: Option<&'static str>,
+  depth: Option<usize>,
+  first_seen: Option<String>,
+  last_seen: Option<String>,
+  title: Option<&'a str>,
+  description: Option<&'a str>,
+  /// The text of the h1, h2 and h3 headings of the page in the document order.
+  headings: Vec<&'a str>,
+  annotation: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct BulkResponse {
+  errors: bool,
+  #[serde(default)]
+  items: Vec<serde_json::Value>,
+}
+
+/// Indexes the urls the crawl of given job found on given host, together with the titles and
+/// the headings of their pages. Every url is a document with an id derived from the url, so that
+/// later crawls update the documents of the urls they find again.
+pub fn index(
+  client: &Client,
+  search: &Elasticsearch,
+  db: &Database,
+  host: &str,
+  job: usize,
+) -> Result<(), String> {
+  // The bulk bodies are built under the lock, but not sent while holding it, so that the api can
+  // read meanwhile.
+  let bodies = {
+    // TODO: Error handling the mutex.
+    let db = db.lock().unwrap();
+
+    match db.get(host) {
+      Some(record) => bulk_bodies(&search.index, record, host, job)?,
+      None => return Ok(()),
+    }
+  };
+
+  for body in bodies {
+    let mut request = client.post(&format!("{}/_bulk", search.url))
+      .header("content-type", "application/x-ndjson");
+
+    if let Some(username) = &search.username {
+      request = request.basic_auth(username, search.password.as_ref());
+    }
+
+    let mut response = request.body(body).send().map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+      return Err(format!("The index responded with {}", response.status()));
+    }
+
+    // Bulk requests succeed even if some of their documents were rejected.
+    let response: BulkResponse = response.json().map_err(|e| e.to_string())?;
+
+    if response.errors {
+      let reason = response.items.iter()
+        .filter_map(|item| item.get("index")?.get("error"))
+        .map(|error| error.to_string())
+        .next()
+        .unwrap_or_default();
+
+      return Err(format!("The index rejected some documents: {}", reason));
+    }
+  }
+
+  Ok(())
+}
+
+/// Builds the bodies of the bulk requests indexing the urls found by given job, at most
+/// `BULK_SIZE` urls per body. Nothing is indexed if the crawl was not recorded.
+fn bulk_bodies(
+  index: &str,
+  record: &Domain,
+  host: &str,
+  job: usize,
+) -> Result<Vec<String>, String> {
+  let session = match record.sessions.iter().find(|session| session.job == job) {
+    Some(session) => session,
+    None => return Ok(vec!()),
+  };
+
+  let mut urls: Vec<&String> = session.urls.keys().collect();
+  urls.sort();
+
+  urls.chunks(BULK_SIZE)
+    .map(|urls| {
+      let mut body = String::new();
+
+      for url in urls {
+        let history = record.records.get(*url);
+        let page = record.pages.get(*url);
+        let document = Document {
+          domain: host,
+          url,
+          job,
+          crawled_at: session.finished_at.to_rfc3339(),
+          status: page.and_then(|page| page.status).or_else(|| session.urls[*url]),
+          state: history.map(|history| history.state.as_str()),
+          depth: history.map(|history| history.depth),
+          first_seen: history.map(|history| history.first_seen.to_rfc3339()),
+          last_seen: history.map(|history| history.last_seen.to_rfc3339()),
+          title: page.and_then(|page| page.title.as_deref()),
+          description: page.and_then(|page| page.description.as_deref()),
+          headings: page.map_or(vec!(), |page| {
+            page.headings.iter().map(|heading| heading.text.as_str()).collect()
+          }),
+          annotation: history.and_then(|history| history.annotation.as_deref()),
+        };
+        let action = serde_json::json!({ "index": { "_index": index, "_id": id(url) } });
+
+        body.push_str(&action.to_string());
+        body.push('\n');
+        body.push_str(&serde_json::to_string(&document).map_err(|e| e.to_string())?);
+        body.push('\n');
+      }
+
+      Ok(body)
+    })
+    .collect()
+}
+
+/// The id of the document of given url. Urls can be longer than ids may be, so they are hashed.
+fn id(url: &str) -> String {
+  export::hex(&sha256(url.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+  use super::{bulk_bodies, id};
+  use super::super::database::{Domain, Heading, Page, Session, UrlRecord};
+  use chrono::Utc;
+  use serde_json::Value;
+
+  #[test]
+  fn test_bulk_bodies() {
+    let mut domain = Domain::default();
+    let page = Page {
+      status: Some(200),
+      title: Some(String::from("GitHub")),
+      headings: vec!(Heading { level: 1, text: String::from("Build software") }),
+      ..Default::default()
+    };
+    domain.pages.insert(String::from("https://github.com"), page);
+    domain.records.insert(String::from("https://github.com"), UrlRecord::new(Utc::now(), 0));
+    domain.sessions.push(Session {
+      job: 3,
+      url: String::from("https://github.com"),
+      started_at: Utc::now(),
+      finished_at: Utc::now(),
+      pages_crawled: 1,
+      pages_failed: 0,
+      bytes_downloaded: 100,
+      truncated: false,
+      error: None,
+      urls: vec!(
+        (String::from("https://github.com"), Some(200)),
+        (String::from("https://github.com/about"), None),
+      ).into_iter().collect(),
+    });
+
+    let bodies = bulk_bodies("crawler", &domain, "github.com", 3).unwrap();
+    assert_eq!(bodies.len(), 1);
+
+    let lines: Vec<Value> = bodies[0].lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0]["index"]["_index"], "crawler");
+    assert_eq!(lines[0]["index"]["_id"], id("https://github.com"));
+    assert_eq!(lines[1]["title"], "GitHub");
+    assert_eq!(lines[1]["status"], 200);
+    assert_eq!(lines[1]["state"], "discovered");
+    assert_eq!(lines[1]["headings"][0], "Build software");
+    assert_eq!(lines[3]["url"], "https://github.com/about");
+    assert_eq!(lines[3]["title"], Value::Null);
+
+    // Jobs which did not finish have nothing to index.
+    assert!(bulk_bodies("crawler", &domain, "github.com", 4).unwrap().is_empty());
+  }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Ids handed out to requests, so that errors reported by clients can be matched to requests.
+static NEXT_REQUEST_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The id of a request, assigned the first time it is asked for.
+struct RequestId(usize);
+
+/// A failed request. Responds with a JSON body carrying a machine readable error code, a human
+/// readable message and the id of the request, e.g.
+/// `{"error":"job_not_found","message":"There is no job with id 3","request_id":17}`.
+#[derive(Debug)]
+pub struct ApiError {
+  status: Status,
+  code: &'static str,
+  message: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+  error: &'static str,
+  message: String,
+  request_id: usize,
+}
+
+impl ApiError {
+  pub fn new(status: Status, code: &'static str, message: impl Into<String>) -> ApiError {
+    ApiError { status, code, message: message.into() }
+  }
+
+  /// The error for a poisoned lock of the database or another shared state.
+  pub fn lock() -> ApiError {
+    ApiError::new(Status::InternalServerError, "internal_error", "The service state is unavailable")
+  }
+
+  /// The HTTP status of the response.
+  pub fn status(&self) -> Status {
+    self.status
+  }
+
+  /// The human readable description of the error.
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+
+  /// The error for a bare status returned by a route or produced by Rocket itself.
+  fn from_status(status: Status) -> ApiError {
+    let code = match status.code {
+      400 => "bad_request",
+      403 => "forbidden",
+      404 => "not_found",
+      415 => "unsupported_media_type",
+      422 => "unprocessable_entity",
+      503 => "service_unavailable",
+      _ => "internal_error",
+    };
+
+    ApiError::new(status, code, status.reason)
+  }
+}
+
+impl<'r> Responder<'r> for ApiError {
+  fn respond_to(self, request: &Request) -> response::Result<'r> {
+    let body = ErrorBody {
+      error: self.code,
+      message: self.message,
+      request_id: request_id(request),
+    };
+
+    Response::build_from(Json(body).respond_to(request)?).status(self.status).ok()
+  }
+}
+
+/// Finds the id of given request.
+fn request_id(request: &Request) -> usize {
+  request.local_cache(|| RequestId(NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed))).0
+}
+
+/// Tags every response with the id of its request in the X-Request-Id header.
+pub fn request_ids() -> impl Fairing {
+  AdHoc::on_response("Request id", |request, response| {
+    response.set_raw_header("X-Request-Id", request_id(request).to_string());
+  })
+}
+
+// Routes which fail with a bare status end up in one of these, so that every error has a JSON
+// body.
+
+#[catch(400)]
+pub fn bad_request(_: &Request) -> ApiError {
+  ApiError::from_status(Status::BadRequest)
+}
+
+#[catch(403)]
+pub fn forbidden(_: &Request) -> ApiError {
+  ApiError::from_status(Status::Forbidden)
+}
+
+#[catch(404)]
+pub fn not_found(_: &Request) -> ApiError {
+  ApiError::from_status(Status::NotFound)
+}
+
+#[catch(415)]
+pub fn unsupported_media_type(_: &Request) -> ApiError {
+  ApiError::from_status(Status::UnsupportedMediaType)
+}
+
+#[catch(422)]
+pub fn unprocessable_entity(_: &Request) -> ApiError {
+  ApiError::from_status(Status::UnprocessableEntity)
+}
+
+#[catch(500)]
+pub fn internal_error(_: &Request) -> ApiError {
+  ApiError::from_status(Status::InternalServerError)
+}
+
+#[catch(503)]
+pub fn service_unavailable(_: &Request) -> ApiError {
+  ApiError::from_status(Status::ServiceUnavailable)
+}
@@ -0,0 +1,1049 @@
+use url::Url;
+use serde_json::Value;
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer};
+use std::sync::{Mutex, OnceLock};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use super::database::{Alternate, Form, Heading};
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Selector};
+
+/// Selectors compiled so far, keyed by their source. Pages are parsed by several threads at once.
+static SELECTORS: OnceLock<Mutex<HashMap<&'static str, Option<&'static Selector>>>> =
+  OnceLock::new();
+
+/// Compiles the selector the first time it is used and reuses it for all following pages instead
+/// of compiling it for every page again. Compiled selectors are kept for the rest of the program,
+/// which is fine as only a fixed set of selectors is ever used. Returns None if the selector is
+/// invalid.
+pub fn cached_selector(css: &'static str) -> Option<&'static Selector> {
+  let mut selectors = SELECTORS.get_or_init(Default::default).lock().ok()?;
+
+  *selectors.entry(css).or_insert_with(|| {
+    Selector::parse(css).ok().map(|selector| &*Box::leak(Box::new(selector)))
+  })
+}
+
+/// Finds the links of pages, so that other ways of finding them can be plugged into the crawler
+/// in place of the built in ones, e.g. for sites which build their links with scripts.
+pub trait LinkExtractor: Sync {
+  /// Returns the href of the base element if the page has one, together with the targets of all
+  /// links, frames and meta refreshes on the page in the document order. The DOM of the page is
+  /// given if the crawler built it to record the details of the page. Returns None if the links
+  /// could not be extracted.
+  fn extract(&self, body: &str, dom: Option<&Html>) -> Option<(Option<String>, Vec<String>)>;
+}
+
+/// Finds the links in the DOM of the page, building it if the crawler did not.
+pub struct DomExtractor;
+
+impl LinkExtractor for DomExtractor {
+  fn extract(&self, body: &str, dom: Option<&Html>) -> Option<(Option<String>, Vec<String>)> {
+    match dom {
+      Some(dom) => Some((base_href(dom), links(dom)?)),
+      None => {
+        let dom = Html::parse_document(body);
+
+        Some((base_href(&dom), links(&dom)?))
+      },
+    }
+  }
+}
+
+/// Finds the links by tokenizing the page, see `hrefs`.
+pub struct TokenExtractor;
+
+impl LinkExtractor for TokenExtractor {
+  fn extract(&self, body: &str, _dom: Option<&Html>) -> Option<(Option<String>, Vec<String>)> {
+    Some(hrefs(body))
+  }
+}
+
+/// Collects the href attributes of all links, the sources of all frames and the targets of meta
+/// refreshes on the page in the document order, together with the href of the base element if
+/// the page has one. Unlike the other extractors, it only tokenizes the page instead of building
+/// its DOM, which is considerably cheaper for large pages.
+pub fn hrefs(body: &str) -> (Option<String>, Vec<String>) {
+  let mut queue = BufferQueue::new();
+  queue.push_back(StrTendril::from(body));
+
+  let mut tokenizer = Tokenizer::new(HrefSink::default(), Default::default());
+  // The tokenizer only stops early for scripts to be executed, which the sink never asks for.
+  let _ = tokenizer.feed(&mut queue);
+  tokenizer.end();
+
+  (tokenizer.sink.base, tokenizer.sink.hrefs)
+}
+
+/// Receives tokens of a page and keeps the href attributes of links and of the base element, the
+/// sources of frames and the targets of meta refreshes.
+#[derive(Default)]
+struct HrefSink {
+  base: Option<String>,
+  hrefs: Vec<String>,
+}
+
+impl TokenSink for HrefSink {
+  type Handle = ();
+
+  fn process_token(&mut self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+    let (name, attrs) = match token {
+      Token::TagToken(Tag { kind: TagKind::StartTag, name, attrs, .. }) => (name, attrs),
+      _ => return TokenSinkResult::Continue,
+    };
+
+    let attr = |name: &str| {
+      attrs.iter().find(|attr| &*attr.name.local == name).map(|attr| attr.value.to_string())
+    };
+
+    // Without a tree builder, the tokenizer has to be told about elements whose content is not
+    // markup, otherwise e.g. links in script strings would be mistaken for real ones.
+    match &*name {
+      "a" => {
+        self.hrefs.extend(attr("href"));
+
+        TokenSinkResult::Continue
+      },
+      "frame" => {
+        self.hrefs.extend(attr("src"));
+
+        TokenSinkResult::Continue
+      },
+      "iframe" => {
+        self.hrefs.extend(attr("src"));
+
+        TokenSinkResult::RawData(RawKind::Rawtext)
+      },
+      "meta" if attr("http-equiv").map(|value| is_refresh(&value)).unwrap_or(false) => {
+        self.hrefs.extend(attr("content").and_then(|content| refresh_target(&content)));
+
+        TokenSinkResult::Continue
+      },
+      // Only the first base element counts.
+      "base" if self.base.is_none() => {
+        self.base = attr("href");
+
+        TokenSinkResult::Continue
+      },
+      "script" => TokenSinkResult::RawData(RawKind::ScriptData),
+      "style" | "xmp" | "noembed" | "noframes" => {
+        TokenSinkResult::RawData(RawKind::Rawtext)
+      },
+      "title" | "textarea" => TokenSinkResult::RawData(RawKind::Rcdata),
+      _ => TokenSinkResult::Continue,
+    }
+  }
+}
+
+/// Collects the href attributes of all links, the sources of all frames and the targets of meta
+/// refreshes on the page in the document order. Framed sites keep their content in the frame
+/// sources and pages which refresh to another url are effectively redirects, so these are
+/// crawled as any other linked page.
+pub fn links(dom: &Html) -> Option<Vec<String>> {
+  let selector = cached_selector(LINK_SELECTOR)?;
+
+  Some(dom.select(selector).filter_map(|node| link_target(node.value())).collect())
+}
+
+/// Collects the links like `links` does, but only the ones inside of elements matching given
+/// scope, or matching it themselves. Meta refreshes are always kept, as they redirect the page
+/// rather than link from some part of it.
+pub fn scoped_links(dom: &Html, scope: &Selector) -> Option<Vec<String>> {
+  let selector = cached_selector(LINK_SELECTOR)?;
+
+  Some(dom.select(selector)
+    .filter(|node| {
+      node.value().name() == "meta" || scope.matches(node) ||
+        node.ancestors().filter_map(ElementRef::wrap).any(|ancestor| scope.matches(&ancestor))
+    })
+    .filter_map(|node| link_target(node.value()))
+    .collect())
+}
+
+/// Elements which link to other pages, see `link_target`.
+const LINK_SELECTOR: &str = "a[href], frame[src], iframe[src], meta[http-equiv][content]";
+
+/// The target of a link, frame or meta refresh.
+fn link_target(element: &Element) -> Option<String> {
+  match element.name() {
+    "a" => element.attr("href").map(String::from),
+    "meta" if element.attr("http-equiv").map(is_refresh).unwrap_or(false) => {
+      refresh_target(element.attr("content")?)
+    },
+    "meta" => None,
+    _ => element.attr("src").map(String::from),
+  }
+}
+
+/// Checks whether the http-equiv attribute of a meta element makes it a refresh.
+fn is_refresh(http_equiv: &str) -> bool {
+  http_equiv.trim().eq_ignore_ascii_case("refresh")
+}
+
+/// Parses the url out of the content of a meta refresh in the `<delay>; url=<url>` format the way
+/// browsers do, tolerating a missing `url=` prefix and quotes around the url. Returns None if the
+/// page only refreshes itself.
+pub fn refresh_target(content: &str) -> Option<String> {
+  let content = content.trim_start();
+  let rest = content.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+  // The delay is mandatory.
+  if rest.len() == content.len() {
+    return None;
+  }
+
+  let rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ';' || c == ',');
+  let rest = match rest.get(..3) {
+    Some(prefix) if prefix.eq_ignore_ascii_case("url") => {
+      match rest[3..].trim_start().strip_prefix('=') {
+        Some(url) => url.trim_start(),
+        None => rest,
+      }
+    },
+    _ => rest,
+  };
+
+  let url = match rest.chars().next() {
+    Some(quote) if quote == '"' || quote == '\'' => rest[1..].split(quote).next().unwrap_or(""),
+    _ => rest,
+  };
+
+  Some(url.trim().to_string()).filter(|url| !url.is_empty())
+}
+
+/// Finds the href of the base element, against which browsers resolve relative links of the page.
+/// Only the first base element counts.
+pub fn base_href(dom: &Html) -> Option<String> {
+  let selector = cached_selector("base[href]")?;
+
+  dom.select(selector).next()?.value().attr("href").map(String::from)
+}
+
+/// Finds the title of the page. Whitespace in the title is collapsed as browsers do when
+/// displaying it. Returns None if the page has no title or it is empty.
+pub fn title(dom: &Html) -> Option<String> {
+  let selector = cached_selector("title")?;
+  let node = dom.select(selector).next()?;
+
+  non_empty(collapse_whitespace(&node.text().collect::<String>()))
+}
+
+/// Finds the content of the description meta tag. Returns None if the page has no description
+/// or it is empty.
+pub fn description(dom: &Html) -> Option<String> {
+  let selector = cached_selector("meta[name=description]")?;
+  let node = dom.select(selector).next()?;
+
+  non_empty(collapse_whitespace(node.value().attr("content")?))
+}
+
+/// Extracts the fields of given rules, pairs of a field name and a selector, from the page. A
+/// field is the text of the first element its selector matches, or the `content` attribute of
+/// elements without text, such as meta tags. A field with several selectors gets the value of
+/// the first one which matches. Fields no selector matches are left out.
+pub fn fields(dom: &Html, rules: &[(String, Selector)]) -> BTreeMap<String, String> {
+  let mut fields = BTreeMap::new();
+
+  for (field, selector) in rules {
+    if fields.contains_key(field) {
+      continue;
+    }
+
+    let value = dom.select(selector).next().and_then(|node| {
+      non_empty(collapse_whitespace(&node.text().collect::<String>()))
+        .or_else(|| non_empty(collapse_whitespace(node.value().attr("content")?)))
+    });
+
+    if let Some(value) = value {
+      fields.insert(field.clone(), value);
+    }
+  }
+
+  fields
+}
+
+/// Lists the sources of all images on the page which lack the alt attribute. An empty alt
+/// attribute is valid as it marks decorative images.
+pub fn images_missing_alt(dom: &Html) -> Vec<String> {
+  let selector = match cached_selector("img:not([alt])") {
+    Some(selector) => selector,
+    None => return vec!(),
+  };
+
+  dom.select(selector)
+    .map(|node| node.value().attr("src").unwrap_or_default().to_string())
+    .collect()
+}
+
+/// Lists the h1, h2 and h3 headings of the page in the document order.
+pub fn headings(dom: &Html) -> Vec<Heading> {
+  let selector = match cached_selector("h1, h2, h3") {
+    Some(selector) => selector,
+    None => return vec!(),
+  };
+
+  dom.select(selector)
+    .map(|node| Heading {
+      // Unwrap is safe as the selector only matches the three heading elements.
+      level: node.value().name()[1..].parse().unwrap(),
+      text: collapse_whitespace(&node.text().collect::<String>()),
+    })
+    .collect()
+}
+
+/// Finds the canonical url the page declares, resolved against the url of the page.
+pub fn canonical(dom: &Html, page_url: &Url) -> Option<Url> {
+  let selector = cached_selector("link[rel=canonical]")?;
+  let href = dom.select(selector).next()?.value().attr("href")?;
+
+  page_url.join(href.trim()).ok()
+}
+
+/// Finds the url of the AMP variant the page declares with an amphtml link, resolved against the
+/// url of the page.
+pub fn amphtml(dom: &Html, page_url: &Url) -> Option<Url> {
+  let selector = cached_selector("link[rel=amphtml][href]")?;
+  let href = dom.select(selector).next()?.value().attr("href")?;
+
+  page_url.join(href.trim()).ok()
+}
+
+/// Checks whether the page is an AMP page, which its html element marks with either the `amp` or
+/// the `⚡` attribute.
+pub fn is_amp(dom: &Html) -> bool {
+  let html = dom.root_element().value();
+
+  html.attr("amp").is_some() || html.attr("⚡").is_some()
+}
+
+/// Collects the hrefs of the links and anchors which point to the next or the previous page of a
+/// paginated listing, with `rel="next"` or `rel="prev"`, in the document order.
+pub fn pagination(dom: &Html) -> Vec<String> {
+  let selector = match cached_selector("link[rel][href], a[rel][href]") {
+    Some(selector) => selector,
+    None => return vec!(),
+  };
+
+  dom.select(selector)
+    .filter(|node| {
+      // Unwrap is safe as the selector only matches elements with the attribute.
+      node.value().attr("rel").unwrap().split_whitespace().any(|rel| {
+        ["next", "prev", "previous"].iter().any(|pagination| rel.eq_ignore_ascii_case(pagination))
+      })
+    })
+    .filter_map(|node| node.value().attr("href").map(String::from))
+    .collect()
+}
+
+/// Lists the language alternates the page declares with hreflang links, resolved against the url
+/// of the page.
+pub fn alternates(dom: &Html, page_url: &Url) -> Vec<Alternate> {
+  let selector = match cached_selector("link[rel=alternate][hreflang]") {
+    Some(selector) => selector,
+    None => return vec!(),
+  };
+
+  dom.select(selector)
+    .filter_map(|node| {
+      let href = node.value().attr("href")?;
+
+      Some(Alternate {
+        // Unwrap is safe as the selector only matches links with the attribute.
+        lang: node.value().attr("hreflang").unwrap().trim().to_lowercase(),
+        url: page_url.join(href.trim()).ok()?.as_str().to_string(),
+      })
+    })
+    .collect()
+}
+
+/// Lists the forms on the page in the document order. Forms with an unparseable action are
+/// skipped.
+pub fn forms(dom: &Html, page_url: &Url) -> Vec<Form> {
+  let selector = match cached_selector("form") {
+    Some(selector) => selector,
+    None => return vec!(),
+  };
+
+  dom.select(selector)
+    .filter_map(|node| {
+      let action = page_url.join(node.value().attr("action").unwrap_or_default().trim()).ok()?;
+      // Browsers fall back to GET for missing or unknown methods.
+      let method = match node.value().attr("method").map(|method| method.trim().to_uppercase()) {
+        Some(method) if method == "POST" || method == "DIALOG" => method,
+        _ => String::from("GET"),
+      };
+
+      Some(Form {
+        insecure: action.scheme() == "http",
+        action: action.as_str().to_string(),
+        method,
+      })
+    })
+    .collect()
+}
+
+/// Checks whether a robots meta tag of the page excludes it from search engine indexes.
+pub fn has_noindex_meta(dom: &Html) -> bool {
+  let selector = match cached_selector("meta[name]") {
+    Some(selector) => selector,
+    None => return false,
+  };
+
+  dom.select(selector)
+    .filter(|node| {
+      // Robots meta tags can also be addressed to a single crawler such as googlebot.
+      let name = node.value().attr("name").unwrap_or_default().to_lowercase();
+
+      name == "robots" || name == "googlebot" || name == "bingbot"
+    })
+    .filter_map(|node| node.value().attr("content"))
+    .any(is_noindex)
+}
+
+/// Checks whether the value of a robots meta tag or X-Robots-Tag header contains the noindex
+/// directive. The header value can be prefixed with the name of the crawler it is addressed to.
+pub fn is_noindex(directives: &str) -> bool {
+  directives
+    .split([',', ':'])
+    .map(|directive| directive.trim().to_lowercase())
+    .any(|directive| directive == "noindex" || directive == "none")
+}
+
+/// Response headers which protect pages against common attacks such as clickjacking, content
+/// injection or protocol downgrades.
+pub const SECURITY_HEADERS: &[&str] = &[
+  "content-security-policy",
+  "strict-transport-security",
+  "x-frame-options",
+  "x-content-type-options",
+];
+
+/// Elements and their attributes which load assets into the page.
+const ASSET_ATTRIBUTES: &[(&str, &str)] = &[
+  ("img[src]", "src"),
+  ("script[src]", "src"),
+  ("link[href][rel~=stylesheet]", "href"),
+  ("link[href][rel~=icon]", "href"),
+  ("iframe[src]", "src"),
+  ("audio[src]", "src"),
+  ("video[src]", "src"),
+  ("source[src]", "src"),
+  ("embed[src]", "src"),
+  ("object[data]", "data"),
+];
+
+/// Finds references to plain http urls. Returns the assets loaded by the page and the links
+/// pointing away from it separately, as only the former break the security of an https page.
+pub fn insecure_references(dom: &Html) -> (Vec<String>, Vec<String>) {
+  let is_insecure = |value: &str| value.trim().to_lowercase().starts_with("http://");
+
+  let mut assets: BTreeSet<String> = BTreeSet::new();
+
+  for (selector, attribute) in ASSET_ATTRIBUTES {
+    if let Some(selector) = cached_selector(selector) {
+      assets.extend(dom.select(selector)
+        .filter_map(|node| node.value().attr(attribute))
+        .filter(|value| is_insecure(value))
+        .map(|value| value.trim().to_string()));
+    }
+  }
+
+  let links: BTreeSet<String> = match cached_selector("a[href], form[action]") {
+    Some(selector) => dom.select(selector)
+      .filter_map(|node| node.value().attr("href").or_else(|| node.value().attr("action")))
+      .filter(|value| is_insecure(value))
+      .map(|value| value.trim().to_string())
+      .collect(),
+    None => BTreeSet::new(),
+  };
+
+  (assets.into_iter().collect(), links.into_iter().collect())
+}
+
+/// Finds the hosts other than the page's own host and its subdomains which the page loads assets
+/// from or links to. Returns the asset hosts and the link hosts separately, in alphabetical order.
+pub fn third_party_hosts(dom: &Html, url: &Url) -> (Vec<String>, Vec<String>) {
+  let own_host = url.host_str().unwrap_or_default().to_lowercase();
+  let host_of = |value: &str| {
+    let referenced = url.join(value.trim()).ok()?;
+    let host = referenced.host_str()?;
+
+    if (referenced.scheme() != "http" && referenced.scheme() != "https")
+      || host == own_host
+      || host.ends_with(&format!(".{}", own_host)) {
+      return None;
+    }
+
+    Some(host.to_string())
+  };
+
+  let mut assets: BTreeSet<String> = BTreeSet::new();
+
+  for (selector, attribute) in ASSET_ATTRIBUTES {
+    if let Some(selector) = cached_selector(selector) {
+      assets.extend(dom.select(selector)
+        .filter_map(|node| node.value().attr(attribute))
+        .filter_map(host_of));
+    }
+  }
+
+  let links: BTreeSet<String> = match cached_selector("a[href]") {
+    Some(selector) => dom.select(selector)
+      .filter_map(|node| node.value().attr("href"))
+      .filter_map(host_of)
+      .collect(),
+    None => BTreeSet::new(),
+  };
+
+  (assets.into_iter().collect(), links.into_iter().collect())
+}
+
+/// Collects the email addresses and phone numbers published on the page, both from `mailto:` and
+/// `tel:` links and from the text of the page. To avoid mistaking dates or prices for phone
+/// numbers, only numbers in the international format starting with `+` are taken from the text.
+/// Emails are lowercased and phone numbers stripped of everything but digits and the leading `+`.
+/// Returns the emails and the phones separately, in alphabetical order.
+pub fn contacts(dom: &Html) -> (Vec<String>, Vec<String>) {
+  let mut emails: BTreeSet<String> = BTreeSet::new();
+  let mut phones: BTreeSet<String> = BTreeSet::new();
+
+  if let Some(selector) = cached_selector("a[href]") {
+    for href in dom.select(selector).filter_map(|node| node.value().attr("href")) {
+      let href = href.trim();
+
+      if href.len() > 7 && href[..7].eq_ignore_ascii_case("mailto:") {
+        // Mailto links can carry a subject and other fields after the address.
+        emails.extend(href[7..].split('?').next().and_then(as_email));
+      } else if href.len() > 4 && href[..4].eq_ignore_ascii_case("tel:") {
+        phones.extend(as_phone(&href[4..]));
+      }
+    }
+  }
+
+  if let Some(selector) = cached_selector("body") {
+    let text: String = dom.select(selector)
+      .flat_map(|node| node.text())
+      .collect::<Vec<_>>()
+      .join(" ");
+
+    emails.extend(text.split_whitespace().filter_map(as_email));
+
+    // International numbers are a plus sign followed by digits, optionally grouped by spaces,
+    // dashes, dots or parentheses.
+    for (start, _) in text.match_indices('+') {
+      let number: String = text[start..].chars()
+        .take_while(|c| c.is_ascii_digit() || "+ -.()".contains(*c))
+        .collect();
+
+      phones.extend(as_phone(&number));
+    }
+  }
+
+  (emails.into_iter().collect(), phones.into_iter().collect())
+}
+
+/// Validates that the word, stripped of the punctuation around it, looks like an email address.
+fn as_email(word: &str) -> Option<String> {
+  let word = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+  let (local, domain) = word.split_at(word.find('@')?);
+  let domain = &domain[1..];
+  let tld = domain.rsplit('.').next()?;
+
+  let is_local = !local.is_empty()
+    && local.chars().all(|c| c.is_ascii_alphanumeric() || "._%+-".contains(c));
+  let is_domain = domain.contains('.')
+    && domain.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    && tld.len() >= 2
+    && tld.chars().all(|c| c.is_ascii_alphabetic());
+
+  if is_local && is_domain {
+    Some(word.to_lowercase())
+  } else {
+    None
+  }
+}
+
+/// Strips the number of formatting, keeping the leading plus sign. Phone numbers have at least 7
+/// and at most 15 digits.
+fn as_phone(number: &str) -> Option<String> {
+  let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+
+  if digits.len() < 7 || digits.len() > 15 {
+    return None;
+  }
+
+  if number.trim().starts_with('+') {
+    Some(format!("+{}", digits))
+  } else {
+    Some(digits)
+  }
+}
+
+/// Well-known analytics and tracking services and a piece of the url of the script or pixel
+/// through which each of them is embedded.
+const TRACKERS: &[(&str, &str)] = &[
+  ("Google Analytics", "google-analytics.com/"),
+  ("Google Tag Manager", "googletagmanager.com/"),
+  ("DoubleClick", "doubleclick.net/"),
+  ("Facebook Pixel", "connect.facebook.net/"),
+  ("Hotjar", "static.hotjar.com/"),
+  ("Mixpanel", "cdn.mxpnl.com/"),
+  ("Segment", "cdn.segment.com/"),
+  ("LinkedIn Insight", "snap.licdn.com/"),
+  ("Twitter Ads", "static.ads-twitter.com/"),
+  ("Microsoft Clarity", "clarity.ms/"),
+  ("HubSpot", "js.hs-scripts.com/"),
+  ("Matomo", "matomo.js"),
+  ("Plausible", "plausible.io/js/"),
+  ("Yandex Metrica", "mc.yandex.ru/"),
+  ("TikTok Pixel", "analytics.tiktok.com/"),
+];
+
+/// Lists the names of the well-known trackers the page embeds, in alphabetical order. Trackers
+/// are recognized by the urls of scripts, frames and pixel images, as well as by inline scripts
+/// which load them.
+pub fn trackers(dom: &Html) -> Vec<String> {
+  let mut sources: Vec<String> = vec!();
+
+  if let Some(selector) = cached_selector("script[src], img[src], iframe[src]") {
+    // Unwrap is safe as the selector only matches elements with the attribute.
+    sources.extend(dom.select(selector).map(|node| {
+      node.value().attr("src").unwrap().to_lowercase()
+    }));
+  }
+
+  if let Some(selector) = cached_selector("script:not([src])") {
+    sources.extend(dom.select(selector).map(|node| node.text().collect::<String>().to_lowercase()));
+  }
+
+  let found: BTreeSet<&str> = TRACKERS.iter()
+    .filter(|(_, pattern)| sources.iter().any(|source| source.contains(pattern)))
+    .map(|(name, _)| *name)
+    .collect();
+
+  found.into_iter().map(String::from).collect()
+}
+
+/// Lists the schema.org types the page declares with JSON-LD scripts or microdata. Types are
+/// stripped of the schema.org prefix, e.g. both `https://schema.org/Product` and `Product`
+/// become `Product`.
+pub fn schema_types(dom: &Html) -> Vec<String> {
+  let mut types: BTreeSet<String> = BTreeSet::new();
+
+  if let Some(selector) = cached_selector("script[type=\"application/ld+json\"]") {
+    for node in dom.select(selector) {
+      // Malformed JSON-LD is ignored the same way search engines ignore it.
+      if let Ok(json) = serde_json::from_str::<Value>(&node.text().collect::<String>()) {
+        collect_json_ld_types(&json, &mut types);
+      }
+    }
+  }
+
+  if let Some(selector) = cached_selector("[itemtype]") {
+    for node in dom.select(selector) {
+      // Unwrap is safe as the selector only matches elements with the attribute.
+      types.extend(node.value().attr("itemtype").unwrap().split_whitespace().map(strip_schema_prefix));
+    }
+  }
+
+  types.into_iter().collect()
+}
+
+/// Walks the JSON-LD document and collects all `@type` values, including those of nested
+/// entities and entities in `@graph`.
+fn collect_json_ld_types(json: &Value, types: &mut BTreeSet<String>) {
+  match json {
+    Value::Object(map) => {
+      match map.get("@type") {
+        Some(Value::String(name)) => { types.insert(strip_schema_prefix(name)); },
+        Some(Value::Array(names)) => {
+          types.extend(names.iter().filter_map(|name| name.as_str()).map(strip_schema_prefix));
+        },
+        _ => (),
+      }
+
+      for value in map.values() {
+        collect_json_ld_types(value, types);
+      }
+    },
+    Value::Array(values) => {
+      for value in values {
+        collect_json_ld_types(value, types);
+      }
+    },
+    _ => (),
+  }
+}
+
+fn strip_schema_prefix(name: &str) -> String {
+  let name = name.trim();
+
+  ["https://schema.org/", "http://schema.org/", "schema:"].iter()
+    .find(|prefix| name.starts_with(*prefix))
+    .map(|prefix| &name[prefix.len()..])
+    .unwrap_or(name)
+    .to_string()
+}
+
+/// Replaces all runs of whitespace with a single space and trims the text.
+fn collapse_whitespace(text: &str) -> String {
+  text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn non_empty(text: String) -> Option<String> {
+  if text.is_empty() { None } else { Some(text) }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_fields() {
+    let dom = Html::parse_document(concat!(
+      "<html><head><meta itemprop=\"sku\" content=\"A-1\"></head><body>",
+      "<h1 class=\"name\">\n  Link   Crawler </h1><span class=\"price\">$4</span></body></html>",
+    ));
+    let rules: Vec<(String, Selector)> = vec!(
+      ("name", "h1.name"), ("price", ".sale-price"), ("price", ".price"), ("sku", "[itemprop=sku]"),
+      ("stock", ".stock"),
+    )
+      .into_iter()
+      .map(|(field, selector)| (field.to_string(), Selector::parse(selector).unwrap()))
+      .collect();
+
+    let fields = fields(&dom, &rules);
+
+    assert_eq!(fields.len(), 3);
+    assert_eq!(fields["name"], "Link Crawler");
+    assert_eq!(fields["price"], "$4");
+    assert_eq!(fields["sku"], "A-1");
+  }
+
+  #[test]
+  fn test_title_and_description() {
+    let dom = Html::parse_document(concat!(
+      "<html><head><title>\n  Link   Crawler\n</title>",
+      "<meta name=\"description\" content=\" Crawls links. \"></head></html>",
+    ));
+
+    assert_eq!(title(&dom), Some("Link Crawler".to_string()));
+    assert_eq!(description(&dom), Some("Crawls links.".to_string()));
+  }
+
+  #[test]
+  fn test_missing_title_and_description() {
+    let dom = Html::parse_document("<html><head><title> </title></head></html>");
+
+    assert_eq!(title(&dom), None);
+    assert_eq!(description(&dom), None);
+  }
+
+  #[test]
+  fn test_images_missing_alt() {
+    let dom = Html::parse_document(concat!(
+      "<img src=\"/logo.png\" alt=\"Logo\"><img src=\"/spacer.gif\" alt=\"\">",
+      "<img src=\"/photo.jpg\"><img>",
+    ));
+
+    assert_eq!(images_missing_alt(&dom), vec!("/photo.jpg".to_string(), "".to_string()));
+  }
+
+  #[test]
+  fn test_canonical() {
+    let page_url = Url::parse("https://github.com/about?ref=nav").unwrap();
+    let dom = Html::parse_document("<head><link rel=\"canonical\" href=\" /about \"></head>");
+
+    assert_eq!(canonical(&dom, &page_url), Some(Url::parse("https://github.com/about").unwrap()));
+    assert_eq!(canonical(&Html::parse_document("<head></head>"), &page_url), None);
+  }
+
+  #[test]
+  fn test_alternates() {
+    let page_url = Url::parse("https://github.com/en/").unwrap();
+    let dom = Html::parse_document(concat!(
+      "<head><link rel=\"alternate\" hreflang=\"en\" href=\"/en/\">",
+      "<link rel=\"alternate\" hreflang=\"DE\" href=\"https://github.com/de/\">",
+      "<link rel=\"alternate\" href=\"/feed.xml\"></head>",
+    ));
+    let alternates: Vec<(String, String)> = alternates(&dom, &page_url).into_iter()
+      .map(|alternate| (alternate.lang, alternate.url))
+      .collect();
+
+    assert_eq!(alternates, vec!(
+      ("en".to_string(), "https://github.com/en/".to_string()),
+      ("de".to_string(), "https://github.com/de/".to_string()),
+    ));
+  }
+
+  #[test]
+  fn test_noindex() {
+    assert!(has_noindex_meta(&Html::parse_document("<meta name=\"Robots\" content=\"NOINDEX, follow\">")));
+    assert!(has_noindex_meta(&Html::parse_document("<meta name=\"googlebot\" content=\"none\">")));
+    assert!(!has_noindex_meta(&Html::parse_document("<meta name=\"description\" content=\"noindex\">")));
+    assert!(is_noindex("googlebot: noindex"));
+    assert!(!is_noindex("nofollow, noarchive"));
+  }
+
+  #[test]
+  fn test_insecure_references() {
+    let dom = Html::parse_document(concat!(
+      "<link rel=\"stylesheet\" href=\"http://cdn.github.com/main.css\"><img src=\"/logo.png\">",
+      "<script src=\"HTTP://cdn.github.com/main.js\"></script><img src=\"https://cdn.github.com/a.png\">",
+      "<a href=\"http://gitlab.com\">GitLab</a><a href=\"/about\">About</a>",
+    ));
+
+    assert_eq!(insecure_references(&dom), (
+      vec!("HTTP://cdn.github.com/main.js".to_string(), "http://cdn.github.com/main.css".to_string()),
+      vec!("http://gitlab.com".to_string()),
+    ));
+  }
+
+  #[test]
+  fn test_third_party_hosts() {
+    let url = Url::parse("https://github.com/about").unwrap();
+    let dom = Html::parse_document(concat!(
+      "<script src=\"https://www.googletagmanager.com/gtag.js\"></script><img src=\"/logo.png\">",
+      "<img src=\"//avatars.github.com/u/1\"><link rel=\"stylesheet\" href=\"https://CDN.example.com/a.css\">",
+      "<a href=\"https://twitter.com/github\">Twitter</a><a href=\"mailto:support@github.com\">Mail</a>",
+      "<a href=\"https://twitter.com/other\">Twitter</a><a href=\"/pricing\">Pricing</a>",
+    ));
+
+    assert_eq!(third_party_hosts(&dom, &url), (
+      vec!("cdn.example.com".to_string(), "www.googletagmanager.com".to_string()),
+      vec!("twitter.com".to_string()),
+    ));
+  }
+
+  #[test]
+  fn test_contacts() {
+    let dom = Html::parse_document(concat!(
+      "<body><p>Write to Sales@GitHub.com, or call +1 (877) 448-4820.</p>",
+      "<p>Released on 2019-07-20 for $1234567. Not an email: user@localhost</p>",
+      "<a href=\"mailto:support@github.com?subject=Hi\">Support</a><a href=\"tel:020 7946 0018\">Call</a>",
+      "</body>",
+    ));
+
+    assert_eq!(contacts(&dom), (
+      vec!("sales@github.com".to_string(), "support@github.com".to_string()),
+      vec!("+18774484820".to_string(), "02079460018".to_string()),
+    ));
+  }
+
+  #[test]
+  fn test_forms() {
+    let url = Url::parse("https://github.com/login").unwrap();
+    let dom = Html::parse_document(concat!(
+      "<form action=\"/session\" method=\"post\"></form><form method=\"PUT\"></form>",
+      "<form action=\"http://newsletter.example.com/subscribe\" method=\"Post\"></form>",
+    ));
+
+    let forms: Vec<(String, String, bool)> = forms(&dom, &url).into_iter()
+      .map(|form| (form.action, form.method, form.insecure))
+      .collect();
+
+    assert_eq!(forms, vec!(
+      ("https://github.com/session".to_string(), "POST".to_string(), false),
+      ("https://github.com/login".to_string(), "GET".to_string(), false),
+      ("http://newsletter.example.com/subscribe".to_string(), "POST".to_string(), true),
+    ));
+  }
+
+  #[test]
+  fn test_trackers() {
+    let dom = Html::parse_document(concat!(
+      "<script async src=\"https://www.GoogleTagManager.com/gtag/js?id=UA-1\"></script>",
+      "<script>!function(f,b,e,v,n,t,s){t.src='https://connect.facebook.net/en_US/fbevents.js'}</script>",
+      "<img src=\"https://github.com/google-analytics.png\"><script src=\"/main.js\"></script>",
+    ));
+
+    assert_eq!(trackers(&dom), vec!("Facebook Pixel".to_string(), "Google Tag Manager".to_string()));
+  }
+
+  #[test]
+  fn test_cached_selector() {
+    let first = cached_selector("a[href]").unwrap();
+    let second = cached_selector("a[href]").unwrap();
+
+    assert!(std::ptr::eq(first, second));
+    assert!(cached_selector("a[").is_none());
+  }
+
+  #[test]
+  fn test_hrefs() {
+    let body = concat!(
+      "<a href=\"/about\">About</a><a name=\"top\"></a><A HREF='https://gitlab.com'>GitLab</A>",
+      "<script>document.write('<a href=\"/hidden\">')</script><title><a href=\"/title\"></title>",
+      "<a href=\"/a?b=1&amp;c=2\">Query</a>",
+    );
+
+    assert_eq!(hrefs(body), (None, vec!(
+      "/about".to_string(),
+      "https://gitlab.com".to_string(),
+      "/a?b=1&c=2".to_string(),
+    )));
+  }
+
+  #[test]
+  fn test_link_extractors() {
+    let body = concat!(
+      "<base href=\"https://github.com/docs/\"><a href=\"guide\">Guide</a>",
+      "<iframe src=\"/embedded\"></iframe><meta http-equiv=\"refresh\" content=\"0; url=/new\">",
+    );
+    let expected = (Some("https://github.com/docs/".to_string()), vec!(
+      "guide".to_string(), "/embedded".to_string(), "/new".to_string(),
+    ));
+    let dom = Html::parse_document(body);
+
+    // The extractors find the same links, whether the DOM was built or not.
+    assert_eq!(DomExtractor.extract(body, Some(&dom)), Some(expected.clone()));
+    assert_eq!(DomExtractor.extract(body, None), Some(expected.clone()));
+    assert_eq!(TokenExtractor.extract(body, Some(&dom)), Some(expected));
+  }
+
+  #[test]
+  fn test_frames() {
+    let frameset = "<frameset><frame src=\"/menu\"><frame src=\"/content\"></frameset>";
+    let body = concat!(
+      "<iframe src=\"/embedded\"><a href=\"/fallback\">Fallback</a></iframe><iframe></iframe>",
+      "<a href=\"/about\">About</a>",
+    );
+    let expected = vec!("/embedded".to_string(), "/about".to_string());
+
+    assert_eq!(hrefs(frameset).1, vec!("/menu".to_string(), "/content".to_string()));
+    assert_eq!(links(&Html::parse_document(frameset)).unwrap(), hrefs(frameset).1);
+    assert_eq!(hrefs(body).1, expected);
+    assert_eq!(links(&Html::parse_document(body)).unwrap(), expected);
+  }
+
+  #[test]
+  fn test_scoped_links() {
+    let dom = Html::parse_document(concat!(
+      "<meta http-equiv=\"refresh\" content=\"0; url=/moved\">",
+      "<nav><ul><li><a href=\"/docs\">Docs</a></li></ul></nav>",
+      "<main><a href=\"/post\">Post</a><a class=\"more\" href=\"/more\">More</a></main>",
+      "<footer><a href=\"/privacy\">Privacy</a><a class=\"more\" href=\"/jobs\">Jobs</a></footer>",
+    ));
+    let scoped = |scope: &str| scoped_links(&dom, &Selector::parse(scope).unwrap()).unwrap();
+
+    assert_eq!(scoped("nav"), vec!("/moved", "/docs"));
+    assert_eq!(scoped("main"), vec!("/moved", "/post", "/more"));
+    // Links matching the scope themselves are kept too.
+    assert_eq!(scoped("nav, .more"), vec!("/moved", "/docs", "/more", "/jobs"));
+    assert_eq!(scoped("aside"), vec!("/moved"));
+  }
+
+  #[test]
+  fn test_amp() {
+    let url = Url::parse("https://github.com/blog/post").unwrap();
+    let canonical = Html::parse_document("<link rel=\"amphtml\" href=\" amp \"><p>Post</p>");
+    let amp = Html::parse_document("<html ⚡><link rel=\"canonical\" href=\"/blog/post\"></html>");
+
+    assert_eq!(amphtml(&canonical, &url).unwrap().as_str(), "https://github.com/blog/amp");
+    assert!(!is_amp(&canonical));
+    assert_eq!(amphtml(&amp, &url), None);
+    assert!(is_amp(&amp));
+    assert!(is_amp(&Html::parse_document("<html amp lang=\"en\"><p>Post</p></html>")));
+  }
+
+  #[test]
+  fn test_pagination() {
+    let dom = Html::parse_document(concat!(
+      "<link rel=\"prev\" href=\"/blog?page=1\"><link rel=\"Next\" href=\"/blog?page=3\">",
+      "<link rel=\"stylesheet\" href=\"/style.css\"><link rel=\"next\">",
+      "<a href=\"/about\">About</a><a rel=\"nofollow next\" href=\"/blog?page=3\">Older</a>",
+    ));
+
+    assert_eq!(pagination(&dom), vec!("/blog?page=1", "/blog?page=3", "/blog?page=3"));
+  }
+
+  #[test]
+  fn test_refresh_target() {
+    assert_eq!(refresh_target("0;url=/new"), Some("/new".to_string()));
+    assert_eq!(refresh_target(" 5 ; URL = '/new page' "), Some("/new page".to_string()));
+    assert_eq!(refresh_target("0.5, \"/new\" "), Some("/new".to_string()));
+    assert_eq!(refresh_target("0; /new"), Some("/new".to_string()));
+    assert_eq!(refresh_target("30"), None);
+    assert_eq!(refresh_target("url=/new"), None);
+  }
+
+  #[test]
+  fn test_meta_refresh_links() {
+    let body = concat!(
+      "<meta http-equiv=\"Refresh\" content=\"0;url=/moved\">",
+      "<meta http-equiv=\"refresh\" content=\"60\">",
+      "<meta http-equiv=\"expires\" content=\"0;url=/x\">",
+      "<a href=\"/about\">About</a>",
+    );
+    let expected = vec!("/moved".to_string(), "/about".to_string());
+
+    assert_eq!(hrefs(body).1, expected);
+    assert_eq!(links(&Html::parse_document(body)).unwrap(), expected);
+  }
+
+  #[test]
+  fn test_hrefs_are_decoded() {
+    let body = concat!(
+      "<base href=\"https://github.com/a&amp;b/\">",
+      "<a href=\"/a?b=1&amp;c=2\">Named</a><a href=\"/a&#x2F;b&#47;c\">Numeric</a>",
+      "<a href=\"/a?b=1&c=2&copy\">Legacy</a>",
+    );
+    let dom = Html::parse_document(body);
+    let decoded = links(&dom).unwrap();
+
+    // Both the tokenizer and the DOM decode entities in attribute values, so links are stored in
+    // the same form regardless of how the page escaped them.
+    assert_eq!(decoded, vec!(
+      "/a?b=1&c=2".to_string(),
+      "/a/b/c".to_string(),
+      "/a?b=1&c=2©".to_string(),
+    ));
+    assert_eq!(hrefs(body), (Some("https://github.com/a&b/".to_string()), decoded));
+    assert_eq!(base_href(&dom), Some("https://github.com/a&b/".to_string()));
+  }
+
+  #[test]
+  fn test_base_href() {
+    let body = "<base href=\"https://github.com/docs/\"><base href=\"/other/\"><a href=\"guide\">A</a>";
+    let base = Some("https://github.com/docs/".to_string());
+
+    assert_eq!(base_href(&Html::parse_document(body)), base);
+    assert_eq!(hrefs(body).0, base);
+    assert_eq!(base_href(&Html::parse_document("<a href=\"guide\">Guide</a>")), None);
+  }
+
+  #[test]
+  fn test_schema_types() {
+    let dom = Html::parse_document(concat!(
+      "<script type=\"application/ld+json\">{\"@context\": \"https://schema.org\", \"@graph\": [",
+      "{\"@type\": \"Organization\", \"address\": {\"@type\": \"PostalAddress\"}},",
+      "{\"@type\": [\"WebSite\", \"http://schema.org/CreativeWork\"]}]}</script>",
+      "<script type=\"application/ld+json\">{ malformed</script>",
+      "<div itemscope itemtype=\"https://schema.org/Product\"></div>",
+    ));
+
+    assert_eq!(schema_types(&dom), vec!(
+      "CreativeWork".to_string(),
+      "Organization".to_string(),
+      "PostalAddress".to_string(),
+      "Product".to_string(),
+      "WebSite".to_string(),
+    ));
+  }
+
+  #[test]
+  fn test_headings() {
+    let dom = Html::parse_document("<h2>Intro</h2><h1>Link <b>Crawler</b></h1><h4>Skipped</h4><h3>End</h3>");
+    let headings: Vec<(u8, String)> = headings(&dom).into_iter()
+      .map(|heading| (heading.level, heading.text))
+      .collect();
+
+    assert_eq!(headings, vec!(
+      (2, "Intro".to_string()),
+      (1, "Link Crawler".to_string()),
+      (3, "End".to_string()),
+    ));
+  }
+
+}
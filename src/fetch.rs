@@ -0,0 +1,626 @@
+use std::error::Error;
+use url::{Host, Url};
+use super::config::Config;
+use super::tunnel::Tunnel;
+use super::tls::{self, Certificate};
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs};
+use reqwest::header::{HeaderMap, HeaderValue, HOST, LOCATION};
+use reqwest::{Client, Method, Proxy, RedirectPolicy, RequestBuilder, Response, StatusCode};
+
+/// Redirects are followed at most this many times, after that the last redirect response is
+/// returned as is.
+const MAX_REDIRECTS_FOLLOWED: usize = 10;
+
+/// Resolved addresses of a host are reused for this long before the host is resolved again, so
+/// that hosts which move to other addresses are followed soon.
+const DNS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Addresses hosts resolved to, keyed by host and port, with the time they were resolved at.
+/// Every fetched url is checked for pointing to internal addresses, which would otherwise
+/// resolve the same host over and over when crawling a domain.
+#[derive(Default)]
+pub struct DnsCache {
+  resolved: Mutex<HashMap<String, (Instant, Vec<IpAddr>)>>,
+}
+
+impl DnsCache {
+  /// Resolves the host of given url, reusing addresses resolved within the last half a minute.
+  pub fn lookup(&self, url: &Url) -> Vec<IpAddr> {
+    let port = url.port_or_known_default().unwrap_or(80);
+    let key = format!("{}:{}", url.host_str().unwrap_or_default(), port);
+
+    // A poisoned cache is bypassed rather than failing the fetch.
+    let cached = match self.resolved.lock() {
+      Ok(cache) => cache.get(&key).cloned(),
+      Err(_) => return resolve(url),
+    };
+
+    if let Some((resolved_at, addresses)) = cached {
+      if resolved_at.elapsed() < DNS_CACHE_TTL {
+        return addresses;
+      }
+    }
+
+    // The cache isn't locked while resolving, so that a slow host doesn't hold up the fetches of
+    // the other threads.
+    let addresses = resolve(url);
+    if let Ok(mut cache) = self.resolved.lock() {
+      cache.insert(key, (Instant::now(), addresses.clone()));
+    }
+
+    addresses
+  }
+}
+
+/// Fetches the urls of crawls. The crawler talks to hosts only through a fetcher, so that tests
+/// can crawl made up sites without any network access, see `MockFetcher`.
+pub trait Fetcher: Sync {
+  /// Fetches given url and follows its redirects. Returns the final response together with the
+  /// chain of urls the request was redirected through, which is empty if the url did not
+  /// redirect. Returns None if the request failed or the url may not be fetched.
+  ///
+  /// Following stops when a redirect points to a url already visited, in which case the looping
+  /// url is the last item in the chain and the returned response is the redirect itself.
+  fn get(&self, url: &Url) -> Option<(Response, Vec<String>)> {
+    self.get_with(url, &HeaderMap::new())
+  }
+
+  /// Like `get`, but sends given headers with the request and with the requests of the urls it
+  /// redirects to.
+  fn get_with(&self, url: &Url, headers: &HeaderMap) -> Option<(Response, Vec<String>)>;
+
+  /// Like `get`, but only finds the status code the url responds with, without downloading the
+  /// body where possible.
+  fn head(&self, url: &Url) -> Option<(StatusCode, Vec<String>)>;
+
+  /// Inspects the certificate the host of given https url presents, see `tls::inspect`.
+  fn certificate(&self, url: &Url) -> Option<Certificate>;
+
+  /// Finds the status code given url responds with, following redirects. Returns None if the
+  /// request failed.
+  fn status(&self, url: &str) -> Option<StatusCode> {
+    let url = Url::parse(url).ok()?;
+
+    self.head(&url).map(|(status, _)| status)
+  }
+
+  /// Checks whether given url responds successfully.
+  fn is_alive(&self, url: &str) -> bool {
+    self.status(url).map(|status| status.is_success()).unwrap_or(false)
+  }
+}
+
+/// Fetches urls with a single client, so that connections to a host are kept alive and reused
+/// by subsequent requests instead of a new TCP and TLS handshake being made for every url.
+///
+/// Requests are only ever sent to the addresses hosts were checked against, as a host could
+/// otherwise resolve to a public address for the check and to an internal one for the request.
+/// Requests over https go through the `Tunnel`, which connects to the checked address for the
+/// client, so that the certificate is still verified against the name of the host.
+pub struct HttpFetcher {
+  client: Client,
+  config: Config,
+  dns: Arc<DnsCache>,
+  /// Kept alive for as long as the client uses it.
+  _tunnel: Tunnel,
+}
+
+impl HttpFetcher {
+  /// Builds the client according to the service settings. Fails if the TLS backend can't be
+  /// initialized or the tunnel can't listen.
+  pub fn new(config: &Config) -> Result<HttpFetcher, Box<dyn Error>> {
+    let dns = Arc::new(DnsCache::default());
+    let tunnel = Tunnel::start(config, Arc::clone(&dns))?;
+    let builder = Client::builder()
+      .redirect(RedirectPolicy::none())
+      .proxy(Proxy::https(&tunnel.url())?)
+      .danger_accept_invalid_certs(config.accept_invalid_certs);
+
+    // Only the rustls backend negotiates HTTP/2 with servers, so that requests to a host are
+    // multiplexed over a single connection. Servers without HTTP/2 support are still spoken to
+    // over HTTP/1.1. The native backend remains available for servers rustls can't talk to.
+    let client = if config.http2 {
+      builder.use_rustls_tls().build()?
+    } else {
+      builder.danger_accept_invalid_hostnames(config.accept_invalid_certs).build()?
+    };
+
+    Ok(HttpFetcher { client, config: config.clone(), dns, _tunnel: tunnel })
+  }
+
+  /// Sends the request and follows redirects, checking every url on the way against denied
+  /// domains and internal addresses. Redirects are followed manually rather than by the client
+  /// so that the chain of urls the request went through can be recorded.
+  fn request(
+    &self,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+  ) -> Option<(Response, Vec<String>)> {
+    let mut chain: Vec<String> = vec!();
+    let mut current = url.clone();
+
+    loop {
+      if is_denied(&self.config, &current) {
+        println!("[Crawler] Refusing to fetch url of a denied domain {}", current);
+
+        return None;
+      }
+
+      let addresses = self.dns.lookup(&current);
+      if has_forbidden_address(&self.config, &current, &addresses) {
+        println!("[Crawler] Refusing to fetch internal url {}", current);
+
+        return None;
+      }
+
+      let res = send(&self.client, method.clone(), &current, &addresses, headers)?;
+
+      if !res.status().is_redirection() || chain.len() >= MAX_REDIRECTS_FOLLOWED {
+        return Some((res, chain));
+      }
+
+      // A redirect without a location header cannot be followed.
+      let location = match res.headers().get(LOCATION).and_then(|header| header.to_str().ok()) {
+        Some(location) => location.to_string(),
+        None => return Some((res, chain)),
+      };
+
+      // Location can be relative to the url which issued the redirect.
+      current = current.join(&location).ok()?;
+
+      let is_loop = current == *url || chain.iter().any(|visited| visited == current.as_str());
+      chain.push(current.as_str().to_string());
+
+      if is_loop {
+        return Some((res, chain));
+      }
+    }
+  }
+}
+
+impl Fetcher for HttpFetcher {
+  fn get_with(&self, url: &Url, headers: &HeaderMap) -> Option<(Response, Vec<String>)> {
+    self.request(Method::GET, url, headers)
+  }
+
+  /// Uses a HEAD request, falling back to GET for servers which do not support HEAD.
+  fn head(&self, url: &Url) -> Option<(StatusCode, Vec<String>)> {
+    let (res, chain) = self.request(Method::HEAD, url, &HeaderMap::new())?;
+
+    match res.status() {
+      StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => {
+        self.get(url).map(|(res, chain)| (res.status(), chain))
+      },
+      status => Some((status, chain)),
+    }
+  }
+
+  /// Connects to the addresses the host was checked against, like the client does.
+  fn certificate(&self, url: &Url) -> Option<Certificate> {
+    tls::inspect(&self.config, &self.dns, url)
+  }
+}
+
+/// A made up site for tests. Urls respond with the pages they were given or with 404 Not Found,
+/// unless they were made unreachable. Redirects are not followed.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockFetcher {
+  /// The pages keyed by their urls.
+  pages: HashMap<String, MockPage>,
+  unreachable: Vec<String>,
+  /// Every url requested so far in the order of the requests.
+  requested: Mutex<Vec<String>>,
+  /// The headers sent with the last request of each url.
+  headers: Mutex<HashMap<String, HeaderMap>>,
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct MockPage {
+  status: u16,
+  headers: Vec<(String, String)>,
+  body: String,
+}
+
+#[cfg(test)]
+impl MockFetcher {
+  /// Serves given html at given url.
+  pub fn page(self, url: &str, body: &str) -> MockFetcher {
+    self.response(url, 200, &[("content-type", "text/html")], body)
+  }
+
+  pub fn response(
+    mut self,
+    url: &str,
+    status: u16,
+    headers: &[(&str, &str)],
+    body: &str,
+  ) -> MockFetcher {
+    let headers = headers.iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect();
+    self.pages.insert(url.to_string(), MockPage { status, headers, body: body.to_string() });
+
+    self
+  }
+
+  /// Makes requests of given url fail as if the host did not respond.
+  pub fn unreachable(mut self, url: &str) -> MockFetcher {
+    self.unreachable.push(url.to_string());
+
+    self
+  }
+
+  pub fn requested(&self) -> Vec<String> {
+    self.requested.lock().unwrap().clone()
+  }
+
+  /// The headers sent with the last request of given url, if it was requested.
+  pub fn headers(&self, url: &str) -> Option<HeaderMap> {
+    self.headers.lock().unwrap().get(url).cloned()
+  }
+}
+
+#[cfg(test)]
+impl Fetcher for MockFetcher {
+  fn get_with(&self, url: &Url, headers: &HeaderMap) -> Option<(Response, Vec<String>)> {
+    self.requested.lock().unwrap().push(url.as_str().to_string());
+    self.headers.lock().unwrap().insert(url.as_str().to_string(), headers.clone());
+
+    if self.unreachable.iter().any(|unreachable| unreachable == url.as_str()) {
+      return None;
+    }
+
+    let page = self.pages.get(url.as_str()).cloned()
+      .unwrap_or(MockPage { status: 404, ..Default::default() });
+    let mut response = http::Response::builder();
+    response.status(page.status);
+
+    for (name, value) in &page.headers {
+      response.header(name.as_str(), value.as_str());
+    }
+
+    Some((Response::from(response.body(page.body).ok()?), vec!()))
+  }
+
+  fn head(&self, url: &Url) -> Option<(StatusCode, Vec<String>)> {
+    self.get(url).map(|(response, chain)| (response.status(), chain))
+  }
+
+  /// Made up sites have no certificates.
+  fn certificate(&self, _url: &Url) -> Option<Certificate> {
+    None
+  }
+}
+
+/// Parses the value of the Retry-After header, which is either a number of seconds or a date, into
+/// how long to wait from now. Dates in the past mean no wait. Returns None for invalid values.
+pub fn retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+  let value = value.trim();
+
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+
+  let date = DateTime::parse_from_rfc2822(value).ok()?;
+
+  Some((date.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+/// Checks whether the host of given url resolves to a loopback, private or link-local address
+/// and it is not in the `allowed_internal_hosts` setting. Fetching such urls would let users of
+/// the service reach internal infrastructure through it. Hosts which can't be resolved are not
+/// forbidden, as requests to them fail anyway.
+pub fn is_forbidden(config: &Config, url: &Url) -> bool {
+  has_forbidden_address(config, url, &resolve(url))
+}
+
+/// Finds the addresses the host of given url resolves to. Empty if it can't be resolved.
+fn resolve(url: &Url) -> Vec<IpAddr> {
+  match url.host() {
+    Some(Host::Ipv4(ip)) => vec!(IpAddr::V4(ip)),
+    Some(Host::Ipv6(ip)) => vec!(IpAddr::V6(ip)),
+    Some(Host::Domain(domain)) => {
+      match (domain, url.port_or_known_default().unwrap_or(80)).to_socket_addrs() {
+        Ok(addresses) => addresses.map(|address| address.ip()).collect(),
+        Err(_) => vec!(),
+      }
+    },
+    None => vec!(),
+  }
+}
+
+/// Checks whether any of the addresses the host of given url resolved to is internal and not
+/// allowed by the `allowed_internal_hosts` setting.
+pub fn has_forbidden_address(config: &Config, url: &Url, addresses: &[IpAddr]) -> bool {
+  let is_allowed = |ip: &IpAddr| {
+    config.allowed_internal_hosts.iter().any(|allowed| {
+      url.host_str().map(|host| host.eq_ignore_ascii_case(allowed)).unwrap_or(false)
+        || *allowed == ip.to_string()
+    })
+  };
+
+  addresses.iter().any(|ip| is_internal(ip) && !is_allowed(ip))
+}
+
+/// Checks whether the host of given url is excluded from crawling by the `allowed_domains` and
+/// `denied_domains` settings. A domain in either list also covers all of its subdomains.
+pub fn is_denied(config: &Config, url: &Url) -> bool {
+  let host = match url.host_str() {
+    Some(host) => host.to_lowercase(),
+    None => return true,
+  };
+
+  let matches = |domain: &String| {
+    let domain = ascii_host(domain.trim_start_matches('.'));
+
+    host == domain || host.ends_with(&format!(".{}", domain))
+  };
+
+  config.denied_domains.iter().any(matches)
+    || (!config.allowed_domains.is_empty() && !config.allowed_domains.iter().any(matches))
+}
+
+/// Builds a request of given url to one of the addresses its host was checked against. Plain
+/// http requests are sent to the address with the host in the `Host` header, requests over https
+/// are pinned to the address by the tunnel the client goes through instead.
+pub fn pinned_request(
+  client: &Client,
+  method: Method,
+  url: &Url,
+  addresses: &[IpAddr],
+) -> Option<RequestBuilder> {
+  let mut target = url.clone();
+  let mut host = None;
+
+  if let ("http", Some(Host::Domain(domain))) = (url.scheme(), url.host()) {
+    host = Some(match url.port() {
+      Some(port) => format!("{}:{}", domain, port),
+      None => domain.to_string(),
+    });
+    target.set_ip_host(*addresses.first()?).ok()?;
+  }
+
+  let mut request = client.request(method, target.as_str());
+  if let Some(host) = host {
+    request = request.header(HOST, HeaderValue::from_str(&host).ok()?);
+  }
+
+  Some(request)
+}
+
+/// Sends a single request of given url with given headers to one of the addresses its host was
+/// checked against, see `pinned_request`.
+fn send(
+  client: &Client,
+  method: Method,
+  url: &Url,
+  addresses: &[IpAddr],
+  headers: &HeaderMap,
+) -> Option<Response> {
+  pinned_request(client, method, url, addresses)?.headers(headers.clone()).send().ok()
+}
+
+/// Converts an internationalized host to its punycode form, which is what hosts of parsed urls
+/// are compared in. Hosts which are not valid domains are only lowercased.
+pub fn ascii_host(host: &str) -> String {
+  match Host::parse(host) {
+    Ok(Host::Domain(domain)) => domain,
+    _ => host.to_lowercase(),
+  }
+}
+
+/// Checks whether the address belongs to a loopback, private, link-local or otherwise
+/// non-public range.
+fn is_internal(ip: &IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => {
+      let octets = ip.octets();
+
+      ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_multicast()
+        // The current network, including the unspecified address.
+        || octets[0] == 0
+        // Shared address space used for carrier-grade NAT.
+        || (octets[0] == 100 && (octets[1] & 0b1100_0000) == 64)
+        // Benchmarking networks.
+        || (octets[0] == 198 && (octets[1] & 0b1111_1110) == 18)
+        // Reserved addresses, including the broadcast address.
+        || octets[0] >= 240
+    },
+    IpAddr::V6(ip) => {
+      let segments = ip.segments();
+
+      // IPv4 addresses can be smuggled in as IPv4-mapped or the deprecated IPv4-compatible
+      // addresses, or reached through the NAT64 and 6to4 prefixes. The IPv4 address is checked,
+      // which also covers the loopback and unspecified addresses.
+      let is_mapped = segments[..5].iter().all(|segment| *segment == 0)
+        && (segments[5] == 0xffff || segments[5] == 0);
+      let is_nat64 = segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0];
+      if is_mapped || is_nat64 {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+
+        return is_internal(&IpAddr::V4(Ipv4Addr::new(a, b, c, d)));
+      }
+
+      if segments[0] == 0x2002 {
+        let [a, b] = segments[1].to_be_bytes();
+        let [c, d] = segments[2].to_be_bytes();
+
+        return is_internal(&IpAddr::V4(Ipv4Addr::new(a, b, c, d)));
+      }
+
+      ip.is_multicast()
+        // Unique local addresses.
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local addresses.
+        || (segments[0] & 0xffc0) == 0xfe80
+    },
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use std::thread;
+  use std::net::TcpListener;
+  use reqwest::{Client, Method, StatusCode};
+  use reqwest::header::HeaderMap;
+  use super::super::config::Config;
+  use std::io::{BufRead, BufReader, Write};
+  use std::net::IpAddr;
+  use std::time::Instant;
+  use chrono::{TimeZone, Utc};
+  use std::time::Duration;
+  use super::{ascii_host, has_forbidden_address, is_denied, is_forbidden, is_internal};
+  use super::{retry_after, send, Fetcher, HttpFetcher};
+
+  #[test]
+  fn test_internal_urls_are_forbidden() {
+    let config = Config::from_rocket(&rocket::Config::development());
+
+    for url in &[
+      "http://127.0.0.1:8000/admin",
+      "http://169.254.169.254/",
+      "http://10.0.0.1/",
+      "http://[::1]/",
+      "http://[::ffff:192.168.0.1]/",
+    ] {
+      assert!(is_forbidden(&config, &Url::parse(url).unwrap()), "{} should be forbidden", url);
+    }
+
+    assert!(!is_forbidden(&config, &Url::parse("http://93.184.216.34/").unwrap()));
+  }
+
+  #[test]
+  fn test_internal_ranges() {
+    for (ip, expected) in &[
+      ("0.1.2.3", true),
+      ("1.1.1.1", false),
+      ("100.64.0.1", true),
+      ("198.18.0.1", true),
+      ("198.20.0.1", false),
+      ("224.0.0.251", true),
+      ("240.0.0.1", true),
+      ("255.255.255.255", true),
+      ("::", true),
+      ("::1", true),
+      ("::127.0.0.1", true),
+      ("::ffff:10.0.0.1", true),
+      ("::ffff:93.184.216.34", false),
+      ("64:ff9b::a9fe:a9fe", true),
+      ("64:ff9b::5db8:d822", false),
+      ("2002:7f00:1::", true),
+      ("2002:5db8:d822::", false),
+      ("fc00::1", true),
+      ("fe80::1", true),
+      ("ff02::1", true),
+      ("2606:4700::1111", false),
+    ] {
+      assert_eq!(is_internal(&ip.parse().unwrap()), *expected, "{}", ip);
+    }
+  }
+
+  #[test]
+  fn test_requests_go_to_checked_address() {
+    let host = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = host.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+      let (stream, _) = host.accept().unwrap();
+      let mut reader = BufReader::new(stream);
+      let mut request = vec!();
+      loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+          break;
+        }
+        request.push(line.trim().to_lowercase());
+      }
+      reader.get_mut().write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").unwrap();
+
+      request
+    });
+
+    // The host can't be resolved, so the request only arrives if it is sent to the address the
+    // host was checked against.
+    let url = Url::parse(&format!("http://crawler.invalid:{}/", port)).unwrap();
+    let addresses = ["127.0.0.1".parse().unwrap()];
+    let res = send(&Client::new(), Method::GET, &url, &addresses, &HeaderMap::new()).unwrap();
+
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert!(server.join().unwrap().contains(&format!("host: crawler.invalid:{}", port)));
+  }
+
+  #[test]
+  fn test_allowed_internal_hosts() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.allowed_internal_hosts.push(String::from("10.0.0.1"));
+
+    assert!(!is_forbidden(&config, &Url::parse("http://10.0.0.1/").unwrap()));
+    assert!(is_forbidden(&config, &Url::parse("http://10.0.0.2/").unwrap()));
+  }
+
+  #[test]
+  fn test_lookup_is_cached() {
+    let config = Config::from_rocket(&rocket::Config::development());
+    let fetcher = HttpFetcher::new(&config).unwrap();
+    let url = Url::parse("http://crawler.invalid/").unwrap();
+
+    // Pretends the host has recently resolved to an internal address.
+    fetcher.dns.resolved.lock().unwrap().insert(
+      String::from("crawler.invalid:80"), (Instant::now(), vec!("10.0.0.1".parse().unwrap())),
+    );
+
+    let addresses = fetcher.dns.lookup(&url);
+
+    assert_eq!(addresses, vec!("10.0.0.1".parse::<IpAddr>().unwrap()));
+    assert!(has_forbidden_address(&config, &url, &addresses));
+    assert!(fetcher.get(&url).is_none());
+  }
+
+  #[test]
+  fn test_denied_domains() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    let url = |url: &str| Url::parse(url).unwrap();
+
+    assert!(!is_denied(&config, &url("https://example.com/")));
+
+    config.allowed_domains.push(String::from("example.com"));
+    config.denied_domains.push(String::from("private.example.com"));
+
+    assert!(!is_denied(&config, &url("https://example.com/")));
+    assert!(!is_denied(&config, &url("https://blog.Example.com/")));
+    assert!(is_denied(&config, &url("https://notexample.com/")));
+    assert!(is_denied(&config, &url("https://private.example.com/")));
+    assert!(is_denied(&config, &url("https://a.private.example.com/")));
+  }
+
+  #[test]
+  fn test_retry_after() {
+    let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 28, 0).unwrap();
+
+    assert_eq!(retry_after(" 120 ", now), Some(Duration::from_secs(120)));
+    assert_eq!(retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+    assert_eq!(retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now), Some(Duration::from_secs(0)));
+    assert_eq!(retry_after("soon", now), None);
+  }
+
+  #[test]
+  fn test_internationalized_domains() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.denied_domains.push(String::from("bücher.de"));
+
+    assert_eq!(ascii_host("Bücher.de"), "xn--bcher-kva.de");
+    assert_eq!(ascii_host("xn--bcher-kva.de"), "xn--bcher-kva.de");
+    assert!(is_denied(&config, &Url::parse("https://shop.xn--bcher-kva.de/").unwrap()));
+    assert!(is_denied(&config, &Url::parse("https://BÜCHER.de/").unwrap()));
+  }
+
+}
@@ -0,0 +1,84 @@
+use url::Url;
+use regex::Regex;
+use super::config::Config;
+
+/// Decides which of the links found on pages the crawler follows, e.g. skipping urls containing
+/// session tokens. Links the filters reject are dropped as if the pages did not link to them.
+pub trait UrlFilter: Send + Sync {
+  fn allows(&self, url: &Url) -> bool;
+}
+
+/// Closures deciding about links are filters too.
+impl<F> UrlFilter for F where F: Fn(&Url) -> bool + Send + Sync {
+  fn allows(&self, url: &Url) -> bool {
+    self(url)
+  }
+}
+
+/// The built in filter matching whole urls against the regular expressions of the service
+/// settings.
+pub struct PatternFilter {
+  /// If not empty, links have to match at least one of these.
+  include: Vec<Regex>,
+  /// Links matching any of these are rejected.
+  exclude: Vec<Regex>,
+}
+
+impl PatternFilter {
+  /// Compiles the `url_include_patterns` and `url_exclude_patterns`. Like the crawl windows,
+  /// invalid patterns are reported rather than skipped, as skipping an exclusion could crawl
+  /// what was meant to be left alone.
+  pub fn from_config(config: &Config) -> Result<PatternFilter, String> {
+    let compile = |patterns: &[String]| patterns.iter()
+      .map(|pattern| Regex::new(pattern)
+        .map_err(|e| format!("The url pattern {} is invalid: {}", pattern, e)))
+      .collect::<Result<Vec<Regex>, String>>();
+
+    Ok(PatternFilter {
+      include: compile(&config.url_include_patterns)?,
+      exclude: compile(&config.url_exclude_patterns)?,
+    })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.include.is_empty() && self.exclude.is_empty()
+  }
+}
+
+impl UrlFilter for PatternFilter {
+  fn allows(&self, url: &Url) -> bool {
+    let url = url.as_str();
+
+    (self.include.is_empty() || self.include.iter().any(|pattern| pattern.is_match(url)))
+      && !self.exclude.iter().any(|pattern| pattern.is_match(url))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use super::{PatternFilter, UrlFilter};
+  use super::super::config::Config;
+
+  #[test]
+  fn test_pattern_filter() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.url_include_patterns = vec!(String::from("^https://github.com/(docs|blog)/"));
+    config.url_exclude_patterns = vec!(String::from("[?&](sid|session)="));
+    let filter = PatternFilter::from_config(&config).unwrap();
+    let allows = |url: &str| filter.allows(&Url::parse(url).unwrap());
+
+    assert!(allows("https://github.com/docs/api?page=2"));
+    assert!(allows("https://github.com/blog/"));
+    assert!(!allows("https://github.com/pricing"));
+    assert!(!allows("https://github.com/docs/api?page=2&sid=4f2a"));
+
+    config.url_include_patterns = vec!();
+    assert!(PatternFilter::from_config(&config).unwrap().allows(
+      &Url::parse("https://github.com/pricing").unwrap(),
+    ));
+
+    config.url_exclude_patterns = vec!(String::from("(unclosed"));
+    assert!(PatternFilter::from_config(&config).is_err());
+  }
+}
@@ -0,0 +1,184 @@
+use url::Url;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The order urls are fetched in, the lowest first: whether the url is not a pagination link, the
+/// rank of the depth of the url, whether it has a query, how many segments its path has, how long
+/// it is and the rank of when it was queued. The ranks depend on the order of the crawl.
+type Priority = (bool, i64, bool, usize, usize, i64);
+
+/// How a crawl works its way through the urls it finds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CrawlOrder {
+  /// Urls closer to the seed go first, so that a crawl which runs out of pages covers the site
+  /// evenly.
+  #[default]
+  BreadthFirst,
+  /// The urls found last go first, following a single branch of the site as deep as it goes.
+  DepthFirst,
+}
+
+impl CrawlOrder {
+  pub const ALL: [CrawlOrder; 2] = [CrawlOrder::BreadthFirst, CrawlOrder::DepthFirst];
+
+  /// The name of the order as used in requests.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      CrawlOrder::BreadthFirst => "bfs",
+      CrawlOrder::DepthFirst => "dfs",
+    }
+  }
+}
+
+/// The urls a crawl is yet to fetch. Crawls are limited in how many pages they fetch, so in the
+/// breadth first order the urls most likely to be worth it go first: the ones closest to the
+/// seed, then the ones without a query, as queries tend to be filters and sorts of other pages,
+/// then the ones with shorter paths. Urls equal in all of that are fetched in the order they
+/// were found.
+///
+/// In the depth first order, the deepest urls go first and urls at the same depth are judged
+/// the same way, except that the urls found last are fetched first.
+///
+/// In either order, the next and previous pages of paginated listings go before all other urls,
+/// so that the listings are traversed completely. While any are queued, they are taken on their
+/// own, so that the pages they lead to are fetched before the other urls too.
+#[derive(Default)]
+pub struct Frontier {
+  order: CrawlOrder,
+  urls: BinaryHeap<Reverse<(Priority, String, usize)>>,
+  /// How many urls were queued so far, the order of the next one.
+  pushed: i64,
+}
+
+impl Frontier {
+  pub fn new(order: CrawlOrder) -> Frontier {
+    Frontier { order, ..Default::default() }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.urls.is_empty()
+  }
+
+  /// Queues given url found at given depth.
+  pub fn push(&mut self, url: String, depth: usize) {
+    self.queue(url, depth, false);
+  }
+
+  /// Queues given url found at given depth as a pagination link, ahead of the other urls.
+  pub fn push_pagination(&mut self, url: String, depth: usize) {
+    self.queue(url, depth, true);
+  }
+
+  fn queue(&mut self, url: String, depth: usize, is_pagination: bool) {
+    let (has_query, segments, length) = match Url::parse(&url) {
+      Ok(parsed) => (
+        parsed.query().is_some(),
+        parsed.path().split('/').filter(|segment| !segment.is_empty()).count(),
+        url.len(),
+      ),
+      // Urls are parsed before they are queued, so this is only defensive.
+      Err(_) => (true, usize::MAX, usize::MAX),
+    };
+
+    let (depth_rank, pushed_rank) = match self.order {
+      CrawlOrder::BreadthFirst => (depth as i64, self.pushed),
+      CrawlOrder::DepthFirst => (-(depth as i64), -self.pushed),
+    };
+    let priority = (!is_pagination, depth_rank, has_query, segments, length, pushed_rank);
+
+    self.urls.push(Reverse((priority, url, depth)));
+    self.pushed += 1;
+  }
+
+  /// Takes at most given number of the most valuable urls together with their depths. If any
+  /// pagination links are queued, only they are taken.
+  pub fn pop(&mut self, count: usize) -> Vec<(String, usize)> {
+    let is_paginating = self.urls.peek().is_some_and(|Reverse(((is_other, ..), _, _))| !is_other);
+
+    (0..count)
+      .map_while(|_| match self.urls.peek() {
+        Some(Reverse(((is_other, ..), _, _))) if is_paginating && *is_other => None,
+        _ => self.urls.pop(),
+      })
+      .map(|Reverse((_, url, depth))| (url, depth))
+      .collect()
+  }
+
+  /// The urls yet to be taken, in no particular order.
+  pub fn urls(&self) -> Vec<String> {
+    self.urls.iter().map(|Reverse((_, url, _))| url.clone()).collect()
+  }
+
+  /// The urls which were never taken, in no particular order.
+  pub fn into_urls(self) -> Vec<String> {
+    self.urls.into_iter().map(|Reverse((_, url, _))| url).collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{CrawlOrder, Frontier};
+
+  fn frontier(order: CrawlOrder) -> Frontier {
+    let mut frontier = Frontier::new(order);
+    for (url, depth) in &[
+      ("https://github.com/a/b/c", 1), ("https://github.com/search?q=rust", 1),
+      ("https://github.com/about", 2), ("https://github.com/a/b", 1),
+      ("https://github.com/pricing", 1), ("https://github.com/", 0),
+      ("https://github.com/careers", 1),
+    ] {
+      frontier.push(url.to_string(), *depth);
+    }
+
+    frontier
+  }
+
+  #[test]
+  fn test_pop() {
+    let mut frontier = frontier(CrawlOrder::BreadthFirst);
+
+    let urls: Vec<String> = frontier.pop(6).into_iter().map(|(url, _)| url).collect();
+    assert_eq!(urls, vec!(
+      "https://github.com/",
+      // Urls as long as each other are fetched in the order they were found.
+      "https://github.com/pricing",
+      "https://github.com/careers",
+      "https://github.com/a/b",
+      "https://github.com/a/b/c",
+      "https://github.com/search?q=rust",
+    ));
+    assert_eq!(frontier.pop(2), vec!((String::from("https://github.com/about"), 2)));
+    assert!(frontier.is_empty());
+  }
+
+  #[test]
+  fn test_pop_pagination() {
+    let mut frontier = frontier(CrawlOrder::BreadthFirst);
+    frontier.push_pagination(String::from("https://github.com/blog?page=3"), 3);
+    frontier.push_pagination(String::from("https://github.com/blog?page=2"), 2);
+
+    let urls: Vec<String> = frontier.pop(3).into_iter().map(|(url, _)| url).collect();
+    assert_eq!(urls, vec!(
+      "https://github.com/blog?page=2",
+      "https://github.com/blog?page=3",
+    ));
+    // The other urls wait until the listings have been traversed.
+    assert_eq!(frontier.pop(1), vec!((String::from("https://github.com/"), 0)));
+  }
+
+  #[test]
+  fn test_pop_depth_first() {
+    let urls: Vec<String> = frontier(CrawlOrder::DepthFirst).pop(4)
+      .into_iter()
+      .map(|(url, _)| url)
+      .collect();
+
+    assert_eq!(urls, vec!(
+      "https://github.com/about",
+      // Urls as long as each other are fetched from the one found last.
+      "https://github.com/careers",
+      "https://github.com/pricing",
+      "https://github.com/a/b",
+    ));
+  }
+}
@@ -0,0 +1,368 @@
+use super::fetch;
+use super::routes;
+use rocket::State;
+use super::Database;
+use std::sync::{Arc, Mutex, MutexGuard};
+use chrono::{DateTime, Utc};
+use super::errors::ApiError;
+use super::jobs::{Job, Jobs};
+use rocket::request::Form;
+use super::database::{Domain, UrlRecord};
+use juniper::{EmptyMutation, FieldError, FieldResult, RootNode, Value};
+use juniper_rocket::{GraphQLRequest, GraphQLResponse};
+
+/// The schema of the GraphQL api. It is read only, crawls are submitted with the REST api.
+pub type Schema = RootNode<'static, Query, EmptyMutation<Context>>;
+
+/// The state the resolvers read from.
+pub struct Context {
+  db: Database,
+  jobs: Jobs,
+}
+
+impl juniper::Context for Context {}
+
+pub struct Query;
+
+/// A crawled domain.
+pub struct DomainNode {
+  name: String,
+}
+
+/// A unique url of a domain, with what the crawler knows about it at the time of the query.
+pub struct UrlNode {
+  domain: String,
+  url: String,
+  record: Option<UrlRecord>,
+  status: Option<u16>,
+}
+
+/// A crawl submitted to the service.
+pub struct JobNode {
+  id: usize,
+  job: Job,
+}
+
+pub fn schema() -> Schema {
+  Schema::new(Query, EmptyMutation::new())
+}
+
+#[get("/?<request..>")]
+pub fn get(
+  cache: State<Database>,
+  jobs: State<Jobs>,
+  schema: State<Schema>,
+  request: Form<GraphQLRequest>,
+) -> GraphQLResponse {
+  request.execute(&schema, &Context { db: Arc::clone(&cache), jobs: Arc::clone(&jobs) })
+}
+
+#[post("/", data = "<request>")]
+pub fn post(
+  cache: State<Database>,
+  jobs: State<Jobs>,
+  schema: State<Schema>,
+  request: GraphQLRequest,
+) -> GraphQLResponse {
+  request.execute(&schema, &Context { db: Arc::clone(&cache), jobs: Arc::clone(&jobs) })
+}
+
+#[juniper::object(Context = Context)]
+impl Query {
+  /// Every known domain in alphabetical order, optionally only the ones with given tag.
+  fn domains(context: &Context, tag: Option<String>) -> FieldResult<Vec<DomainNode>> {
+    let db = lock(&context.db)?;
+    let mut domains: Vec<DomainNode> = db.iter()
+      .filter(|(_, record)| tag.as_ref().map_or(true, |tag| record.tags.contains(tag)))
+      .map(|(domain, _)| DomainNode { name: domain.clone() })
+      .collect();
+    domains.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(domains)
+  }
+
+  /// The domain with given name, normalized the same way as in the paths of the REST api.
+  fn domain(context: &Context, name: String) -> FieldResult<Option<DomainNode>> {
+    let name = fetch::ascii_host(name.trim());
+
+    Ok(if lock(&context.db)?.contains_key(&name) { Some(DomainNode { name }) } else { None })
+  }
+
+  /// Every submitted crawl in the order of submission.
+  fn jobs(context: &Context) -> FieldResult<Vec<JobNode>> {
+    let jobs = lock(&context.jobs)?;
+
+    Ok(jobs.iter().enumerate().map(|(id, job)| JobNode { id, job: job.clone() }).collect())
+  }
+
+  /// The crawl with given id.
+  fn job(context: &Context, id: i32) -> FieldResult<Option<JobNode>> {
+    if id < 0 {
+      return Ok(None);
+    }
+    let id = id as usize;
+    let jobs = lock(&context.jobs)?;
+
+    Ok(jobs.get(id).map(|job| JobNode { id, job: job.clone() }))
+  }
+}
+
+#[juniper::object(Context = Context, name = "Domain")]
+impl DomainNode {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn tags(&self, context: &Context) -> FieldResult<Vec<String>> {
+    with_domain(context, &self.name, |record| record.tags.iter().cloned().collect())
+  }
+
+  /// When the last crawl of the domain finished.
+  fn crawled_at(&self, context: &Context) -> FieldResult<Option<DateTime<Utc>>> {
+    with_domain(context, &self.name, |record| record.crawled_at)
+  }
+
+  /// How many unique urls were crawled on the domain.
+  fn url_count(&self, context: &Context) -> FieldResult<i32> {
+    with_domain(context, &self.name, |record| int(record.urls.len()))
+  }
+
+  /// The unique urls of the domain in alphabetical order, optionally only the ones which
+  /// responded with given status code or are in given state.
+  fn urls(
+    &self,
+    context: &Context,
+    status: Option<i32>,
+    state: Option<String>,
+  ) -> FieldResult<Vec<UrlNode>> {
+    let state = match state {
+      Some(state) => Some(routes::parse_state(&state).map_err(error)?),
+      None => None,
+    };
+
+    with_domain(context, &self.name, |record| {
+      let mut urls: Vec<UrlNode> = record.urls.iter()
+        .map(|url| UrlNode {
+          domain: self.name.clone(),
+          url: url.clone(),
+          record: record.records.get(url).cloned(),
+          status: record.pages.get(url).and_then(|page| page.status),
+        })
+        .filter(|node| status.map_or(true, |status| node.status.map(i32::from) == Some(status)))
+        .filter(|node| state.map_or(true, |state| {
+          node.record.as_ref().map(|record| record.state) == Some(state)
+        }))
+        .collect();
+      urls.sort_by(|a, b| a.url.cmp(&b.url));
+
+      urls
+    })
+  }
+}
+
+#[juniper::object(Context = Context, name = "Url")]
+impl UrlNode {
+  fn url(&self) -> &str {
+    &self.url
+  }
+
+  /// The status code of the response the redirects of the url ended at, if it was fetched.
+  fn status(&self) -> Option<i32> {
+    self.status.map(i32::from)
+  }
+
+  /// Where the url is in its lifecycle, unless the url predates the history of urls.
+  fn state(&self) -> Option<&str> {
+    self.record.as_ref().map(|record| record.state.as_str())
+  }
+
+  /// How many links away from the start of the crawl the url was first found.
+  fn depth(&self) -> Option<i32> {
+    self.record.as_ref().map(|record| int(record.depth))
+  }
+
+  fn first_seen(&self) -> Option<DateTime<Utc>> {
+    self.record.as_ref().map(|record| record.first_seen)
+  }
+
+  fn last_seen(&self) -> Option<DateTime<Utc>> {
+    self.record.as_ref().map(|record| record.last_seen)
+  }
+
+  fn annotation(&self) -> Option<&str> {
+    self.record.as_ref().and_then(|record| record.annotation.as_deref())
+  }
+
+  /// The pages of the domain linking to the url in alphabetical order, at most 100 of them.
+  fn referrers(&self) -> Vec<&str> {
+    self.record.as_ref()
+      .map(|record| record.referrers.iter().map(String::as_str).collect())
+      .unwrap_or_default()
+  }
+
+  /// The urls of the domain the url links to in alphabetical order. Only links recorded as
+  /// referrers of their targets are known.
+  fn links(&self, context: &Context) -> FieldResult<Vec<String>> {
+    with_domain(context, &self.domain, |record| {
+      let mut links: Vec<String> = record.records.iter()
+        .filter(|(_, target)| target.referrers.contains(&self.url))
+        .map(|(url, _)| url.clone())
+        .collect();
+      links.sort();
+
+      links
+    })
+  }
+}
+
+#[juniper::object(Context = Context, name = "Job")]
+impl JobNode {
+  fn id(&self) -> i32 {
+    int(self.id)
+  }
+
+  fn url(&self) -> &str {
+    &self.job.url
+  }
+
+  /// One of queued, running, finished or failed.
+  fn state(&self) -> &str {
+    self.job.state.as_str()
+  }
+
+  fn submitted_at(&self) -> DateTime<Utc> {
+    self.job.submitted_at
+  }
+
+  fn started_at(&self) -> Option<DateTime<Utc>> {
+    self.job.started_at
+  }
+
+  fn finished_at(&self) -> Option<DateTime<Utc>> {
+    self.job.finished_at
+  }
+
+  fn pages_crawled(&self) -> i32 {
+    int(self.job.pages_crawled)
+  }
+
+  fn pages_failed(&self) -> i32 {
+    int(self.job.pages_failed)
+  }
+
+  fn bytes_downloaded(&self) -> i32 {
+    int(self.job.bytes_downloaded)
+  }
+
+  fn truncated(&self) -> bool {
+    self.job.truncated
+  }
+
+  fn error(&self) -> Option<&str> {
+    self.job.error.as_deref()
+  }
+
+  fn stop_reason(&self) -> Option<&str> {
+    self.job.stop_reason.map(|reason| reason.as_str())
+  }
+}
+
+/// Reads the record of given domain. The domain can be deleted between resolving it and its
+/// fields, in which case it is reported as an error.
+fn with_domain<T>(
+  context: &Context,
+  name: &str,
+  read: impl FnOnce(&Domain) -> T,
+) -> FieldResult<T> {
+  let db = lock(&context.db)?;
+
+  db.get(name).map(read).ok_or_else(|| error(routes::domain_not_found(name)))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> FieldResult<MutexGuard<'_, T>> {
+  mutex.lock().map_err(|_| error(ApiError::lock()))
+}
+
+/// Reports an error of the REST api as a field error.
+fn error(error: ApiError) -> FieldError {
+  FieldError::new(error.message(), Value::null())
+}
+
+/// GraphQL integers are 32 bit, larger numbers are capped.
+fn int(number: usize) -> i32 {
+  number.min(i32::MAX as usize) as i32
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Context, schema};
+  use super::super::jobs::Job;
+  use super::super::database::{Domain, Page, UrlRecord};
+  use chrono::Utc;
+  use juniper::Variables;
+  use serde_json::json;
+
+  #[test]
+  fn test_query() {
+    let context = Context { db: Default::default(), jobs: Default::default() };
+    let mut domain = Domain::default();
+    for url in &["https://github.com/a", "https://github.com/b", "https://github.com/c"] {
+      domain.urls.insert(url.to_string());
+    }
+    domain.records.insert("https://github.com/a".to_string(), UrlRecord::new(Utc::now(), 0));
+    let mut record = UrlRecord::new(Utc::now(), 1);
+    record.found(Utc::now(), 1, Some(&"https://github.com/a".to_string()));
+    domain.records.insert("https://github.com/b".to_string(), record);
+    let page = Page { status: Some(404), ..Default::default() };
+    domain.pages.insert("https://github.com/b".to_string(), page);
+    context.db.lock().unwrap().insert("github.com".to_string(), domain);
+    context.jobs.lock().unwrap().push(Job::new("https://github.com".to_string()));
+
+    let query = r#"{
+      domain(name: "GitHub.com") {
+        name
+        urlCount
+        urls(status: 404) { url status depth referrers }
+        all: urls { url links }
+      }
+      missing: domain(name: "gitlab.com") { name }
+      job(id: 0) { id url state pagesCrawled finishedAt }
+    }"#;
+    let (value, errors) = juniper::execute(query, None, &schema(), &Variables::new(), &context)
+      .unwrap();
+
+    assert!(errors.is_empty());
+    assert_eq!(serde_json::to_value(value).unwrap(), json!({
+      "domain": {
+        "name": "github.com",
+        "urlCount": 3,
+        "urls": [
+          {"url": "https://github.com/b", "status": 404, "depth": 1,
+            "referrers": ["https://github.com/a"]},
+        ],
+        "all": [
+          {"url": "https://github.com/a", "links": ["https://github.com/b"]},
+          {"url": "https://github.com/b", "links": []},
+          {"url": "https://github.com/c", "links": []},
+        ],
+      },
+      "missing": null,
+      "job": {
+        "id": 0, "url": "https://github.com", "state": "queued", "pagesCrawled": 0,
+        "finishedAt": null,
+      },
+    }));
+  }
+
+  #[test]
+  fn test_invalid_state() {
+    let context = Context { db: Default::default(), jobs: Default::default() };
+    context.db.lock().unwrap().insert("github.com".to_string(), Domain::default());
+
+    let query = r#"{ domain(name: "github.com") { urls(state: "lost") { url } } }"#;
+    let (_, errors) = juniper::execute(query, None, &schema(), &Variables::new(), &context)
+      .unwrap();
+
+    assert_eq!(errors.len(), 1);
+  }
+}
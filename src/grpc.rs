@@ -0,0 +1,163 @@
+use super::fetch;
+use super::routes;
+use super::Database;
+use super::jobs::Jobs;
+use super::config::Config;
+use super::errors::ApiError;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+use tokio::runtime::Runtime;
+use tonic::{Code, Request, Response, Status};
+use tonic::transport::Server;
+
+/// The types and the service trait generated from `proto/crawler.proto`.
+pub mod proto {
+  tonic::include_proto!("crawler.v1");
+}
+
+use self::proto::crawler_server::{Crawler, CrawlerServer};
+use self::proto::{CrawlReply, CrawlRequest, GetJobRequest, Job, ListUrlsReply, ListUrlsRequest};
+
+/// Serves the crawl submission, job status and url listing operations over gRPC. It shares the
+/// database, the jobs and the crawler channel with the REST api.
+pub struct Service {
+  pub db: Database,
+  pub jobs: Jobs,
+  pub config: Config,
+  pub producer: Mutex<Sender<usize>>,
+}
+
+/// Runs the gRPC server on given address. Blocks the thread until the server stops.
+pub fn serve(address: SocketAddr, service: Service) {
+  let runtime = match Runtime::new() {
+    Ok(runtime) => runtime,
+    Err(e) => {
+      println!("[gRPC] Error during runtime creation: {:?}", e);
+
+      return;
+    },
+  };
+
+  println!("[gRPC] Listening on {}", address);
+  let server = Server::builder().add_service(CrawlerServer::new(service)).serve(address);
+
+  if let Err(e) = runtime.block_on(server) {
+    println!("[gRPC] Error during serving: {:?}", e);
+  }
+}
+
+#[tonic::async_trait]
+impl Crawler for Service {
+  async fn crawl(&self, request: Request<CrawlRequest>) -> Result<Response<CrawlReply>, Status> {
+    let job = routes::new_job(&self.config, &request.get_ref().url).map_err(status)?;
+    let id = routes::enqueue(&self.producer, &self.jobs, job).map_err(status)?;
+
+    Ok(Response::new(CrawlReply { id: id as u64 }))
+  }
+
+  async fn get_job(&self, request: Request<GetJobRequest>) -> Result<Response<Job>, Status> {
+    let id = request.get_ref().id as usize;
+    let jobs = self.jobs.lock().map_err(|_| status(ApiError::lock()))?;
+    let job = jobs.get(id).ok_or_else(|| status(routes::job_not_found(id)))?;
+    let time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+      time.map(|time| time.to_rfc3339()).unwrap_or_default()
+    };
+
+    Ok(Response::new(Job {
+      id: id as u64,
+      url: job.url.clone(),
+      state: job.state.as_str().to_string(),
+      submitted_at: job.submitted_at.to_rfc3339(),
+      started_at: time(job.started_at),
+      finished_at: time(job.finished_at),
+      pages_crawled: job.pages_crawled as u64,
+      bytes_downloaded: job.bytes_downloaded as u64,
+      truncated: job.truncated,
+      error: job.error.clone().unwrap_or_default(),
+      stop_reason: job.stop_reason.map(|reason| reason.as_str().to_string()).unwrap_or_default(),
+    }))
+  }
+
+  async fn list_urls(
+    &self,
+    request: Request<ListUrlsRequest>,
+  ) -> Result<Response<ListUrlsReply>, Status> {
+    // Domains are looked up the same way as in the paths of the REST api.
+    let domain = fetch::ascii_host(request.get_ref().domain.trim());
+    let db = self.db.lock().map_err(|_| status(ApiError::lock()))?;
+    let mut urls: Vec<String> = db.get(&domain)
+      .map(|record| record.urls.iter().cloned().collect())
+      .unwrap_or_default();
+    urls.sort();
+
+    Ok(Response::new(ListUrlsReply { urls }))
+  }
+}
+
+/// Converts an error of the REST api into the closest gRPC status.
+fn status(error: ApiError) -> Status {
+  let code = match error.status().code {
+    400 => Code::InvalidArgument,
+    403 => Code::PermissionDenied,
+    404 => Code::NotFound,
+    503 => Code::Unavailable,
+    _ => Code::Internal,
+  };
+
+  Status::new(code, error.message())
+}
+
+#[cfg(test)]
+mod test {
+  use super::Service;
+  use super::proto::crawler_server::Crawler;
+  use super::proto::{CrawlRequest, GetJobRequest, ListUrlsRequest};
+  use super::super::config::Config;
+  use super::super::database::Domain;
+  use std::sync::Mutex;
+  use std::sync::mpsc::channel;
+  use tokio::runtime::Runtime;
+  use tonic::{Code, Request};
+
+  #[test]
+  fn test_service() {
+    let (producer, consumer) = channel::<usize>();
+    let config = Config::from_rocket(&rocket::Config::development());
+    let service = Service {
+      db: Default::default(),
+      jobs: Default::default(),
+      config,
+      producer: Mutex::new(producer),
+    };
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/b"));
+    domain.urls.insert(String::from("https://github.com/a"));
+    service.db.lock().unwrap().insert("github.com".to_string(), domain);
+    let runtime = Runtime::new().unwrap();
+
+    let reply = runtime.block_on(service.crawl(Request::new(CrawlRequest {
+      url: String::from("https://github.com"),
+    }))).unwrap();
+    assert_eq!(reply.get_ref().id, 0);
+    assert_eq!(consumer.try_recv(), Ok(0));
+
+    let job = runtime.block_on(service.get_job(Request::new(GetJobRequest { id: 0 }))).unwrap();
+    assert_eq!(job.get_ref().url, "https://github.com");
+    assert_eq!(job.get_ref().state, "queued");
+    assert_eq!(job.get_ref().finished_at, "");
+
+    let missing = runtime.block_on(service.get_job(Request::new(GetJobRequest { id: 1 })));
+    assert_eq!(missing.unwrap_err().code(), Code::NotFound);
+
+    let invalid = runtime.block_on(service.crawl(Request::new(CrawlRequest {
+      url: String::from("ftp://github.com"),
+    })));
+    assert_eq!(invalid.unwrap_err().code(), Code::InvalidArgument);
+
+    let urls = runtime.block_on(service.list_urls(Request::new(ListUrlsRequest {
+      domain: String::from("GitHub.com"),
+    }))).unwrap();
+    assert_eq!(urls.get_ref().urls, vec!("https://github.com/a", "https://github.com/b"));
+  }
+}
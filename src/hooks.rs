@@ -0,0 +1,204 @@
+use url::Url;
+use super::config::Config;
+use super::filter::{PatternFilter, UrlFilter};
+use std::collections::HashSet;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// The request the crawler is about to send for a page of a crawl.
+pub struct PageRequest {
+  /// The url to fetch. The page is still stored under the url the crawl found if it is changed.
+  pub url: Url,
+  /// Headers sent on top of the ones of the client, including to the urls the page redirects to.
+  pub headers: HeaderMap,
+}
+
+/// A custom policy of the crawler, registered when the service starts. Every hook point does
+/// nothing unless the hook implements it.
+pub trait Hook: Send + Sync {
+  /// Runs before a page of a crawl is fetched. The hook can change the request or veto it by
+  /// returning false, in which case the page is skipped.
+  fn before_fetch(&self, _request: &mut PageRequest) -> bool {
+    true
+  }
+
+  /// Runs once the links of a fetched page were found, with the urls of the crawled host the
+  /// crawl follows and the links to other hosts.
+  fn after_parse(&self, _url: &Url, _links: &HashSet<String>, _outbound: &HashSet<String>) {}
+}
+
+/// A closure run before every page is fetched, see `Hook::before_fetch`.
+pub struct BeforeFetch<F>(pub F);
+
+impl<F> Hook for BeforeFetch<F> where F: Fn(&mut PageRequest) -> bool + Send + Sync {
+  fn before_fetch(&self, request: &mut PageRequest) -> bool {
+    (self.0)(request)
+  }
+}
+
+/// A closure run with the links of every fetched page, see `Hook::after_parse`.
+pub struct AfterParse<F>(pub F);
+
+impl<F> Hook for AfterParse<F> where F: Fn(&Url, &HashSet<String>, &HashSet<String>) + Send + Sync {
+  fn after_parse(&self, url: &Url, links: &HashSet<String>, outbound: &HashSet<String>) {
+    (self.0)(url, links, outbound)
+  }
+}
+
+/// The hooks of the crawler in the order they were registered, which is the order they run in,
+/// together with the filters of the links found on pages.
+#[derive(Default)]
+pub struct Hooks {
+  hooks: Vec<Box<dyn Hook>>,
+  filters: Vec<Box<dyn UrlFilter>>,
+}
+
+impl Hooks {
+  /// The hooks implementing the policies of the service settings, which run before any custom
+  /// ones. The `request_headers` are added to every request, replacing the headers of the same
+  /// name. Headers with invalid names or values are left out. Links are filtered by the url
+  /// patterns, see `PatternFilter`, which fails if any of them is invalid.
+  pub fn from_config(config: &Config) -> Result<Hooks, String> {
+    let mut hooks = Hooks::default();
+    let patterns = PatternFilter::from_config(config)?;
+
+    if !patterns.is_empty() {
+      hooks.filter(patterns);
+    }
+
+    let headers: HeaderMap = config.request_headers.iter()
+      .filter_map(|(name, value)| {
+        Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?))
+      })
+      .collect();
+
+    if !headers.is_empty() {
+      hooks.register(BeforeFetch(move |request: &mut PageRequest| {
+        for (name, value) in &headers {
+          request.headers.insert(name.clone(), value.clone());
+        }
+
+        true
+      }));
+    }
+
+    Ok(hooks)
+  }
+
+  pub fn register(&mut self, hook: impl Hook + 'static) {
+    self.hooks.push(Box::new(hook));
+  }
+
+  /// Adds a filter of the links found on pages. Links are followed only if every filter allows
+  /// them.
+  pub fn filter(&mut self, filter: impl UrlFilter + 'static) {
+    self.filters.push(Box::new(filter));
+  }
+
+  pub fn allows(&self, url: &Url) -> bool {
+    self.filters.iter().all(|filter| filter.allows(url))
+  }
+
+  /// Runs the hooks before given request is sent. The first hook which vetoes the request stops
+  /// the ones after it from running.
+  pub fn before_fetch(&self, request: &mut PageRequest) -> bool {
+    self.hooks.iter().all(|hook| hook.before_fetch(request))
+  }
+
+  pub fn after_parse(&self, url: &Url, links: &HashSet<String>, outbound: &HashSet<String>) {
+    for hook in &self.hooks {
+      hook.after_parse(url, links, outbound);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use super::{AfterParse, BeforeFetch, Hooks, PageRequest};
+  use super::super::config::Config;
+  use super::super::filter::PatternFilter;
+  use reqwest::header::HeaderMap;
+  use std::collections::HashSet;
+  use std::sync::{Arc, Mutex};
+
+  #[test]
+  fn test_hooks() {
+    let observed = Arc::new(Mutex::new(vec!()));
+    let mut hooks = Hooks::default();
+    hooks.register(BeforeFetch(|request: &mut PageRequest| {
+      request.headers.insert("x-crawl", "1".parse().unwrap());
+
+      !request.url.path().starts_with("/private")
+    }));
+    hooks.register(BeforeFetch(|request: &mut PageRequest| {
+      request.url.set_query(Some("lang=en"));
+
+      true
+    }));
+    let links = Arc::clone(&observed);
+    hooks.register(AfterParse(move |url: &Url, _: &HashSet<String>, _: &HashSet<String>| {
+      links.lock().unwrap().push(url.to_string());
+    }));
+
+    let request = |url: &str| {
+      PageRequest { url: Url::parse(url).unwrap(), headers: HeaderMap::new() }
+    };
+    let mut public = request("https://github.com/about");
+    let mut private = request("https://github.com/private/keys");
+
+    assert!(hooks.before_fetch(&mut public));
+    assert_eq!(public.url.as_str(), "https://github.com/about?lang=en");
+    assert_eq!(public.headers["x-crawl"], "1");
+    // Vetoed requests are not passed to the following hooks.
+    assert!(!hooks.before_fetch(&mut private));
+    assert_eq!(private.url.query(), None);
+
+    hooks.after_parse(&public.url, &HashSet::new(), &HashSet::new());
+    assert_eq!(*observed.lock().unwrap(), vec!("https://github.com/about?lang=en"));
+  }
+
+  #[test]
+  fn test_from_config() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.request_headers = vec!(
+      (String::from("Authorization"), String::from("Bearer token")),
+      (String::from("invalid name"), String::from("value")),
+    );
+    let mut request = PageRequest {
+      url: Url::parse("https://github.com/").unwrap(),
+      headers: HeaderMap::new(),
+    };
+
+    config.url_exclude_patterns = vec!(String::from("/logout$"));
+
+    let hooks = Hooks::from_config(&config).unwrap();
+    assert!(hooks.before_fetch(&mut request));
+    assert_eq!(request.headers.len(), 1);
+    assert_eq!(request.headers["authorization"], "Bearer token");
+    assert!(!hooks.allows(&Url::parse("https://github.com/logout").unwrap()));
+    // Without any headers or patterns configured, there is nothing to do.
+    config.request_headers = vec!();
+    config.url_exclude_patterns = vec!();
+    let hooks = Hooks::from_config(&config).unwrap();
+    assert!(hooks.hooks.is_empty() && hooks.filters.is_empty());
+    // Invalid patterns are reported.
+    config.url_include_patterns = vec!(String::from("["));
+    assert!(Hooks::from_config(&config).is_err());
+  }
+
+  #[test]
+  fn test_filters() {
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.url_include_patterns = vec!(String::from("^https://github.com/"));
+    let mut hooks = Hooks::default();
+    hooks.filter(PatternFilter::from_config(&config).unwrap());
+    hooks.filter(|url: &Url| !url.query_pairs().any(|(name, _)| name == "token"));
+    let allows = |url: &str| hooks.allows(&Url::parse(url).unwrap());
+
+    assert!(allows("https://github.com/about?lang=en"));
+    // Links are followed only if every filter allows them.
+    assert!(!allows("https://github.com/about?token=4f2a"));
+    assert!(!allows("https://gitlab.com/about"));
+    assert!(Hooks::default().allows(&Url::parse("https://gitlab.com/about").unwrap()));
+  }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use super::frontier::CrawlOrder;
+use super::traps::Trap;
+
+/// Every crawl submitted to the service is tracked as a job. Jobs are identified by their
+/// position in the list.
+pub type Jobs = Arc<Mutex<Vec<Job>>>;
+
+/// The progress of a single crawl.
+#[derive(Clone)]
+pub struct Job {
+  /// The url the crawl starts from.
+  pub url: String,
+  /// Whether the url was submitted as a bare domain and https was assumed. The crawler falls
+  /// back to http if the host does not respond over https.
+  pub guessed_scheme: bool,
+  /// Urls the crawl starts from instead of the url, used to fetch the urls of the host which
+  /// failed in earlier crawls again and to resume crawls. Empty for crawls of the whole host.
+  pub retried: Vec<String>,
+  /// If set, the crawl fetches again the urls of the host which were not fetched within this
+  /// many hours, rather than only the url and the urls it finds for the first time.
+  pub stale_after_hours: Option<u64>,
+  /// Whether the crawl goes breadth or depth first.
+  pub order: CrawlOrder,
+  /// Limits of the crawl on top of the ones of the service.
+  pub stop: StopConditions,
+  /// Whether the crawl only finds out which urls it would fetch. Dry runs fetch pages as usual,
+  /// but store nothing they learn and don't notify, export or index anything.
+  pub dry_run: bool,
+  /// A CSS selector limiting which links of the pages the crawl follows to the ones inside of
+  /// matching elements, e.g. `nav`. All links are followed if unset.
+  pub link_scope: Option<String>,
+  /// How long the crawl waits between pages as the robots.txt of the host asks, if it does.
+  pub crawl_delay: Option<Duration>,
+  pub state: JobState,
+  pub submitted_at: DateTime<Utc>,
+  pub started_at: Option<DateTime<Utc>>,
+  pub finished_at: Option<DateTime<Utc>>,
+  /// How many pages have been fetched so far.
+  pub pages_crawled: usize,
+  /// How many pages did not respond or responded with an error status so far.
+  pub pages_failed: usize,
+  /// How many bytes of page bodies have been downloaded so far.
+  pub bytes_downloaded: usize,
+  /// Whether the crawl was stopped early because it ran out of its byte budget, the domain ran
+  /// out of its daily budget of pages or its crawl window closed.
+  pub truncated: bool,
+  /// Why the crawl could not be carried out, if it failed.
+  pub error: Option<String>,
+  /// Why the crawl stopped, once it did.
+  pub stop_reason: Option<StopReason>,
+  /// The urls the crawl was yet to fetch when it stopped, from which it can be resumed.
+  pub frontier: Vec<String>,
+  /// The urls a dry run fetched so far, in the order it fetched them. Always empty for other
+  /// crawls.
+  pub would_crawl: Vec<String>,
+  /// Branches of the host the crawl stopped expanding as they seemed to be infinite url spaces.
+  pub traps: Vec<Trap>,
+  pub timing: Timing,
+}
+
+/// Conditions a single crawl stops on besides running out of urls, unset ones don't apply.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StopConditions {
+  /// How many pages the crawl fetches at most, instead of the default of 16.
+  pub max_pages: Option<usize>,
+  /// How long the crawl may take, checked between its rounds of pages.
+  pub max_duration: Option<Duration>,
+  /// After how many pages in a row which did not respond or responded with a server error the
+  /// crawl gives up, as the host is most likely down.
+  pub max_consecutive_errors: Option<usize>,
+}
+
+/// The condition a crawl stopped on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+  /// The crawl fetched every url it found.
+  FrontierExhausted,
+  MaxPages,
+  MaxDuration,
+  MaxConsecutiveErrors,
+  /// The crawl downloaded more than `max_bytes_per_job`.
+  ByteBudget,
+  /// The domain ran out of its `max_pages_per_domain_per_day`.
+  DailyBudget,
+  CrawlWindow,
+}
+
+/// Time a crawl spent in each of its phases. Pages of a crawl are fetched in parallel and the
+/// times are summed over all pages, therefore they can add up to more than the crawl took.
+#[derive(Clone, Copy, Default)]
+pub struct Timing {
+  /// Waiting for responses, including sitemap, certificate and link checks.
+  pub fetch: Duration,
+  /// Parsing pages and extracting their details.
+  pub parse: Duration,
+  /// Storing pages and links in the database, including waiting for its lock.
+  pub insert: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JobState {
+  /// The crawler has not picked the job up yet.
+  Queued,
+  Running,
+  Finished,
+  /// The crawl could not be carried out at all.
+  Failed,
+}
+
+impl Job {
+  /// Creates a job which waits for the crawler.
+  pub fn new(url: String) -> Job {
+    Job {
+      url,
+      guessed_scheme: false,
+      retried: vec!(),
+      stale_after_hours: None,
+      order: CrawlOrder::BreadthFirst,
+      stop: StopConditions::default(),
+      dry_run: false,
+      link_scope: None,
+      crawl_delay: None,
+      state: JobState::Queued,
+      submitted_at: Utc::now(),
+      started_at: None,
+      finished_at: None,
+      pages_crawled: 0,
+      pages_failed: 0,
+      bytes_downloaded: 0,
+      truncated: false,
+      error: None,
+      stop_reason: None,
+      frontier: vec!(),
+      would_crawl: vec!(),
+      traps: vec!(),
+      timing: Timing::default(),
+    }
+  }
+
+  /// Creates a job which continues a crawl of given url from the urls it was yet to fetch.
+  pub fn resuming(url: String, frontier: Vec<String>) -> Job {
+    let mut job = Job::new(url);
+    job.retried = frontier;
+
+    job
+  }
+}
+
+impl Timing {
+  /// Adds the time spent in the phases of another part of the crawl.
+  pub fn add(&mut self, other: Timing) {
+    self.fetch += other.fetch;
+    self.parse += other.parse;
+    self.insert += other.insert;
+  }
+}
+
+impl JobState {
+  /// The name of the state as used in responses.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      JobState::Queued => "queued",
+      JobState::Running => "running",
+      JobState::Finished => "finished",
+      JobState::Failed => "failed",
+    }
+  }
+}
+
+impl StopReason {
+  /// The name of the reason as used in responses.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      StopReason::FrontierExhausted => "frontier_exhausted",
+      StopReason::MaxPages => "max_pages",
+      StopReason::MaxDuration => "max_seconds",
+      StopReason::MaxConsecutiveErrors => "max_consecutive_errors",
+      StopReason::ByteBudget => "max_bytes_per_job",
+      StopReason::DailyBudget => "max_pages_per_domain_per_day",
+      StopReason::CrawlWindow => "crawl_window",
+    }
+  }
+}
+
+/// Applies given change to the job with given id. Jobs are never removed, so the id is always
+/// valid if it was handed out by the service.
+pub fn update(jobs: &Jobs, id: usize, change: impl FnOnce(&mut Job)) {
+  // TODO: Error handling the mutex.
+  let mut jobs = jobs.lock().unwrap();
+
+  if let Some(job) = jobs.get_mut(id) {
+    change(job);
+  }
+}
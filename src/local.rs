@@ -0,0 +1,219 @@
+use url::Url;
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use reqwest::{Response, StatusCode};
+use reqwest::header::HeaderMap;
+use super::config::Config;
+use super::fetch::Fetcher;
+use super::tls::Certificate;
+
+/// Serves `file://` urls from the local directories the service may crawl, so that builds of
+/// static sites can be link checked before they are deployed. All other urls are fetched with
+/// another fetcher.
+///
+/// Directories are served their `index.html`, redirecting to the url with a trailing slash
+/// first if it is missing, like web servers do, so that relative links resolve the same way.
+/// Missing files respond with 404 Not Found.
+pub struct LocalFetcher<F> {
+  fetcher: F,
+  /// The `local_directories` setting with symbolic links resolved.
+  roots: Vec<PathBuf>,
+}
+
+impl<F: Fetcher> LocalFetcher<F> {
+  pub fn new(fetcher: F, config: &Config) -> LocalFetcher<F> {
+    LocalFetcher { fetcher, roots: roots(config) }
+  }
+
+  /// Reads the file of given url.
+  fn read(&self, url: &Url) -> Option<(Response, Vec<String>)> {
+    let path = url.to_file_path().ok()?;
+
+    if !is_within(&self.roots, &path) {
+      println!("[Crawler] Refusing to read file outside of the local directories {}", url);
+
+      return None;
+    }
+
+    let mut chain = vec!();
+    let file = if path.is_dir() {
+      if !url.path().ends_with('/') {
+        let mut redirected = url.clone();
+        redirected.set_path(&format!("{}/", url.path()));
+        chain.push(redirected.as_str().to_string());
+      }
+
+      path.join("index.html")
+    } else {
+      path
+    };
+
+    let (status, body) = match fs::read(&file) {
+      Ok(body) => (200, body),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => (404, vec!()),
+      Err(_) => return None,
+    };
+    let mut response = http::Response::builder();
+    response.status(status).header("content-type", content_type(&file));
+
+    Some((Response::from(response.body(body).ok()?), chain))
+  }
+}
+
+impl<F: Fetcher> Fetcher for LocalFetcher<F> {
+  fn get_with(&self, url: &Url, headers: &HeaderMap) -> Option<(Response, Vec<String>)> {
+    if url.scheme() == "file" {
+      self.read(url)
+    } else {
+      self.fetcher.get_with(url, headers)
+    }
+  }
+
+  fn head(&self, url: &Url) -> Option<(StatusCode, Vec<String>)> {
+    if url.scheme() == "file" {
+      self.read(url).map(|(response, chain)| (response.status(), chain))
+    } else {
+      self.fetcher.head(url)
+    }
+  }
+
+  fn certificate(&self, url: &Url) -> Option<Certificate> {
+    if url.scheme() == "file" {
+      None
+    } else {
+      self.fetcher.certificate(url)
+    }
+  }
+}
+
+/// The domain given url is stored under. Local trees have no host, so their files are stored
+/// under the one of the `local_directories` they are in, with symbolic links resolved, e.g.
+/// `/srv/site/public`. Trees of different directories are then kept apart like hosts are.
+/// Returns None for files outside of the directories.
+pub fn host(config: &Config, url: &Url) -> Option<String> {
+  match url.host_str() {
+    Some(host) => Some(host.to_string()),
+    None if url.scheme() == "file" => {
+      let roots = roots(config);
+
+      root_of(&roots, &url.to_file_path().ok()?)?.to_str().map(String::from)
+    },
+    None => None,
+  }
+}
+
+/// Checks whether the file of given `file://` url is within one of the `local_directories`.
+pub fn is_allowed(config: &Config, url: &Url) -> bool {
+  match url.to_file_path() {
+    Ok(path) => is_within(&roots(config), &path),
+    Err(_) => false,
+  }
+}
+
+/// The `local_directories` setting with symbolic links resolved. Directories which don't exist
+/// are left as they are.
+fn roots(config: &Config) -> Vec<PathBuf> {
+  config.local_directories.iter()
+    .map(|directory| fs::canonicalize(directory).unwrap_or_else(|_| PathBuf::from(directory)))
+    .collect()
+}
+
+/// Checks whether given path is within one of given directories.
+fn is_within(roots: &[PathBuf], path: &Path) -> bool {
+  root_of(roots, path).is_some()
+}
+
+/// Finds the directory of given ones which given path is within, the innermost one if they are
+/// nested. Symbolic links are resolved first, so that they can't point out of the directories.
+/// Urls can't either, as their `..` segments are resolved when they are parsed.
+fn root_of<'a>(roots: &'a [PathBuf], path: &Path) -> Option<&'a PathBuf> {
+  let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+  roots.iter()
+    .filter(|root| path.starts_with(root))
+    .max_by_key(|root| root.as_os_str().len())
+}
+
+/// Guesses the content type of a file from its extension.
+fn content_type(path: &Path) -> &'static str {
+  let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+
+  match extension.to_lowercase().as_str() {
+    "html" | "htm" => "text/html",
+    "xml" => "application/xml",
+    "txt" => "text/plain",
+    "css" => "text/css",
+    "js" => "application/javascript",
+    "json" => "application/json",
+    _ => "application/octet-stream",
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use super::{host, is_allowed, LocalFetcher};
+  use super::super::config::Config;
+  use super::super::fetch::{Fetcher, MockFetcher};
+  use std::env;
+  use std::fs;
+
+  #[test]
+  fn test_local_fetcher() {
+    let directory = env::temp_dir().join("link-crawler-test-local");
+    fs::create_dir_all(directory.join("site/docs")).unwrap();
+    fs::write(directory.join("site/index.html"), "<a href=\"docs/\">Docs</a>").unwrap();
+    fs::write(directory.join("site/docs/index.html"), "<a href=\"../about.html\">x</a>").unwrap();
+    fs::write(directory.join("secret.txt"), "").unwrap();
+
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.local_directories = vec!(directory.join("site").to_str().unwrap().to_string());
+    let fetcher = LocalFetcher::new(MockFetcher::default(), &config);
+    let url = |path: &str| Url::from_file_path(directory.join(path)).unwrap();
+
+    let (mut response, chain) = fetcher.get(&url("site/index.html")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/html");
+    assert_eq!(response.text().unwrap(), "<a href=\"docs/\">Docs</a>");
+    assert!(chain.is_empty());
+    // Directories are served their index, with the trailing slash.
+    let (mut response, chain) = fetcher.get(&url("site/docs")).unwrap();
+    assert_eq!(response.text().unwrap(), "<a href=\"../about.html\">x</a>");
+    assert_eq!(chain, vec!(format!("{}/", url("site/docs"))));
+    assert_eq!(fetcher.status(url("site/about.html").as_str()).unwrap(), 404);
+    // Files outside of the local directories are never read.
+    assert!(fetcher.get(&url("secret.txt")).is_none());
+    assert!(is_allowed(&config, &url("site/docs/")));
+    assert!(!is_allowed(&config, &url("secret.txt")));
+    // Other urls are fetched as usual.
+    assert_eq!(fetcher.status("https://github.com/").unwrap(), 404);
+
+    fs::remove_dir_all(&directory).unwrap();
+  }
+
+  #[test]
+  fn test_host() {
+    let directory = env::temp_dir().join("link-crawler-test-local-host");
+    fs::create_dir_all(directory.join("blog/drafts")).unwrap();
+    fs::create_dir_all(directory.join("docs")).unwrap();
+    let root = fs::canonicalize(&directory).unwrap();
+
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.local_directories = ["blog", "blog/drafts", "docs"].iter()
+      .map(|path| directory.join(path).to_str().unwrap().to_string())
+      .collect();
+    let url = |path: &str| Url::from_file_path(directory.join(path)).unwrap();
+    let domain = |path: &str| Some(root.join(path).to_str().unwrap().to_string());
+
+    // Each tree is stored apart from the others, nested ones included.
+    assert_eq!(host(&config, &url("blog/index.html")), domain("blog"));
+    assert_eq!(host(&config, &url("blog/drafts/")), domain("blog/drafts"));
+    assert_eq!(host(&config, &url("docs/missing.html")), domain("docs"));
+    assert_eq!(host(&config, &url("secret.txt")), None);
+    assert_eq!(host(&config, &Url::parse("https://github.com/").unwrap()).unwrap(), "github.com");
+    assert_eq!(host(&config, &Url::parse("mailto:a@github.com").unwrap()), None);
+
+    fs::remove_dir_all(&directory).unwrap();
+  }
+}
@@ -1,68 +1,308 @@
 #![feature(proc_macro_hygiene, decl_macro)]
 
 extern crate url;
+extern crate flate2;
 extern crate serde;
 #[macro_use]
 extern crate rocket;
 extern crate scraper;
+extern crate html5ever;
 extern crate reqwest;
 extern crate rocket_contrib;
 
+mod cli;
+mod tls;
+mod jobs;
+mod fetch;
+mod tunnel;
+mod local;
+mod config;
+mod options;
+mod compression;
+mod errors;
 mod routes;
+mod reports;
+mod sitemap;
+mod robots;
 mod crawler;
+mod extract;
+mod export;
+mod warc;
+mod elasticsearch;
+mod grpc;
+mod graphql;
+mod throttle;
+mod budget;
+mod frontier;
+mod traps;
+mod checkpoint;
+mod replay;
+mod hooks;
+mod filter;
+mod script;
+mod webhooks;
+mod trash;
+mod storage;
+mod schedule;
+mod window;
+mod pg;
+mod ui;
+mod database;
 
+use std::env;
 use std::thread;
+use std::process;
+use cli::Command;
 use rocket::Rocket;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender};
-use std::collections::{HashMap, HashSet};
+use jobs::Jobs;
+use trash::Trash;
+use webhooks::Webhooks;
+use storage::{Changes, Storage};
+use config::Config;
+use database::Domain;
+use std::collections::HashMap;
 
-pub type Database = Arc<Mutex<HashMap<String, HashSet<String>>>>;
+pub type Database = Arc<Mutex<HashMap<String, Domain>>>;
+
+/// Prefixes the routes are mounted under.
+const API_VERSIONS: &[&str] = &["/v1", "/v2", ""];
 
 fn main() {
+  match cli::parse(env::args().skip(1)) {
+    Ok(Command::Serve) => serve(),
+    Ok(command) => {
+      if let Err(e) = cli::run(command) {
+        eprintln!("{}", e);
+        process::exit(1);
+      }
+    },
+    Err(e) => {
+      if !e.is_empty() {
+        eprintln!("{}\n", e);
+      }
+      eprintln!("{}", cli::USAGE);
+      process::exit(2);
+    },
+  }
+}
+
+/// Runs the web server together with the crawler and, if enabled, the gRPC api.
+fn serve() {
   // Creates an empty hash map where the domains and their unique urls are stored.
   let db: Database = Default::default();
   let cache: Database = Arc::clone(&db);
 
-  // Creates a channel on which the POST /host/${domain} endpoint commits new domains to crawler.
-  let (producer, consumer) = channel::<String>();
+  // Creates a channel on which the POST /host endpoint commits ids of new jobs to crawler.
+  let (producer, consumer) = channel::<usize>();
+
+  let server = server(cache, producer.clone());
+  // Unwrap here is safe as the settings and jobs are always managed by the server.
+  let config = server.state::<Config>().unwrap().clone();
+  let jobs = Arc::clone(server.state::<Jobs>().unwrap());
+  let webhooks = Arc::clone(server.state::<Webhooks>().unwrap());
+  let changes = Arc::clone(server.state::<Changes>().unwrap());
+  let schedules = match schedule::Schedule::from_rocket(server.config()) {
+    Ok(schedules) => schedules,
+    Err(e) => {
+      eprintln!("[Scheduler] {}", e);
+      process::exit(1);
+    },
+  };
+
+  // The settings fall back to defaults for invalid values, which for windows would mean crawling
+  // the domains at any time.
+  if let Err(e) = window::CrawlWindow::from_rocket(server.config()) {
+    eprintln!("[Crawler] {}", e);
+    process::exit(1);
+  }
+
+  // Broken page scripts are reported when the service starts rather than on every page.
+  if let Some(path) = &config.page_script {
+    if let Err(e) = script::PageScript::load(path) {
+      eprintln!("[Crawler] {}", e);
+      process::exit(1);
+    }
+  }
+
+  // Custom policies of the crawler are registered with its hooks here, after the built in ones,
+  // see `hooks::Hook` and `filter::UrlFilter`.
+  let hooks = match hooks::Hooks::from_config(&config) {
+    Ok(hooks) => hooks,
+    Err(e) => {
+      eprintln!("[Crawler] {}", e);
+      process::exit(1);
+    },
+  };
+
+  // With a storage configured, the service starts with the domains it stored before and a new
+  // thread keeps writing the domains which change to the storage.
+  if let Some(url) = &config.postgres_url {
+    let storage = match pg::Postgres::connect(url, config.postgres_pool_size as u32) {
+      Ok(storage) => storage,
+      Err(e) => {
+        eprintln!("[Storage] Error during connecting to PostgreSQL: {}", e);
+        process::exit(1);
+      },
+    };
+
+    match storage.load() {
+      // Unwrap here is safe as nothing else uses the database yet.
+      Ok(domains) => *db.lock().unwrap() = domains,
+      Err(e) => {
+        eprintln!("[Storage] Error during loading of the domains: {}", e);
+        process::exit(1);
+      },
+    }
+
+    let (producer, consumer) = channel::<String>();
+    // TODO: Error handling the mutex.
+    *changes.lock().unwrap() = Some(producer);
+    let db = Arc::clone(&db);
+    thread::spawn(move || storage::listen(Box::new(storage), db, consumer));
+  }
+
+  // Crawls interrupted by the previous run of the service are resumed first, once the stored
+  // domains are loaded so that the crawls know the depths of their urls.
+  if let Some(directory) = &config.checkpoint_directory {
+    let checkpoints = match checkpoint::take(directory) {
+      Ok(checkpoints) => checkpoints,
+      Err(e) => {
+        eprintln!("[Crawler] Error during reading of the checkpoints: {}", e);
+        process::exit(1);
+      },
+    };
+    let producer = Mutex::new(producer.clone());
+
+    for checkpoint in checkpoints {
+      println!("[Crawler] Resuming crawl of {}", checkpoint.url);
+      // The crawler is not running yet, but the jobs wait for it in the channel.
+      let _ = routes::enqueue(&producer, &jobs, checkpoint.into_job());
+    }
+  }
+
+  // Scheduled crawls are submitted from their own thread, the same way the api submits them.
+  if !schedules.is_empty() {
+    let db = Arc::clone(&db);
+    let jobs = Arc::clone(&jobs);
+    let changes = Arc::clone(&changes);
+    let config = config.clone();
+    let producer = producer.clone();
+    thread::spawn(move || schedule::run(schedules, db, jobs, changes, config, producer));
+  }
+
+  // The gRPC api runs on its own thread next to the web server if it is enabled. It shares the
+  // database, the jobs and the channel to the crawler with the web server.
+  if let Some(address) = config.grpc_address {
+    let service = grpc::Service {
+      db: Arc::clone(&db),
+      jobs: Arc::clone(&jobs),
+      config: config.clone(),
+      producer: Mutex::new(producer),
+    };
+    thread::spawn(move || grpc::serve(address, service));
+  }
 
   // Creates a new thread on which the crawler runs. It has access to the database to which it
-  // commits new urls and to the consumer half of the channel.
-  thread::spawn(move || crawler::listen(db, consumer));
+  // commits new urls, to the jobs it reports progress to, to the storage it reports changed
+  // domains to, to the service settings and to the consumer half of the channel.
+  thread::spawn(move || crawler::listen(db, jobs, webhooks, hooks, changes, config, consumer));
 
-  server(cache, producer).launch();
+  server.launch();
 }
 
-/// Starts the web server with crawl, list and count routes. Also adds url cache and crawler
-/// channel to the container to be used by the endpoints.
-fn server(cache: Database, producer: Sender<String>) -> Rocket {
-  rocket::ignite()
-    .mount("/host", routes![routes::crawl, routes::list, routes::count])
+/// Starts the web server with crawl, job, list, count and report routes. Also adds url cache, job
+/// list, service settings and crawler channel to the container to be used by the endpoints.
+fn server(cache: Database, producer: Sender<usize>) -> Rocket {
+  let rocket = rocket::ignite();
+  let config = Config::from_rocket(rocket.config());
+
+  // The api is versioned. Breaking changes to its responses go to a new version, while the
+  // routes without a version remain an alias of the first one for clients which predate
+  // versioning. Routes which did not change are available under every version.
+  API_VERSIONS.iter()
+    .fold(rocket, |rocket, prefix| mount(rocket, prefix))
+    // The dashboard is not a part of the api and is not versioned.
+    .mount("/ui", routes![ui::dashboard])
+    .register(catchers![
+      errors::bad_request, errors::forbidden, errors::not_found, errors::unsupported_media_type,
+      errors::unprocessable_entity, errors::internal_error, errors::service_unavailable,
+    ])
+    .attach(errors::request_ids())
+    .attach(compression::Gzip)
+    .attach(options::Options::default())
     .manage(cache)
+    .manage(Jobs::default())
+    .manage(Trash::default())
+    .manage(Webhooks::default())
+    .manage(Changes::default())
+    .manage(graphql::schema())
+    .manage(config)
     // TODO: Find a better way of sharing producer channel handle without mutex since rocket
     // can't move .clone().
     .manage(Mutex::new(producer))
 }
 
+/// Mounts all routes of the api under given prefix.
+fn mount(rocket: Rocket, prefix: &str) -> Rocket {
+  // The second version lists urls a page at a time, in an envelope.
+  let listing = if prefix == "/v2" { routes![routes::list_page] } else { routes![routes::list] };
+
+  rocket
+    .mount(&format!("{}/host", prefix), listing)
+    .mount(&format!("{}/host", prefix), routes![
+      routes::crawl, routes::count, reports::orphans, reports::sitemap_coverage,
+      reports::redirects, reports::redirect_warnings,
+      routes::sitemap, routes::sitemap_part, routes::tree, reports::broken_outbound,
+      reports::duplicates, reports::missing_alt, reports::headings, reports::canonicals,
+      reports::amp, reports::hreflang, reports::noindex, reports::performance,
+      reports::mixed_content,
+      reports::structured_data, reports::tls, reports::security_headers,
+      reports::third_parties, routes::contacts, routes::script_data, reports::forms,
+      reports::trackers, routes::details, routes::lookup, routes::crawls,
+      routes::diff, routes::domains, routes::tags, routes::set_tags, routes::extraction_rules,
+      routes::set_extraction_rules, routes::fields,
+      routes::annotate, trash::delete_domain, trash::restore_domain, trash::delete_url,
+      trash::restore_url, routes::retry_failed, webhooks::webhook, webhooks::register,
+      webhooks::unregister,
+    ])
+    .mount(&format!("{}/url", prefix), routes![routes::query])
+    .mount(&format!("{}/jobs", prefix), routes![routes::job, routes::resume])
+    .mount(&format!("{}/trash", prefix), routes![trash::list, trash::purge])
+    .mount(&format!("{}/debug", prefix), routes![routes::memory])
+    .mount(&format!("{}/graphql", prefix), routes![graphql::get, graphql::post])
+}
+
 #[cfg(test)]
 mod test {
   use std::thread;
   use super::server;
+  use std::time::Duration;
   use super::rocket;
   use super::Database;
+  use std::sync::Arc;
+  use std::io::Read;
+  use flate2::read::GzDecoder;
   use rocket::http::Status;
   use rocket::local::Client;
+  use super::Domain;
+  use chrono::{TimeZone, Utc};
+  use super::jobs;
+  use super::tls::Certificate;
+  use super::jobs::{JobState, Jobs, StopConditions, StopReason};
+  use super::frontier::CrawlOrder;
+  use super::database::{Alternate, Form, Heading, Page, Session, SitemapCheck, UrlRecord, UrlState};
   use std::sync::mpsc::channel;
-  use std::collections::HashSet;
-  use rocket::http::ContentType;
+  use rocket::http::{ContentType, Header};
+  use super::sitemap::MAX_URLS_PER_SITEMAP;
 
   #[test]
   fn test_list_without_urls() {
     // Creates an empty database.
     let db: Database = Default::default();
 
-    let (producer, _) = channel::<String>();
+    let (producer, _) = channel::<usize>();
 
     let client = Client::new(server(db, producer)).unwrap();
     let mut response = client.get("/host/github.com/url").dispatch();
@@ -75,11 +315,11 @@ mod test {
   fn test_list_with_urls() {
     // Inserts a link into the database.
     let db: Database = Default::default();
-    let mut set = HashSet::new();
-    set.insert(String::from("https://github.com"));
-    db.lock().unwrap().insert("github.com".to_string(), set);
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
 
-    let (producer, _) = channel::<String>();
+    let (producer, _) = channel::<usize>();
 
     let client = Client::new(server(db, producer)).unwrap();
     let mut response = client.get("/host/github.com/url").dispatch();
@@ -89,56 +329,1919 @@ mod test {
   }
 
   #[test]
-  fn test_count_without_urls() {
-    // Creates an empty database.
+  fn test_list_formats() {
     let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/a,b"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
 
-    let (producer, _) = channel::<String>();
+    let (producer, _) = channel::<usize>();
 
     let client = Client::new(server(db, producer)).unwrap();
-    let mut response = client.get("/host/github.com/url/count").dispatch();
+    let mut response = client
+      .get("/host/github.com/url")
+      .header(Header::new("Accept", "text/csv"))
+      .dispatch();
 
     assert_eq!(response.status(), Status::Ok);
-    assert_eq!(response.body_string(), Some("{\"count\":0}".into()));
+    assert_eq!(response.content_type(), Some(ContentType::CSV));
+    assert_eq!(response.body_string(), Some("url\r\n\"https://github.com/a,b\"\r\n".into()));
+
+    let mut response = client
+      .get("/host/github.com/url")
+      .header(Header::new("Accept", "application/json;q=0.5, text/plain"))
+      .dispatch();
+
+    assert_eq!(response.content_type(), Some(ContentType::Plain));
+    assert_eq!(response.body_string(), Some("https://github.com/a,b\n".into()));
+
+    let mut response = client
+      .get("/host/github.com/url")
+      .header(Header::new("Accept", "*/*"))
+      .dispatch();
+
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    assert_eq!(response.body_string(), Some("[\"https://github.com/a,b\"]".into()));
   }
 
   #[test]
-  fn test_count_with_urls() {
-    // Inserts a link into the database.
+  fn test_etag() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    domain.version = 1;
+    db.lock().unwrap().insert("github.com".to_string(), Domain::default());
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(Arc::clone(&db), producer)).unwrap();
+    let response = client.get("/host/github.com/url").dispatch();
+    let etag = response.headers().get_one("ETag").unwrap().to_string();
+
+    // Other formats and endpoints have different tags.
+    let csv = client
+      .get("/host/github.com/url")
+      .header(Header::new("Accept", "text/csv"))
+      .dispatch();
+    let count = client.get("/host/github.com/url/count").dispatch();
+
+    assert_ne!(csv.headers().get_one("ETag"), Some(etag.as_str()));
+    assert_ne!(count.headers().get_one("ETag"), Some(etag.as_str()));
+
+    let mut response = client
+      .get("/host/github.com/url")
+      .header(Header::new("If-None-Match", etag.clone()))
+      .dispatch();
+
+    assert_eq!(response.status(), Status::NotModified);
+    assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+    assert_eq!(response.body_string(), None);
+
+    // The crawler found new urls.
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let mut response = client
+      .get("/host/github.com/url")
+      .header(Header::new("If-None-Match", etag.clone()))
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_ne!(response.headers().get_one("ETag"), Some(etag.as_str()));
+    assert_eq!(response.body_string(), Some("[\"https://github.com\"]".into()));
+  }
+
+  #[test]
+  fn test_gzip() {
     let db: Database = Default::default();
-    let mut set = HashSet::new();
-    set.insert(String::from("https://github.com"));
-    db.lock().unwrap().insert("github.com".to_string(), set);
+    let mut domain = Domain::default();
+    for i in 0..100 {
+      domain.urls.insert(format!("https://github.com/{}", i));
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
 
-    let (producer, _) = channel::<String>();
+    let (producer, _) = channel::<usize>();
 
     let client = Client::new(server(db, producer)).unwrap();
-    let mut response = client.get("/host/github.com/url/count").dispatch();
+    let mut plain = client.get("/host/github.com/url").dispatch();
+    let plain = plain.body_string().unwrap();
+    let mut response = client
+      .get("/host/github.com/url")
+      .header(Header::new("Accept-Encoding", "br;q=1.0, gzip;q=0.8"))
+      .dispatch();
 
     assert_eq!(response.status(), Status::Ok);
-    assert_eq!(response.body_string(), Some("{\"count\":1}".into()));
+    assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+
+    let compressed = response.body_bytes().unwrap();
+    let mut decompressed = String::new();
+    GzDecoder::new(&compressed[..]).read_to_string(&mut decompressed).unwrap();
+
+    assert!(compressed.len() < plain.len());
+    assert_eq!(decompressed, plain);
+
+    // Small bodies and clients refusing gzip get the body as it is.
+    let count = client
+      .get("/host/github.com/url/count")
+      .header(Header::new("Accept-Encoding", "gzip"))
+      .dispatch();
+    let refused = client
+      .get("/host/github.com/url")
+      .header(Header::new("Accept-Encoding", "gzip;q=0, identity"))
+      .dispatch();
+
+    assert_eq!(count.headers().get_one("Content-Encoding"), None);
+    assert_eq!(refused.headers().get_one("Content-Encoding"), None);
   }
 
   #[test]
-  fn test_crawl() {
+  fn test_versioned_routes() {
     let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
 
-    let (producer, consumer) = channel::<String>();
+    let (producer, _) = channel::<usize>();
 
     let client = Client::new(server(db, producer)).unwrap();
 
-    let test_receiver = thread::spawn(move || {
-      assert_eq!("https://github.com", consumer.recv().unwrap());
-    });
+    for path in &["/v1/host/github.com/url/count", "/host/github.com/url/count"] {
+      let mut response = client.get(*path).dispatch();
+
+      assert_eq!(response.status(), Status::Ok);
+      assert_eq!(response.body_string(), Some("{\"count\":1}".into()));
+    }
+
+    let mut response = client.get("/v1/jobs/0").dispatch();
+
+    assert!(response.body_string().unwrap().contains("\"job_not_found\""));
+    assert_eq!(client.get("/v1/debug/memory").dispatch().status(), Status::Ok);
+  }
+
+  #[test]
+  fn test_head_and_options() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.head("/host/github.com/url").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("1"));
+    assert!(response.body_bytes().map(|body| body.is_empty()).unwrap_or(true));
+
+    let response = client.options("/v1/host/github.com/url").dispatch();
+
+    assert_eq!(response.status(), Status::NoContent);
+    assert_eq!(response.headers().get_one("Allow"), Some("DELETE, GET, HEAD, OPTIONS"));
+
+    let response = client.options("/host").dispatch();
+
+    assert_eq!(response.headers().get_one("Allow"), Some("GET, HEAD, OPTIONS, POST"));
+    assert_eq!(client.options("/nowhere").dispatch().status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_query() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/b"));
+    domain.urls.insert(String::from("https://github.com/a"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client
+      .post("/url/query")
+      .header(ContentType::JSON)
+      .body("{\"domains\":[\"GitHub.com\",\"gitlab.com\"]}")
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"github.com\":{\"count\":2,\"urls\":[\"https://github.com/a\",\"https://github.com/b\"]},",
+      "\"gitlab.com\":{\"count\":0,\"urls\":[]}}",
+    ).into()));
+
+    let mut response = client
+      .post("/v1/url/query")
+      .header(ContentType::JSON)
+      .body("{\"domains\":[\"github.com\"],\"urls\":false}")
+      .dispatch();
 
+    assert_eq!(response.body_string(), Some("{\"github.com\":{\"count\":2}}".into()));
+
+    let domains: Vec<String> = (0..101).map(|i| format!("\"{}.com\"", i)).collect();
     let response = client
-      .post("/host")
+      .post("/url/query")
       .header(ContentType::JSON)
-      .body("{\"url\":\"https://github.com\"}")
+      .body(format!("{{\"domains\":[{}]}}", domains.join(",")))
+      .dispatch();
+
+    assert_eq!(response.status(), Status::BadRequest);
+  }
+
+  #[test]
+  fn test_list_page() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/c"));
+    domain.urls.insert(String::from("https://github.com/a"));
+    domain.urls.insert(String::from("https://github.com/b"));
+    domain.crawled_at = Some(Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/v2/host/github.com/url?page=2&per_page=2").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"total\":3,\"page\":2,\"per_page\":2,\"pages\":2,",
+      "\"crawled_at\":\"2019-07-20T12:00:00+00:00\",\"urls\":[\"https://github.com/c\"]}",
+    ).into()));
+
+    let mut response = client.get("/v2/host/gitlab.com/url").dispatch();
+
+    assert_eq!(response.body_string(), Some(
+      "{\"total\":0,\"page\":1,\"per_page\":1000,\"pages\":0,\"crawled_at\":null,\"urls\":[]}".into(),
+    ));
+
+    // The first version still lists the urls as a bare array, other routes are the same.
+    let mut v1 = client.get("/v1/host/github.com/url").dispatch();
+    let invalid = client.get("/v2/host/github.com/url?page=0").dispatch();
+
+    assert!(v1.body_string().unwrap().starts_with('['));
+    assert_eq!(client.get("/v2/host/github.com/url/count").dispatch().status(), Status::Ok);
+    assert_eq!(invalid.status(), Status::BadRequest);
+  }
+
+  #[test]
+  fn test_url_details() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/b"));
+    domain.urls.insert(String::from("https://github.com/a"));
+    domain.urls.insert(String::from("https://github.com/c"));
+    domain.records.insert(String::from("https://github.com/b"), UrlRecord {
+      first_seen: Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap(),
+      last_seen: Utc.with_ymd_and_hms(2019, 7, 21, 12, 0, 0).unwrap(),
+      depth: 1,
+      referrers: Default::default(),
+      annotation: Some(String::from("Moved to /a")),
+      state: UrlState::Fetched,
+    });
+    domain.records.insert(
+      String::from("https://github.com/c"),
+      UrlRecord::new(Utc.with_ymd_and_hms(2019, 7, 21, 12, 0, 0).unwrap(), 3),
+    );
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/url/details").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"url\":\"https://github.com/a\",\"first_seen\":null,\"last_seen\":null,",
+      "\"depth\":null,\"annotation\":null,\"state\":null},",
+      "{\"url\":\"https://github.com/b\",\"first_seen\":\"2019-07-20T12:00:00+00:00\",",
+      "\"last_seen\":\"2019-07-21T12:00:00+00:00\",\"depth\":1,\"annotation\":\"Moved to /a\",",
+      "\"state\":\"fetched\"},",
+      "{\"url\":\"https://github.com/c\",\"first_seen\":\"2019-07-21T12:00:00+00:00\",",
+      "\"last_seen\":\"2019-07-21T12:00:00+00:00\",\"depth\":3,\"annotation\":null,",
+      "\"state\":\"discovered\"}]",
+    ).into()));
+
+    // Urls of unknown depth are left out when filtering.
+    let mut response = client.get("/host/github.com/url/details?max_depth=2").dispatch();
+    let body = response.body_string().unwrap();
+    assert!(body.contains("https://github.com/b"));
+    assert!(!body.contains("https://github.com/a"));
+    assert!(!body.contains("https://github.com/c"));
+
+    let mut response = client.get("/host/github.com/url/details?min_depth=2").dispatch();
+    let body = response.body_string().unwrap();
+    assert!(!body.contains("https://github.com/b"));
+    assert!(body.contains("https://github.com/c"));
+
+    let mut response = client.get("/host/github.com/url/details?state=fetched").dispatch();
+    let body = response.body_string().unwrap();
+    assert!(body.contains("https://github.com/b"));
+    assert!(!body.contains("https://github.com/c"));
+
+    let response = client.get("/host/github.com/url/details?state=lost").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+  }
+
+  #[test]
+  fn test_url_lookup() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut record = UrlRecord::new(Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap(), 2);
+    let seen_at = Utc.with_ymd_and_hms(2019, 7, 21, 12, 0, 0).unwrap();
+    record.found(seen_at, 1, Some(&String::from("https://github.com/b")));
+    record.found(seen_at, 3, Some(&String::from("https://github.com/a")));
+    record.found(seen_at, 3, Some(&String::from("https://github.com/b")));
+    domain.urls.insert(String::from("https://github.com/c"));
+    domain.records.insert(String::from("https://github.com/c"), record);
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/url/lookup?url=https://github.com/c").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"url\":\"https://github.com/c\",\"first_seen\":\"2019-07-20T12:00:00+00:00\",",
+      "\"last_seen\":\"2019-07-21T12:00:00+00:00\",\"depth\":1,\"annotation\":null,",
+      "\"state\":\"discovered\",\"referrers\":[\"https://github.com/a\",\"https://github.com/b\"]}",
+    ).into()));
+
+    let mut response = client.put("/host/github.com/url/annotation")
+      .header(ContentType::JSON)
+      .body("{\"url\": \"https://github.com/c\", \"annotation\": \" Known 404, fix scheduled \"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let annotation = "\"annotation\":\"Known 404, fix scheduled\"";
+    assert!(response.body_string().unwrap().contains(annotation));
+
+    let mut response = client.get("/host/github.com/url/details").dispatch();
+    assert!(response.body_string().unwrap().contains(annotation));
+
+    let mut response = client.put("/host/github.com/url/annotation")
+      .header(ContentType::JSON)
+      .body("{\"url\": \"https://github.com/c\", \"annotation\": null}")
+      .dispatch();
+    assert!(response.body_string().unwrap().contains("\"annotation\":null"));
+
+    let response = client.put("/host/github.com/url/annotation")
+      .header(ContentType::JSON)
+      .body("{\"url\": \"https://github.com/d\", \"annotation\": \"Gone\"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client.get("/host/github.com/url/lookup?url=https://github.com/d").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_crawls() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.sessions.push(Session {
+      job: 4,
+      url: String::from("https://github.com"),
+      started_at: Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap(),
+      finished_at: Utc.with_ymd_and_hms(2019, 7, 20, 12, 1, 0).unwrap(),
+      pages_crawled: 16,
+      pages_failed: 2,
+      bytes_downloaded: 1024,
+      truncated: false,
+      error: None,
+      urls: Default::default(),
+    });
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/crawls").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"id\":0,\"job\":4,\"url\":\"https://github.com\",",
+      "\"started_at\":\"2019-07-20T12:00:00+00:00\",",
+      "\"finished_at\":\"2019-07-20T12:01:00+00:00\",\"pages_crawled\":16,",
+      "\"pages_failed\":2,\"bytes_downloaded\":1024,\"truncated\":false,\"error\":null}]",
+    ).into()));
+
+    let mut response = client.get("/host/gitlab.com/crawls").dispatch();
+    assert_eq!(response.body_string(), Some("[]".into()));
+  }
+
+  #[test]
+  fn test_diff() {
+    let session = |urls: Vec<(&str, Option<u16>)>| Session {
+      job: 0,
+      url: String::from("https://github.com"),
+      started_at: Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap(),
+      finished_at: Utc.with_ymd_and_hms(2019, 7, 20, 12, 1, 0).unwrap(),
+      pages_crawled: 0,
+      pages_failed: 0,
+      bytes_downloaded: 0,
+      truncated: false,
+      error: None,
+      urls: urls.into_iter().map(|(url, status)| (url.to_string(), status)).collect(),
+    };
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.sessions.push(session(vec!(
+      ("https://github.com", Some(200)),
+      ("https://github.com/a", Some(200)),
+      ("https://github.com/b", Some(200)),
+      ("https://github.com/c", None),
+    )));
+    domain.sessions.push(session(vec!(
+      ("https://github.com", Some(200)),
+      ("https://github.com/a", Some(404)),
+      // Not fetching the url again is not a change.
+      ("https://github.com/c", Some(500)),
+      ("https://github.com/d", None),
+    )));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/diff?from=0&to=1").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"from\":0,\"to\":1,\"added\":[\"https://github.com/d\"],",
+      "\"removed\":[\"https://github.com/b\"],\"changed\":[{\"url\":\"https://github.com/a\",",
+      "\"from_status\":200,\"to_status\":404}]}",
+    ).into()));
+
+    let response = client.get("/host/github.com/diff?from=0&to=2").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_tags() {
+    let db: Database = Default::default();
+    db.lock().unwrap().insert("github.com".to_string(), Domain::default());
+    db.lock().unwrap().insert("gitlab.com".to_string(), Domain::default());
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.put("/host/github.com/tags")
+      .header(ContentType::JSON)
+      .body("[\"team-b\", \" team-a \", \"team-b\"]")
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some("[\"team-a\",\"team-b\"]".into()));
+
+    let mut response = client.get("/host/github.com/tags").dispatch();
+    assert_eq!(response.body_string(), Some("[\"team-a\",\"team-b\"]".into()));
+
+    let mut response = client.get("/host").dispatch();
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"domain\":\"github.com\",\"tags\":[\"team-a\",\"team-b\"],\"urls\":0},",
+      "{\"domain\":\"gitlab.com\",\"tags\":[],\"urls\":0}]",
+    ).into()));
+
+    let mut response = client.get("/host?tag=team-a").dispatch();
+    assert_eq!(response.body_string(), Some(
+      "[{\"domain\":\"github.com\",\"tags\":[\"team-a\",\"team-b\"],\"urls\":0}]".into(),
+    ));
+
+    let response = client.put("/host/github.com/tags")
+      .header(ContentType::JSON)
+      .body("[\" \"]")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.put("/host/bitbucket.org/tags")
+      .header(ContentType::JSON)
+      .body("[\"team-a\"]")
       .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_soft_delete() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    domain.urls.insert(String::from("https://github.com/a"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    // Deleted urls disappear from the listings until they are restored.
+    let response = client.delete("/host/github.com/url?url=https://github.com/a").dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+    let mut response = client.get("/host/github.com/url/count").dispatch();
+    assert_eq!(response.body_string(), Some("{\"count\":1}".into()));
+    let response = client.post("/host/github.com/url/restore?url=https://github.com/a").dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+    let mut response = client.get("/host/github.com/url/count").dispatch();
+    assert_eq!(response.body_string(), Some("{\"count\":2}".into()));
+
+    let response = client.delete("/host/github.com").dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+    let mut response = client.get("/host").dispatch();
+    assert_eq!(response.body_string(), Some("[]".into()));
+    let mut response = client.get("/trash").dispatch();
+    assert!(response.body_string().unwrap().contains("\"domain\":\"github.com\""));
+
+    let response = client.post("/host/github.com/restore").dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+    let mut response = client.get("/host/github.com/url/count").dispatch();
+    assert_eq!(response.body_string(), Some("{\"count\":2}".into()));
+    let response = client.post("/host/github.com/restore").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    // Purged domains can't be restored.
+    client.delete("/host/github.com").dispatch();
+    let mut response = client.delete("/trash?domain=github.com").dispatch();
+    assert_eq!(response.body_string(), Some("{\"domains\":1,\"urls\":0}".into()));
+    let response = client.post("/host/github.com/restore").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_retry_failed() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+
+    for (url, state) in &[
+      ("https://github.com", UrlState::Fetched),
+      ("https://github.com/b", UrlState::Failed),
+      ("https://github.com/a", UrlState::Failed),
+    ] {
+      let mut record = UrlRecord::new(Utc::now(), 1);
+      record.state = *state;
+      domain.urls.insert(url.to_string());
+      domain.records.insert(url.to_string(), record);
+    }
+
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.post("/host/github.com/retry-failed").dispatch();
 
     assert_eq!(response.status(), Status::Accepted);
-    test_receiver.join().unwrap();
+    assert_eq!(response.body_string(), Some("{\"id\":0}".into()));
+    assert_eq!(consumer.try_recv(), Ok(0));
+
+    let jobs = client.rocket().state::<Jobs>().unwrap().lock().unwrap();
+    assert_eq!(jobs[0].url, "https://github.com/a");
+    assert_eq!(jobs[0].retried, vec!("https://github.com/a", "https://github.com/b"));
+    drop(jobs);
+
+    // The failed urls are queued now, so there is nothing to retry.
+    let response = client.post("/host/github.com/retry-failed").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_retry_failed_without_crawler() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut record = UrlRecord::new(Utc::now(), 1);
+    record.state = UrlState::Failed;
+    domain.urls.insert(String::from("https://github.com/a"));
+    domain.records.insert(String::from("https://github.com/a"), record);
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, consumer) = channel::<usize>();
+    drop(consumer);
+
+    let client = Client::new(server(Arc::clone(&db), producer)).unwrap();
+    let response = client.post("/host/github.com/retry-failed").dispatch();
+
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+    // The urls were never queued, so they are still reported as failed.
+    let state = db.lock().unwrap()["github.com"].records["https://github.com/a"].state;
+    assert_eq!(state, UrlState::Failed);
+  }
+
+  #[test]
+  fn test_webhooks() {
+    let db: Database = Default::default();
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.put("/host/GitHub.com/webhook")
+      .header(ContentType::JSON)
+      .body("{\"url\": \"https://example.com/hooks/urls\"}")
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some("{\"url\":\"https://example.com/hooks/urls\"}".into()));
+
+    let mut response = client.get("/host/github.com/webhook").dispatch();
+    assert_eq!(response.body_string(), Some("{\"url\":\"https://example.com/hooks/urls\"}".into()));
+
+    let response = client.put("/host/github.com/webhook")
+      .header(ContentType::JSON)
+      .body("{\"url\": \"ftp://example.com\"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.put("/host/github.com/webhook")
+      .header(ContentType::JSON)
+      .body("{\"url\": \"http://127.0.0.1/hooks/urls\"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+
+    assert_eq!(client.delete("/host/github.com/webhook").dispatch().status(), Status::NoContent);
+    assert_eq!(client.get("/host/github.com/webhook").dispatch().status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_graphql() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client
+      .post("/graphql")
+      .header(ContentType::JSON)
+      .body("{\"query\":\"{ domains { name urls { url } } jobs { id } }\"}")
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"data\":{\"domains\":[{\"name\":\"github.com\",",
+      "\"urls\":[{\"url\":\"https://github.com\"}]}],\"jobs\":[]}}",
+    ).into()));
+
+    let response = client.get("/v1/graphql?query=%7B%20jobs%20%7B%20id%20%7D%20%7D").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+  }
+
+  #[test]
+  fn test_dashboard() {
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(Default::default(), producer)).unwrap();
+    let mut response = client.get("/ui").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::HTML));
+    assert!(response.body_string().unwrap().contains("<title>Link Crawler</title>"));
+    assert_eq!(client.get("/v1/ui").dispatch().status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_count_without_urls() {
+    // Creates an empty database.
+    let db: Database = Default::default();
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/url/count").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some("{\"count\":0}".into()));
+  }
+
+  #[test]
+  fn test_count_with_urls() {
+    // Inserts a link into the database.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/url/count").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some("{\"count\":1}".into()));
+  }
+
+  #[test]
+  fn test_domain_is_normalized() {
+    // Domains are stored the way parsed urls spell them, lowercase and in punycode.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://xn--bcher-kva.de/"));
+    db.lock().unwrap().insert("xn--bcher-kva.de".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    for path in &["/host/B%C3%BCcher.DE/url/count", "/host/XN--BCHER-KVA.de/url/count"] {
+      let mut response = client.get(*path).dispatch();
+
+      assert_eq!(response.status(), Status::Ok);
+      assert_eq!(response.body_string(), Some("{\"count\":1}".into()));
+    }
+  }
+
+  #[test]
+  fn test_crawl() {
+    let db: Database = Default::default();
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    let test_receiver = thread::spawn(move || {
+      assert_eq!(0, consumer.recv().unwrap());
+    });
+
+    let mut response = client
+      .post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"https://github.com\"}")
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Accepted);
+    assert_eq!(response.body_string(), Some("{\"id\":0}".into()));
+    test_receiver.join().unwrap();
+  }
+
+  #[test]
+  fn test_job() {
+    let db: Database = Default::default();
+
+    let (producer, _consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    client
+      .post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"https://github.com\"}")
+      .dispatch();
+
+    // Pretends the crawler ran out of the byte budget.
+    let jobs = client.rocket().state::<Jobs>().unwrap();
+    jobs::update(jobs, 0, |job| {
+      job.state = JobState::Finished;
+      job.submitted_at = Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap();
+      job.started_at = Some(Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 1).unwrap());
+      job.finished_at = Some(Utc.with_ymd_and_hms(2019, 7, 20, 12, 1, 0).unwrap());
+      job.pages_crawled = 3;
+      job.bytes_downloaded = 60_000_000;
+      job.truncated = true;
+      job.stop_reason = Some(StopReason::ByteBudget);
+      job.timing.fetch = Duration::from_millis(4200);
+      job.timing.parse = Duration::from_millis(310);
+      job.timing.insert = Duration::from_millis(2);
+    });
+
+    let mut response = client.get("/jobs/0").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"id\":0,\"url\":\"https://github.com\",\"state\":\"finished\",",
+      "\"submitted_at\":\"2019-07-20T12:00:00+00:00\",\"started_at\":\"2019-07-20T12:00:01+00:00\",",
+      "\"finished_at\":\"2019-07-20T12:01:00+00:00\",\"pages_crawled\":3,",
+      "\"bytes_downloaded\":60000000,\"truncated\":true,\"error\":null,",
+      "\"stop_reason\":\"max_bytes_per_job\",\"dry_run\":false,",
+      "\"timing\":{\"fetch_ms\":4200,\"parse_ms\":310,\"insert_ms\":2}}",
+    ).into()));
+
+    let mut response = client.get("/jobs/1").dispatch();
+    let request_id = response.headers().get_one("X-Request-Id").unwrap().to_string();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(response.body_string(), Some(format!(
+      "{{\"error\":\"job_not_found\",\"message\":\"There is no job with id 1\",\"request_id\":{}}}",
+      request_id,
+    )));
+  }
+
+  #[test]
+  fn test_dry_run_job() {
+    let db: Database = Default::default();
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    client
+      .post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"https://github.com\",\"dry_run\":true}")
+      .dispatch();
+    assert_eq!(consumer.recv().unwrap(), 0);
+
+    let jobs = client.rocket().state::<Jobs>().unwrap();
+    assert!(jobs.lock().unwrap()[0].dry_run);
+    jobs::update(jobs, 0, |job| job.would_crawl = vec!(String::from("https://github.com")));
+
+    let mut response = client.get("/jobs/0").dispatch();
+    let job: serde_json::Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(job["dry_run"], true);
+    assert_eq!(job["would_crawl"], serde_json::json!(["https://github.com"]));
+  }
+
+  #[test]
+  fn test_link_scope_job() {
+    let db: Database = Default::default();
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    let response = client
+      .post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"https://github.com\",\"link_scope\":\"nav >\"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    client
+      .post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"https://github.com\",\"link_scope\":\"main, nav\"}")
+      .dispatch();
+    assert_eq!(consumer.recv().unwrap(), 0);
+
+    let mut response = client.get("/jobs/0").dispatch();
+    let job: serde_json::Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(job["link_scope"], "main, nav");
+  }
+
+  #[test]
+  fn test_errors_have_json_bodies() {
+    let db: Database = Default::default();
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut first = client.get("/nowhere").dispatch();
+    let mut second = client.get("/nowhere").dispatch();
+
+    assert_eq!(first.status(), Status::NotFound);
+    assert_eq!(first.content_type(), Some(ContentType::JSON));
+
+    let first: serde_json::Value = serde_json::from_str(&first.body_string().unwrap()).unwrap();
+    let second: serde_json::Value = serde_json::from_str(&second.body_string().unwrap()).unwrap();
+
+    assert_eq!(first["error"], "not_found");
+    assert_eq!(first["message"], "Not Found");
+    // Every request gets its own id.
+    assert_ne!(first["request_id"], second["request_id"]);
+  }
+
+  #[test]
+  fn test_orphans() {
+    // Inserts a domain whose sitemap and crawled urls overlap only partially.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/"));
+    domain.urls.insert(String::from("https://github.com/about"));
+    domain.sitemap.insert(String::from("https://github.com/"));
+    domain.sitemap.insert(String::from("https://github.com/pricing"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/orphans").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+      response.body_string(),
+      Some("{\"orphaned\":[\"https://github.com/pricing\"],\"unlisted\":[\"https://github.com/about\"]}".into())
+    );
+  }
+
+  #[test]
+  fn test_sitemap_coverage() {
+    // Inserts sitemap urls which were crawled, checked or neither, and crawled pages of which
+    // only some belong into the sitemap.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for url in &["/", "/old", "/gone", "/down", "/new", "/private", "/copy"] {
+      domain.urls.insert(format!("https://github.com{}", url));
+    }
+    for url in &["/", "/old", "/gone", "/down", "/pricing"] {
+      domain.sitemap.insert(format!("https://github.com{}", url));
+    }
+    let ok = Page { status: Some(200), ..Default::default() };
+    domain.pages.insert(String::from("https://github.com/"), ok);
+    let moved = Page {
+      status: Some(200),
+      redirects: vec!(String::from("https://github.com/new")),
+      ..Default::default()
+    };
+    domain.pages.insert(String::from("https://github.com/old"), moved);
+    domain.pages.insert(String::from("https://github.com/down"), Page::default());
+    let check = SitemapCheck { status: Some(404), redirects: vec!() };
+    domain.sitemap_checks.insert(String::from("https://github.com/gone"), check);
+    let new = Page { status: Some(200), ..Default::default() };
+    domain.pages.insert(String::from("https://github.com/new"), new);
+    let private = Page { status: Some(200), noindex_meta: true, ..Default::default() };
+    domain.pages.insert(String::from("https://github.com/private"), private);
+    let copy = Page {
+      status: Some(200),
+      canonical: Some(String::from("https://github.com/")),
+      ..Default::default()
+    };
+    domain.pages.insert(String::from("https://github.com/copy"), copy);
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/sitemap-coverage").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"sitemap_urls\":5,\"broken\":[",
+      "{\"url\":\"https://github.com/down\",\"status\":null,\"destination\":null},",
+      "{\"url\":\"https://github.com/gone\",\"status\":404,\"destination\":null}],",
+      "\"redirected\":[{\"url\":\"https://github.com/old\",\"status\":200,",
+      "\"destination\":\"https://github.com/new\"}],",
+      "\"unchecked\":[\"https://github.com/pricing\"],",
+      "\"missing\":[\"https://github.com/new\"]}",
+    ).into()));
+  }
+
+  #[test]
+  fn test_redirects() {
+    // Inserts two pages, one of which redirected through more hops than the default limit.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut short = Page::default();
+    short.redirects.push(String::from("https://github.com/b"));
+    let mut long = Page::default();
+    for hop in 1..5 {
+      long.redirects.push(format!("https://github.com/{}", hop));
+    }
+    domain.pages.insert(String::from("https://github.com/a"), short);
+    domain.pages.insert(String::from("https://github.com/c"), long);
+    domain.pages.insert(String::from("https://github.com/d"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/redirects").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"source\":\"https://github.com/a\",\"destination\":\"https://github.com/b\",",
+      "\"hops\":1,\"exceeds_limit\":false,\"loops\":false},",
+      "{\"source\":\"https://github.com/c\",\"destination\":\"https://github.com/4\",",
+      "\"hops\":4,\"exceeds_limit\":true,\"loops\":false}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_broken_outbound() {
+    // Inserts one page with a dead outbound link and one without.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut page = Page::default();
+    page.broken_links.push(String::from("https://gitlab.com/missing"));
+    domain.pages.insert(String::from("https://github.com/a"), page);
+    domain.pages.insert(String::from("https://github.com/b"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/broken-outbound").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/a\",\"count\":1,",
+      "\"broken_links\":[\"https://gitlab.com/missing\"]}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_sitemap() {
+    // Inserts one url which was crawled and one which was only found.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let page = Page {
+      fetched_at: Some(Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap()),
+      ..Default::default()
+    };
+    domain.urls.insert(String::from("https://github.com/?a=1&b=2"));
+    domain.urls.insert(String::from("https://github.com/about"));
+    domain.pages.insert(String::from("https://github.com/about"), page);
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/sitemap.xml").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::XML));
+    assert_eq!(response.body_string(), Some(concat!(
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+      "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+      "<url><loc>https://github.com/?a=1&amp;b=2</loc></url>\n",
+      "<url><loc>https://github.com/about</loc><lastmod>2019-07-20T12:00:00Z</lastmod></url>\n",
+      "</urlset>\n",
+    ).into()));
+  }
+
+  #[test]
+  fn test_sitemap_index() {
+    // Inserts one more url than fits into a single sitemap.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for n in 0..=MAX_URLS_PER_SITEMAP {
+      domain.urls.insert(format!("https://github.com/{:06}", n));
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client
+      .get("/host/github.com/sitemap.xml")
+      .header(Header::new("Host", "localhost:8000"))
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+      "<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+      "<sitemap><loc>http://localhost:8000/host/github.com/sitemap/1.xml</loc></sitemap>\n",
+      "<sitemap><loc>http://localhost:8000/host/github.com/sitemap/2.xml</loc></sitemap>\n",
+      "</sitemapindex>\n",
+    ).into()));
+
+    // The second part contains only the last url.
+    let mut response = client.get("/host/github.com/sitemap/2.xml").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.body_string().unwrap().contains("<loc>https://github.com/050000</loc>"));
+
+    let response = client.get("/host/github.com/sitemap/3.xml").dispatch();
+
+    assert_eq!(response.status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_tree() {
+    // Inserts urls nested in two directories.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/"));
+    domain.urls.insert(String::from("https://github.com/about"));
+    domain.urls.insert(String::from("https://github.com/docs/"));
+    domain.urls.insert(String::from("https://github.com/docs/api?v=1"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/tree").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"count\":4,\"children\":{",
+      "\"about\":{\"count\":1,\"children\":{}},",
+      "\"docs\":{\"count\":2,\"children\":{\"api\":{\"count\":1,\"children\":{}}}}",
+      "}}",
+    ).into()));
+  }
+
+  #[test]
+  fn test_duplicates() {
+    // Inserts two pages sharing a title and a third page with a unique one.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for (url, title) in &[("a", "GitHub"), ("b", "GitHub"), ("c", "About")] {
+      let page = Page {
+        title: Some(title.to_string()),
+        description: Some(String::from("Where the world builds software")),
+        ..Default::default()
+      };
+      domain.pages.insert(format!("https://github.com/{}", url), page);
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/duplicates").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"titles\":[{\"value\":\"GitHub\",",
+      "\"pages\":[\"https://github.com/a\",\"https://github.com/b\"]}],",
+      "\"descriptions\":[{\"value\":\"Where the world builds software\",",
+      "\"pages\":[\"https://github.com/a\",\"https://github.com/b\",\"https://github.com/c\"]}]}",
+    ).into()));
+  }
+
+  #[test]
+  fn test_missing_alt() {
+    // Inserts one page with an image lacking alt text and one without.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut page = Page::default();
+    page.images_missing_alt.push(String::from("/logo.png"));
+    domain.pages.insert(String::from("https://github.com/a"), page);
+    domain.pages.insert(String::from("https://github.com/b"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/missing-alt").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+      response.body_string(),
+      Some("[{\"page\":\"https://github.com/a\",\"count\":1,\"images\":[\"/logo.png\"]}]".into())
+    );
+  }
+
+  #[test]
+  fn test_headings() {
+    // Inserts a page with a single h1, a page with two and a page which failed to load.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for (url, levels) in &[("a", vec!(1, 2)), ("b", vec!(1, 1))] {
+      let page = Page {
+        status: Some(200),
+        headings: levels.iter().map(|level| Heading { level: *level, text: "Hi".to_string() }).collect(),
+        ..Default::default()
+      };
+      domain.pages.insert(format!("https://github.com/{}", url), page);
+    }
+    domain.pages.insert(String::from("https://github.com/c"), Page { status: Some(404), ..Default::default() });
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/headings?flagged=true").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/b\",\"h1_count\":2,\"flagged\":true,",
+      "\"headings\":[{\"level\":1,\"text\":\"Hi\"},{\"level\":1,\"text\":\"Hi\"}]}]",
+    ).into()));
+
+    let mut response = client.get("/host/github.com/report/headings").dispatch();
+
+    assert!(response.body_string().unwrap().contains("\"page\":\"https://github.com/a\",\"h1_count\":1"));
+  }
+
+  #[test]
+  fn test_canonicals() {
+    // Inserts a self-canonical page, a page pointing elsewhere and one pointing to a missing page.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for (url, canonical, status) in &[("a", "a", None), ("b", "a", Some(200)), ("c", "x", Some(404))] {
+      let page = Page {
+        canonical: Some(format!("https://github.com/{}", canonical)),
+        canonical_status: *status,
+        ..Default::default()
+      };
+      domain.pages.insert(format!("https://github.com/{}", url), page);
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/canonicals").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/b\",\"canonical\":\"https://github.com/a\",",
+      "\"canonical_status\":200,\"is_broken\":false},",
+      "{\"page\":\"https://github.com/c\",\"canonical\":\"https://github.com/x\",",
+      "\"canonical_status\":404,\"is_broken\":true}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_amp() {
+    // Inserts a page with a crawled AMP variant pointing back, and a page whose AMP variant was
+    // not crawled.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for url in &["a", "b"] {
+      let amp = Some(format!("https://github.com/amp/{}", url));
+      let page = Page { amp, ..Default::default() };
+      domain.pages.insert(format!("https://github.com/{}", url), page);
+    }
+    domain.pages.insert(String::from("https://github.com/amp/a"), Page {
+      is_amp: true,
+      canonical: Some(String::from("https://github.com/a")),
+      ..Default::default()
+    });
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/amp").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"canonical\":\"https://github.com/a\",\"amp\":\"https://github.com/amp/a\",",
+      "\"is_mutual\":true},",
+      "{\"canonical\":\"https://github.com/b\",\"amp\":\"https://github.com/amp/b\",",
+      "\"is_mutual\":false}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_hreflang() {
+    // Inserts English and German pages referencing each other, a French page the English page
+    // references without it referencing back, and an unrelated Czech page.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let alternates = |langs: &[&str]| -> Vec<Alternate> {
+      langs.iter()
+        .map(|lang| Alternate { lang: lang.to_string(), url: format!("https://github.com/{}", lang) })
+        .collect()
+    };
+    for (lang, langs) in &[("en", vec!("en", "de", "fr")), ("de", vec!("de", "en")), ("fr", vec!()), ("cs", vec!("cs"))] {
+      let page = Page { status: Some(200), alternates: alternates(langs), ..Default::default() };
+      domain.pages.insert(format!("https://github.com/{}", lang), page);
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/hreflang").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"pages\":[\"https://github.com/de\",\"https://github.com/en\",\"https://github.com/fr\"],",
+      "\"issues\":[{\"from\":\"https://github.com/en\",\"to\":\"https://github.com/fr\",",
+      "\"lang\":\"fr\",\"problem\":\"missing_return\"}]}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_noindex() {
+    // Inserts a page excluded by a meta tag, one excluded by a header and an indexable page.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.pages.insert(String::from("https://github.com/a"), Page { noindex_meta: true, ..Default::default() });
+    domain.pages.insert(String::from("https://github.com/b"), Page { noindex_header: true, ..Default::default() });
+    domain.pages.insert(String::from("https://github.com/c"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/noindex").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/a\",\"meta\":true,\"header\":false},",
+      "{\"page\":\"https://github.com/b\",\"meta\":false,\"header\":true}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_performance() {
+    // Inserts a small fast page, a large fast page and a small slow page.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for (url, size, duration) in &[("a", 100, 10), ("b", 5000, 20), ("c", 200, 900)] {
+      let page = Page {
+        size: Some(*size),
+        ttfb_ms: Some(5),
+        duration_ms: Some(*duration),
+        ..Default::default()
+      };
+      domain.pages.insert(format!("https://github.com/{}", url), page);
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/performance?min_duration=15").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/c\",\"size\":200,\"ttfb_ms\":5,\"duration_ms\":900},",
+      "{\"page\":\"https://github.com/b\",\"size\":5000,\"ttfb_ms\":5,\"duration_ms\":20}]",
+    ).into()));
+
+    let mut response = client.get("/host/github.com/report/performance?min_size=150&sort=size").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/b\",\"size\":5000,\"ttfb_ms\":5,\"duration_ms\":20},",
+      "{\"page\":\"https://github.com/c\",\"size\":200,\"ttfb_ms\":5,\"duration_ms\":900}]",
+    ).into()));
+
+    let mut response = client.get("/host/github.com/report/performance?sort=name").dispatch();
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert!(response.body_string().unwrap().starts_with("{\"error\":\"invalid_sort\","));
+  }
+
+  #[test]
+  fn test_mixed_content() {
+    // Inserts a page with an insecure script and a page without insecure references.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut page = Page::default();
+    page.insecure_assets.push(String::from("http://cdn.github.com/main.js"));
+    domain.pages.insert(String::from("https://github.com/a"), page);
+    domain.pages.insert(String::from("https://github.com/b"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/mixed-content").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/a\",\"assets\":[\"http://cdn.github.com/main.js\"],",
+      "\"links\":[]}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_redirect_warnings() {
+    // Inserts a short chain, a chain which loops and a chain with more hops than the limit.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for (url, hops) in &[("a", vec!("b")), ("c", vec!("d", "c")), ("e", vec!("1", "2", "3", "4"))] {
+      let page = Page {
+        redirects: hops.iter().map(|hop| format!("https://github.com/{}", hop)).collect(),
+        ..Default::default()
+      };
+      domain.pages.insert(format!("https://github.com/{}", url), page);
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/redirect-warnings").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"source\":\"https://github.com/c\",\"destination\":\"https://github.com/c\",",
+      "\"hops\":2,\"exceeds_limit\":false,\"loops\":true},",
+      "{\"source\":\"https://github.com/e\",\"destination\":\"https://github.com/4\",",
+      "\"hops\":4,\"exceeds_limit\":true,\"loops\":false}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_structured_data() {
+    // Inserts two pages declaring overlapping types.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    for (url, types) in &[("a", vec!("Organization", "WebSite")), ("b", vec!("WebSite"))] {
+      let page = Page { schema_types: types.iter().map(|name| name.to_string()).collect(), ..Default::default() };
+      domain.pages.insert(format!("https://github.com/{}", url), page);
+    }
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/structured-data").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"Organization\":[\"https://github.com/a\"],",
+      "\"WebSite\":[\"https://github.com/a\",\"https://github.com/b\"]}",
+    ).into()));
+  }
+
+  #[test]
+  fn test_recrawl() {
+    let db: Database = Default::default();
+
+    let (producer, _consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    for body in &["{\"url\":\"github.com\",\"stale_after_hours\":12}", "{\"url\":\"github.com\"}"] {
+      let response = client.post("/host").header(ContentType::JSON).body(*body).dispatch();
+      assert_eq!(response.status(), Status::Accepted);
+    }
+
+    let jobs = client.rocket().state::<Jobs>().unwrap().lock().unwrap();
+
+    assert_eq!(jobs[0].stale_after_hours, Some(12));
+    assert_eq!(jobs[1].stale_after_hours, None);
+  }
+
+  #[test]
+  fn test_crawl_order() {
+    let db: Database = Default::default();
+
+    let (producer, _consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    for body in &["{\"url\":\"github.com\",\"order\":\"dfs\"}", "{\"url\":\"github.com\"}"] {
+      let response = client.post("/host").header(ContentType::JSON).body(*body).dispatch();
+      assert_eq!(response.status(), Status::Accepted);
+    }
+
+    let mut response = client.post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"github.com\",\"order\":\"random\"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert!(response.body_string().unwrap().contains("invalid_order"));
+
+    let jobs = client.rocket().state::<Jobs>().unwrap().lock().unwrap();
+
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[0].order, CrawlOrder::DepthFirst);
+    assert_eq!(jobs[1].order, CrawlOrder::BreadthFirst);
+  }
+
+  #[test]
+  fn test_resume() {
+    let db: Database = Default::default();
+
+    let (producer, _consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let body = "{\"url\":\"https://github.com\",\"order\":\"dfs\",\"max_pages\":2}";
+    client.post("/host").header(ContentType::JSON).body(body).dispatch();
+    client.post("/host").header(ContentType::JSON).body(body).dispatch();
+
+    // Pretends the first crawl ran out of pages and the second one fetched everything.
+    let jobs = client.rocket().state::<Jobs>().unwrap();
+    jobs::update(jobs, 0, |job| {
+      job.state = JobState::Finished;
+      job.frontier = vec!(String::from("https://github.com/about"));
+    });
+    jobs::update(jobs, 1, |job| job.state = JobState::Finished);
+
+    let mut response = client.post("/jobs/0/resume").dispatch();
+    assert_eq!(response.status(), Status::Accepted);
+    assert_eq!(response.body_string(), Some("{\"id\":2}".into()));
+
+    // Jobs can be resumed only once they stopped and only if they left something to fetch.
+    let mut response = client.post("/jobs/2/resume").dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    assert!(response.body_string().unwrap().contains("job_not_stopped"));
+    let mut response = client.post("/jobs/1/resume").dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    assert!(response.body_string().unwrap().contains("nothing_to_resume"));
+    assert_eq!(client.post("/jobs/3/resume").dispatch().status(), Status::NotFound);
+
+    let jobs = jobs.lock().unwrap();
+    assert_eq!(jobs[2].url, "https://github.com");
+    assert_eq!(jobs[2].retried, vec!("https://github.com/about"));
+    assert_eq!(jobs[2].order, CrawlOrder::DepthFirst);
+    assert_eq!(jobs[2].stop.max_pages, Some(2));
+  }
+
+  #[test]
+  fn test_stop_conditions() {
+    let db: Database = Default::default();
+
+    let (producer, _consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let body = "{\"url\":\"github.com\",\"max_pages\":100,\"max_seconds\":60}";
+    let response = client.post("/host").header(ContentType::JSON).body(body).dispatch();
+    assert_eq!(response.status(), Status::Accepted);
+
+    let mut response = client.post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"github.com\",\"max_consecutive_errors\":0}")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert!(response.body_string().unwrap().contains("max_consecutive_errors"));
+
+    let mut response = client.post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"github.com\",\"max_pages\":18446744073709551615}")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert!(response.body_string().unwrap().contains("at most 10000"));
+
+    let jobs = client.rocket().state::<Jobs>().unwrap().lock().unwrap();
+
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].stop, StopConditions {
+      max_pages: Some(100),
+      max_duration: Some(Duration::from_secs(60)),
+      max_consecutive_errors: None,
+    });
+  }
+
+  #[test]
+  fn test_crawl_bare_domain() {
+    let db: Database = Default::default();
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let response = client
+      .post("/host")
+      .header(ContentType::JSON)
+      .body("{\"url\":\"github.com/about\"}")
+      .dispatch();
+
+    assert_eq!(response.status(), Status::Accepted);
+    assert_eq!(consumer.try_recv(), Ok(0));
+
+    let jobs = client.rocket().state::<Jobs>().unwrap().lock().unwrap();
+
+    assert_eq!(jobs[0].url, "https://github.com/about");
+    assert!(jobs[0].guessed_scheme);
+  }
+
+  #[test]
+  fn test_crawl_invalid_url() {
+    let db: Database = Default::default();
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    for (url, message) in &[
+      ("https://git hub.com", "The url is malformed: invalid domain character"),
+      ("ftp://github.com", "Only http, https and file urls can be crawled, not ftp"),
+      ("https://", "The url is malformed: empty host"),
+    ] {
+      let mut response = client
+        .post("/host")
+        .header(ContentType::JSON)
+        .body(format!("{{\"url\":\"{}\"}}", url))
+        .dispatch();
+
+      assert_eq!(response.status(), Status::BadRequest);
+
+      let body: serde_json::Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+      assert_eq!(body["error"], "invalid_url");
+      assert_eq!(body["message"], *message);
+    }
+
+    // No job was created for the invalid urls.
+    assert!(consumer.try_recv().is_err());
+    assert_eq!(client.get("/jobs/0").dispatch().status(), Status::NotFound);
+  }
+
+  #[test]
+  fn test_crawl_internal_url() {
+    let db: Database = Default::default();
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    for url in &["http://127.0.0.1:8000/admin", "http://169.254.169.254/latest/meta-data"] {
+      let response = client
+        .post("/host")
+        .header(ContentType::JSON)
+        .body(format!("{{\"url\":\"{}\"}}", url))
+        .dispatch();
+
+      assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    assert!(consumer.try_recv().is_err());
+  }
+
+  #[test]
+  fn test_crawl_local_directory() {
+    let db: Database = Default::default();
+
+    let (producer, consumer) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+
+    // No local directories may be crawled unless configured.
+    for url in &["/etc", "file:///etc/passwd"] {
+      let mut response = client
+        .post("/host")
+        .header(ContentType::JSON)
+        .body(format!("{{\"url\":\"{}\"}}", url))
+        .dispatch();
+
+      assert_eq!(response.status(), Status::Forbidden);
+
+      let body: serde_json::Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+      assert_eq!(body["error"], "directory_denied");
+    }
+
+    assert!(consumer.try_recv().is_err());
+  }
+
+  #[test]
+  fn test_list_local_urls() {
+    // Inserts the file of a local tree, which is stored under its directory.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("file:///srv/Site/index.html"));
+    db.lock().unwrap().insert("/srv/Site".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/%2Fsrv%2FSite/url").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some("[\"file:///srv/Site/index.html\"]".into()));
+  }
+
+  #[test]
+  fn test_tls() {
+    // Inserts a certificate which expires soon and was issued for another host.
+    let db: Database = Default::default();
+    let domain = Domain {
+      certificate: Some(Certificate {
+        checked_at: Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap(),
+        expires: String::from("Aug  1 00:00:00 2019 GMT"),
+        days_remaining: 11,
+        issues: vec!(String::from("hostname_mismatch"), String::from("expires_soon")),
+        error: Some(String::from("hostname mismatch")),
+      }),
+      ..Default::default()
+    };
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/tls").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"checked_at\":\"2019-07-20T12:00:00+00:00\",\"expires\":\"Aug  1 00:00:00 2019 GMT\",",
+      "\"days_remaining\":11,\"is_valid\":false,\"issues\":[\"hostname_mismatch\",\"expires_soon\"],",
+      "\"error\":\"hostname mismatch\"}",
+    ).into()));
+
+    let mut response = client.get("/host/gitlab.com/report/tls").dispatch();
+
+    assert_eq!(response.body_string(), Some("null".into()));
+  }
+
+  #[test]
+  fn test_security_headers() {
+    // Inserts an https page missing HSTS, an http page served with all other headers and a page
+    // which failed to load.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut secure = Page { size: Some(1024), ..Default::default() };
+    secure.security_headers.push(String::from("x-frame-options"));
+    let mut plain = Page { size: Some(1024), ..Default::default() };
+    plain.security_headers.push(String::from("content-security-policy"));
+    plain.security_headers.push(String::from("x-frame-options"));
+    plain.security_headers.push(String::from("x-content-type-options"));
+    domain.pages.insert(String::from("https://github.com/a"), secure);
+    domain.pages.insert(String::from("http://github.com/b"), plain);
+    domain.pages.insert(String::from("https://github.com/c"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/security-headers").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/a\",\"missing\":[\"content-security-policy\",",
+      "\"strict-transport-security\",\"x-content-type-options\"]}]",
+    ).into()));
+  }
+
+  #[test]
+  fn test_third_parties() {
+    // Inserts two pages, both loading analytics and one of them linking to a social network.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut home = Page::default();
+    home.asset_hosts.push(String::from("www.googletagmanager.com"));
+    home.linked_hosts.push(String::from("twitter.com"));
+    let mut about = Page::default();
+    about.asset_hosts.push(String::from("www.googletagmanager.com"));
+    domain.pages.insert(String::from("https://github.com/"), home);
+    domain.pages.insert(String::from("https://github.com/about"), about);
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/third-parties").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"twitter.com\":{\"assets\":[],\"links\":[\"https://github.com/\"]},",
+      "\"www.googletagmanager.com\":{\"assets\":[\"https://github.com/\",\"https://github.com/about\"],",
+      "\"links\":[]}}",
+    ).into()));
+  }
+
+  #[test]
+  fn test_contacts() {
+    // Inserts two pages publishing the same email address.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut home = Page::default();
+    home.emails.push(String::from("support@github.com"));
+    home.phones.push(String::from("+18774484820"));
+    let mut about = Page::default();
+    about.emails.push(String::from("support@github.com"));
+    domain.pages.insert(String::from("https://github.com/about"), about);
+    domain.pages.insert(String::from("https://github.com/"), home);
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/contacts").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"emails\":{\"support@github.com\":[\"https://github.com/\",\"https://github.com/about\"]},",
+      "\"phones\":{\"+18774484820\":[\"https://github.com/\"]}}",
+    ).into()));
+  }
+
+  #[test]
+  fn test_script_data() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut pricing = Page::default();
+    pricing.script_data.insert(String::from("price"), String::from("$4"));
+    domain.pages.insert(String::from("https://github.com/pricing"), pricing);
+    domain.pages.insert(String::from("https://github.com/"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/script-data").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(
+      "{\"pages\":{\"https://github.com/pricing\":{\"price\":\"$4\"}}}".into(),
+    ));
+  }
+
+  #[test]
+  fn test_extraction_rules() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut pricing = Page::default();
+    pricing.fields.insert(String::from("price"), String::from("$4"));
+    domain.pages.insert(String::from("https://github.com/pricing"), pricing);
+    domain.pages.insert(String::from("https://github.com/"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let response = client.get("/host/gitlab.com/extraction-rules").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    // Rules can be set before the domain is crawled.
+    let mut response = client.put("/host/gitlab.com/extraction-rules")
+      .header(ContentType::JSON)
+      .body("{\" .price \": \"price\", \"meta[itemprop=sku]\": \"sku\"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(
+      "{\".price\":\"price\",\"meta[itemprop=sku]\":\"sku\"}".into(),
+    ));
+
+    let mut response = client.get("/host/gitlab.com/extraction-rules").dispatch();
+    assert_eq!(response.body_string(), Some(
+      "{\".price\":\"price\",\"meta[itemprop=sku]\":\"sku\"}".into(),
+    ));
+
+    let response = client.put("/host/gitlab.com/extraction-rules")
+      .header(ContentType::JSON)
+      .body("{\"div[\": \"price\"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.put("/host/gitlab.com/extraction-rules")
+      .header(ContentType::JSON)
+      .body("{\".price\": \" \"}")
+      .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let mut response = client.get("/host/github.com/fields").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(
+      "{\"pages\":{\"https://github.com/pricing\":{\"price\":\"$4\"}}}".into(),
+    ));
+  }
+
+  #[test]
+  fn test_forms() {
+    // Inserts a page with a login form and a newsletter form submitting over plain http.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut login = Page::default();
+    login.forms.push(Form {
+      action: String::from("https://github.com/session"),
+      method: String::from("POST"),
+      insecure: false,
+    });
+    login.forms.push(Form {
+      action: String::from("http://newsletter.example.com/subscribe"),
+      method: String::from("POST"),
+      insecure: true,
+    });
+    domain.pages.insert(String::from("https://github.com/login"), login);
+    domain.pages.insert(String::from("https://github.com/about"), Page::default());
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/forms?insecure=true").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "[{\"page\":\"https://github.com/login\",\"forms\":[{\"action\":",
+      "\"http://newsletter.example.com/subscribe\",\"method\":\"POST\",\"insecure\":true}]}]",
+    ).into()));
+
+    let mut response = client.get("/host/github.com/report/forms").dispatch();
+
+    assert_eq!(response.body_string().unwrap().matches("\"action\"").count(), 2);
+  }
+
+  #[test]
+  fn test_trackers() {
+    // Inserts two pages embedding analytics, one of them also a marketing pixel.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    let mut home = Page::default();
+    home.trackers.push(String::from("Facebook Pixel"));
+    home.trackers.push(String::from("Google Analytics"));
+    let mut about = Page::default();
+    about.trackers.push(String::from("Google Analytics"));
+    domain.pages.insert(String::from("https://github.com/about"), about);
+    domain.pages.insert(String::from("https://github.com/"), home);
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/host/github.com/report/trackers").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(concat!(
+      "{\"Facebook Pixel\":[\"https://github.com/\"],",
+      "\"Google Analytics\":[\"https://github.com/\",\"https://github.com/about\"]}",
+    ).into()));
+  }
+
+  #[test]
+  fn test_memory() {
+    // Inserts a domain with a single url and a single page and an empty domain.
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com/about"));
+    domain.pages.insert(String::from("https://github.com/about"), Page::default());
+    let expected = domain.approximate_size();
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+    db.lock().unwrap().insert("gitlab.com".to_string(), Domain::default());
+    let empty = Domain::default().approximate_size();
+
+    let (producer, _) = channel::<usize>();
+
+    let client = Client::new(server(db, producer)).unwrap();
+    let mut response = client.get("/debug/memory").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(format!(
+      concat!(
+        "{{\"approximate_bytes\":{},\"domains\":[{{\"domain\":\"github.com\",\"urls\":1,",
+        "\"sitemap_urls\":0,\"pages\":1,\"average_url_length\":24,\"approximate_bytes\":{}}},",
+        "{{\"domain\":\"gitlab.com\",\"urls\":0,\"sitemap_urls\":0,\"pages\":0,",
+        "\"average_url_length\":0,\"approximate_bytes\":{}}}]}}",
+      ),
+      expected + empty, expected, empty,
+    )));
+    // The url is stored twice, once among the urls and once as the key of the page.
+    assert!(expected > empty + 2 * "https://github.com/about".len());
   }
 
   // TODO: Test cases for crawler.
@@ -0,0 +1,113 @@
+use std::sync::OnceLock;
+use rocket::http::{Method, Status};
+use rocket::{Request, Response, Rocket};
+use rocket::fairing::{Fairing, Info, Kind};
+
+/// Answers OPTIONS requests with the methods the requested path supports in the Allow header,
+/// as load balancers and gateways expect. Rocket answers HEAD requests by itself, by running the
+/// GET route and dropping the body.
+#[derive(Default)]
+pub struct Options {
+  /// The method and path of every mounted route, known once the fairing is attached.
+  routes: OnceLock<Vec<(Method, String)>>,
+}
+
+impl Fairing for Options {
+  fn info(&self) -> Info {
+    Info { name: "OPTIONS requests", kind: Kind::Attach | Kind::Response }
+  }
+
+  fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+    let routes = rocket.routes()
+      .map(|route| (route.method, route.uri.path().to_string()))
+      .collect();
+    let _ = self.routes.set(routes);
+
+    Ok(rocket)
+  }
+
+  fn on_response(&self, request: &Request, response: &mut Response) {
+    // No route handles OPTIONS, so the requests end up in the not found catcher.
+    if request.method() != Method::Options || response.status() != Status::NotFound {
+      return;
+    }
+
+    let routes = self.routes.get().map(Vec::as_slice).unwrap_or_default();
+    let allowed = allowed_methods(routes, request.uri().path());
+
+    if allowed.is_empty() {
+      return;
+    }
+
+    response.set_status(Status::NoContent);
+    response.set_raw_header("Allow", allowed.join(", "));
+    response.remove_header("Content-Type");
+    response.take_body();
+  }
+}
+
+/// Lists the methods the routes matching given path support, including HEAD for every GET
+/// route and OPTIONS itself. Empty if no route matches the path.
+fn allowed_methods(routes: &[(Method, String)], path: &str) -> Vec<&'static str> {
+  let mut methods: Vec<Method> = routes.iter()
+    .filter(|(_, route)| matches(route, path))
+    .map(|(method, _)| *method)
+    .collect();
+
+  if methods.is_empty() {
+    return vec!();
+  }
+
+  if methods.contains(&Method::Get) {
+    methods.push(Method::Head);
+  }
+
+  methods.push(Method::Options);
+  methods.sort_by_key(|method| method.as_str());
+  methods.dedup();
+
+  methods.into_iter().map(|method| method.as_str()).collect()
+}
+
+/// Checks whether the path of a route, with dynamic segments such as `<domain>`, matches given
+/// request path.
+fn matches(route: &str, path: &str) -> bool {
+  let mut route = route.split('/').filter(|segment| !segment.is_empty());
+  let mut path = path.split('/').filter(|segment| !segment.is_empty());
+
+  loop {
+    match (route.next(), path.next()) {
+      (Some(segment), _) if segment.starts_with('<') && segment.ends_with("..>") => return true,
+      (Some(segment), Some(_)) if segment.starts_with('<') => continue,
+      (Some(segment), Some(requested)) if segment == requested => continue,
+      (None, None) => return true,
+      _ => return false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use rocket::http::Method;
+  use super::allowed_methods;
+
+  #[test]
+  fn test_allowed_methods() {
+    let routes = vec!(
+      (Method::Post, String::from("/host")),
+      (Method::Get, String::from("/host/<domain>/url")),
+      (Method::Get, String::from("/host/<domain>/url/count")),
+      (Method::Delete, String::from("/host/<domain>/url")),
+      (Method::Get, String::from("/static/<path..>")),
+    );
+
+    assert_eq!(allowed_methods(&routes, "/host"), vec!("OPTIONS", "POST"));
+    assert_eq!(
+      allowed_methods(&routes, "/host/github.com/url"), vec!("DELETE", "GET", "HEAD", "OPTIONS"),
+    );
+    assert_eq!(allowed_methods(&routes, "/static/a/b.css"), vec!("GET", "HEAD", "OPTIONS"));
+    assert!(allowed_methods(&routes, "/host/github.com").is_empty());
+    assert!(allowed_methods(&routes, "/host/github.com/url/count/more").is_empty());
+  }
+
+}
@@ -0,0 +1,250 @@
+use chrono::{DateTime, Utc};
+use r2d2::{Pool, PooledConnection};
+use postgres::{Client, NoTls, Transaction};
+use postgres::types::Type;
+use postgres::binary_copy::BinaryCopyInWriter;
+use r2d2_postgres::PostgresConnectionManager;
+use std::collections::{BTreeSet, HashMap};
+use super::database::{Domain, UrlRecord, UrlState};
+use super::storage::{Snapshot, Storage};
+
+/// Changes to the schema in the order they were made. Migrations are applied once each, in
+/// order, and must never change after they were released.
+const MIGRATIONS: &[&str] = &[
+  "
+  CREATE TABLE domains (
+    name TEXT PRIMARY KEY,
+    crawled_at TIMESTAMPTZ,
+    tags TEXT[] NOT NULL DEFAULT '{}'
+  );
+
+  CREATE TABLE urls (
+    domain TEXT NOT NULL REFERENCES domains (name) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    first_seen TIMESTAMPTZ,
+    last_seen TIMESTAMPTZ,
+    depth BIGINT,
+    state TEXT,
+    annotation TEXT,
+    referrers TEXT[] NOT NULL DEFAULT '{}',
+    PRIMARY KEY (domain, url)
+  );
+  ",
+];
+
+/// Identifies the advisory lock instances of the service hold while migrating the schema, so
+/// that instances starting at the same time don't apply the same migration twice.
+const MIGRATION_LOCK: i64 = 0x6c69_6e6b;
+
+/// Stores the crawled domains in a PostgreSQL database. Domains are stored in the `domains`
+/// table and their urls in the `urls` table, so that other applications can query them.
+pub struct Postgres {
+  pool: Pool<PostgresConnectionManager<NoTls>>,
+}
+
+impl Postgres {
+  /// Connects to the database at given url, e.g. `postgres://crawler@localhost/crawler`, with a
+  /// pool of at most given number of connections and migrates its schema to the latest version.
+  pub fn connect(url: &str, pool_size: u32) -> Result<Postgres, String> {
+    let config = url.parse().map_err(|e| format!("The url is not valid: {}", e))?;
+    let pool = Pool::builder()
+      .max_size(pool_size.max(1))
+      .build(PostgresConnectionManager::new(config, NoTls))
+      .map_err(|e| format!("Could not connect: {}", e))?;
+    let postgres = Postgres { pool };
+
+    let mut client = postgres.client()?;
+    migrate(&mut client).map_err(|e| format!("Could not migrate the schema: {}", e))?;
+
+    Ok(postgres)
+  }
+
+  fn client(&self) -> Result<PooledConnection<PostgresConnectionManager<NoTls>>, String> {
+    self.pool.get().map_err(|e| format!("No connection is available: {}", e))
+  }
+}
+
+impl Storage for Postgres {
+  fn load(&self) -> Result<HashMap<String, Domain>, String> {
+    let mut client = self.client()?;
+    let mut snapshots: HashMap<String, Snapshot> = HashMap::new();
+
+    for row in client.query("SELECT name, crawled_at, tags FROM domains", &[]).map_err(error)? {
+      let snapshot = Snapshot { crawled_at: row.get(1), tags: row.get(2), urls: vec!() };
+      snapshots.insert(row.get(0), snapshot);
+    }
+
+    let query = "
+      SELECT domain, url, first_seen, last_seen, depth, state, annotation, referrers FROM urls
+    ";
+    for row in client.query(query, &[]).map_err(error)? {
+      let first_seen: Option<DateTime<Utc>> = row.get(2);
+      let last_seen: Option<DateTime<Utc>> = row.get(3);
+      let depth: Option<i64> = row.get(4);
+      let state: Option<String> = row.get(5);
+      let referrers: Vec<String> = row.get(7);
+
+      // Urls are stored without a history if the crawler had none for them.
+      let record = first_seen.map(|first_seen| UrlRecord {
+        first_seen,
+        last_seen: last_seen.unwrap_or(first_seen),
+        depth: depth.unwrap_or(0) as usize,
+        referrers: referrers.into_iter().collect::<BTreeSet<String>>(),
+        annotation: row.get(6),
+        state: state.as_ref()
+          .and_then(|state| UrlState::ALL.iter().cloned().find(|known| known.as_str() == state))
+          .unwrap_or(UrlState::Discovered),
+      });
+
+      // The foreign key guarantees that the domain of every url was loaded.
+      if let Some(snapshot) = snapshots.get_mut(&row.get::<_, String>(0)) {
+        snapshot.urls.push((row.get(1), record));
+      }
+    }
+
+    Ok(snapshots.into_iter().map(|(name, snapshot)| (name, snapshot.into_domain())).collect())
+  }
+
+  fn save(&self, domain: &str, snapshot: &Snapshot) -> Result<(), String> {
+    let mut client = self.client()?;
+    let mut transaction = client.transaction().map_err(error)?;
+
+    transaction.execute(
+      "
+      INSERT INTO domains (name, crawled_at, tags) VALUES ($1, $2, $3)
+      ON CONFLICT (name) DO UPDATE SET crawled_at = EXCLUDED.crawled_at, tags = EXCLUDED.tags
+      ",
+      &[&domain, &snapshot.crawled_at, &snapshot.tags],
+    ).map_err(error)?;
+    transaction.execute("DELETE FROM urls WHERE domain = $1", &[&domain]).map_err(error)?;
+    copy_urls(&mut transaction, domain, snapshot).map_err(error)?;
+
+    transaction.commit().map_err(error)
+  }
+
+  fn remove(&self, domain: &str) -> Result<(), String> {
+    let mut client = self.client()?;
+
+    client.execute("DELETE FROM domains WHERE name = $1", &[&domain]).map(|_| ()).map_err(error)
+  }
+}
+
+/// Applies the migrations which were not applied to the database yet.
+fn migrate(client: &mut Client) -> Result<(), postgres::Error> {
+  let mut transaction = client.transaction()?;
+  transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])?;
+  transaction.batch_execute("
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+      version INTEGER PRIMARY KEY,
+      applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )
+  ")?;
+
+  let applied: i64 = transaction.query_one("SELECT count(*) FROM schema_migrations", &[])?.get(0);
+
+  for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+    println!("[Storage] Applying migration {}", version + 1);
+    transaction.batch_execute(migration)?;
+    transaction.execute(
+      "INSERT INTO schema_migrations (version) VALUES ($1)", &[&(version as i32 + 1)],
+    )?;
+  }
+
+  transaction.commit()
+}
+
+/// Inserts the urls of a domain with a single copy, which is much faster than inserting them
+/// one by one for domains with many urls.
+fn copy_urls(
+  transaction: &mut Transaction,
+  domain: &str,
+  snapshot: &Snapshot,
+) -> Result<(), postgres::Error> {
+  let sink = transaction.copy_in("
+    COPY urls (domain, url, first_seen, last_seen, depth, state, annotation, referrers)
+    FROM STDIN BINARY
+  ")?;
+  let types = &[
+    Type::TEXT, Type::TEXT, Type::TIMESTAMPTZ, Type::TIMESTAMPTZ, Type::INT8, Type::TEXT,
+    Type::TEXT, Type::TEXT_ARRAY,
+  ];
+  let mut writer = BinaryCopyInWriter::new(sink, types);
+
+  for (url, record) in &snapshot.urls {
+    let record = record.as_ref();
+    let referrers: Vec<&String> = record.map_or(vec!(), |record| record.referrers.iter().collect());
+
+    writer.write(&[
+      &domain,
+      url,
+      &record.map(|record| record.first_seen),
+      &record.map(|record| record.last_seen),
+      &record.map(|record| record.depth as i64),
+      &record.map(|record| record.state.as_str()),
+      &record.and_then(|record| record.annotation.as_ref()),
+      &referrers,
+    ])?;
+  }
+
+  writer.finish().map(|_| ())
+}
+
+fn error(error: postgres::Error) -> String {
+  error.to_string()
+}
+
+#[cfg(test)]
+mod test {
+  use super::Postgres;
+  use super::super::database::{Domain, UrlRecord, UrlState};
+  use super::super::storage::{Snapshot, Storage};
+  use chrono::Utc;
+  use std::env;
+
+  /// Runs against the database at the url in the `TEST_POSTGRES_URL` environment variable and
+  /// is skipped if there is none. The test owns the tables of the database.
+  #[test]
+  fn test_storage() {
+    let url = match env::var("TEST_POSTGRES_URL") {
+      Ok(url) => url,
+      Err(_) => return,
+    };
+
+    let postgres = Postgres::connect(&url, 2).unwrap();
+    // Connecting again must not apply the migrations twice.
+    let postgres = Postgres::connect(&url, 2).map(|_| postgres).unwrap();
+    postgres.remove("github.com").unwrap();
+
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    domain.urls.insert(String::from("https://github.com/a"));
+    let mut record = UrlRecord::new(Utc::now(), 1);
+    record.found(Utc::now(), 1, Some(&String::from("https://github.com")));
+    record.annotation = Some(String::from("Moved"));
+    record.state = UrlState::Failed;
+    domain.records.insert(String::from("https://github.com/a"), record);
+    domain.tags.insert(String::from("code"));
+    domain.crawled_at = Some(Utc::now());
+
+    postgres.save("github.com", &Snapshot::of(&domain)).unwrap();
+    // Saving again replaces the urls of the domain.
+    domain.urls.remove("https://github.com");
+    postgres.save("github.com", &Snapshot::of(&domain)).unwrap();
+
+    let loaded = postgres.load().unwrap();
+    let github = &loaded["github.com"];
+    assert_eq!(github.urls, domain.urls);
+    assert_eq!(github.tags, domain.tags);
+    // The database stores times with microsecond precision.
+    let seconds = |domain: &Domain| domain.crawled_at.map(|at| at.timestamp());
+    assert_eq!(seconds(github), seconds(&domain));
+    let record = &github.records["https://github.com/a"];
+    assert_eq!(record.depth, 1);
+    assert_eq!(record.state, UrlState::Failed);
+    assert_eq!(record.annotation.as_deref(), Some("Moved"));
+    assert!(record.referrers.contains("https://github.com"));
+
+    postgres.remove("github.com").unwrap();
+    assert!(!postgres.load().unwrap().contains_key("github.com"));
+  }
+}
@@ -0,0 +1,210 @@
+use url::Url;
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+use reqwest::{Response, StatusCode};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
+use super::export::hex;
+use super::fetch::Fetcher;
+use super::tls::Certificate;
+
+/// A response as it was recorded, stored as JSON in a file next to the file of its body.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Recording {
+  /// Either `GET` or `HEAD`.
+  method: String,
+  url: String,
+  status: u16,
+  /// The headers of the response as pairs of the name and the value. Headers describing how the
+  /// body was transferred are left out, as bodies are recorded decompressed.
+  headers: Vec<(String, String)>,
+  /// Urls the request was redirected through, see `Fetcher::get`.
+  redirects: Vec<String>,
+}
+
+/// Fetches urls with another fetcher and records every response it gets into a directory, so
+/// that the crawl can later be replayed without network access, see `Replayer`. A url fetched
+/// again replaces its recording.
+pub struct Recorder<F> {
+  fetcher: F,
+  directory: PathBuf,
+}
+
+impl<F: Fetcher> Recorder<F> {
+  pub fn new(fetcher: F, directory: &str) -> Recorder<F> {
+    Recorder { fetcher, directory: PathBuf::from(directory) }
+  }
+
+  /// Writes the recording and its body. The body is written first, so that a recording is never
+  /// read without its body.
+  fn save(&self, recording: &Recording, body: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(&self.directory)?;
+
+    let (metadata, body_path) = paths(&self.directory, &recording.method, &recording.url);
+    fs::write(body_path, body)?;
+
+    fs::write(metadata, serde_json::to_vec(recording)?)
+  }
+
+  /// Logs a recording which could not be written. The crawl goes on either way.
+  fn record(&self, recording: Recording, body: &[u8]) {
+    if let Err(e) = self.save(&recording, body) {
+      println!("[Crawler] Error during recording of {}: {}", recording.url, e);
+    }
+  }
+}
+
+impl<F: Fetcher> Fetcher for Recorder<F> {
+  /// The body is read in full to be recorded, and the response is then rebuilt around it.
+  fn get_with(&self, url: &Url, headers: &HeaderMap) -> Option<(Response, Vec<String>)> {
+    let (mut response, redirects) = self.fetcher.get_with(url, headers)?;
+    let mut body = vec!();
+    response.copy_to(&mut body).ok()?;
+
+    let recording = Recording {
+      method: String::from("GET"),
+      url: url.as_str().to_string(),
+      status: response.status().as_u16(),
+      headers: response.headers().iter()
+        .filter(|(name, _)| {
+          **name != CONTENT_ENCODING && **name != CONTENT_LENGTH && **name != TRANSFER_ENCODING
+        })
+        .filter_map(|(name, value)| {
+          Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect(),
+      redirects,
+    };
+    let response = rebuild(&recording, body.clone())?;
+    let redirects = recording.redirects.clone();
+    self.record(recording, &body);
+
+    Some((response, redirects))
+  }
+
+  fn head(&self, url: &Url) -> Option<(StatusCode, Vec<String>)> {
+    let (status, redirects) = self.fetcher.head(url)?;
+    let recording = Recording {
+      method: String::from("HEAD"),
+      url: url.as_str().to_string(),
+      status: status.as_u16(),
+      headers: vec!(),
+      redirects: redirects.clone(),
+    };
+    self.record(recording, &[]);
+
+    Some((status, redirects))
+  }
+
+  /// Certificates are inspected as usual but not recorded, replayed crawls don't inspect any.
+  fn certificate(&self, url: &Url) -> Option<Certificate> {
+    self.fetcher.certificate(url)
+  }
+}
+
+/// Serves the responses recorded by a `Recorder` back to the crawler, so that crawls are
+/// reproducible and can be debugged offline. Urls which were not recorded fail as if their host
+/// did not respond.
+pub struct Replayer {
+  directory: PathBuf,
+}
+
+impl Replayer {
+  pub fn new(directory: &str) -> Replayer {
+    Replayer { directory: PathBuf::from(directory) }
+  }
+
+  /// Reads the recording of given request with its body, if there is one.
+  fn load(&self, method: &str, url: &Url) -> Option<(Recording, Vec<u8>)> {
+    let (metadata, body) = paths(&self.directory, method, url.as_str());
+    let recording = serde_json::from_slice(&fs::read(metadata).ok()?).ok()?;
+
+    Some((recording, fs::read(body).ok()?))
+  }
+}
+
+impl Fetcher for Replayer {
+  /// The headers of the request are ignored, the recorded response is served regardless.
+  fn get_with(&self, url: &Url, _: &HeaderMap) -> Option<(Response, Vec<String>)> {
+    let (recording, body) = self.load("GET", url)?;
+
+    Some((rebuild(&recording, body)?, recording.redirects))
+  }
+
+  /// Urls which were only fetched with GET answer with the status they responded with to it.
+  fn head(&self, url: &Url) -> Option<(StatusCode, Vec<String>)> {
+    let (recording, _) = self.load("HEAD", url).or_else(|| self.load("GET", url))?;
+
+    Some((StatusCode::from_u16(recording.status).ok()?, recording.redirects))
+  }
+
+  fn certificate(&self, _url: &Url) -> Option<Certificate> {
+    None
+  }
+}
+
+/// The paths of the files of the recording of given request and of its body in given directory.
+/// Files are named after the SHA-256 hash of the method and the url, as urls can be longer than
+/// names of files may be and contain characters which names of files may not.
+fn paths(directory: &Path, method: &str, url: &str) -> (PathBuf, PathBuf) {
+  let name = hex(&sha256(format!("{} {}", method, url).as_bytes()));
+
+  (directory.join(format!("{}.json", name)), directory.join(format!("{}.body", name)))
+}
+
+/// Builds the response of given recording with given body.
+fn rebuild(recording: &Recording, body: Vec<u8>) -> Option<Response> {
+  let mut response = http::Response::builder();
+  response.status(recording.status);
+
+  for (name, value) in &recording.headers {
+    response.header(name.as_str(), value.as_str());
+  }
+
+  Some(Response::from(response.body(body).ok()?))
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use super::{Recorder, Replayer};
+  use super::super::fetch::{Fetcher, MockFetcher};
+  use std::env;
+  use std::fs;
+
+  #[test]
+  fn test_replay() {
+    let directory = env::temp_dir().join("link-crawler-test-replay");
+    let directory = directory.to_str().unwrap();
+    let url = |url: &str| Url::parse(url).unwrap();
+    let site = MockFetcher::default()
+      .page("https://github.com/", "<a href=\"/about\">About</a>")
+      .response("https://github.com/about", 500, &[("retry-after", "10")], "")
+      .unreachable("https://github.com/team");
+
+    let recorder = Recorder::new(site, directory);
+    let (mut response, _) = recorder.get(&url("https://github.com/")).unwrap();
+    // The response is still served while it is recorded.
+    assert_eq!(response.text().unwrap(), "<a href=\"/about\">About</a>");
+    assert!(recorder.get(&url("https://github.com/about")).is_some());
+    assert!(recorder.get(&url("https://github.com/team")).is_none());
+
+    let replayer = Replayer::new(directory);
+    let (mut response, redirects) = replayer.get(&url("https://github.com/")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/html");
+    assert_eq!(response.text().unwrap(), "<a href=\"/about\">About</a>");
+    assert!(redirects.is_empty());
+    let (response, _) = replayer.get(&url("https://github.com/about")).unwrap();
+    assert_eq!(response.status(), 500);
+    assert_eq!(response.headers()["retry-after"], "10");
+    assert_eq!(replayer.status("https://github.com/about").unwrap(), 500);
+    // Urls which did not respond or were never recorded don't respond when replayed either.
+    assert!(replayer.get(&url("https://github.com/team")).is_none());
+    assert!(replayer.get(&url("https://github.com/pricing")).is_none());
+
+    fs::remove_dir_all(directory).unwrap();
+  }
+}
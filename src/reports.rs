@@ -0,0 +1,944 @@
+use rocket::State;
+use super::Database;
+use super::extract;
+use super::errors::ApiError;
+use super::routes::DomainName;
+use super::config::Config;
+use rocket::http::Status;
+use serde::Serialize;
+use rocket_contrib::json::Json;
+use super::database::{Domain, Form, Heading, Page};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[get("/<domain>/report/orphans")]
+pub fn orphans(cache: State<Database>, domain: DomainName) -> Result<Json<OrphanReport>, Status> {
+  match cache.lock() {
+    // If lock was acquired, compares the urls listed in the sitemap with the urls found by
+    // following links. A domain that was not crawled yet yields an empty report.
+    Ok(db) => {
+      let report = match db.get(&*domain) {
+        Some(record) => {
+          let mut orphaned: Vec<String> = record.sitemap.difference(&record.urls).cloned().collect();
+          let mut unlisted: Vec<String> = record.urls.difference(&record.sitemap).cloned().collect();
+
+          orphaned.sort();
+          unlisted.sort();
+
+          OrphanReport { orphaned, unlisted }
+        },
+        None => OrphanReport { orphaned: vec!(), unlisted: vec!() },
+      };
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/sitemap-coverage")]
+pub fn sitemap_coverage(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<SitemapCoverage>, Status> {
+  match cache.lock() {
+    // If lock was acquired, compares how the sitemap urls responded with the crawled pages. A
+    // domain that was not crawled yet yields an empty report.
+    Ok(db) => Ok(Json(db.get(&*domain).map(coverage).unwrap_or_default())),
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/redirects")]
+pub fn redirects(
+  cache: State<Database>,
+  config: State<Config>,
+  domain: DomainName,
+) -> Result<Json<Vec<Redirect>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every fetched url of the domain which redirected.
+    Ok(db) => Ok(Json(match db.get(&*domain) {
+      Some(record) => redirect_entries(record, &config),
+      None => vec!(),
+    })),
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/redirect-warnings")]
+pub fn redirect_warnings(
+  cache: State<Database>,
+  config: State<Config>,
+  domain: DomainName,
+) -> Result<Json<Vec<Redirect>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists the fetched urls of the domain whose redirect chains are either
+    // too long or loop.
+    Ok(db) => Ok(Json(match db.get(&*domain) {
+      Some(record) => redirect_entries(record, &config).into_iter()
+        .filter(|redirect| redirect.exceeds_limit || redirect.loops)
+        .collect(),
+      None => vec!(),
+    })),
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+/// Lists every fetched url of the domain which redirected, sorted by the url.
+fn redirect_entries(record: &Domain, config: &Config) -> Vec<Redirect> {
+  let mut entries: Vec<Redirect> = record.pages.iter()
+    .filter_map(|(url, page)| {
+      let (destination, previous) = page.redirects.split_last()?;
+
+      Some(Redirect {
+        source: url.clone(),
+        destination: destination.clone(),
+        hops: page.redirects.len(),
+        exceeds_limit: page.redirects.len() > config.max_redirect_hops,
+        // The crawler stops following a chain once it returns to an already visited url.
+        loops: destination == url || previous.contains(destination),
+      })
+    })
+    .collect();
+
+  entries.sort_by(|a, b| a.source.cmp(&b.source));
+
+  entries
+}
+
+#[get("/<domain>/report/broken-outbound")]
+pub fn broken_outbound(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<Vec<BrokenOutbound>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every fetched page of the domain which links to dead urls on
+    // other hosts.
+    Ok(db) => {
+      let mut report: Vec<BrokenOutbound> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .filter(|(_, page)| !page.broken_links.is_empty())
+          .map(|(url, page)| BrokenOutbound {
+            page: url.clone(),
+            count: page.broken_links.len(),
+            broken_links: page.broken_links.clone(),
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/duplicates")]
+pub fn duplicates(cache: State<Database>, domain: DomainName) -> Result<Json<DuplicateReport>, Status> {
+  match cache.lock() {
+    // If lock was acquired, groups the fetched pages of the domain by their title and description.
+    Ok(db) => {
+      let report = match db.get(&*domain) {
+        Some(record) => DuplicateReport {
+          titles: group_duplicates(record, |page| page.title.as_ref()),
+          descriptions: group_duplicates(record, |page| page.description.as_ref()),
+        },
+        None => DuplicateReport { titles: vec!(), descriptions: vec!() },
+      };
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/missing-alt")]
+pub fn missing_alt(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<MissingAlt>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every fetched page of the domain with images lacking alt text.
+    Ok(db) => {
+      let mut report: Vec<MissingAlt> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .filter(|(_, page)| !page.images_missing_alt.is_empty())
+          .map(|(url, page)| MissingAlt {
+            page: url.clone(),
+            count: page.images_missing_alt.len(),
+            images: page.images_missing_alt.clone(),
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/headings?<flagged>")]
+pub fn headings(
+  cache: State<Database>,
+  domain: DomainName,
+  flagged: Option<bool>,
+) -> Result<Json<Vec<HeadingStructure>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists the headings of every page of the domain which loaded. If
+    // requested, only the pages with zero or multiple h1 headings are listed.
+    Ok(db) => {
+      let mut report: Vec<HeadingStructure> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .filter(|(_, page)| page.is_success())
+          .map(|(url, page)| {
+            let h1_count = page.headings.iter().filter(|heading| heading.level == 1).count();
+
+            HeadingStructure {
+              page: url.clone(),
+              h1_count,
+              flagged: h1_count != 1,
+              headings: page.headings.clone(),
+            }
+          })
+          .filter(|structure| !flagged.unwrap_or(false) || structure.flagged)
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/forms?<insecure>")]
+pub fn forms(
+  cache: State<Database>,
+  domain: DomainName,
+  insecure: Option<bool>,
+) -> Result<Json<Vec<PageForms>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists the forms of every page of the domain which has any. If
+    // requested, only the forms submitting over plain http are listed.
+    Ok(db) => {
+      let mut report: Vec<PageForms> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .map(|(url, page)| PageForms {
+            page: url.clone(),
+            forms: page.forms.iter()
+              .filter(|form| !insecure.unwrap_or(false) || form.insecure)
+              .cloned()
+              .collect(),
+          })
+          .filter(|page| !page.forms.is_empty())
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/canonicals")]
+pub fn canonicals(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<Canonical>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every fetched page of the domain whose canonical url is not
+    // the url the page was loaded from.
+    Ok(db) => {
+      let mut report: Vec<Canonical> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .filter_map(|(url, page)| {
+            let canonical = page.canonical.as_ref()?;
+
+            if canonical == page.final_url(url) {
+              return None;
+            }
+
+            let is_broken = page.canonical_status.map(|status| status >= 400).unwrap_or(true);
+
+            Some(Canonical {
+              page: url.clone(),
+              canonical: canonical.clone(),
+              canonical_status: page.canonical_status,
+              is_broken,
+            })
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/amp")]
+pub fn amp(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<AmpVariant>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, pairs the AMP variants of the domain with their canonical pages, as
+    // the canonical pages declare them with amphtml links and the crawled AMP pages declare their
+    // canonical urls.
+    Ok(db) => {
+      let mut pairs: BTreeMap<(String, String), (bool, bool)> = BTreeMap::new();
+
+      if let Some(record) = db.get(&*domain) {
+        for (url, page) in &record.pages {
+          if let Some(amp) = &page.amp {
+            let pair = (page.final_url(url).to_string(), amp.clone());
+            pairs.entry(pair).or_insert((false, false)).0 = true;
+          }
+
+          if let (true, Some(canonical)) = (page.is_amp, &page.canonical) {
+            let pair = (canonical.clone(), page.final_url(url).to_string());
+            pairs.entry(pair).or_insert((false, false)).1 = true;
+          }
+        }
+      }
+
+      Ok(Json(pairs.into_iter()
+        .map(|((canonical, amp), (from_canonical, from_amp))| AmpVariant {
+          canonical,
+          amp,
+          is_mutual: from_canonical && from_amp,
+        })
+        .collect()))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/hreflang")]
+pub fn hreflang(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<HreflangCluster>>, Status> {
+  match cache.lock() {
+    Ok(db) => {
+      let report = match db.get(&*domain) {
+        Some(record) => hreflang_clusters(record),
+        None => vec!(),
+      };
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/noindex")]
+pub fn noindex(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<Noindex>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every fetched page of the domain excluded from indexing.
+    Ok(db) => {
+      let mut report: Vec<Noindex> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .filter(|(_, page)| page.noindex_meta || page.noindex_header)
+          .map(|(url, page)| Noindex {
+            page: url.clone(),
+            meta: page.noindex_meta,
+            header: page.noindex_header,
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/performance?<min_size>&<min_duration>&<sort>")]
+pub fn performance(
+  cache: State<Database>,
+  domain: DomainName,
+  min_size: Option<usize>,
+  min_duration: Option<u64>,
+  sort: Option<String>,
+) -> Result<Json<Vec<Performance>>, ApiError> {
+  match cache.lock() {
+    // If lock was acquired, lists the size and fetch times of every fetched page of the domain
+    // which is at least as large and as slow as requested.
+    Ok(db) => {
+      let mut report: Vec<Performance> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .filter(|(_, page)| page.size.unwrap_or(0) >= min_size.unwrap_or(0))
+          .filter(|(_, page)| page.duration_ms.unwrap_or(0) >= min_duration.unwrap_or(0))
+          .map(|(url, page)| Performance {
+            page: url.clone(),
+            size: page.size,
+            ttfb_ms: page.ttfb_ms,
+            duration_ms: page.duration_ms,
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      // The heaviest or slowest pages go first.
+      match sort.as_deref() {
+        Some("size") => report.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.page.cmp(&b.page))),
+        Some("duration") | None => {
+          report.sort_by(|a, b| b.duration_ms.cmp(&a.duration_ms).then_with(|| a.page.cmp(&b.page)))
+        },
+        Some(_) => {
+          return Err(ApiError::new(
+            Status::BadRequest, "invalid_sort", "Pages can only be sorted by size or duration",
+          ));
+        },
+      }
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[get("/<domain>/report/mixed-content")]
+pub fn mixed_content(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<MixedContent>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every fetched https page of the domain referencing http urls.
+    Ok(db) => {
+      let mut report: Vec<MixedContent> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          .filter(|(_, page)| !page.insecure_assets.is_empty() || !page.insecure_links.is_empty())
+          .map(|(url, page)| MixedContent {
+            page: url.clone(),
+            assets: page.insecure_assets.clone(),
+            links: page.insecure_links.clone(),
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/security-headers")]
+pub fn security_headers(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<Vec<SecurityHeaders>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every downloaded page of the domain served without some of the
+    // security headers.
+    Ok(db) => {
+      let mut report: Vec<SecurityHeaders> = match db.get(&*domain) {
+        Some(record) => record.pages.iter()
+          // Headers are only recorded for pages whose body was downloaded.
+          .filter(|(_, page)| page.size.is_some())
+          .filter_map(|(url, page)| {
+            // Browsers ignore HSTS on pages served over plain http.
+            let is_https = page.final_url(url).starts_with("https:");
+            let missing: Vec<String> = extract::SECURITY_HEADERS.iter()
+              .filter(|name| is_https || **name != "strict-transport-security")
+              .filter(|name| !page.security_headers.iter().any(|header| header == *name))
+              .map(|name| name.to_string())
+              .collect();
+
+            if missing.is_empty() {
+              None
+            } else {
+              Some(SecurityHeaders { page: url.clone(), missing })
+            }
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      report.sort_by(|a, b| a.page.cmp(&b.page));
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/structured-data")]
+pub fn structured_data(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<BTreeMap<String, Vec<String>>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists for every schema.org type the urls of the pages declaring it.
+    Ok(db) => {
+      let mut inventory: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+      if let Some(record) = db.get(&*domain) {
+        for (url, page) in &record.pages {
+          for name in &page.schema_types {
+            inventory.entry(name.clone()).or_default().push(url.clone());
+          }
+        }
+      }
+
+      for pages in inventory.values_mut() {
+        pages.sort();
+      }
+
+      Ok(Json(inventory))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/third-parties")]
+pub fn third_parties(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<BTreeMap<String, ThirdParty>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists for every host of another party the pages which load assets
+    // from it and the pages which link to it.
+    Ok(db) => {
+      let mut inventory: BTreeMap<String, ThirdParty> = BTreeMap::new();
+
+      if let Some(record) = db.get(&*domain) {
+        for (url, page) in &record.pages {
+          for host in &page.asset_hosts {
+            inventory.entry(host.clone()).or_default().assets.push(url.clone());
+          }
+
+          for host in &page.linked_hosts {
+            inventory.entry(host.clone()).or_default().links.push(url.clone());
+          }
+        }
+      }
+
+      for party in inventory.values_mut() {
+        party.assets.sort();
+        party.links.sort();
+      }
+
+      Ok(Json(inventory))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/trackers")]
+pub fn trackers(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<BTreeMap<String, Vec<String>>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists for every detected tracker the urls of the pages embedding it.
+    Ok(db) => {
+      let mut inventory: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+      if let Some(record) = db.get(&*domain) {
+        for (url, page) in &record.pages {
+          for name in &page.trackers {
+            inventory.entry(name.clone()).or_default().push(url.clone());
+          }
+        }
+      }
+
+      for pages in inventory.values_mut() {
+        pages.sort();
+      }
+
+      Ok(Json(inventory))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/report/tls")]
+pub fn tls(cache: State<Database>, domain: DomainName) -> Result<Json<Option<TlsReport>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, returns the certificate the host presented when last crawled, or null
+    // if it was not crawled over https.
+    Ok(db) => {
+      let report = db.get(&*domain)
+        .and_then(|record| record.certificate.as_ref())
+        .map(|certificate| TlsReport {
+          checked_at: certificate.checked_at.to_rfc3339(),
+          expires: certificate.expires.clone(),
+          days_remaining: certificate.days_remaining,
+          is_valid: certificate.is_valid(),
+          issues: certificate.issues.clone(),
+          error: certificate.error.clone(),
+        });
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+/// Validates that hreflang alternates of the domain reference each other. Pages linked by hreflang
+/// form clusters, and the clusters in which a page references a crawled alternate that failed to
+/// load or does not reference the page back are returned. Alternates which were not crawled can't
+/// be validated and are skipped.
+fn hreflang_clusters(record: &Domain) -> Vec<HreflangCluster> {
+  // Pages are looked up by the url they were loaded from, as that's what alternates point to.
+  let pages: HashMap<&str, &Page> = record.pages.iter()
+    .map(|(url, page)| (page.final_url(url), page))
+    .collect();
+
+  // Each url points to another url in the same cluster, the root of a cluster points to itself.
+  let mut parents: HashMap<&str, &str> = HashMap::new();
+  let mut issues: Vec<(&str, HreflangIssue)> = vec!();
+
+  for (url, page) in &pages {
+    for alternate in &page.alternates {
+      let alternate_url = alternate.url.as_str();
+
+      if alternate_url == *url {
+        continue;
+      }
+
+      let root = find_root(&mut parents, url);
+      let alternate_root = find_root(&mut parents, alternate_url);
+      parents.insert(root, alternate_root);
+
+      let problem = match pages.get(alternate_url) {
+        Some(target) if !target.is_success() => "broken_target",
+        Some(target) if !target.alternates.iter().any(|back| back.url == *url) => "missing_return",
+        _ => continue,
+      };
+
+      issues.push((url, HreflangIssue {
+        from: url.to_string(),
+        to: alternate.url.clone(),
+        lang: alternate.lang.clone(),
+        problem,
+      }));
+    }
+  }
+
+  let mut clusters: BTreeMap<&str, HreflangCluster> = BTreeMap::new();
+
+  for (url, issue) in issues {
+    let root = find_root(&mut parents, url);
+
+    clusters.entry(root)
+      .or_insert_with(|| HreflangCluster { pages: BTreeSet::new(), issues: vec!() })
+      .issues.push(issue);
+  }
+
+  let urls: Vec<&str> = parents.keys().cloned().collect();
+
+  for url in urls {
+    let root = find_root(&mut parents, url);
+
+    if let Some(cluster) = clusters.get_mut(root) {
+      cluster.pages.insert(url.to_string());
+    }
+  }
+
+  let mut clusters: Vec<HreflangCluster> = clusters.into_values().collect();
+
+  for cluster in &mut clusters {
+    cluster.issues.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+  }
+
+  clusters.sort_by(|a, b| a.pages.iter().next().cmp(&b.pages.iter().next()));
+
+  clusters
+}
+
+/// Finds the root url of the cluster given url belongs to.
+fn find_root<'a>(parents: &mut HashMap<&'a str, &'a str>, url: &'a str) -> &'a str {
+  let mut root = url;
+
+  while let Some(parent) = parents.get(root).filter(|parent| **parent != root) {
+    root = parent;
+  }
+
+  parents.insert(url, root);
+
+  root
+}
+
+/// Groups the pages of the domain by given value and returns the groups which contain more than
+/// one page. Pages without the value are skipped.
+fn group_duplicates<F>(record: &Domain, value: F) -> Vec<Duplicate>
+  where F: Fn(&Page) -> Option<&String> {
+  let mut groups: BTreeMap<&String, Vec<String>> = BTreeMap::new();
+
+  for (url, page) in &record.pages {
+    if let Some(value) = value(page) {
+      groups.entry(value).or_default().push(url.clone());
+    }
+  }
+
+  groups.into_iter()
+    .filter(|(_, pages)| pages.len() > 1)
+    .map(|(value, mut pages)| {
+      pages.sort();
+
+      Duplicate { value: value.clone(), pages }
+    })
+    .collect()
+}
+
+/// Sorts the sitemap urls of a domain by how they responded, either when they were crawled or
+/// when they were checked, and finds the pages which should be in the sitemap but are not.
+/// Pages should be in the sitemap if they loaded without redirecting, can be indexed by search
+/// engines and are their own canonical url.
+fn coverage(record: &Domain) -> SitemapCoverage {
+  let mut report = SitemapCoverage { sitemap_urls: record.sitemap.len(), ..Default::default() };
+
+  for url in &record.sitemap {
+    let response = match (record.pages.get(url), record.sitemap_checks.get(url)) {
+      (Some(page), _) => (page.status, &page.redirects),
+      (None, Some(check)) => (check.status, &check.redirects),
+      (None, None) => {
+        report.unchecked.push(url.clone());
+        continue;
+      },
+    };
+    let (status, redirects) = response;
+    let entry = SitemapEntry { url: url.clone(), status, destination: redirects.last().cloned() };
+
+    if status.map_or(true, |status| status >= 400) {
+      report.broken.push(entry);
+    } else if !redirects.is_empty() || status.is_some_and(|status| status >= 300) {
+      report.redirected.push(entry);
+    }
+  }
+
+  report.missing = record.pages.iter()
+    .filter(|(url, _)| record.urls.contains(*url) && !record.sitemap.contains(*url))
+    .filter(|(url, page)| {
+      page.status.is_some_and(|status| (200..300).contains(&status))
+        && page.redirects.is_empty()
+        && !page.noindex_meta
+        && !page.noindex_header
+        && page.canonical.as_ref().map_or(true, |canonical| canonical == *url)
+    })
+    .map(|(url, _)| url.clone())
+    .collect();
+
+  report.broken.sort_by(|a, b| a.url.cmp(&b.url));
+  report.redirected.sort_by(|a, b| a.url.cmp(&b.url));
+  report.unchecked.sort();
+  report.missing.sort();
+
+  report
+}
+
+#[derive(Serialize)]
+pub struct OrphanReport {
+  /// Urls listed in the sitemap which the crawler never found linked from any page.
+  orphaned: Vec<String>,
+  /// Urls the crawler found linked from pages which are missing from the sitemap.
+  unlisted: Vec<String>,
+}
+
+#[derive(Default, Serialize)]
+pub struct SitemapCoverage {
+  /// How many urls the sitemap lists.
+  sitemap_urls: usize,
+  /// Sitemap urls which responded with an error status or did not respond at all.
+  broken: Vec<SitemapEntry>,
+  /// Sitemap urls which redirect elsewhere.
+  redirected: Vec<SitemapEntry>,
+  /// Sitemap urls the crawler neither fetched nor checked.
+  unchecked: Vec<String>,
+  /// Crawled pages which could be indexed but are missing from the sitemap.
+  missing: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SitemapEntry {
+  url: String,
+  /// The status code of the response the redirects ended at. None if the url did not respond.
+  status: Option<u16>,
+  /// The url the redirects ended at, if the url redirected.
+  destination: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Redirect {
+  /// The url which was requested.
+  source: String,
+  /// The url the redirect chain ended at.
+  destination: String,
+  /// How many redirects were followed from the source to the destination.
+  hops: usize,
+  /// Whether the chain has more hops than the configured limit.
+  exceeds_limit: bool,
+  /// Whether the chain returns to a url it already went through.
+  loops: bool,
+}
+
+#[derive(Serialize)]
+pub struct BrokenOutbound {
+  /// The url of the page containing the dead links.
+  page: String,
+  /// How many dead links to other hosts the page contains.
+  count: usize,
+  /// The dead links.
+  broken_links: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DuplicateReport {
+  /// Groups of pages sharing the same title.
+  titles: Vec<Duplicate>,
+  /// Groups of pages sharing the same meta description.
+  descriptions: Vec<Duplicate>,
+}
+
+#[derive(Serialize)]
+pub struct Duplicate {
+  /// The title or description the pages share.
+  value: String,
+  /// Urls of the pages.
+  pages: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MissingAlt {
+  /// The url of the page containing the images.
+  page: String,
+  /// How many images on the page lack alt text.
+  count: usize,
+  /// Sources of the images.
+  images: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct HeadingStructure {
+  /// The url of the page.
+  page: String,
+  /// How many h1 headings the page has.
+  h1_count: usize,
+  /// Whether the page has zero or multiple h1 headings.
+  flagged: bool,
+  /// The h1, h2 and h3 headings of the page in the document order.
+  headings: Vec<Heading>,
+}
+
+#[derive(Serialize)]
+pub struct Canonical {
+  /// The url of the page.
+  page: String,
+  /// The canonical url the page declares.
+  canonical: String,
+  /// The status code the canonical url responds with, if it responded at all.
+  canonical_status: Option<u16>,
+  /// Whether the canonical url responds with an error or does not respond.
+  is_broken: bool,
+}
+
+#[derive(Serialize)]
+pub struct AmpVariant {
+  /// The url of the regular page.
+  canonical: String,
+  /// The url of its AMP variant.
+  amp: String,
+  /// Whether the regular page declares the AMP variant and the AMP variant declares the regular
+  /// page as its canonical url. Only one side is known unless both pages were crawled.
+  is_mutual: bool,
+}
+
+#[derive(Serialize)]
+pub struct HreflangCluster {
+  /// Urls of all pages referencing each other with hreflang links.
+  pages: BTreeSet<String>,
+  /// Alternate references within the cluster which failed validation.
+  issues: Vec<HreflangIssue>,
+}
+
+#[derive(Serialize)]
+pub struct HreflangIssue {
+  /// The url of the page declaring the alternate.
+  from: String,
+  /// The url of the alternate.
+  to: String,
+  /// The language code of the alternate.
+  lang: String,
+  /// Either `broken_target` if the alternate failed to load, or `missing_return` if the alternate
+  /// does not reference the page back.
+  problem: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Noindex {
+  /// The url of the page.
+  page: String,
+  /// Whether a robots meta tag contains the noindex directive.
+  meta: bool,
+  /// Whether the X-Robots-Tag header contains the noindex directive.
+  header: bool,
+}
+
+#[derive(Serialize)]
+pub struct Performance {
+  /// The url of the page.
+  page: String,
+  /// Size of the response body in bytes.
+  size: Option<usize>,
+  /// Milliseconds until the headers of the final response arrived.
+  ttfb_ms: Option<u64>,
+  /// Milliseconds it took to fetch the whole page.
+  duration_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct MixedContent {
+  /// The url of the https page.
+  page: String,
+  /// Plain http assets the page loads, which browsers block or warn about.
+  assets: Vec<String>,
+  /// Plain http links and form actions on the page.
+  links: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct PageForms {
+  /// The url of the page.
+  page: String,
+  /// The forms on the page in the document order.
+  forms: Vec<Form>,
+}
+
+#[derive(Serialize)]
+pub struct SecurityHeaders {
+  /// The url of the page.
+  page: String,
+  /// Names of the security headers the page was served without.
+  missing: Vec<String>,
+}
+
+#[derive(Default, Serialize)]
+pub struct ThirdParty {
+  /// Urls of the pages loading assets from the host, which lets the host observe their visitors.
+  assets: Vec<String>,
+  /// Urls of the pages linking to the host.
+  links: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TlsReport {
+  /// When the certificate was inspected, in RFC 3339 format.
+  checked_at: String,
+  /// The expiry date of the certificate.
+  expires: String,
+  /// How many whole days remain until the certificate expires. Negative if it has expired.
+  days_remaining: i32,
+  /// Whether clients verifying certificates would connect to the host.
+  is_valid: bool,
+  /// Problems with the certificate: `expired`, `expires_soon`, `not_yet_valid`,
+  /// `hostname_mismatch`, `self_signed` or `untrusted`.
+  issues: Vec<String>,
+  /// The verification error as described by OpenSSL.
+  error: Option<String>,
+}
@@ -0,0 +1,112 @@
+use url::Url;
+use std::time::Duration;
+use super::fetch::Fetcher;
+
+/// The name the crawler looks for in the `User-agent` lines of robots.txt files. Groups for all
+/// crawlers, `*`, apply if no group names the crawler.
+pub const AGENT: &str = "link-crawler";
+
+/// Hosts can't slow a crawl down more than this, so that a typo in their robots.txt doesn't stall
+/// the crawler for hours.
+pub const MAX_CRAWL_DELAY: Duration = Duration::from_secs(60);
+
+/// Fetches the robots.txt of the host the url belongs to and returns how long the crawler should
+/// wait between requests to the host. Returns None if the host has no robots.txt or it sets no
+/// delay for the crawler.
+pub fn fetch_crawl_delay(fetcher: &dyn Fetcher, url: &Url) -> Option<Duration> {
+  let robots_url = url.join("/robots.txt").ok()?;
+
+  let (mut req, _) = fetcher.get(&robots_url)?;
+
+  if !req.status().is_success() {
+    return None;
+  }
+
+  let body = req.text().ok()?;
+
+  crawl_delay(&body, AGENT)
+}
+
+/// Finds the `Crawl-delay` directive of the group of given agent in the robots.txt document, or
+/// of the group of all agents if the document has none for given agent. The delay is a number
+/// of seconds, possibly fractional, and is capped at `MAX_CRAWL_DELAY`. Malformed delays are
+/// ignored.
+pub fn crawl_delay(body: &str, agent: &str) -> Option<Duration> {
+  let mut agent_delay = None;
+  let mut wildcard_delay = None;
+  // The agents of the group being read, and whether the group has any rules yet. A user agent
+  // line after a rule starts a new group.
+  let mut agents: Vec<String> = vec!();
+  let mut has_rules = false;
+
+  for line in body.lines() {
+    let line = line.split('#').next().unwrap_or_default();
+    let (key, value) = match line.find(':') {
+      Some(colon) => (line[..colon].trim(), line[colon + 1..].trim()),
+      None => continue,
+    };
+
+    if key.eq_ignore_ascii_case("user-agent") {
+      if has_rules {
+        agents.clear();
+        has_rules = false;
+      }
+
+      agents.push(value.to_lowercase());
+
+      continue;
+    }
+
+    has_rules = true;
+
+    if !key.eq_ignore_ascii_case("crawl-delay") {
+      continue;
+    }
+
+    let delay = match value.parse::<f64>() {
+      Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+        Duration::from_secs_f64(seconds.min(MAX_CRAWL_DELAY.as_secs_f64()))
+      },
+      _ => continue,
+    };
+
+    if agents.iter().any(|name| name.eq_ignore_ascii_case(agent)) {
+      agent_delay = agent_delay.or(Some(delay));
+    } else if agents.iter().any(|name| name == "*") {
+      wildcard_delay = wildcard_delay.or(Some(delay));
+    }
+  }
+
+  agent_delay.or(wildcard_delay)
+}
+
+#[cfg(test)]
+mod test {
+  use super::{crawl_delay, MAX_CRAWL_DELAY};
+  use std::time::Duration;
+
+  #[test]
+  fn test_crawl_delay() {
+    let body = concat!(
+      "User-agent: googlebot\n",
+      "Crawl-delay: 1\n",
+      "\n",
+      "User-agent: *\n",
+      "Disallow: /private # Nobody may crawl this\n",
+      "Crawl-delay: 2.5\n",
+      "\n",
+      "User-agent: Bingbot\n",
+      "User-agent: Link-Crawler\n",
+      "Crawl-delay: 10\n",
+    );
+
+    assert_eq!(crawl_delay(body, "link-crawler"), Some(Duration::from_secs(10)));
+    assert_eq!(crawl_delay(body, "googlebot"), Some(Duration::from_secs(1)));
+    // Crawlers without a group of their own follow the group of all crawlers.
+    assert_eq!(crawl_delay(body, "yandex"), Some(Duration::from_millis(2500)));
+    assert_eq!(crawl_delay("User-agent: *\nDisallow: /\n", "link-crawler"), None);
+    assert_eq!(crawl_delay("User-agent: *\nCrawl-delay: soon\n", "link-crawler"), None);
+    assert_eq!(crawl_delay("User-agent: *\nCrawl-delay: 86400\n", "a"), Some(MAX_CRAWL_DELAY));
+    assert_eq!(crawl_delay("Crawl-delay: 5\n", "link-crawler"), None);
+  }
+}
@@ -1,56 +1,1299 @@
+use url::Url;
+use scraper::Selector;
+use super::fetch;
+use super::local;
+use super::sitemap;
+use super::storage::{self, Changes};
+use super::errors::ApiError;
 use rocket::State;
 use super::Database;
-use rocket::http::Status;
-use std::sync::Mutex;
+use std::ops::Deref;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::{Mutex, OnceLock};
+use chrono::{DateTime, Utc};
+use super::config::Config;
 use std::sync::mpsc::Sender;
+use super::database::{Domain, UrlRecord, UrlState};
+use super::jobs::{Job, JobState, Jobs, StopConditions};
+use super::frontier::CrawlOrder;
+use std::collections::{BTreeMap, BTreeSet};
 use rocket_contrib::json::Json;
+use rocket::response::content::{Content, Xml};
+use rocket::response::status::Accepted;
+use rocket::response::{self, Responder, Response};
 use serde::{Deserialize, Serialize};
+use rocket::request::{self, FromParam, FromRequest, Request};
+use rocket::http::{Accept, ContentType, RawStr, Status};
+use rocket::Outcome;
+
+/// A page of the url listing has this many urls unless the client asks otherwise.
+const DEFAULT_URLS_PER_PAGE: usize = 1000;
+
+/// A page of the url listing has at most this many urls.
+const MAX_URLS_PER_PAGE: usize = 10_000;
+
+/// A single query can ask for the urls of at most this many domains.
+const MAX_QUERIED_DOMAINS: usize = 100;
+
+/// Tags of domains are at most this many characters long.
+const MAX_TAG_LENGTH: usize = 64;
+
+/// Annotations of urls are at most this many characters long.
+const MAX_ANNOTATION_LENGTH: usize = 1000;
 
 #[get("/<domain>/url")]
-pub fn list(cache: State<Database>, domain: String) -> Result<Json<Vec<String>>, Status> {
+pub fn list(
+  cache: State<Database>,
+  domain: DomainName,
+  accept: Option<&Accept>,
+  if_none_match: Option<IfNoneMatch>,
+) -> Result<Tagged<Counted<UrlList>>, Status> {
   match cache.lock() {
     // If lock was acquired, find domain in the list and return all items from the set as
     // vector or an empty vector if domain was not crawled yet.
     Ok(db) => {
-      let list = match db.get(&domain) {
-        // TODO: Paginate results.
-        Some(set) => set.iter().cloned().collect(),
+      let record = db.get(&*domain);
+
+      Ok(Tagged::new(etag(record, UrlList::format(accept)), if_none_match, || {
+        let list = match record {
+          // TODO: Paginate results.
+          Some(record) => record.urls.iter().cloned().collect(),
+          None => vec!(),
+        };
+
+        Counted(list.len(), UrlList::negotiate(list, accept))
+      }))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+/// Lists the urls of the domain a page at a time, wrapped in an envelope describing the page and
+/// how fresh the urls are. This is the url listing of the second version of the api.
+#[get("/<domain>/url?<page>&<per_page>")]
+pub fn list_page(
+  cache: State<Database>,
+  domain: DomainName,
+  page: Option<usize>,
+  per_page: Option<usize>,
+) -> Result<Json<UrlPage>, ApiError> {
+  let page = page.unwrap_or(1);
+  let per_page = per_page.unwrap_or(DEFAULT_URLS_PER_PAGE);
+
+  if page == 0 || per_page == 0 || per_page > MAX_URLS_PER_PAGE {
+    return Err(ApiError::new(
+      Status::BadRequest,
+      "invalid_page",
+      format!("Pages are numbered from 1 and have 1 to {} urls", MAX_URLS_PER_PAGE),
+    ));
+  }
+
+  match cache.lock() {
+    // If lock was acquired, sorts the urls of the domain so that pages are stable and returns the
+    // requested one, which is empty past the last page.
+    Ok(db) => {
+      let record = db.get(&*domain);
+      let mut urls: Vec<&String> = record
+        .map(|record| record.urls.iter().collect())
+        .unwrap_or_default();
+      urls.sort();
+
+      Ok(Json(UrlPage {
+        total: urls.len(),
+        page,
+        per_page,
+        pages: urls.len().div_ceil(per_page),
+        crawled_at: record.and_then(|record| record.crawled_at).map(|time| time.to_rfc3339()),
+        urls: urls.into_iter().skip((page - 1) * per_page).take(per_page).cloned().collect(),
+      }))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[get("/<domain>/url/details?<min_depth>&<max_depth>&<state>")]
+pub fn details(
+  cache: State<Database>,
+  domain: DomainName,
+  min_depth: Option<usize>,
+  max_depth: Option<usize>,
+  state: Option<String>,
+) -> Result<Json<Vec<UrlDetails>>, ApiError> {
+  let state = match state {
+    Some(state) => Some(parse_state(&state)?),
+    None => None,
+  };
+  let is_filtered = min_depth.is_some() || max_depth.is_some() || state.is_some();
+  let min_depth = min_depth.unwrap_or(0);
+  let max_depth = max_depth.unwrap_or(usize::MAX);
+
+  match cache.lock() {
+    // If lock was acquired, lists every url of the domain in alphabetical order with what the
+    // crawler knows about it. When filtering, urls without a history are left out.
+    Ok(db) => {
+      let mut details: Vec<UrlDetails> = match db.get(&*domain) {
+        Some(record) => record.urls.iter()
+          .map(|url| (url, record.records.get(url)))
+          .filter(|(_, history)| match history {
+            Some(history) => history.depth >= min_depth && history.depth <= max_depth
+              && state.map_or(true, |state| history.state == state),
+            None => !is_filtered,
+          })
+          .map(|(url, history)| UrlDetails::new(url, history))
+          .collect(),
         None => vec!(),
       };
+      details.sort_by(|a, b| a.url.cmp(&b.url));
 
-      Ok(Json(list))
+      Ok(Json(details))
     },
-    Err(_) => Err(Status::InternalServerError),
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[get("/<domain>/url/lookup?<url>")]
+pub fn lookup(
+  cache: State<Database>,
+  domain: DomainName,
+  url: String,
+) -> Result<Json<UrlLookup>, ApiError> {
+  match cache.lock() {
+    // If lock was acquired, returns what the crawler knows about the url, including the pages it
+    // was found on, or 404 if the crawler has not found the url on the domain.
+    Ok(db) => {
+      let record = db.get(&*domain)
+        .filter(|record| record.urls.contains(&url))
+        .ok_or_else(|| url_not_found(&url))?;
+
+      Ok(Json(UrlLookup::new(&url, record.records.get(&url))))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[put("/<domain>/url/annotation", format = "application/json", data = "<req>")]
+pub fn annotate(
+  cache: State<Database>,
+  changes: State<Changes>,
+  domain: DomainName,
+  req: Json<Annotation>,
+) -> Result<Json<UrlLookup>, ApiError> {
+  let annotation = req.annotation.as_ref()
+    .map(|annotation| annotation.trim().to_string())
+    .filter(|annotation| !annotation.is_empty());
+
+  let length = annotation.as_ref().map_or(0, |annotation| annotation.chars().count());
+
+  if length > MAX_ANNOTATION_LENGTH {
+    return Err(ApiError::new(
+      Status::BadRequest,
+      "invalid_annotation",
+      format!("Annotations can have at most {} characters", MAX_ANNOTATION_LENGTH),
+    ));
+  }
+
+  match cache.lock() {
+    // If lock was acquired, replaces the annotation of the url or removes it if there is none.
+    Ok(mut db) => {
+      let history = db.get_mut(&*domain)
+        .and_then(|record| record.records.get_mut(&req.url))
+        .ok_or_else(|| url_not_found(&req.url))?;
+      history.annotation = annotation;
+      storage::changed(&changes, &domain);
+
+      Ok(Json(UrlLookup::new(&req.url, Some(history))))
+    },
+    Err(_) => Err(ApiError::lock()),
   }
 }
 
 #[get("/<domain>/url/count")]
-pub fn count(cache: State<Database>, domain: String) -> Result<Json<UrlCount>, Status> {
+pub fn count(
+  cache: State<Database>,
+  domain: DomainName,
+  if_none_match: Option<IfNoneMatch>,
+) -> Result<Tagged<Counted<Json<UrlCount>>>, Status> {
   match cache.lock() {
     // If lock was acquired, find domain and count all urls it has associated with it.
     Ok(db) => {
-      let count: usize = match db.get(&domain) {
-        Some(set) => set.len(),
-        None => 0,
-      };
+      let record = db.get(&*domain);
+
+      Ok(Tagged::new(etag(record, "count"), if_none_match, || {
+        let count: usize = match record {
+          Some(record) => record.urls.len(),
+          None => 0,
+        };
+
+        Counted(count, Json(UrlCount { count }))
+      }))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[post("/query", format = "application/json", data = "<req>")]
+pub fn query(
+  cache: State<Database>,
+  req: Json<DomainQuery>,
+) -> Result<Json<BTreeMap<String, DomainUrls>>, ApiError> {
+  if req.domains.len() > MAX_QUERIED_DOMAINS {
+    return Err(ApiError::new(
+      Status::BadRequest,
+      "too_many_domains",
+      format!("At most {} domains can be queried at once", MAX_QUERIED_DOMAINS),
+    ));
+  }
+
+  match cache.lock() {
+    // If lock was acquired, counts and optionally lists the urls of every requested domain,
+    // including the ones which were not crawled yet.
+    Ok(db) => {
+      let include_urls = req.urls.unwrap_or(true);
+      let report = req.domains.iter()
+        .map(|domain| fetch::ascii_host(domain.trim()))
+        .map(|domain| {
+          let record = db.get(&domain);
+          let count = record.map(|record| record.urls.len()).unwrap_or(0);
+          let urls = if include_urls {
+            let mut urls: Vec<String> = record
+              .map(|record| record.urls.iter().cloned().collect())
+              .unwrap_or_default();
+            urls.sort();
+
+            Some(urls)
+          } else {
+            None
+          };
+
+          (domain, DomainUrls { count, urls })
+        })
+        .collect();
+
+      Ok(Json(report))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[get("/?<tag>")]
+pub fn domains(
+  cache: State<Database>,
+  tag: Option<String>,
+) -> Result<Json<Vec<DomainSummary>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every known domain in alphabetical order, optionally only the
+    // ones with given tag.
+    Ok(db) => {
+      let mut domains: Vec<DomainSummary> = db.iter()
+        .filter(|(_, record)| tag.as_ref().map_or(true, |tag| record.tags.contains(tag)))
+        .map(|(domain, record)| DomainSummary {
+          domain: domain.clone(),
+          tags: record.tags.iter().cloned().collect(),
+          urls: record.urls.len(),
+        })
+        .collect();
+      domains.sort_by(|a, b| a.domain.cmp(&b.domain));
 
-      Ok(Json(UrlCount { count }))
+      Ok(Json(domains))
     },
     Err(_) => Err(Status::InternalServerError),
   }
 }
 
+#[get("/<domain>/tags")]
+pub fn tags(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<String>>, ApiError> {
+  match cache.lock() {
+    // If lock was acquired, lists the tags of the domain in alphabetical order.
+    Ok(db) => {
+      let record = db.get(&*domain).ok_or_else(|| domain_not_found(&domain))?;
+
+      Ok(Json(record.tags.iter().cloned().collect()))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[put("/<domain>/tags", format = "application/json", data = "<req>")]
+pub fn set_tags(
+  cache: State<Database>,
+  changes: State<Changes>,
+  domain: DomainName,
+  req: Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, ApiError> {
+  let tags: BTreeSet<String> = req.iter().map(|tag| tag.trim().to_string()).collect();
+
+  let is_invalid = |tag: &&String| tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH;
+
+  if let Some(tag) = tags.iter().find(is_invalid) {
+    return Err(ApiError::new(
+      Status::BadRequest,
+      "invalid_tag",
+      format!("Tags must have between 1 and {} characters, not {:?}", MAX_TAG_LENGTH, tag),
+    ));
+  }
+
+  match cache.lock() {
+    // If lock was acquired, replaces the tags of the domain.
+    Ok(mut db) => {
+      let record = db.get_mut(&*domain).ok_or_else(|| domain_not_found(&domain))?;
+      record.tags = tags;
+      storage::changed(&changes, &domain);
+
+      Ok(Json(record.tags.iter().cloned().collect()))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[get("/<domain>/extraction-rules")]
+pub fn extraction_rules(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<BTreeMap<String, String>>, ApiError> {
+  match cache.lock() {
+    // If lock was acquired, lists the field names extracted from the pages of the domain keyed
+    // by their selectors.
+    Ok(db) => {
+      let record = db.get(&*domain).ok_or_else(|| domain_not_found(&domain))?;
+
+      Ok(Json(record.extraction_rules.clone()))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[put("/<domain>/extraction-rules", format = "application/json", data = "<req>")]
+pub fn set_extraction_rules(
+  cache: State<Database>,
+  domain: DomainName,
+  req: Json<BTreeMap<String, String>>,
+) -> Result<Json<BTreeMap<String, String>>, ApiError> {
+  let rules: BTreeMap<String, String> = req.iter()
+    .map(|(selector, field)| (selector.trim().to_string(), field.trim().to_string()))
+    .collect();
+
+  for (selector, field) in &rules {
+    if Selector::parse(selector).is_err() {
+      let message = format!("The selector {:?} is malformed", selector);
+
+      return Err(ApiError::new(Status::BadRequest, "invalid_selector", message));
+    }
+
+    if field.is_empty() {
+      let message = format!("The selector {:?} must extract a named field", selector);
+
+      return Err(ApiError::new(Status::BadRequest, "invalid_field", message));
+    }
+  }
+
+  match cache.lock() {
+    // If lock was acquired, replaces the rules of the domain, which applies them to the pages
+    // crawled from now on. Rules can be set before the domain is first crawled.
+    Ok(mut db) => {
+      let record = db.entry(domain.to_string()).or_insert_with(Default::default);
+      record.extraction_rules = rules;
+
+      Ok(Json(record.extraction_rules.clone()))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+/// The error of routes which need a domain the crawler does not know.
+pub fn domain_not_found(domain: &str) -> ApiError {
+  let message = format!("The domain {} was not crawled", domain);
+
+  ApiError::new(Status::NotFound, "domain_not_found", message)
+}
+
+/// Finds the url state with given name.
+pub fn parse_state(state: &str) -> Result<UrlState, ApiError> {
+  UrlState::ALL.iter().cloned().find(|known| known.as_str() == state).ok_or_else(|| {
+    let states: Vec<&str> = UrlState::ALL.iter().map(|state| state.as_str()).collect();
+    let message = format!("The state must be one of {}, not {}", states.join(", "), state);
+
+    ApiError::new(Status::BadRequest, "invalid_state", message)
+  })
+}
+
+/// Finds the crawl order with given name.
+pub fn parse_order(order: &str) -> Result<CrawlOrder, ApiError> {
+  CrawlOrder::ALL.iter().cloned().find(|known| known.as_str() == order).ok_or_else(|| {
+    let orders: Vec<&str> = CrawlOrder::ALL.iter().map(|order| order.as_str()).collect();
+    let message = format!("The order must be one of {}, not {}", orders.join(", "), order);
+
+    ApiError::new(Status::BadRequest, "invalid_order", message)
+  })
+}
+
+/// Reads the stop conditions of a crawl. None of them can be zero, as such a crawl would stop
+/// before it started, and the crawl can't ask for more pages than `max_pages_per_job`.
+fn stop_conditions(config: &Config, req: &UrlToCrawl) -> Result<StopConditions, ApiError> {
+  let conditions = [
+    ("max_pages", req.max_pages),
+    ("max_seconds", req.max_seconds.map(|seconds| seconds as usize)),
+    ("max_consecutive_errors", req.max_consecutive_errors),
+  ];
+
+  if let Some((name, _)) = conditions.iter().find(|(_, value)| *value == Some(0)) {
+    let message = format!("The {} must be a positive integer", name);
+
+    return Err(ApiError::new(Status::BadRequest, "invalid_stop_condition", message));
+  }
+
+  if let Some(max_pages) = req.max_pages.filter(|pages| *pages > config.max_pages_per_job) {
+    let message = format!(
+      "The max_pages must be at most {}, not {}", config.max_pages_per_job, max_pages,
+    );
+
+    return Err(ApiError::new(Status::BadRequest, "invalid_stop_condition", message));
+  }
+
+  Ok(StopConditions {
+    max_pages: req.max_pages,
+    max_duration: req.max_seconds.map(Duration::from_secs),
+    max_consecutive_errors: req.max_consecutive_errors,
+  })
+}
+
+/// The error of routes which need a job which was not submitted.
+pub fn job_not_found(id: usize) -> ApiError {
+  ApiError::new(Status::NotFound, "job_not_found", format!("There is no job with id {}", id))
+}
+
+/// The error of routes which need a url the crawler has not found.
+pub fn url_not_found(url: &str) -> ApiError {
+  ApiError::new(Status::NotFound, "url_not_found", format!("There is no url {}", url))
+}
+
 #[post("/", format = "application/json", data = "<req>")]
-pub fn crawl(producer: State<Mutex<Sender<String>>>, req: Json<UrlToCrawl>) -> Status {
+pub fn crawl(
+  producer: State<Mutex<Sender<usize>>>,
+  jobs: State<Jobs>,
+  config: State<Config>,
+  req: Json<UrlToCrawl>,
+) -> Result<Accepted<Json<JobCreated>>, ApiError> {
+  let mut job = new_job(&config, &req.url)?;
+  job.stale_after_hours = req.stale_after_hours;
+
+  if let Some(order) = &req.order {
+    job.order = parse_order(order)?;
+  }
+
+  job.stop = stop_conditions(&config, &req)?;
+  job.dry_run = req.dry_run;
+
+  if let Some(scope) = &req.link_scope {
+    if Selector::parse(scope).is_err() {
+      let message = format!("The link scope {:?} is malformed", scope);
+
+      return Err(ApiError::new(Status::BadRequest, "invalid_selector", message));
+    }
+
+    job.link_scope = Some(scope.clone());
+  }
+
+  enqueue(&producer, &jobs, job).map(|id| Accepted(Some(Json(JobCreated { id }))))
+}
+
+/// Creates the job of a crawl of given url, if the url can be crawled.
+pub fn new_job(config: &Config, url: &str) -> Result<Job, ApiError> {
+  let (parsed, is_bare) = validate_url(url)?;
+
+  // Local trees can only be crawled from the directories the operator allowed.
+  if parsed.scheme() == "file" {
+    if !local::is_allowed(config, &parsed) {
+      let message = "The path is not within the local directories which may be crawled";
+
+      return Err(ApiError::new(Status::Forbidden, "directory_denied", message));
+    }
+
+    return Ok(Job::new(parsed.to_string()));
+  }
+
+  // Urls of denied domains or pointing to internal infrastructure are refused upfront. The
+  // crawler checks every url it fetches again, as pages can link or redirect there.
+  if fetch::is_denied(config, &parsed) {
+    return Err(ApiError::new(Status::Forbidden, "domain_denied", "The domain may not be crawled"));
+  }
+
+  if fetch::is_forbidden(config, &parsed) {
+    return Err(ApiError::new(
+      Status::Forbidden, "internal_address", "The host resolves to an internal address",
+    ));
+  }
+
+  // Bare domains are stored as the url the crawl is going to start from.
+  let mut job = Job::new(if is_bare { parsed.to_string() } else { url.to_string() });
+  job.guessed_scheme = is_bare;
+
+  Ok(job)
+}
+
+#[post("/<domain>/retry-failed")]
+pub fn retry_failed(
+  producer: State<Mutex<Sender<usize>>>,
+  jobs: State<Jobs>,
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Accepted<Json<JobCreated>>, ApiError> {
+  let failed: Vec<String> = match cache.lock() {
+    Ok(db) => {
+      let record = db.get(&*domain).ok_or_else(|| domain_not_found(&domain))?;
+      let mut failed: Vec<String> = record.records.iter()
+        .filter(|(_, history)| history.state == UrlState::Failed)
+        .map(|(url, _)| url.clone())
+        .collect();
+      failed.sort();
+
+      failed
+    },
+    Err(_) => return Err(ApiError::lock()),
+  };
+
+  if failed.is_empty() {
+    let message = format!("The domain {} has no failed urls", &*domain);
+
+    return Err(ApiError::new(Status::NotFound, "no_failed_urls", message));
+  }
+
+  let mut job = Job::new(failed[0].clone());
+  job.retried = failed.clone();
+  let id = enqueue(&producer, &jobs, job)?;
+
+  // Once the job is queued, so are its urls, so that they are not reported as failed while
+  // waiting for the crawler. The urls the crawler got to in the meantime are left as they are.
+  let mut db = cache.lock().map_err(|_| ApiError::lock())?;
+  if let Some(record) = db.get_mut(&*domain) {
+    for url in &failed {
+      if let Some(history) = record.records.get_mut(url).filter(|h| h.state == UrlState::Failed) {
+        history.state = UrlState::Queued;
+      }
+    }
+  }
+
+  Ok(Accepted(Some(Json(JobCreated { id }))))
+}
+
+#[post("/<id>/resume")]
+pub fn resume(
+  producer: State<Mutex<Sender<usize>>>,
+  jobs: State<Jobs>,
+  id: usize,
+) -> Result<Accepted<Json<JobCreated>>, ApiError> {
+  let job = match jobs.lock() {
+    // If lock was acquired, continues the crawl of the job from the urls it was yet to fetch,
+    // with the same order, stop conditions and link scope.
+    Ok(jobs) => {
+      let stopped = jobs.get(id).ok_or_else(|| job_not_found(id))?;
+
+      if stopped.state == JobState::Queued || stopped.state == JobState::Running {
+        let message = format!("The job {} did not stop yet", id);
+
+        return Err(ApiError::new(Status::Conflict, "job_not_stopped", message));
+      }
+
+      if stopped.frontier.is_empty() {
+        let message = format!("The job {} has no urls left to fetch", id);
+
+        return Err(ApiError::new(Status::Conflict, "nothing_to_resume", message));
+      }
+
+      let mut job = Job::resuming(stopped.url.clone(), stopped.frontier.clone());
+      job.order = stopped.order;
+      job.stop = stopped.stop;
+      job.link_scope = stopped.link_scope.clone();
+
+      job
+    },
+    Err(_) => return Err(ApiError::lock()),
+  };
+
+  enqueue(&producer, &jobs, job).map(|id| Accepted(Some(Json(JobCreated { id }))))
+}
+
+/// Registers given job and hands it over to the crawler. Returns the id of the job.
+pub fn enqueue(producer: &Mutex<Sender<usize>>, jobs: &Jobs, job: Job) -> Result<usize, ApiError> {
+  // Registers the job before the crawler learns about it so that it can always be looked up.
+  let id = match jobs.lock() {
+    Ok(mut jobs) => {
+      jobs.push(job);
+
+      jobs.len() - 1
+    },
+    Err(_) => return Err(ApiError::lock()),
+  };
+
+  let unavailable = || {
+    ApiError::new(Status::ServiceUnavailable, "crawler_unavailable", "The crawler is not running")
+  };
+
   // TODO: Find a better way of creating a channel without using mutex.
   match producer.lock() {
     Ok(producer) => {
-      match producer.send(req.url.clone()) {
-        Ok(_) => Status::Accepted,
-        Err(_) => Status::ServiceUnavailable,
+      match producer.send(id) {
+        Ok(_) => Ok(id),
+        Err(_) => Err(unavailable()),
+      }
+    },
+    Err(_) => Err(unavailable()),
+  }
+}
+
+/// Checks that a submitted url can be crawled, i.e. it is an absolute http or https url with a
+/// host, or a `file://` url. Bare domains such as `example.com` are accepted as https urls, in
+/// which case the returned flag is set. Absolute paths such as `/srv/site` are accepted as file
+/// urls.
+fn validate_url(url: &str) -> Result<(Url, bool), ApiError> {
+  let invalid = |message: String| ApiError::new(Status::BadRequest, "invalid_url", message);
+  let url = url.trim();
+
+  if url.starts_with('/') {
+    // Directories are crawled from their index, relative links of which resolve within them.
+    let parsed = if Path::new(url).is_dir() {
+      Url::from_directory_path(url)
+    } else {
+      Url::from_file_path(url)
+    };
+
+    return parsed
+      .map(|parsed| (parsed, false))
+      .map_err(|_| invalid(String::from("The path is malformed")));
+  }
+  // Checking for the separator rather than for a parse error, as e.g. `localhost:8000` would be
+  // parsed as a url with the `localhost` scheme.
+  let is_bare = !url.contains("://");
+  let url = if is_bare { format!("https://{}", url) } else { url.to_string() };
+  let url = Url::parse(&url).map_err(|e| invalid(format!("The url is malformed: {}", e)))?;
+
+  if url.scheme() == "file" {
+    return Ok((url, false));
+  }
+
+  if url.scheme() != "http" && url.scheme() != "https" {
+    let message = format!("Only http, https and file urls can be crawled, not {}", url.scheme());
+
+    return Err(invalid(message));
+  }
+
+  if !url.has_host() {
+    return Err(invalid(String::from("The url has no host")));
+  }
+
+  Ok((url, is_bare))
+}
+
+#[get("/<id>")]
+pub fn job(jobs: State<Jobs>, id: usize) -> Result<Json<JobStatus>, ApiError> {
+  match jobs.lock() {
+    // If lock was acquired, returns the progress of the job or 404 if there is no such job.
+    Ok(jobs) => {
+      let job = jobs.get(id).ok_or_else(|| job_not_found(id))?;
+
+      Ok(Json(JobStatus {
+        id,
+        url: job.url.clone(),
+        state: job.state.as_str(),
+        submitted_at: job.submitted_at.to_rfc3339(),
+        started_at: job.started_at.map(|time| time.to_rfc3339()),
+        finished_at: job.finished_at.map(|time| time.to_rfc3339()),
+        pages_crawled: job.pages_crawled,
+        bytes_downloaded: job.bytes_downloaded,
+        truncated: job.truncated,
+        error: job.error.clone(),
+        stop_reason: job.stop_reason.map(|reason| reason.as_str()),
+        dry_run: job.dry_run,
+        link_scope: job.link_scope.clone(),
+        crawl_delay_ms: job.crawl_delay.map(|delay| delay.as_millis() as u64),
+        would_crawl: if job.dry_run { Some(job.would_crawl.clone()) } else { None },
+        suspected_traps: job.traps.iter()
+          .map(|trap| SuspectedTrap {
+            pattern: trap.pattern.clone(),
+            kind: trap.kind.as_str(),
+            skipped: trap.skipped,
+          })
+          .collect(),
+        timing: JobTiming {
+          fetch_ms: job.timing.fetch.as_millis() as u64,
+          parse_ms: job.timing.parse.as_millis() as u64,
+          insert_ms: job.timing.insert.as_millis() as u64,
+        },
+      }))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[get("/<domain>/crawls")]
+pub fn crawls(cache: State<Database>, domain: DomainName) -> Result<Json<Vec<CrawlSession>>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists the finished crawls of the domain from the oldest one.
+    Ok(db) => {
+      let sessions = match db.get(&*domain) {
+        Some(record) => record.sessions.iter()
+          .enumerate()
+          .map(|(id, session)| CrawlSession {
+            id,
+            job: session.job,
+            url: session.url.clone(),
+            started_at: session.started_at.to_rfc3339(),
+            finished_at: session.finished_at.to_rfc3339(),
+            pages_crawled: session.pages_crawled,
+            pages_failed: session.pages_failed,
+            bytes_downloaded: session.bytes_downloaded,
+            truncated: session.truncated,
+            error: session.error.clone(),
+          })
+          .collect(),
+        None => vec!(),
+      };
+
+      Ok(Json(sessions))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/diff?<from>&<to>")]
+pub fn diff(
+  cache: State<Database>,
+  domain: DomainName,
+  from: usize,
+  to: usize,
+) -> Result<Json<SessionDiff>, ApiError> {
+  match cache.lock() {
+    // If lock was acquired, compares the urls two crawls of the domain found. Urls are changed if
+    // both crawls fetched them and their pages responded with different statuses.
+    Ok(db) => {
+      let sessions = db.get(&*domain).map(|record| &record.sessions[..]).unwrap_or(&[]);
+      let session = |id: usize| sessions.get(id).ok_or_else(|| {
+        let message = format!("There is no crawl with id {}", id);
+
+        ApiError::new(Status::NotFound, "crawl_not_found", message)
+      });
+      let (old, new) = (session(from)?, session(to)?);
+
+      let mut added: Vec<String> = new.urls.keys()
+        .filter(|url| !old.urls.contains_key(*url))
+        .cloned()
+        .collect();
+      let mut removed: Vec<String> = old.urls.keys()
+        .filter(|url| !new.urls.contains_key(*url))
+        .cloned()
+        .collect();
+      let mut changed: Vec<ChangedUrl> = new.urls.iter()
+        .filter_map(|(url, status)| match (old.urls.get(url), status) {
+          (Some(Some(old_status)), Some(new_status)) if old_status != new_status => {
+            Some(ChangedUrl { url: url.clone(), from_status: *old_status, to_status: *new_status })
+          },
+          _ => None,
+        })
+        .collect();
+      added.sort();
+      removed.sort();
+      changed.sort_by(|a, b| a.url.cmp(&b.url));
+
+      Ok(Json(SessionDiff { from, to, added, removed, changed }))
+    },
+    Err(_) => Err(ApiError::lock()),
+  }
+}
+
+#[get("/<domain>/contacts")]
+pub fn contacts(cache: State<Database>, domain: DomainName) -> Result<Json<Contacts>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists every email address and phone number found on the domain with
+    // the urls of the pages publishing it. Both are empty unless contact extraction is enabled.
+    Ok(db) => {
+      let mut contacts = Contacts::default();
+
+      if let Some(record) = db.get(&*domain) {
+        for (url, page) in &record.pages {
+          for email in &page.emails {
+            contacts.emails.entry(email.clone()).or_insert_with(Vec::new).push(url.clone());
+          }
+
+          for phone in &page.phones {
+            contacts.phones.entry(phone.clone()).or_insert_with(Vec::new).push(url.clone());
+          }
+        }
+      }
+
+      for pages in contacts.emails.values_mut().chain(contacts.phones.values_mut()) {
+        pages.sort();
+      }
+
+      Ok(Json(contacts))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/script-data")]
+pub fn script_data(
+  cache: State<Database>,
+  domain: DomainName,
+) -> Result<Json<ScriptData>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists the data the page script extracted keyed by the urls of the
+    // pages. Pages the script extracted nothing from are left out.
+    Ok(db) => {
+      let pages = db.get(&*domain)
+        .map(|record| record.pages.iter()
+          .filter(|(_, page)| !page.script_data.is_empty())
+          .map(|(url, page)| (url.clone(), page.script_data.clone()))
+          .collect())
+        .unwrap_or_default();
+
+      Ok(Json(ScriptData { pages }))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/fields")]
+pub fn fields(cache: State<Database>, domain: DomainName) -> Result<Json<Fields>, Status> {
+  match cache.lock() {
+    // If lock was acquired, lists the fields the extraction rules extracted keyed by the urls of
+    // the pages. Pages no rule matched are left out.
+    Ok(db) => {
+      let pages = db.get(&*domain)
+        .map(|record| record.pages.iter()
+          .filter(|(_, page)| !page.fields.is_empty())
+          .map(|(url, page)| (url.clone(), page.fields.clone()))
+          .collect())
+        .unwrap_or_default();
+
+      Ok(Json(Fields { pages }))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/memory")]
+pub fn memory(cache: State<Database>) -> Result<Json<MemoryUsage>, Status> {
+  match cache.lock() {
+    // If lock was acquired, estimates the memory used by every domain, the largest ones first.
+    Ok(db) => {
+      let mut domains: Vec<DomainMemory> = db.iter()
+        .map(|(domain, record)| {
+          let url_bytes: usize = record.urls.iter().map(|url| url.len()).sum();
+          let average_url_length = if record.urls.is_empty() { 0 } else { url_bytes / record.urls.len() };
+
+          DomainMemory {
+            domain: domain.clone(),
+            urls: record.urls.len(),
+            sitemap_urls: record.sitemap.len(),
+            pages: record.pages.len(),
+            average_url_length,
+            approximate_bytes: record.approximate_size(),
+          }
+        })
+        .collect();
+
+      domains.sort_by(|a, b| {
+        b.approximate_bytes.cmp(&a.approximate_bytes).then_with(|| a.domain.cmp(&b.domain))
+      });
+
+      Ok(Json(MemoryUsage {
+        approximate_bytes: domains.iter().map(|domain| domain.approximate_bytes).sum(),
+        domains,
+      }))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/tree")]
+pub fn tree(cache: State<Database>, domain: DomainName) -> Result<Json<PathTree>, Status> {
+  match cache.lock() {
+    // If lock was acquired, organizes the urls of the domain by their path segments.
+    Ok(db) => {
+      let mut tree = PathTree::default();
+
+      if let Some(record) = db.get(&*domain) {
+        for url in record.urls.iter().filter_map(|url| Url::parse(url).ok()) {
+          // Unwrap is safe as the crawler only stores urls with a host, which always have a path.
+          tree.insert(url.path_segments().unwrap().filter(|segment| !segment.is_empty()));
+        }
+      }
+
+      Ok(Json(tree))
+    },
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/sitemap.xml")]
+pub fn sitemap(
+  cache: State<Database>,
+  config: State<Config>,
+  host: Option<Host>,
+  domain: DomainName,
+) -> Result<Xml<String>, Status> {
+  match cache.lock() {
+    Ok(db) => {
+      let entries = sitemap_entries(db.get(&*domain));
+
+      if entries.len() <= sitemap::MAX_URLS_PER_SITEMAP {
+        return Ok(Xml(sitemap::render_urlset(&entries)));
       }
+
+      // Large url sets are split into parts which are listed in a sitemap index. The index has to
+      // link to the parts with absolute urls.
+      let base = match (&config.public_url, host) {
+        (Some(public_url), _) => public_url.clone(),
+        (None, Some(Host(host))) => format!("http://{}", host),
+        (None, None) => return Err(Status::BadRequest),
+      };
+      let parts = entries.len().div_ceil(sitemap::MAX_URLS_PER_SITEMAP);
+      let sitemaps: Vec<String> = (1..=parts)
+        .map(|part| format!("{}/host/{}/sitemap/{}.xml", base, &*domain, part))
+        .collect();
+
+      Ok(Xml(sitemap::render_index(&sitemaps)))
     },
-    Err(_) => Status::ServiceUnavailable,
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+#[get("/<domain>/sitemap/<part>")]
+pub fn sitemap_part(
+  cache: State<Database>,
+  domain: DomainName,
+  part: SitemapPart,
+) -> Result<Xml<String>, Status> {
+  match cache.lock() {
+    // Parts are numbered from one, the same way the sitemap index lists them.
+    Ok(db) => sitemap_entries(db.get(&*domain))
+      .chunks(sitemap::MAX_URLS_PER_SITEMAP)
+      .nth(part.0 - 1)
+      .map(|entries| Xml(sitemap::render_urlset(entries)))
+      .ok_or(Status::NotFound),
+    Err(_) => Err(Status::InternalServerError),
+  }
+}
+
+/// Lists all urls of the domain in alphabetical order, each with the time it was last crawled at
+/// if the crawler has fetched it.
+fn sitemap_entries(record: Option<&Domain>) -> Vec<(&str, Option<DateTime<Utc>>)> {
+  let mut entries: Vec<(&str, Option<DateTime<Utc>>)> = match record {
+    Some(record) => record.urls.iter()
+      .map(|url| (url.as_str(), record.pages.get(url).and_then(|page| page.fetched_at)))
+      .collect(),
+    None => vec!(),
+  };
+
+  entries.sort();
+
+  entries
+}
+
+/// The Host header of the request, used to link back to the service.
+pub struct Host(String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Host {
+  type Error = ();
+
+  fn from_request(request: &'a Request<'r>) -> request::Outcome<Host, ()> {
+    match request.headers().get_one("Host") {
+      Some(host) => Outcome::Success(Host(host.to_string())),
+      None => Outcome::Forward(()),
+    }
+  }
+}
+
+/// The urls of a domain in the format the client accepts.
+#[derive(Responder)]
+pub enum UrlList {
+  Json(Json<Vec<String>>),
+  /// A single `url` column with a header.
+  Csv(Content<String>),
+  /// One url per line.
+  Text(Content<String>),
+}
+
+impl UrlList {
+  /// Names the format the client prefers, JSON unless it asks for CSV or plain text.
+  fn format(accept: Option<&Accept>) -> &'static str {
+    match accept.map(|accept| accept.preferred().media_type()) {
+      Some(media_type) if media_type.is_csv() => "csv",
+      Some(media_type) if media_type.is_plain() => "text",
+      _ => "json",
+    }
+  }
+
+  /// Renders the urls in the format the client prefers.
+  fn negotiate(urls: Vec<String>, accept: Option<&Accept>) -> UrlList {
+    match UrlList::format(accept) {
+      "csv" => UrlList::Csv(Content(ContentType::CSV, urls_csv(&urls))),
+      "text" => {
+        let text: String = urls.into_iter().map(|url| url + "\n").collect();
+
+        UrlList::Text(Content(ContentType::Plain, text))
+      },
+      _ => UrlList::Json(Json(urls)),
+    }
+  }
+}
+
+/// Renders urls as a CSV document with a single `url` column with a header.
+pub fn urls_csv(urls: &[String]) -> String {
+  let mut csv = String::from("url\r\n");
+
+  for url in urls {
+    // Urls may contain commas or quotes, which have to be quoted.
+    if url.contains([',', '"', '\r', '\n']) {
+      csv.push_str(&format!("\"{}\"\r\n", url.replace('"', "\"\"")));
+    } else {
+      csv.push_str(&format!("{}\r\n", url));
+    }
+  }
+
+  csv
+}
+
+/// The value of the If-None-Match header, listing the versions of a resource the client has.
+pub struct IfNoneMatch(String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfNoneMatch {
+  type Error = ();
+
+  fn from_request(request: &'a Request<'r>) -> request::Outcome<IfNoneMatch, ()> {
+    match request.headers().get_one("If-None-Match") {
+      Some(tags) => Outcome::Success(IfNoneMatch(tags.to_string())),
+      None => Outcome::Forward(()),
+    }
+  }
+}
+
+impl IfNoneMatch {
+  fn matches(&self, etag: &str) -> bool {
+    self.0.split(',').map(str::trim).any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+  }
+}
+
+/// A response tagged with the version of the resource in the ETag header. If the client already
+/// has that version, the response is not rendered at all and 304 Not Modified is sent instead.
+pub struct Tagged<R> {
+  etag: String,
+  response: Option<R>,
+}
+
+impl<R> Tagged<R> {
+  fn new(etag: String, if_none_match: Option<IfNoneMatch>, render: impl FnOnce() -> R) -> Tagged<R> {
+    let is_fresh = if_none_match.map(|tags| tags.matches(&etag)).unwrap_or(false);
+    let response = if is_fresh { None } else { Some(render()) };
+
+    Tagged { etag, response }
+  }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Tagged<R> {
+  fn respond_to(self, request: &Request) -> response::Result<'r> {
+    let mut response = match self.response {
+      Some(response) => response.respond_to(request)?,
+      None => Response::build().status(Status::NotModified).finalize(),
+    };
+    response.set_raw_header("ETag", self.etag);
+
+    Ok(response)
+  }
+}
+
+/// A response which carries the number of urls it is about in the X-Total-Count header, so that
+/// clients can learn it with a HEAD request.
+pub struct Counted<R>(usize, R);
+
+impl<'r, R: Responder<'r>> Responder<'r> for Counted<R> {
+  fn respond_to(self, request: &Request) -> response::Result<'r> {
+    let mut response = self.1.respond_to(request)?;
+    response.set_raw_header("X-Total-Count", self.0.to_string());
+
+    Ok(response)
+  }
+}
+
+/// Builds the ETag of a resource of the domain from the version of its record. The same version
+/// is rendered differently by different endpoints and formats, which are therefore part of the
+/// tag, as is the time the service started at, since versions start over with every restart.
+fn etag(record: Option<&Domain>, variant: &str) -> String {
+  static STARTED_AT: OnceLock<u64> = OnceLock::new();
+  let started_at = STARTED_AT.get_or_init(|| {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+  });
+
+  format!("\"{}-{}-{}\"", started_at, record.map(|record| record.version).unwrap_or(0), variant)
+}
+
+/// A domain given in the path of a request. Domains are stored in lowercase punycode, as that is
+/// what parsed urls contain, so the domain is converted to that form before being looked up.
+pub struct DomainName(String);
+
+impl<'a> FromParam<'a> for DomainName {
+  type Error = &'a RawStr;
+
+  fn from_param(param: &'a RawStr) -> Result<DomainName, &'a RawStr> {
+    match param.percent_decode() {
+      // Local trees are stored under their directories, see `local::host`.
+      Ok(domain) if domain.starts_with('/') => Ok(DomainName(domain.into_owned())),
+      Ok(domain) => Ok(DomainName(fetch::ascii_host(&domain))),
+      Err(_) => Err(param),
+    }
+  }
+}
+
+impl Deref for DomainName {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    &self.0
+  }
+}
+
+/// A part of a split sitemap in the `<number>.xml` format, numbered from one.
+pub struct SitemapPart(usize);
+
+impl<'a> FromParam<'a> for SitemapPart {
+  type Error = &'a RawStr;
+
+  fn from_param(param: &'a RawStr) -> Result<SitemapPart, &'a RawStr> {
+    match param.as_str().trim_end_matches(".xml").parse::<usize>() {
+      Ok(part) if part > 0 => Ok(SitemapPart(part)),
+      _ => Err(param),
+    }
+  }
+}
+
+#[derive(Default, Serialize)]
+pub struct PathTree {
+  /// How many urls have a path starting with the path to this directory.
+  count: usize,
+  /// Subdirectories keyed by their path segment.
+  children: BTreeMap<String, PathTree>,
+}
+
+impl PathTree {
+  /// Counts a url with given path segments in this directory and all subdirectories on its path.
+  fn insert<'a>(&mut self, mut segments: impl Iterator<Item = &'a str>) {
+    self.count += 1;
+
+    if let Some(segment) = segments.next() {
+      self.children.entry(segment.to_string()).or_default().insert(segments);
+    }
+  }
+}
+
+#[derive(Default, Serialize)]
+pub struct Contacts {
+  /// Email addresses keyed to the urls of the pages publishing them.
+  emails: BTreeMap<String, Vec<String>>,
+  /// Phone numbers keyed to the urls of the pages publishing them.
+  phones: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize)]
+pub struct ScriptData {
+  /// The data the page script extracted keyed to the urls of the pages.
+  pages: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Serialize)]
+pub struct Fields {
+  /// The fields the extraction rules extracted keyed to the urls of the pages.
+  pages: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Serialize)]
+pub struct MemoryUsage {
+  /// Approximate bytes used by all domains together.
+  approximate_bytes: usize,
+  /// Memory used by each domain, the largest ones first.
+  domains: Vec<DomainMemory>,
+}
+
+#[derive(Serialize)]
+pub struct DomainMemory {
+  domain: String,
+  /// How many unique urls the crawler has found on the domain.
+  urls: usize,
+  /// How many urls the domain lists in its sitemap.
+  sitemap_urls: usize,
+  /// How many pages of the domain have been fetched.
+  pages: usize,
+  /// The average length of the unique urls in bytes.
+  average_url_length: usize,
+  /// Approximate bytes used by the urls, sitemap and pages of the domain.
+  approximate_bytes: usize,
+}
+
+#[derive(Serialize)]
+pub struct UrlPage {
+  /// How many unique urls has the crawler found for the domain.
+  total: usize,
+  /// The number of this page, counted from one.
+  page: usize,
+  per_page: usize,
+  /// How many pages the urls span.
+  pages: usize,
+  /// When the last crawl of the domain finished, null if no crawl has finished yet.
+  crawled_at: Option<String>,
+  /// The urls on this page in alphabetical order.
+  urls: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct UrlDetails {
+  url: String,
+  /// When the crawler first found the url.
+  first_seen: Option<String>,
+  /// When a crawl last found the url, linked from a page or fetched itself.
+  last_seen: Option<String>,
+  /// How many links away from the url a crawl started at the url was found.
+  depth: Option<usize>,
+  /// The note the users attached to the url.
+  annotation: Option<String>,
+  /// One of `discovered`, `queued`, `fetched`, `failed` or `skipped`.
+  state: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct UrlLookup {
+  #[serde(flatten)]
+  details: UrlDetails,
+  /// Pages of the domain which link to the url, in alphabetical order.
+  referrers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Annotation {
+  url: String,
+  /// The note to attach to the url, none to remove it.
+  annotation: Option<String>,
+}
+
+impl UrlDetails {
+  /// Details of a url with given history. All of them are unknown without the history.
+  fn new(url: &str, history: Option<&UrlRecord>) -> UrlDetails {
+    UrlDetails {
+      url: url.to_string(),
+      first_seen: history.map(|history| history.first_seen.to_rfc3339()),
+      last_seen: history.map(|history| history.last_seen.to_rfc3339()),
+      depth: history.map(|history| history.depth),
+      annotation: history.and_then(|history| history.annotation.clone()),
+      state: history.map(|history| history.state.as_str()),
+    }
+  }
+}
+
+impl UrlLookup {
+  fn new(url: &str, history: Option<&UrlRecord>) -> UrlLookup {
+    UrlLookup {
+      details: UrlDetails::new(url, history),
+      referrers: history.map(|history| history.referrers.iter().cloned().collect())
+        .unwrap_or_default(),
+    }
   }
 }
 
@@ -60,8 +1303,162 @@ pub struct UrlCount {
   count: usize
 }
 
+#[derive(Serialize)]
+pub struct JobCreated {
+  /// The id under which the progress of the crawl can be looked up.
+  id: usize,
+}
+
+#[derive(Serialize)]
+pub struct JobStatus {
+  id: usize,
+  /// The url the crawl starts from.
+  url: String,
+  /// One of `queued`, `running`, `finished` or `failed`.
+  state: &'static str,
+  /// When the crawl was submitted, in RFC 3339 format.
+  submitted_at: String,
+  started_at: Option<String>,
+  finished_at: Option<String>,
+  /// How many pages have been fetched so far.
+  pages_crawled: usize,
+  /// How many bytes of page bodies have been downloaded so far.
+  bytes_downloaded: usize,
+  /// Whether the crawl was stopped early because it exceeded the `max_bytes_per_job` budget or
+  /// the `max_pages_per_domain_per_day` budget of the domain, or its crawl window closed.
+  truncated: bool,
+  /// Why the crawl could not be carried out, if it failed.
+  error: Option<String>,
+  /// Which condition the crawl stopped on, once it did, e.g. `frontier_exhausted` if it fetched
+  /// every url it found.
+  stop_reason: Option<&'static str>,
+  /// Whether the crawl only finds out which urls it would fetch, without storing anything.
+  dry_run: bool,
+  /// The CSS selector the links the crawl follows are limited to. Left out if it follows all.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  link_scope: Option<String>,
+  /// How many milliseconds the crawl waits between pages as the robots.txt of the host asks. Left
+  /// out if the host sets no crawl delay.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  crawl_delay_ms: Option<u64>,
+  /// The urls a dry run fetched so far, in the order it fetched them. Left out for other crawls.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  would_crawl: Option<Vec<String>>,
+  /// Branches of the host the crawl stopped expanding as they seemed to be infinite url spaces.
+  /// Left out if there are none.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  suspected_traps: Vec<SuspectedTrap>,
+  /// How long the crawl spent in each of its phases.
+  timing: JobTiming,
+}
+
+#[derive(Serialize)]
+pub struct CrawlSession {
+  /// The position of the crawl in the history of the domain.
+  id: usize,
+  /// The id of the job which carried out the crawl.
+  job: usize,
+  url: String,
+  started_at: String,
+  finished_at: String,
+  pages_crawled: usize,
+  /// How many pages did not respond or responded with an error status.
+  pages_failed: usize,
+  bytes_downloaded: usize,
+  truncated: bool,
+  error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SessionDiff {
+  from: usize,
+  to: usize,
+  /// Urls the later crawl found and the earlier one did not.
+  added: Vec<String>,
+  /// Urls the earlier crawl found and the later one did not.
+  removed: Vec<String>,
+  /// Urls both crawls fetched, whose pages responded with different statuses.
+  changed: Vec<ChangedUrl>,
+}
+
+#[derive(Serialize)]
+pub struct ChangedUrl {
+  url: String,
+  from_status: u16,
+  to_status: u16,
+}
+
+#[derive(Serialize)]
+pub struct DomainSummary {
+  domain: String,
+  /// Tags of the domain in alphabetical order.
+  tags: Vec<String>,
+  /// How many unique urls were crawled on the domain.
+  urls: usize,
+}
+
+#[derive(Serialize)]
+pub struct SuspectedTrap {
+  /// The urls of the branch with their varying parts replaced, e.g.
+  /// `https://example.com/calendar/{year}/{month}`.
+  pattern: String,
+  /// One of `calendar`, `combinatorial_parameters` or `repeating_path`.
+  kind: &'static str,
+  /// How many urls of the branch the crawl found but did not fetch.
+  skipped: usize,
+}
+
+#[derive(Serialize)]
+pub struct JobTiming {
+  /// Milliseconds spent waiting for responses, summed over pages fetched in parallel.
+  fetch_ms: u64,
+  /// Milliseconds spent parsing pages and extracting their details.
+  parse_ms: u64,
+  /// Milliseconds spent storing pages and links in the database.
+  insert_ms: u64,
+}
+
+#[derive(Deserialize)]
+pub struct DomainQuery {
+  /// Domains whose urls are requested.
+  domains: Vec<String>,
+  /// Whether to list the urls rather than only count them, true unless set otherwise.
+  urls: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct DomainUrls {
+  /// How many unique urls has the crawler found for the domain.
+  count: usize,
+  /// The urls in alphabetical order, unless only their count was requested.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  urls: Option<Vec<String>>,
+}
+
 #[derive(Deserialize)]
 pub struct UrlToCrawl {
   /// A url which should the crawler visit.
-  url: String
+  url: String,
+  /// Makes the crawl a recrawl of the urls which were not fetched within this many hours.
+  #[serde(default)]
+  stale_after_hours: Option<u64>,
+  /// Either `bfs`, the default, or `dfs`.
+  #[serde(default)]
+  order: Option<String>,
+  /// The crawl stops after fetching this many pages, instead of the default of 16.
+  #[serde(default)]
+  max_pages: Option<usize>,
+  /// The crawl stops once it has run for this many seconds.
+  #[serde(default)]
+  max_seconds: Option<u64>,
+  /// The crawl stops once this many pages in a row did not respond or responded with a server
+  /// error.
+  #[serde(default)]
+  max_consecutive_errors: Option<usize>,
+  /// Only finds out which urls the crawl would fetch, without storing anything.
+  #[serde(default)]
+  dry_run: bool,
+  /// Only the links inside of elements matching this CSS selector are followed.
+  #[serde(default)]
+  link_scope: Option<String>,
 }
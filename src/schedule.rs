@@ -0,0 +1,352 @@
+use url::Url;
+use std::thread;
+use super::local;
+use super::routes;
+use super::storage::{self, Changes};
+use super::Database;
+use super::config::Config;
+use super::jobs::{JobState, Jobs};
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use std::collections::BTreeSet;
+use chrono::{DateTime, Datelike, Duration as TimeDelta, TimeZone, Timelike, Utc};
+
+/// The scheduler wakes up at least this often, so that it does not oversleep when the clock of
+/// the machine is adjusted.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// A crawl the service submits by itself whenever its cron expression is due, declared in the
+/// configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+  /// The url the crawls start from, accepted in the same forms as by `POST /host`.
+  pub url: String,
+  pub cron: Cron,
+  /// Tags attached to the crawled domain whenever a crawl is submitted, in addition to the tags
+  /// the domain already has.
+  pub tags: BTreeSet<String>,
+  /// Makes the crawls recrawls of the urls which were not fetched within this many hours, see
+  /// `Job::stale_after_hours`.
+  pub stale_after_hours: Option<u64>,
+}
+
+/// A cron expression with the five standard fields, `minute hour day month weekday`, evaluated
+/// in UTC. Every field is a set of the values it matches, stored as a bit mask.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+  minutes: u64,
+  hours: u64,
+  days: u64,
+  months: u64,
+  /// Days of the week from Sunday as 0.
+  weekdays: u64,
+  /// Whether the day and weekday fields are `*`. As in cron, if both fields are restricted, a
+  /// day matches if it matches either of them.
+  any_day: bool,
+  any_weekday: bool,
+}
+
+impl Schedule {
+  /// Reads the schedules from the `schedules` array of the Rocket configuration. Unlike other
+  /// settings, invalid schedules are not skipped, as the service would silently not monitor
+  /// what it was told to.
+  pub fn from_rocket(config: &rocket::Config) -> Result<Vec<Schedule>, String> {
+    let entries = match config.get_slice("schedules") {
+      Ok(entries) => entries,
+      Err(_) => return Ok(vec!()),
+    };
+
+    entries.iter()
+      .enumerate()
+      .map(|(index, entry)| {
+        let invalid = |message: &str| format!("The schedule {} is invalid: {}", index + 1, message);
+        let get = |name: &str| entry.get(name).and_then(|value| value.as_str());
+
+        let url = get("url").ok_or_else(|| invalid("url must be a string"))?;
+        let cron = get("cron").ok_or_else(|| invalid("cron must be a string"))?;
+        let cron = Cron::parse(cron).map_err(|e| invalid(&e))?;
+        let tags = match entry.get("tags") {
+          Some(tags) => tags.as_array()
+            .and_then(|tags| tags.iter().map(|tag| tag.as_str()).collect::<Option<Vec<&str>>>())
+            .ok_or_else(|| invalid("tags must be an array of strings"))?
+            .into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+          None => BTreeSet::new(),
+        };
+        let stale_after_hours = match entry.get("stale_after_hours") {
+          Some(hours) => Some(hours.as_integer().filter(|hours| *hours >= 0)
+            .ok_or_else(|| invalid("stale_after_hours must be a non-negative integer"))? as u64),
+          None => None,
+        };
+
+        Ok(Schedule { url: url.to_string(), cron, tags, stale_after_hours })
+      })
+      .collect()
+  }
+}
+
+impl Cron {
+  /// Parses an expression such as `0 */6 * * 1-5`. Fields are lists of values, `*`, ranges and
+  /// steps. The `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` shorthands are accepted
+  /// as well.
+  pub fn parse(expression: &str) -> Result<Cron, String> {
+    let expression = match expression.trim() {
+      "@hourly" => "0 * * * *",
+      "@daily" | "@midnight" => "0 0 * * *",
+      "@weekly" => "0 0 * * 0",
+      "@monthly" => "0 0 1 * *",
+      "@yearly" | "@annually" => "0 0 1 1 *",
+      expression => expression,
+    };
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+
+    if fields.len() != 5 {
+      return Err(format!("Cron expressions have 5 fields, not {}", fields.len()));
+    }
+
+    let weekdays = field(fields[4], 0, 7)?;
+
+    Ok(Cron {
+      minutes: field(fields[0], 0, 59)?,
+      hours: field(fields[1], 0, 23)?,
+      days: field(fields[2], 1, 31)?,
+      months: field(fields[3], 1, 12)?,
+      // Both 0 and 7 stand for Sunday.
+      weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+      any_day: fields[2] == "*",
+      any_weekday: fields[4] == "*",
+    })
+  }
+
+  /// Finds the first minute after given time the expression matches. None if it matches no
+  /// minute in the next few years, e.g. for the 30th of February.
+  pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let truncated = after.with_second(0)?.with_nanosecond(0)?;
+    let mut time = truncated + TimeDelta::minutes(1);
+    let limit = time + TimeDelta::days(5 * 366);
+
+    while time < limit {
+      if !has(self.months, time.month()) {
+        let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else {
+          (time.year(), time.month() + 1)
+        };
+        time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+      } else if !self.matches_day(time) {
+        let midnight = time.date_naive().and_hms_opt(0, 0, 0)?;
+        time = Utc.from_utc_datetime(&midnight) + TimeDelta::days(1);
+      } else if !has(self.hours, time.hour()) {
+        time = time.with_minute(0)? + TimeDelta::hours(1);
+      } else if !has(self.minutes, time.minute()) {
+        time += TimeDelta::minutes(1);
+      } else {
+        return Some(time);
+      }
+    }
+
+    None
+  }
+
+  fn matches_day(&self, time: DateTime<Utc>) -> bool {
+    let day = has(self.days, time.day());
+    let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+
+    match (self.any_day, self.any_weekday) {
+      (false, false) => day || weekday,
+      _ => day && weekday,
+    }
+  }
+}
+
+/// Submits the crawls of the schedules whenever they are due, for as long as the service runs.
+/// A crawl is not submitted again while the previous crawl of the same schedule still waits or
+/// runs.
+pub fn run(
+  schedules: Vec<Schedule>,
+  db: Database,
+  jobs: Jobs,
+  changes: Changes,
+  config: Config,
+  producer: Sender<usize>,
+) {
+  let producer = Mutex::new(producer);
+  let now = Utc::now();
+  let mut due: Vec<Option<DateTime<Utc>>> = schedules.iter()
+    .map(|schedule| schedule.cron.next_after(now))
+    .collect();
+  let mut submitted: Vec<Option<usize>> = vec!(None; schedules.len());
+
+  loop {
+    let now = Utc::now();
+
+    for (index, schedule) in schedules.iter().enumerate() {
+      match due[index] {
+        Some(at) if at <= now => due[index] = schedule.cron.next_after(now),
+        _ => continue,
+      }
+
+      // TODO: Error handling the mutex.
+      let is_pending = submitted[index]
+        .and_then(|id| jobs.lock().unwrap().get(id).map(|job| job.state))
+        .is_some_and(|state| state == JobState::Queued || state == JobState::Running);
+
+      if is_pending {
+        println!("[Scheduler] Skipping crawl of {} as the last one did not finish", schedule.url);
+        continue;
+      }
+
+      match submit(schedule, &db, &jobs, &changes, &config, &producer) {
+        Ok(id) => {
+          println!("[Scheduler] Submitted job {} crawling {}", id, schedule.url);
+          submitted[index] = Some(id);
+        },
+        Err(e) => println!("[Scheduler] Error during submitting crawl of {}: {}", schedule.url, e),
+      }
+    }
+
+    let next = match due.iter().flatten().min() {
+      Some(next) => *next,
+      // None of the expressions will ever match again.
+      None => return,
+    };
+    thread::sleep((next - Utc::now()).to_std().unwrap_or_default().min(MAX_SLEEP));
+  }
+}
+
+/// Submits the crawl of a schedule the same way `POST /host` does and tags its domain.
+fn submit(
+  schedule: &Schedule,
+  db: &Database,
+  jobs: &Jobs,
+  changes: &Changes,
+  config: &Config,
+  producer: &Mutex<Sender<usize>>,
+) -> Result<usize, String> {
+  let mut job = routes::new_job(config, &schedule.url).map_err(|e| e.message().to_string())?;
+  job.stale_after_hours = schedule.stale_after_hours;
+  // Unwrap here is safe as urls of jobs always have a host, see `local::host`.
+  let host = local::host(config, &Url::parse(&job.url).map_err(|e| e.to_string())?).unwrap();
+  let id = routes::enqueue(producer, jobs, job).map_err(|e| e.message().to_string())?;
+
+  if !schedule.tags.is_empty() {
+    // TODO: Error handling the mutex.
+    let mut db = db.lock().unwrap();
+    let record = db.entry(host.clone()).or_default();
+    record.tags.extend(schedule.tags.iter().cloned());
+    storage::changed(changes, &host);
+  }
+
+  Ok(id)
+}
+
+/// Reads a single field of an expression into the bit mask of the values it matches.
+fn field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+  let invalid = || format!("The cron field {:?} is invalid", field);
+  let mut mask = 0;
+
+  for part in field.split(',') {
+    let mut parts = part.splitn(2, '/');
+    let range = parts.next().unwrap_or_default();
+    let step = match parts.next() {
+      Some(step) => step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?,
+      None => 1,
+    };
+    let value = |value: &str| value.parse::<u32>().ok().filter(|v| *v >= min && *v <= max);
+
+    let (start, end) = if range == "*" {
+      (min, max)
+    } else if let Some((start, end)) = range.split_once('-') {
+      (value(start).ok_or_else(invalid)?, value(end).ok_or_else(invalid)?)
+    } else {
+      let start = value(range).ok_or_else(invalid)?;
+      // A single value with a step, e.g. `5/15`, runs from the value to the end of the range.
+      (start, if step > 1 { max } else { start })
+    };
+
+    if start > end {
+      return Err(invalid());
+    }
+
+    for value in (start..=end).step_by(step as usize) {
+      mask |= 1 << value;
+    }
+  }
+
+  Ok(mask)
+}
+
+fn has(mask: u64, value: u32) -> bool {
+  mask & (1 << value) != 0
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Cron, Schedule};
+  use chrono::{TimeZone, Utc};
+  use rocket::config::{Config, Environment, Value};
+
+  #[test]
+  fn test_cron() {
+    let at = |year, month, day, hour, minute| {
+      Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    };
+    // The 20th of July 2019 is a Saturday.
+    let now = at(2019, 7, 20, 12, 30);
+
+    let every_six_hours = Cron::parse("0 */6 * * *").unwrap();
+    assert_eq!(every_six_hours.next_after(now), Some(at(2019, 7, 20, 18, 0)));
+    let weekdays = Cron::parse("15 9 * * 1-5").unwrap();
+    assert_eq!(weekdays.next_after(now), Some(at(2019, 7, 22, 9, 15)));
+    let sundays = Cron::parse("0 0 * * 7").unwrap();
+    assert_eq!(sundays.next_after(now), Some(at(2019, 7, 21, 0, 0)));
+    // With both the day and the weekday restricted, either of them is enough.
+    let either = Cron::parse("0 0 1 * 1").unwrap();
+    assert_eq!(either.next_after(now), Some(at(2019, 7, 22, 0, 0)));
+    assert_eq!(Cron::parse("@monthly").unwrap().next_after(now), Some(at(2019, 8, 1, 0, 0)));
+    let minutes = Cron::parse("5/20,59 12 20 7 *").unwrap();
+    assert_eq!(minutes.next_after(now), Some(at(2019, 7, 20, 12, 45)));
+    // The next match is strictly after the given time.
+    let exact = Cron::parse("30 12 * * *").unwrap();
+    assert_eq!(exact.next_after(now), Some(at(2019, 7, 21, 12, 30)));
+    assert_eq!(Cron::parse("0 0 30 2 *").unwrap().next_after(now), None);
+
+    assert!(Cron::parse("0 0 * *").is_err());
+    assert!(Cron::parse("60 * * * *").is_err());
+    assert!(Cron::parse("*/0 * * * *").is_err());
+    assert!(Cron::parse("0 5-1 * * *").is_err());
+  }
+
+  #[test]
+  fn test_from_rocket() {
+    let schedule = |entries: &str| {
+      let value: Value = entries.parse().unwrap();
+      let config = Config::build(Environment::Development)
+        .extra("schedules", value.get("schedules").unwrap().clone())
+        .unwrap();
+
+      Schedule::from_rocket(&config)
+    };
+
+    let schedules = schedule(r#"schedules = [
+      { url = "github.com", cron = "@daily", tags = ["monitored", " code "] },
+      { url = "https://gitlab.com", cron = "0 * * * *", stale_after_hours = 24 },
+    ]"#).unwrap();
+    assert_eq!(schedules.len(), 2);
+    assert_eq!(schedules[0].url, "github.com");
+    assert_eq!(schedules[0].cron, Cron::parse("0 0 * * *").unwrap());
+    assert_eq!(schedules[0].tags.iter().collect::<Vec<_>>(), vec!("code", "monitored"));
+    assert!(schedules[1].tags.is_empty());
+    assert_eq!(schedules[0].stale_after_hours, None);
+    assert_eq!(schedules[1].stale_after_hours, Some(24));
+
+    assert!(schedule(r#"schedules = [{ url = "github.com" }]"#).is_err());
+    assert!(schedule(r#"schedules = [{ url = "github.com", cron = "daily" }]"#).is_err());
+    assert!(schedule(r#"schedules = [{ url = "github.com", cron = "@daily", tags = [1] }]"#)
+      .is_err());
+    assert!(schedule(r#"schedules = [{ url = "a.com", cron = "@daily", stale_after_hours = -1 }]"#)
+      .is_err());
+    assert!(Schedule::from_rocket(&Config::development()).unwrap().is_empty());
+  }
+}
@@ -0,0 +1,223 @@
+use std::fs;
+use std::cell::Cell;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use mlua::{ChunkMode, HookTriggers, Lua, LuaOptions, LuaSerdeExt, StdLib, Value, VmState};
+
+/// A script may allocate at most this many bytes, including the page it is given.
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// A script may run at most this many instructions for a single page, which takes well under a
+/// second.
+const MAX_INSTRUCTIONS: u64 = 20_000_000;
+
+/// The instruction count is checked after every this many instructions.
+const INSTRUCTIONS_PER_CHECK: u32 = 10_000;
+
+/// The functions of the base library which read files or load code other than the script, which
+/// the scripts can't use.
+const REMOVED_GLOBALS: [&str; 5] = ["dofile", "loadfile", "load", "collectgarbage", "print"];
+
+/// What the page script is given as its global `page` table.
+#[derive(Serialize)]
+pub struct ScriptInput<'a> {
+  /// The url the page was loaded from.
+  pub url: &'a str,
+  pub status: u16,
+  pub body: &'a str,
+  /// The links of the crawled host found on the page, in alphabetical order.
+  pub links: Vec<&'a str>,
+}
+
+/// What the page script returns, as a table. Both fields are optional.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ScriptOutput {
+  /// The links the crawl follows. Links the crawler did not find are ignored. All of them are
+  /// followed if missing.
+  pub follow: Option<Vec<String>>,
+  /// Named strings the script extracted from the page, stored with the page.
+  pub data: BTreeMap<String, String>,
+}
+
+impl ScriptOutput {
+  /// Keeps only the given links the script decided to follow.
+  pub fn filter(&self, links: &mut HashSet<String>) {
+    if let Some(follow) = &self.follow {
+      let follow: HashSet<&String> = follow.iter().collect();
+      links.retain(|link| follow.contains(link));
+    }
+  }
+}
+
+/// A Lua script run with every successfully loaded page of the crawled host, so that users
+/// customize which links are followed and what is extracted from pages without recompiling the
+/// crawler.
+///
+/// Every page gets a Lua state of its own with only the `string`, `table`, `math` and `utf8`
+/// libraries and without the functions which read files or load other code. Scripts can't reach
+/// the file system, the network or other processes, and are stopped once they use too much
+/// memory or run too many instructions.
+pub struct PageScript {
+  /// The path the script was loaded from, which names it in errors.
+  path: String,
+  source: String,
+}
+
+impl PageScript {
+  /// Reads the script at given path. Fails if it can't be read or is not valid Lua, so that
+  /// broken scripts are found when the service starts rather than on every page.
+  pub fn load(path: &str) -> Result<PageScript, String> {
+    let source = fs::read_to_string(path)
+      .map_err(|e| format!("The page script {} can't be read: {}", path, e))?;
+    let script = PageScript { path: path.to_string(), source };
+
+    script.sandbox()
+      .and_then(|lua| script.chunk(&lua).into_function().map(|_| ()))
+      .map_err(|e| format!("The page script {} is not valid: {}", path, e))?;
+
+    Ok(script)
+  }
+
+  /// Runs the script with given page, which it finds in its global `page` table, and reads the
+  /// table it returns. Returns None if the script failed, hit one of its limits or returned
+  /// something else than its output, in which case the error is logged.
+  pub fn run(&self, input: &ScriptInput) -> Option<ScriptOutput> {
+    match self.evaluate(input) {
+      Ok(output) => Some(output),
+      Err(e) => {
+        println!("[Crawler] Error during running of the page script on {}: {}", input.url, e);
+
+        None
+      },
+    }
+  }
+
+  fn evaluate(&self, input: &ScriptInput) -> mlua::Result<ScriptOutput> {
+    let lua = self.sandbox()?;
+    lua.globals().set("page", lua.to_value(input)?)?;
+
+    // Counts the instructions of the script, including the instructions of the previous checks.
+    let instructions = Cell::new(0);
+    lua.set_hook(HookTriggers::new().every_nth_instruction(INSTRUCTIONS_PER_CHECK), move |_, _| {
+      instructions.set(instructions.get() + u64::from(INSTRUCTIONS_PER_CHECK));
+
+      if instructions.get() > MAX_INSTRUCTIONS {
+        return Err(mlua::Error::runtime("the script ran too many instructions"));
+      }
+
+      Ok(VmState::Continue)
+    });
+
+    match self.chunk(&lua).eval::<Value>()? {
+      // Scripts can leave out what they don't decide about.
+      Value::Nil => Ok(ScriptOutput::default()),
+      output => lua.from_value(output),
+    }
+  }
+
+  /// A Lua state the script runs in, see `PageScript`.
+  fn sandbox(&self) -> mlua::Result<Lua> {
+    let libraries = StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8;
+    let lua = Lua::new_with(libraries, LuaOptions::default())?;
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+
+    for name in &REMOVED_GLOBALS {
+      lua.globals().raw_remove(*name)?;
+    }
+
+    Ok(lua)
+  }
+
+  /// The script as source code. Precompiled chunks are refused, as Lua does not check them.
+  fn chunk<'a>(&'a self, lua: &'a Lua) -> mlua::Chunk<'a> {
+    lua.load(&self.source).set_name(format!("@{}", self.path)).set_mode(ChunkMode::Text)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{PageScript, ScriptInput, ScriptOutput};
+  use std::collections::{BTreeMap, HashSet};
+
+  fn script(source: &str) -> PageScript {
+    PageScript { path: String::from("page.lua"), source: source.to_string() }
+  }
+
+  fn input() -> ScriptInput<'static> {
+    ScriptInput {
+      url: "https://github.com/",
+      status: 200,
+      body: "<a href=\"/about\">About</a> <a href=\"/logout\">Log out</a>",
+      links: vec!("https://github.com/about", "https://github.com/logout"),
+    }
+  }
+
+  #[test]
+  fn test_run() {
+    let input = input();
+
+    // The script stores the title of the page and follows only the about page and a link the
+    // crawler did not find.
+    let output = script(r#"
+      return {
+        follow = { "https://github.com/about", "https://gitlab.com" },
+        data = { url = page.url, first = page.links[1], title = page.body:match(">(%w+)</a>") },
+      }
+    "#).run(&input).unwrap();
+
+    let mut data = BTreeMap::new();
+    data.insert(String::from("url"), String::from("https://github.com/"));
+    data.insert(String::from("first"), String::from("https://github.com/about"));
+    data.insert(String::from("title"), String::from("About"));
+    assert_eq!(output.data, data);
+
+    let mut links: HashSet<String> = input.links.iter().map(|link| link.to_string()).collect();
+    output.filter(&mut links);
+    // Links the crawler did not find are not followed however the script decided.
+    assert_eq!(links, vec!(String::from("https://github.com/about")).into_iter().collect());
+
+    // Scripts can leave out what they don't decide about.
+    assert_eq!(script("").run(&input), Some(ScriptOutput::default()));
+    assert_eq!(script("return {}").run(&input), Some(ScriptOutput::default()));
+    let output = script("return { follow = {} }").run(&input).unwrap();
+    assert_eq!(output.follow, Some(vec!()));
+
+    assert_eq!(script("return 'not a table'").run(&input), None);
+    assert_eq!(script("error('failed')").run(&input), None);
+  }
+
+  #[test]
+  fn test_run_is_sandboxed() {
+    let input = input();
+
+    for source in &[
+      "return { data = { passwd = io.open('/etc/passwd'):read('a') } }",
+      "os.execute('touch /tmp/crawler-script')",
+      "return dofile('/etc/passwd')",
+      "return load('return 1')()",
+      "return require('os')",
+    ] {
+      assert_eq!(script(source).run(&input), None, "{}", source);
+    }
+
+    // Scripts which never finish or allocate without bounds are stopped.
+    assert_eq!(script("while true do end").run(&input), None);
+    assert_eq!(script("local t = {} while true do t[#t + 1] = page.body end").run(&input), None);
+    assert_eq!(script("return { data = { x = string.rep('x', 1 << 30) } }").run(&input), None);
+  }
+
+  #[test]
+  fn test_load() {
+    assert!(PageScript::load("/nonexistent/page.lua").is_err());
+
+    let path = std::env::temp_dir().join("crawler-test-page-script.lua");
+    std::fs::write(&path, "return {").unwrap();
+    assert!(PageScript::load(path.to_str().unwrap()).is_err());
+
+    std::fs::write(&path, "return { follow = page.links }").unwrap();
+    let output = PageScript::load(path.to_str().unwrap()).unwrap().run(&input()).unwrap();
+    assert_eq!(output.follow.unwrap(), input().links);
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,264 @@
+use url::Url;
+use std::io::Read;
+use flate2::read::GzDecoder;
+use super::fetch::Fetcher;
+use std::collections::{HashSet, VecDeque};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// The sitemap protocol allows at most this many urls in a single sitemap file. Larger url sets
+/// have to be split into several files listed in a sitemap index.
+pub const MAX_URLS_PER_SITEMAP: usize = 50_000;
+
+/// The sitemap protocol allows sitemap files of at most this many bytes when uncompressed.
+/// Sitemaps are not downloaded nor decompressed beyond it.
+const MAX_SITEMAP_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Sitemap indexes are followed at most this many levels deep. The protocol does not allow
+/// indexes of indexes, but some sites nest them anyway.
+const MAX_SITEMAP_DEPTH: usize = 3;
+
+/// At most this many sitemap files of a host are fetched, indexes included.
+const MAX_SITEMAP_FILES: usize = 100;
+
+/// Fetches the sitemap of the host the url belongs to and returns all urls listed in it that
+/// belong to the same host name. If the sitemap is an index, the sitemaps it lists are fetched
+/// too, recursively up to `MAX_SITEMAP_DEPTH` levels and `MAX_SITEMAP_FILES` files. Gzipped
+/// sitemaps are decompressed. Returns None if the host has no sitemap.
+pub fn fetch(fetcher: &dyn Fetcher, url: &Url) -> Option<HashSet<String>> {
+  let host = url.host_str()?;
+  let sitemap_url = url.join("/sitemap.xml").ok()?;
+
+  let body = fetch_file(fetcher, &sitemap_url)?;
+  let (mut urls, sitemaps) = parse(&body, host);
+
+  // Sitemaps are fetched breadth first, so that the limits cut off the deepest ones.
+  let mut queue: VecDeque<(String, usize)> = sitemaps.into_iter().map(|url| (url, 1)).collect();
+  let mut fetched: HashSet<String> = HashSet::new();
+  fetched.insert(sitemap_url.as_str().to_string());
+
+  while let Some((sitemap, depth)) = queue.pop_front() {
+    if fetched.len() >= MAX_SITEMAP_FILES {
+      println!("[Crawler] Fetched the most sitemaps of {} allowed, skipping the rest", host);
+
+      break;
+    }
+
+    if depth > MAX_SITEMAP_DEPTH || !fetched.insert(sitemap.clone()) {
+      continue;
+    }
+
+    // Nested sitemaps which fail to load are left out, the others are still used.
+    let body = match Url::parse(&sitemap).ok().and_then(|url| fetch_file(fetcher, &url)) {
+      Some(body) => body,
+      None => continue,
+    };
+    let (nested_urls, nested_sitemaps) = parse(&body, host);
+    urls.extend(nested_urls);
+    queue.extend(nested_sitemaps.into_iter().map(|url| (url, depth + 1)));
+  }
+
+  Some(urls)
+}
+
+/// Fetches a single sitemap file, decompressing it if it is gzipped. Sitemaps named `.xml.gz` are
+/// usually served as gzip files rather than with a gzip content encoding, so they are recognized
+/// by their contents. Files larger than `MAX_SITEMAP_BYTES`, compressed or not, are cut there.
+fn fetch_file(fetcher: &dyn Fetcher, url: &Url) -> Option<String> {
+  let (mut req, _) = fetcher.get(url)?;
+
+  if !req.status().is_success() {
+    return None;
+  }
+
+  let (bytes, is_cut) = read_limited(&mut req, MAX_SITEMAP_BYTES)?;
+  let (bytes, is_cut_decompressed) = decompress(bytes)?;
+
+  if is_cut || is_cut_decompressed {
+    println!("[Crawler] Reading only the first {} bytes of the sitemap {}", MAX_SITEMAP_BYTES, url);
+  }
+
+  Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Decompresses given bytes if they are gzipped, returning the other bytes as they are, together
+/// with whether they had to be cut at `MAX_SITEMAP_BYTES`. Returns None if the gzip stream is
+/// corrupted.
+fn decompress(bytes: Vec<u8>) -> Option<(Vec<u8>, bool)> {
+  if !bytes.starts_with(&[0x1f, 0x8b]) {
+    return Some((bytes, false));
+  }
+
+  read_limited(GzDecoder::new(&bytes[..]), MAX_SITEMAP_BYTES)
+}
+
+/// Reads at most given number of bytes, together with whether there was more to read.
+fn read_limited(reader: impl Read, limit: u64) -> Option<(Vec<u8>, bool)> {
+  let mut bytes = vec!();
+  // One more byte tells whether the limit cut the rest off.
+  reader.take(limit + 1).read_to_end(&mut bytes).ok()?;
+
+  let is_cut = bytes.len() as u64 > limit;
+  bytes.truncate(limit as usize);
+
+  Some((bytes, is_cut))
+}
+
+/// Collects the contents of all `<loc>` elements in the sitemap document which are valid urls on
+/// given host name. Returns the urls of pages, listed in `<url>` elements, together with the urls
+/// of sitemaps, listed in `<sitemap>` elements of sitemap indexes.
+pub fn parse(body: &str, host: &str) -> (HashSet<String>, Vec<String>) {
+  let doc = match roxmltree::Document::parse(body) {
+    Ok(doc) => doc,
+    Err(_) => return (HashSet::new(), vec!()),
+  };
+
+  let mut urls = HashSet::new();
+  let mut sitemaps = vec!();
+  let locs = doc.descendants().filter(|node| node.tag_name().name() == "loc");
+
+  for node in locs {
+    let url = match node.text().and_then(|text| Url::parse(text.trim()).ok()) {
+      Some(url) if url.host_str() == Some(host) => url.as_str().to_string(),
+      _ => continue,
+    };
+
+    match node.parent_element().map(|parent| parent.tag_name().name()) {
+      Some("sitemap") => sitemaps.push(url),
+      _ => {
+        urls.insert(url);
+      },
+    }
+  }
+
+  (urls, sitemaps)
+}
+
+/// Renders a sitemap document listing given urls, each with the time it was last crawled at if
+/// it has been crawled.
+pub fn render_urlset(urls: &[(&str, Option<DateTime<Utc>>)]) -> String {
+  let mut xml = String::from(concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+  ));
+
+  for (url, lastmod) in urls {
+    xml.push_str("<url><loc>");
+    xml.push_str(&escape(url));
+    xml.push_str("</loc>");
+
+    if let Some(lastmod) = lastmod {
+      xml.push_str("<lastmod>");
+      xml.push_str(&lastmod.to_rfc3339_opts(SecondsFormat::Secs, true));
+      xml.push_str("</lastmod>");
+    }
+
+    xml.push_str("</url>\n");
+  }
+
+  xml.push_str("</urlset>\n");
+
+  xml
+}
+
+/// Renders a sitemap index document pointing to given sitemap urls.
+pub fn render_index(sitemaps: &[String]) -> String {
+  let mut xml = String::from(concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+  ));
+
+  for sitemap in sitemaps {
+    xml.push_str("<sitemap><loc>");
+    xml.push_str(&escape(sitemap));
+    xml.push_str("</loc></sitemap>\n");
+  }
+
+  xml.push_str("</sitemapindex>\n");
+
+  xml
+}
+
+/// Escapes the characters which have a special meaning in XML.
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use super::{decompress, fetch, parse, read_limited};
+  use super::super::fetch::MockFetcher;
+  use flate2::Compression;
+  use flate2::write::GzEncoder;
+  use std::io::Write;
+
+  #[test]
+  fn test_parse_filters_foreign_hosts() {
+    let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+      <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+        <url><loc>https://github.com/about</loc></url>
+        <url><loc> https://github.com/pricing </loc></url>
+        <url><loc>https://gitlab.com/about</loc></url>
+      </urlset>"#;
+
+    let (urls, sitemaps) = parse(body, "github.com");
+
+    assert_eq!(urls.len(), 2);
+    assert!(urls.contains("https://github.com/about"));
+    assert!(urls.contains("https://github.com/pricing"));
+    assert!(sitemaps.is_empty());
+  }
+
+  #[test]
+  fn test_parse_malformed_sitemap() {
+    assert!(parse("<urlset><url>", "github.com").0.is_empty());
+  }
+
+  #[test]
+  fn test_fetch_sitemap_index() {
+    let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+        <sitemap><loc>https://github.com/sitemap-posts.xml</loc></sitemap>
+        <sitemap><loc>https://github.com/sitemap-nested.xml</loc></sitemap>
+        <sitemap><loc>https://gitlab.com/sitemap.xml</loc></sitemap>
+      </sitemapindex>"#;
+    let nested = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+        <sitemap><loc>https://github.com/sitemap-team.xml</loc></sitemap>
+        <sitemap><loc>https://github.com/sitemap.xml</loc></sitemap>
+      </sitemapindex>"#;
+    let urlset = |url: &str| format!("<urlset><url><loc>{}</loc></url></urlset>", url);
+    let fetcher = MockFetcher::default()
+      .page("https://github.com/sitemap.xml", index)
+      .page("https://github.com/sitemap-posts.xml", &urlset("https://github.com/blog"))
+      .page("https://github.com/sitemap-nested.xml", nested)
+      .page("https://github.com/sitemap-team.xml", &urlset("https://github.com/team"));
+
+    let urls = fetch(&fetcher, &Url::parse("https://github.com/").unwrap()).unwrap();
+
+    let mut urls: Vec<String> = urls.into_iter().collect();
+    urls.sort();
+    assert_eq!(urls, vec!("https://github.com/blog", "https://github.com/team"));
+    // Sitemaps of other hosts are never fetched, nor are sitemaps fetched twice.
+    let requested = fetcher.requested();
+    assert!(requested.iter().all(|url| url.starts_with("https://github.com/")));
+    assert_eq!(requested.len(), 4);
+  }
+
+  #[test]
+  fn test_decompress() {
+    let sitemap = b"<urlset><url><loc>https://github.com/</loc></url></urlset>".to_vec();
+    let mut encoder = GzEncoder::new(vec!(), Compression::default());
+    encoder.write_all(&sitemap).unwrap();
+
+    assert_eq!(decompress(encoder.finish().unwrap()), Some((sitemap.clone(), false)));
+    assert_eq!(decompress(sitemap.clone()), Some((sitemap.clone(), false)));
+    assert_eq!(decompress(vec!(0x1f, 0x8b, 0)), None);
+
+    assert_eq!(read_limited(&sitemap[..], 8), Some((sitemap[..8].to_vec(), true)));
+    assert_eq!(read_limited(&sitemap[..], sitemap.len() as u64), Some((sitemap, false)));
+  }
+}
@@ -0,0 +1,175 @@
+use super::Database;
+use chrono::{DateTime, Utc};
+use super::database::{Domain, UrlRecord};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, Sender};
+use std::collections::{BTreeSet, HashMap};
+
+/// A durable store of the crawled domains. The database in memory stays the source of truth
+/// while the service runs, the storage keeps a copy of it which outlives the process and can be
+/// read by other applications. Only the urls of domains, their history and the tags of domains
+/// are stored, pages and crawl sessions are kept in memory only.
+pub trait Storage: Send {
+  /// Reads every stored domain, used to fill the database when the service starts.
+  fn load(&self) -> Result<HashMap<String, Domain>, String>;
+
+  /// Replaces the stored copy of given domain.
+  fn save(&self, domain: &str, snapshot: &Snapshot) -> Result<(), String>;
+
+  /// Removes given domain from the storage, if it is there.
+  fn remove(&self, domain: &str) -> Result<(), String>;
+}
+
+/// The channel on which routes and the crawler report domains they changed, disconnected until
+/// a storage is configured.
+pub type Changes = Arc<Mutex<Option<Sender<String>>>>;
+
+/// The part of a domain which is stored.
+pub struct Snapshot {
+  pub crawled_at: Option<DateTime<Utc>>,
+  pub tags: Vec<String>,
+  /// The unique urls of the domain with their history, if the crawler has one.
+  pub urls: Vec<(String, Option<UrlRecord>)>,
+}
+
+impl Snapshot {
+  pub fn of(domain: &Domain) -> Snapshot {
+    Snapshot {
+      crawled_at: domain.crawled_at,
+      tags: domain.tags.iter().cloned().collect(),
+      urls: domain.urls.iter().map(|url| (url.clone(), domain.records.get(url).cloned())).collect(),
+    }
+  }
+
+  /// Creates the record of a domain as the crawler would have left it.
+  pub fn into_domain(self) -> Domain {
+    let mut domain = Domain {
+      crawled_at: self.crawled_at,
+      tags: self.tags.into_iter().collect(),
+      ..Default::default()
+    };
+
+    for (url, record) in self.urls {
+      if let Some(record) = record {
+        domain.records.insert(url.clone(), record);
+      }
+      domain.urls.insert(url);
+    }
+
+    domain
+  }
+}
+
+/// Reports that given domain was changed, created or removed, so that the storage catches up
+/// with it. Does nothing if there is no storage.
+pub fn changed(changes: &Changes, domain: &str) {
+  // TODO: Error handling the mutex.
+  if let Some(producer) = changes.lock().unwrap().as_ref() {
+    // The writer only stops when the service does.
+    let _ = producer.send(domain.to_string());
+  }
+}
+
+/// Writes changed domains to the storage. The domains are read from the database at the time of
+/// writing rather than sent along with the changes, so that the storage always ends up with the
+/// latest version of a domain even when it changes faster than it can be written.
+pub fn listen(storage: Box<dyn Storage>, db: Database, consumer: Receiver<String>) {
+  while let Ok(domain) = consumer.recv() {
+    // Domains which changed several times while the previous write was running are only
+    // written once.
+    let mut domains = BTreeSet::new();
+    domains.insert(domain);
+    domains.extend(consumer.try_iter());
+
+    for domain in domains {
+      let snapshot = match db.lock() {
+        Ok(db) => db.get(&domain).map(Snapshot::of),
+        Err(_) => {
+          println!("[Storage] Error during database locking");
+
+          return;
+        },
+      };
+
+      let written = match snapshot {
+        Some(snapshot) => storage.save(&domain, &snapshot),
+        None => storage.remove(&domain),
+      };
+
+      if let Err(e) = written {
+        println!("[Storage] Error during writing of {}: {}", domain, e);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{changed, listen, Changes, Snapshot, Storage};
+  use super::super::Database;
+  use super::super::database::{Domain, UrlRecord};
+  use chrono::Utc;
+  use std::thread;
+  use std::sync::{Arc, Mutex};
+  use std::sync::mpsc::channel;
+  use std::collections::HashMap;
+
+  /// Records the writes instead of carrying them out.
+  struct Recorder(Arc<Mutex<Vec<String>>>);
+
+  impl Storage for Recorder {
+    fn load(&self) -> Result<HashMap<String, Domain>, String> {
+      Ok(HashMap::new())
+    }
+
+    fn save(&self, domain: &str, snapshot: &Snapshot) -> Result<(), String> {
+      self.0.lock().unwrap().push(format!("save {} {}", domain, snapshot.urls.len()));
+
+      Ok(())
+    }
+
+    fn remove(&self, domain: &str) -> Result<(), String> {
+      self.0.lock().unwrap().push(format!("remove {}", domain));
+
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_snapshot() {
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    domain.urls.insert(String::from("https://github.com/a"));
+    domain.records.insert(String::from("https://github.com/a"), UrlRecord::new(Utc::now(), 1));
+    domain.tags.insert(String::from("code"));
+
+    let restored = Snapshot::of(&domain).into_domain();
+
+    assert_eq!(restored.urls, domain.urls);
+    assert_eq!(restored.records.len(), 1);
+    assert_eq!(restored.records["https://github.com/a"].depth, 1);
+    assert_eq!(restored.tags, domain.tags);
+  }
+
+  #[test]
+  fn test_listen() {
+    let db: Database = Default::default();
+    let mut domain = Domain::default();
+    domain.urls.insert(String::from("https://github.com"));
+    db.lock().unwrap().insert("github.com".to_string(), domain);
+
+    let (producer, consumer) = channel::<String>();
+    let changes: Changes = Arc::new(Mutex::new(Some(producer)));
+    changed(&changes, "github.com");
+    changed(&changes, "github.com");
+    changed(&changes, "gitlab.com");
+    // Stops the writer once it has written the changes.
+    changes.lock().unwrap().take();
+
+    let writes = Arc::default();
+    let storage = Box::new(Recorder(Arc::clone(&writes)));
+    thread::spawn(move || listen(storage, db, consumer)).join().unwrap();
+
+    assert_eq!(*writes.lock().unwrap(), vec!("save github.com 1", "remove gitlab.com"));
+  }
+}
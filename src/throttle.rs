@@ -0,0 +1,260 @@
+use std::thread;
+use std::sync::Mutex;
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+use openssl::rand::rand_bytes;
+
+/// Limits how fast the crawler downloads. Every chunk of a body pushes the time the next one may
+/// be read further into the future by as long as it takes to transfer its bytes at the allowed
+/// rate, so the crawler never downloads faster than that for longer than a chunk takes.
+pub struct Throttle {
+  /// How many bytes per second may be downloaded, zero for no limit.
+  bytes_per_second: usize,
+  /// When the next chunk may be read.
+  available_at: Option<Instant>,
+}
+
+impl Throttle {
+  pub fn new(bytes_per_second: usize) -> Throttle {
+    Throttle { bytes_per_second, available_at: None }
+  }
+
+  /// Records downloaded bytes and calculates how long to wait before downloading again.
+  fn delay(&mut self, bytes: usize, now: Instant) -> Duration {
+    if self.bytes_per_second == 0 {
+      return Duration::from_secs(0);
+    }
+
+    // Time the crawler spent idle does not accumulate into a burst allowance.
+    let start = match self.available_at {
+      Some(available_at) if available_at > now => available_at,
+      _ => now,
+    };
+    let available_at = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+    self.available_at = Some(available_at);
+
+    available_at - now
+  }
+}
+
+/// Reads a body and charges every chunk to the throttle as soon as it arrives, so that a large
+/// body is slowed down while it downloads rather than only after it has been downloaded. The
+/// throttle is shared by all bodies downloaded at the same time, each of them waits for the
+/// chunks charged before its own.
+pub struct Throttled<'a, R> {
+  body: R,
+  throttle: &'a Mutex<Throttle>,
+}
+
+impl<'a, R: Read> Throttled<'a, R> {
+  pub fn new(body: R, throttle: &'a Mutex<Throttle>) -> Throttled<'a, R> {
+    Throttled { body, throttle }
+  }
+}
+
+impl<R: Read> Read for Throttled<'_, R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = self.body.read(buf)?;
+
+    // The throttle is not locked while waiting, so that other bodies can queue their chunks.
+    // A poisoned throttle no longer limits anything.
+    let delay = match self.throttle.lock() {
+      Ok(mut throttle) => throttle.delay(read, Instant::now()),
+      Err(_) => Duration::from_secs(0),
+    };
+
+    if delay > Duration::from_secs(0) {
+      thread::sleep(delay);
+    }
+
+    Ok(read)
+  }
+}
+
+/// Adapts how fast a single crawl fetches the pages of its host to how the host copes. Rounds in
+/// which pages fail with server errors or respond much slower than the host did while it was
+/// healthy halve the number of pages fetched in parallel and add a growing delay between rounds.
+/// Healthy rounds first shrink the delay and then fetch one more page in parallel, up to the
+/// configured concurrency.
+pub struct AdaptiveRate {
+  max_concurrency: usize,
+  concurrency: usize,
+  /// How long to wait before fetching the next round.
+  delay: Duration,
+  /// The average time to the first byte of the healthy rounds, weighted towards recent ones.
+  baseline_ms: Option<f64>,
+}
+
+/// A round is slow if its pages take this many times longer to respond than the baseline.
+const SLOW_FACTOR: f64 = 2.0;
+
+/// Rounds slower than the baseline by less than this are never slow, so that hosts which
+/// respond in a few milliseconds are not slowed down by noise.
+const MIN_SLOWDOWN_MS: f64 = 100.0;
+
+/// The delay the first slow round adds.
+const MIN_DELAY: Duration = Duration::from_millis(250);
+
+/// The delay between rounds never grows beyond this.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+impl AdaptiveRate {
+  pub fn new(max_concurrency: usize) -> AdaptiveRate {
+    let max_concurrency = max_concurrency.max(1);
+
+    AdaptiveRate {
+      max_concurrency,
+      concurrency: max_concurrency,
+      delay: Duration::from_secs(0),
+      baseline_ms: None,
+    }
+  }
+
+  /// How many pages the next round fetches in parallel.
+  pub fn concurrency(&self) -> usize {
+    self.concurrency
+  }
+
+  /// How long to wait before the next round.
+  pub fn delay(&self) -> Duration {
+    self.delay
+  }
+
+  /// Records how the pages of a round responded, with the times to the first byte of the pages
+  /// which responded without a server error in milliseconds and the number of pages which did
+  /// not respond or responded with a server error.
+  pub fn record(&mut self, latencies: &[u64], errors: usize) {
+    let average = if latencies.is_empty() {
+      None
+    } else {
+      Some(latencies.iter().sum::<u64>() as f64 / latencies.len() as f64)
+    };
+    let is_slow = match (average, self.baseline_ms) {
+      (Some(average), Some(baseline)) => {
+        average > baseline * SLOW_FACTOR && average > baseline + MIN_SLOWDOWN_MS
+      },
+      _ => false,
+    };
+
+    if errors > 0 || is_slow {
+      self.slow_down();
+
+      return;
+    }
+
+    if let Some(average) = average {
+      self.baseline_ms = Some(self.baseline_ms.map_or(average, |baseline| {
+        baseline * 0.8 + average * 0.2
+      }));
+    }
+
+    if self.delay > Duration::from_secs(0) {
+      self.delay /= 2;
+
+      if self.delay < MIN_DELAY {
+        self.delay = Duration::from_secs(0);
+      }
+    } else if self.concurrency < self.max_concurrency {
+      self.concurrency += 1;
+    }
+  }
+
+  /// Halves the number of pages fetched in parallel and doubles the delay between rounds.
+  pub fn slow_down(&mut self) {
+    self.concurrency = (self.concurrency / 2).max(1);
+    self.delay = (self.delay * 2).max(MIN_DELAY).min(MAX_DELAY);
+  }
+}
+
+/// A random delay of at most given length, so that requests don't go out at perfectly regular
+/// times or all at once.
+pub fn jitter(max: Duration) -> Duration {
+  let max_ms = max.as_millis() as u64;
+
+  if max_ms == 0 {
+    return Duration::from_secs(0);
+  }
+
+  let mut bytes = [0; 8];
+  // Without randomness, the requests just are not delayed.
+  if rand_bytes(&mut bytes).is_err() {
+    return Duration::from_secs(0);
+  }
+
+  Duration::from_millis(u64::from_le_bytes(bytes) % (max_ms + 1))
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::Read;
+  use std::sync::Mutex;
+  use super::{jitter, AdaptiveRate, Throttle, Throttled};
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn test_delay() {
+    let now = Instant::now();
+    let mut throttle = Throttle::new(1000);
+
+    assert_eq!(throttle.delay(500, now), Duration::from_millis(500));
+    // The second download is queued behind the first one.
+    assert_eq!(throttle.delay(1000, now), Duration::from_millis(1500));
+    // After being idle for a long time, the crawler still can't burst.
+    assert_eq!(throttle.delay(1000, now + Duration::from_secs(60)), Duration::from_secs(1));
+  }
+
+  #[test]
+  fn test_unlimited() {
+    let mut throttle = Throttle::new(0);
+
+    assert_eq!(throttle.delay(1_000_000, Instant::now()), Duration::from_secs(0));
+  }
+
+  #[test]
+  fn test_throttled() {
+    let throttle = Mutex::new(Throttle::new(1000));
+    let body = [b'a'; 50];
+    let started_at = Instant::now();
+
+    let mut read = vec!();
+    Throttled::new(&body[..], &throttle).read_to_end(&mut read).unwrap();
+
+    // The 50 bytes take 50 milliseconds at 1000 bytes per second.
+    assert_eq!(read.len(), 50);
+    assert!(started_at.elapsed() >= Duration::from_millis(50));
+  }
+
+  #[test]
+  fn test_jitter() {
+    assert_eq!(jitter(Duration::from_secs(0)), Duration::from_secs(0));
+
+    let delays: Vec<Duration> = (0..100).map(|_| jitter(Duration::from_millis(50))).collect();
+    assert!(delays.iter().all(|delay| *delay <= Duration::from_millis(50)));
+    assert!(delays.iter().any(|delay| *delay != delays[0]));
+  }
+
+  #[test]
+  fn test_adaptive_rate() {
+    let mut rate = AdaptiveRate::new(4);
+    rate.record(&[100, 120, 80, 100], 0);
+    assert_eq!((rate.concurrency(), rate.delay()), (4, Duration::from_secs(0)));
+
+    // Latencies climbing well above the baseline slow the crawl down, and so do errors.
+    rate.record(&[400, 500], 0);
+    assert_eq!((rate.concurrency(), rate.delay()), (2, Duration::from_millis(250)));
+    rate.record(&[100], 1);
+    assert_eq!((rate.concurrency(), rate.delay()), (1, Duration::from_millis(500)));
+    // Slightly slower responses are not a reason to slow down.
+    rate.record(&[150], 0);
+    assert_eq!((rate.concurrency(), rate.delay()), (1, Duration::from_millis(250)));
+
+    // Once the host recovers, the delay goes away first, then the concurrency grows back.
+    rate.record(&[100], 0);
+    assert_eq!((rate.concurrency(), rate.delay()), (1, Duration::from_secs(0)));
+    for _ in 0..5 {
+      rate.record(&[100], 0);
+    }
+    assert_eq!((rate.concurrency(), rate.delay()), (4, Duration::from_secs(0)));
+  }
+
+}
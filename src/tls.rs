@@ -0,0 +1,108 @@
+use url::Url;
+use super::fetch;
+use super::fetch::DnsCache;
+use super::tunnel;
+use std::time::Duration;
+use super::config::Config;
+use chrono::{DateTime, Utc};
+use openssl::asn1::Asn1Time;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+
+/// Handshakes which take longer than this are given up on.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Raw OpenSSL verification error codes, see `X509_V_ERR_*` in `x509_vfy.h`.
+const X509_V_ERR_CERT_NOT_YET_VALID: i32 = 9;
+const X509_V_ERR_CERT_HAS_EXPIRED: i32 = 10;
+const X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT: i32 = 18;
+const X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN: i32 = 19;
+const X509_V_ERR_HOSTNAME_MISMATCH: i32 = 62;
+
+/// What the crawler found out about the certificate a host presented.
+#[derive(Clone)]
+pub struct Certificate {
+  /// When the certificate was inspected.
+  pub checked_at: DateTime<Utc>,
+  /// The expiry date of the certificate as formatted by OpenSSL.
+  pub expires: String,
+  /// How many whole days remain until the certificate expires. Negative if it has expired.
+  pub days_remaining: i32,
+  /// Problems with the certificate, such as `expired` or `hostname_mismatch`. Empty if the
+  /// certificate is valid and does not expire soon.
+  pub issues: Vec<String>,
+  /// The verification error as described by OpenSSL, if verification failed.
+  pub error: Option<String>,
+}
+
+impl Certificate {
+  /// Whether clients verifying certificates would refuse to connect to the host. A certificate
+  /// which only expires soon is still valid.
+  pub fn is_valid(&self) -> bool {
+    self.error.is_none() && self.days_remaining >= 0
+  }
+}
+
+/// Connects to the https url's host and inspects the certificate it presents. The handshake
+/// does not abort on invalid certificates so that all problems can be recorded. Returns None if
+/// the url is not https or the host could not be reached. Like fetches, only the addresses the
+/// host was checked against are connected to, as resolved through given cache.
+pub fn inspect(config: &Config, dns: &DnsCache, url: &Url) -> Option<Certificate> {
+  if url.scheme() != "https" || fetch::is_denied(config, url) {
+    return None;
+  }
+
+  let addresses = dns.lookup(url);
+  if fetch::has_forbidden_address(config, url, &addresses) {
+    return None;
+  }
+
+  let host = url.host_str()?;
+  let stream = tunnel::connect_to(url, &addresses)?;
+  stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).ok()?;
+  stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT)).ok()?;
+
+  let mut connector = SslConnector::builder(SslMethod::tls()).ok()?;
+  connector.set_verify(SslVerifyMode::NONE);
+  let stream = connector.build().connect(host, stream).ok()?;
+
+  let certificate = stream.ssl().peer_certificate()?;
+  let now = Asn1Time::days_from_now(0).ok()?;
+  let remaining = now.diff(certificate.not_after()).ok()?;
+  let is_expired = remaining.days < 0 || remaining.secs < 0;
+
+  let verify_result = stream.ssl().verify_result();
+  let mut issues: Vec<String> = vec!();
+
+  match verify_result.as_raw() {
+    0 => (),
+    X509_V_ERR_CERT_HAS_EXPIRED => (),
+    X509_V_ERR_CERT_NOT_YET_VALID => issues.push("not_yet_valid".to_string()),
+    X509_V_ERR_HOSTNAME_MISMATCH => issues.push("hostname_mismatch".to_string()),
+    X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT | X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN => {
+      issues.push("self_signed".to_string())
+    },
+    _ => issues.push("untrusted".to_string()),
+  }
+
+  // Expiry is checked on the dates directly, as OpenSSL reports only one verification error and
+  // an expired certificate might be hidden behind another problem.
+  if is_expired {
+    issues.insert(0, "expired".to_string());
+  } else if remaining.days < config.cert_expiry_warning_days as i32 {
+    issues.push("expires_soon".to_string());
+  }
+
+  let error = if verify_result.as_raw() == 0 {
+    None
+  } else {
+    Some(verify_result.error_string().to_string())
+  };
+
+  Some(Certificate {
+    checked_at: Utc::now(),
+    expires: certificate.not_after().to_string(),
+    days_remaining: if is_expired { remaining.days.min(-1) } else { remaining.days },
+    issues,
+    error,
+  })
+}
@@ -0,0 +1,308 @@
+use url::Url;
+use std::collections::{BTreeMap, HashMap};
+
+/// A branch of urls is a calendar trap once a crawl finds more than this many urls which differ
+/// only in their dates, as many as five years of monthly archives.
+const MAX_DATED_URLS: usize = 60;
+
+/// A path is a combinatorial trap once a crawl finds more than this many urls of it which differ
+/// only in their queries of several parameters, such as every combination of listing filters.
+const MAX_QUERY_COMBINATIONS: usize = 100;
+
+/// A path is a trap if one of its segments occurs this many times, e.g. `/a/b/a/b/a/b` of
+/// relative links which resolve against the page deeper every time.
+const MAX_SEGMENT_REPEATS: usize = 3;
+
+/// How a branch of a host seems to generate urls without end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrapKind {
+  /// Calendars linking to the next day, month or year forever.
+  Calendar,
+  /// Filters and sorts whose parameters combine into ever more queries.
+  CombinatorialParameters,
+  /// Paths which repeat their own segments.
+  RepeatingPath,
+}
+
+impl TrapKind {
+  /// The name of the kind as used in responses.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      TrapKind::Calendar => "calendar",
+      TrapKind::CombinatorialParameters => "combinatorial_parameters",
+      TrapKind::RepeatingPath => "repeating_path",
+    }
+  }
+}
+
+/// A branch a crawl stopped expanding as it seemed to be an infinite url space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trap {
+  /// The urls of the branch with their varying parts replaced, e.g.
+  /// `https://example.com/calendar/{year}/{month}`.
+  pub pattern: String,
+  pub kind: TrapKind,
+  /// How many urls of the branch the crawl found but did not queue.
+  pub skipped: usize,
+}
+
+/// Watches the urls a crawl finds for infinite url spaces, such as calendars which link to the
+/// next month forever or filters whose parameters combine into ever more pages. Once a branch
+/// looks like one, the rest of its urls are not queued, so that the crawl spends its pages on the
+/// rest of the host. The urls queued before the branch was suspected are still fetched.
+#[derive(Default)]
+pub struct Traps {
+  /// The dated urls found so far, keyed by their patterns.
+  dated: HashMap<String, usize>,
+  /// The urls with queries of several parameters found so far, keyed by their paths.
+  queried: HashMap<String, usize>,
+  /// The suspected traps keyed by their patterns.
+  suspected: BTreeMap<String, Trap>,
+}
+
+impl Traps {
+  /// Records given url found by the crawl and checks whether it is worth queuing, which it is
+  /// unless it belongs to a suspected trap.
+  pub fn admit(&mut self, url: &str) -> bool {
+    let parsed = match Url::parse(url) {
+      Ok(parsed) => parsed,
+      Err(_) => return true,
+    };
+    if let Some(pattern) = repeating_pattern(&parsed) {
+      return self.suspect(pattern, TrapKind::RepeatingPath);
+    }
+
+    if let Some(pattern) = dated_pattern(&parsed) {
+      if self.is_suspected(&pattern) {
+        return self.suspect(pattern, TrapKind::Calendar);
+      }
+
+      let count = self.dated.entry(pattern.clone()).or_insert(0);
+      *count += 1;
+
+      if *count > MAX_DATED_URLS {
+        return self.suspect(pattern, TrapKind::Calendar);
+      }
+    }
+
+    if parsed.query_pairs().count() >= 2 {
+      let mut path = parsed.clone();
+      path.set_query(None);
+      path.set_fragment(None);
+      let pattern = format!("{}?{{query}}", path);
+
+      if self.is_suspected(&pattern) {
+        return self.suspect(pattern, TrapKind::CombinatorialParameters);
+      }
+
+      let count = self.queried.entry(pattern.clone()).or_insert(0);
+      *count += 1;
+
+      if *count > MAX_QUERY_COMBINATIONS {
+        return self.suspect(pattern, TrapKind::CombinatorialParameters);
+      }
+    }
+
+    true
+  }
+
+  /// The traps suspected so far in the alphabetical order of their patterns.
+  pub fn suspected(&self) -> Vec<Trap> {
+    self.suspected.values().cloned().collect()
+  }
+
+  fn is_suspected(&self, pattern: &str) -> bool {
+    self.suspected.contains_key(pattern)
+  }
+
+  /// Records a url of given trap which is not queued. The first one reports the trap.
+  fn suspect(&mut self, pattern: String, kind: TrapKind) -> bool {
+    let trap = self.suspected.entry(pattern.clone()).or_insert_with(|| {
+      println!("[Crawler] Suspecting an infinite url space at {}", pattern);
+
+      Trap { pattern, kind, skipped: 0 }
+    });
+    trap.skipped += 1;
+
+    false
+  }
+}
+
+/// The url cut where one of the segments of its path occurs for the `MAX_SEGMENT_REPEATS`th time,
+/// or None if none occurs that often. Deeper urls of the same loop share the pattern.
+fn repeating_pattern(url: &Url) -> Option<String> {
+  let segments: Vec<&str> = url.path().split('/').filter(|segment| !segment.is_empty()).collect();
+  let mut seen: HashMap<&str, usize> = HashMap::new();
+
+  for (index, segment) in segments.iter().enumerate() {
+    let count = seen.entry(*segment).or_insert(0);
+    *count += 1;
+
+    if *count >= MAX_SEGMENT_REPEATS {
+      let origin = url.origin().ascii_serialization();
+
+      return Some(format!("{}/{}/...", origin, segments[..index].join("/")));
+    }
+  }
+
+  None
+}
+
+/// The url with its dates replaced by placeholders, or None if it has no dates. Dates are years
+/// followed by months and optionally days as path segments, e.g. `/2024/05/17`, or written out
+/// in a single segment or query value, e.g. `2024-05` or `2024-05-17`.
+fn dated_pattern(url: &Url) -> Option<String> {
+  let segments: Vec<&str> = url.path().split('/').collect();
+  let mut pattern: Vec<String> = vec!();
+  let mut is_dated = false;
+  let mut index = 0;
+
+  while index < segments.len() {
+    let segment = segments[index];
+    let has_month = segments.get(index + 1).is_some_and(|month| is_number_within(month, 1, 12));
+
+    if is_number_within(segment, 1900, 2100) && has_month {
+      pattern.push(String::from("{year}"));
+      pattern.push(String::from("{month}"));
+      index += 2;
+
+      if segments.get(index).is_some_and(|day| is_number_within(day, 1, 31)) {
+        pattern.push(String::from("{day}"));
+        index += 1;
+      }
+
+      is_dated = true;
+
+      continue;
+    }
+
+    if is_date(segment) {
+      pattern.push(String::from("{date}"));
+      is_dated = true;
+    } else {
+      pattern.push(segment.to_string());
+    }
+
+    index += 1;
+  }
+
+  let mut query: Vec<String> = vec!();
+  for (name, value) in url.query_pairs() {
+    if is_date(&value) {
+      query.push(format!("{}={{date}}", name));
+      is_dated = true;
+    } else {
+      query.push(format!("{}={}", name, value));
+    }
+  }
+
+  if !is_dated {
+    return None;
+  }
+
+  let origin = url.origin().ascii_serialization();
+  let pattern = format!("{}{}", origin, pattern.join("/"));
+
+  if query.is_empty() {
+    Some(pattern)
+  } else {
+    Some(format!("{}?{}", pattern, query.join("&")))
+  }
+}
+
+/// Checks whether given text is a number between given bounds, written with at most as many
+/// digits as the upper bound.
+fn is_number_within(text: &str, min: u32, max: u32) -> bool {
+  !text.is_empty()
+    && text.len() <= max.to_string().len()
+    && text.chars().all(|c| c.is_ascii_digit())
+    && text.parse::<u32>().is_ok_and(|number| number >= min && number <= max)
+}
+
+/// Checks whether given text is a year and a month, optionally with a day, separated by dashes.
+fn is_date(text: &str) -> bool {
+  let parts: Vec<&str> = text.split('-').collect();
+
+  match parts.as_slice() {
+    [year, month] => is_number_within(year, 1900, 2100) && is_number_within(month, 1, 12),
+    [year, month, day] => {
+      is_number_within(year, 1900, 2100) && is_number_within(month, 1, 12)
+        && is_number_within(day, 1, 31)
+    },
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use url::Url;
+  use super::{dated_pattern, TrapKind, Traps, MAX_DATED_URLS, MAX_QUERY_COMBINATIONS};
+
+  #[test]
+  fn test_dated_pattern() {
+    let pattern = |url: &str| dated_pattern(&Url::parse(url).unwrap());
+
+    assert_eq!(pattern("https://github.com/calendar/2024/05"), Some(String::from(
+      "https://github.com/calendar/{year}/{month}",
+    )));
+    assert_eq!(pattern("https://github.com/2024/05/17/release"), Some(String::from(
+      "https://github.com/{year}/{month}/{day}/release",
+    )));
+    assert_eq!(pattern("https://github.com/events?day=2024-05-17&view=week"), Some(String::from(
+      "https://github.com/events?day={date}&view=week",
+    )));
+    assert_eq!(pattern("https://github.com/issues/2024/99"), None);
+    assert_eq!(pattern("https://github.com/about"), None);
+  }
+
+  #[test]
+  fn test_calendar_trap() {
+    let mut traps = Traps::default();
+
+    for month in 0..MAX_DATED_URLS {
+      let url = format!("https://github.com/calendar/{}/{}", 2000 + month / 12, month % 12 + 1);
+      assert!(traps.admit(&url));
+    }
+
+    assert!(!traps.admit("https://github.com/calendar/2099/01"));
+    assert!(!traps.admit("https://github.com/calendar/2099/02"));
+    // Other branches of the host are still queued, dated or not.
+    assert!(traps.admit("https://github.com/2024/05/17/release"));
+    assert!(traps.admit("https://github.com/about"));
+
+    let suspected = traps.suspected();
+    assert_eq!(suspected.len(), 1);
+    assert_eq!(suspected[0].pattern, "https://github.com/calendar/{year}/{month}");
+    assert_eq!(suspected[0].kind, TrapKind::Calendar);
+    assert_eq!(suspected[0].skipped, 2);
+  }
+
+  #[test]
+  fn test_combinatorial_trap() {
+    let mut traps = Traps::default();
+
+    for page in 0..MAX_QUERY_COMBINATIONS {
+      assert!(traps.admit(&format!("https://github.com/search?q=rust&page={}", page)));
+    }
+
+    assert!(!traps.admit("https://github.com/search?q=go&sort=stars"));
+    // Queries of a single parameter never combine.
+    assert!(traps.admit("https://github.com/search?q=go"));
+    assert_eq!(traps.suspected()[0].kind, TrapKind::CombinatorialParameters);
+  }
+
+  #[test]
+  fn test_repeating_path_trap() {
+    let mut traps = Traps::default();
+
+    assert!(traps.admit("https://github.com/docs/api/docs/api"));
+    assert!(!traps.admit("https://github.com/docs/api/docs/api/docs/api"));
+    assert!(!traps.admit("https://github.com/docs/api/docs/api/docs/api/docs"));
+
+    let suspected = traps.suspected();
+    assert_eq!(suspected.len(), 1);
+    assert_eq!(suspected[0].pattern, "https://github.com/docs/api/docs/api/...");
+    assert_eq!(suspected[0].kind, TrapKind::RepeatingPath);
+    assert_eq!(suspected[0].skipped, 2);
+  }
+}
@@ -0,0 +1,251 @@
+use rocket::State;
+use super::fetch;
+use super::Database;
+use serde::Serialize;
+use super::errors::ApiError;
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use rocket_contrib::json::Json;
+use super::database::{Domain, Page, UrlRecord};
+use super::routes::{self, DomainName};
+use super::storage::{self, Changes};
+
+/// Domains and urls which were deleted. They are kept aside until they are purged, so that
+/// deleting them by accident can be undone.
+pub type Trash = Arc<Mutex<Deleted>>;
+
+#[derive(Default)]
+pub struct Deleted {
+  /// Deleted domains keyed by their name.
+  domains: HashMap<String, DeletedDomain>,
+  /// Urls deleted from domains, keyed by the domain and then by the url.
+  urls: HashMap<String, HashMap<String, DeletedUrl>>,
+}
+
+struct DeletedDomain {
+  deleted_at: DateTime<Utc>,
+  domain: Domain,
+}
+
+/// A deleted url with everything the crawler knew about it.
+struct DeletedUrl {
+  deleted_at: DateTime<Utc>,
+  record: Option<UrlRecord>,
+  page: Option<Page>,
+}
+
+#[delete("/<domain>")]
+pub fn delete_domain(
+  cache: State<Database>,
+  trash: State<Trash>,
+  changes: State<Changes>,
+  domain: DomainName,
+) -> Result<Status, ApiError> {
+  // The database is always locked before the trash, so that the two routes moving records
+  // between them can't deadlock.
+  let mut db = cache.lock().map_err(|_| ApiError::lock())?;
+  let mut trash = trash.lock().map_err(|_| ApiError::lock())?;
+
+  // Moves the domain with all its urls and pages into the trash.
+  let record = db.remove(&*domain).ok_or_else(|| routes::domain_not_found(&domain))?;
+  let deleted = DeletedDomain { deleted_at: Utc::now(), domain: record };
+  trash.domains.insert(domain.to_string(), deleted);
+  storage::changed(&changes, &domain);
+
+  Ok(Status::NoContent)
+}
+
+#[post("/<domain>/restore")]
+pub fn restore_domain(
+  cache: State<Database>,
+  trash: State<Trash>,
+  changes: State<Changes>,
+  domain: DomainName,
+) -> Result<Status, ApiError> {
+  let mut db = cache.lock().map_err(|_| ApiError::lock())?;
+  let mut trash = trash.lock().map_err(|_| ApiError::lock())?;
+
+  if !trash.domains.contains_key(&*domain) {
+    let message = format!("The domain {} was not deleted", &*domain);
+
+    return Err(ApiError::new(Status::NotFound, "not_deleted", message));
+  }
+
+  // Crawling the domain after it was deleted created a new record, which would be lost.
+  if db.contains_key(&*domain) {
+    let message = format!("The domain {} was crawled again since it was deleted", &*domain);
+
+    return Err(ApiError::new(Status::Conflict, "domain_exists", message));
+  }
+
+  // Unwrap here is safe as we have just checked for the domain.
+  let deleted = trash.domains.remove(&*domain).unwrap();
+  db.insert(domain.to_string(), deleted.domain);
+  storage::changed(&changes, &domain);
+
+  Ok(Status::NoContent)
+}
+
+#[delete("/<domain>/url?<url>")]
+pub fn delete_url(
+  cache: State<Database>,
+  trash: State<Trash>,
+  changes: State<Changes>,
+  domain: DomainName,
+  url: String,
+) -> Result<Status, ApiError> {
+  let mut db = cache.lock().map_err(|_| ApiError::lock())?;
+  let mut trash = trash.lock().map_err(|_| ApiError::lock())?;
+
+  let record = db.get_mut(&*domain).ok_or_else(|| routes::domain_not_found(&domain))?;
+
+  if !record.urls.remove(&url) {
+    return Err(routes::url_not_found(&url));
+  }
+
+  // The url disappears from the listings, so clients polling them have to see a new version.
+  record.version += 1;
+  let deleted = DeletedUrl {
+    deleted_at: Utc::now(),
+    record: record.records.remove(&url),
+    page: record.pages.remove(&url),
+  };
+  trash.urls.entry(domain.to_string()).or_insert_with(HashMap::new).insert(url, deleted);
+  storage::changed(&changes, &domain);
+
+  Ok(Status::NoContent)
+}
+
+#[post("/<domain>/url/restore?<url>")]
+pub fn restore_url(
+  cache: State<Database>,
+  trash: State<Trash>,
+  changes: State<Changes>,
+  domain: DomainName,
+  url: String,
+) -> Result<Status, ApiError> {
+  let mut db = cache.lock().map_err(|_| ApiError::lock())?;
+  let mut trash = trash.lock().map_err(|_| ApiError::lock())?;
+
+  let is_deleted = trash.urls.get(&*domain).is_some_and(|urls| urls.contains_key(&url));
+
+  if !is_deleted {
+    let message = format!("The url {} was not deleted", url);
+
+    return Err(ApiError::new(Status::NotFound, "not_deleted", message));
+  }
+
+  // Restoring urls of a deleted domain would create an incomplete record of the domain.
+  let record = db.get_mut(&*domain).ok_or_else(|| routes::domain_not_found(&domain))?;
+
+  if record.urls.contains(&url) {
+    let message = format!("The url {} was found again since it was deleted", url);
+
+    return Err(ApiError::new(Status::Conflict, "url_exists", message));
+  }
+
+  // Unwraps here are safe as we have just checked for the url.
+  let urls = trash.urls.get_mut(&*domain).unwrap();
+  let deleted = urls.remove(&url).unwrap();
+
+  if urls.is_empty() {
+    trash.urls.remove(&*domain);
+  }
+
+  if let Some(history) = deleted.record {
+    record.records.insert(url.clone(), history);
+  }
+
+  if let Some(page) = deleted.page {
+    record.pages.insert(url.clone(), page);
+  }
+
+  record.urls.insert(url);
+  record.version += 1;
+  storage::changed(&changes, &domain);
+
+  Ok(Status::NoContent)
+}
+
+#[get("/")]
+pub fn list(trash: State<Trash>) -> Result<Json<TrashListing>, ApiError> {
+  let trash = trash.lock().map_err(|_| ApiError::lock())?;
+
+  let mut domains: Vec<DeletedDomainSummary> = trash.domains.iter()
+    .map(|(name, deleted)| DeletedDomainSummary {
+      domain: name.clone(),
+      deleted_at: deleted.deleted_at.to_rfc3339(),
+      urls: deleted.domain.urls.len(),
+    })
+    .collect();
+  let mut urls: Vec<DeletedUrlSummary> = trash.urls.iter()
+    .flat_map(|(name, urls)| urls.iter().map(move |(url, deleted)| DeletedUrlSummary {
+      domain: name.clone(),
+      url: url.clone(),
+      deleted_at: deleted.deleted_at.to_rfc3339(),
+    }))
+    .collect();
+  domains.sort_by(|a, b| a.domain.cmp(&b.domain));
+  urls.sort_by(|a, b| (&a.domain, &a.url).cmp(&(&b.domain, &b.url)));
+
+  Ok(Json(TrashListing { domains, urls }))
+}
+
+#[delete("/?<domain>")]
+pub fn purge(trash: State<Trash>, domain: Option<String>) -> Result<Json<Purged>, ApiError> {
+  let mut trash = trash.lock().map_err(|_| ApiError::lock())?;
+
+  // Without a domain, the whole trash is emptied.
+  let purged = match domain.map(|domain| fetch::ascii_host(domain.trim())) {
+    Some(domain) => Purged {
+      domains: trash.domains.remove(&domain).map_or(0, |_| 1),
+      urls: trash.urls.remove(&domain).map_or(0, |urls| urls.len()),
+    },
+    None => {
+      let purged = Purged {
+        domains: trash.domains.len(),
+        urls: trash.urls.values().map(|urls| urls.len()).sum(),
+      };
+      trash.domains.clear();
+      trash.urls.clear();
+
+      purged
+    },
+  };
+
+  Ok(Json(purged))
+}
+
+#[derive(Serialize)]
+pub struct TrashListing {
+  /// Deleted domains in alphabetical order.
+  domains: Vec<DeletedDomainSummary>,
+  /// Urls deleted from domains, ordered by the domain and the url.
+  urls: Vec<DeletedUrlSummary>,
+}
+
+#[derive(Serialize)]
+pub struct DeletedDomainSummary {
+  domain: String,
+  /// When the domain was deleted, in RFC 3339 format.
+  deleted_at: String,
+  /// How many unique urls the domain had.
+  urls: usize,
+}
+
+#[derive(Serialize)]
+pub struct DeletedUrlSummary {
+  domain: String,
+  url: String,
+  deleted_at: String,
+}
+
+#[derive(Serialize)]
+pub struct Purged {
+  /// How many deleted domains were removed for good.
+  domains: usize,
+  /// How many deleted urls were removed for good.
+  urls: usize,
+}
@@ -0,0 +1,189 @@
+use url::Url;
+use std::thread;
+use super::fetch::{self, DnsCache};
+use std::sync::Arc;
+use std::time::Duration;
+use super::config::Config;
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream};
+
+/// How long the tunnel waits for the client to ask for a host and for the host to accept the
+/// connection.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A proxy on the loopback interface which requests over https go through. The client asks for
+/// a host with a `CONNECT` request, and the tunnel resolves the host, refuses internal addresses
+/// and connects to the very address it checked. The client would otherwise resolve the host
+/// again when connecting, and a host which changes its address in between could make the
+/// crawler fetch internal services. Plain http requests don't need the tunnel, as they can be
+/// sent to the checked address directly, see `fetch::send`.
+pub struct Tunnel {
+  address: SocketAddr,
+  is_stopped: Arc<AtomicBool>,
+}
+
+impl Tunnel {
+  /// Listens on a random port and serves every connection on a thread of its own until the
+  /// tunnel is dropped. Hosts are resolved through given cache, which the client checked them
+  /// with already.
+  pub fn start(config: &Config, dns: Arc<DnsCache>) -> io::Result<Tunnel> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let is_stopped = Arc::new(AtomicBool::new(false));
+    let config = config.clone();
+
+    let stop = Arc::clone(&is_stopped);
+    thread::spawn(move || {
+      for stream in listener.incoming().filter_map(Result::ok) {
+        if stop.load(Ordering::SeqCst) {
+          break;
+        }
+
+        let config = config.clone();
+        let dns = Arc::clone(&dns);
+
+        thread::spawn(move || {
+          // The client sees the connection drop, so there's nothing to report it to.
+          let _ = serve(&config, &dns, stream);
+        });
+      }
+    });
+
+    Ok(Tunnel { address, is_stopped })
+  }
+
+  /// The url to use the tunnel as a proxy with.
+  pub fn url(&self) -> String {
+    format!("http://{}", self.address)
+  }
+}
+
+impl Drop for Tunnel {
+  /// Stops accepting connections. The listener only notices once it accepts one more, hence the
+  /// tunnel connects to itself. Connections already relayed are left to finish.
+  fn drop(&mut self) {
+    self.is_stopped.store(true, Ordering::SeqCst);
+    let _ = TcpStream::connect(self.address);
+  }
+}
+
+/// Reads the `CONNECT` request of the client and relays the bytes between the client and the
+/// host it asked for until either side closes the connection.
+fn serve(config: &Config, dns: &DnsCache, mut client: TcpStream) -> io::Result<()> {
+  client.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+  let mut reader = BufReader::new(client.try_clone()?);
+
+  let mut request_line = String::new();
+  reader.read_line(&mut request_line)?;
+  // The headers of the request carry nothing the tunnel needs.
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+      break;
+    }
+  }
+
+  let authority = match request_line.split_whitespace().collect::<Vec<_>>().as_slice() {
+    ["CONNECT", authority, _] => authority.to_string(),
+    _ => return client.write_all(b"HTTP/1.1 405 Method Not Allowed\r\n\r\n"),
+  };
+
+  let mut host = match connect(config, dns, &authority) {
+    Some(host) => host,
+    None => return client.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n"),
+  };
+
+  client.set_read_timeout(None)?;
+  client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?;
+
+  let mut upstream = host.try_clone()?;
+  let forward = thread::spawn(move || {
+    // The reader goes first, as it may have buffered the first bytes of the client already.
+    let _ = io::copy(&mut reader, &mut upstream);
+    let _ = upstream.shutdown(Shutdown::Write);
+  });
+
+  let _ = io::copy(&mut host, &mut client);
+  let _ = client.shutdown(Shutdown::Write);
+  let _ = forward.join();
+
+  Ok(())
+}
+
+/// Connects to the host of given `host:port` authority, unless it resolves to an internal
+/// address. Only the checked addresses are connected to.
+fn connect(config: &Config, dns: &DnsCache, authority: &str) -> Option<TcpStream> {
+  let url = Url::parse(&format!("https://{}/", authority)).ok()?;
+  let addresses = dns.lookup(&url);
+
+  if fetch::has_forbidden_address(config, &url, &addresses) {
+    println!("[Crawler] Refusing to fetch internal url {}", url);
+
+    return None;
+  }
+
+  connect_to(&url, &addresses)
+}
+
+/// Connects to the first of given addresses of the host of given url which accepts the
+/// connection, on the port of the url.
+pub fn connect_to(url: &Url, addresses: &[IpAddr]) -> Option<TcpStream> {
+  let port = url.port_or_known_default()?;
+
+  addresses.iter().find_map(|ip| {
+    TcpStream::connect_timeout(&SocketAddr::new(*ip, port), HANDSHAKE_TIMEOUT).ok()
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use std::thread;
+  use std::net::{TcpListener, TcpStream};
+  use std::io::{BufRead, BufReader, Read, Write};
+  use std::sync::Arc;
+  use super::super::config::Config;
+  use super::super::fetch::DnsCache;
+  use super::Tunnel;
+
+  /// Sends a `CONNECT` request for given authority through the tunnel and returns the status
+  /// line of the response together with the connection.
+  fn connect(tunnel: &Tunnel, authority: &str) -> (String, BufReader<TcpStream>) {
+    let mut stream = TcpStream::connect(tunnel.address).unwrap();
+    write!(stream, "CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n", authority, authority).unwrap();
+
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).unwrap();
+    // Skips the empty line after the status.
+    reader.read_line(&mut String::new()).unwrap();
+
+    (status.trim().to_string(), reader)
+  }
+
+  #[test]
+  fn test_tunnel() {
+    let host = TcpListener::bind("127.0.0.1:0").unwrap();
+    let authority = host.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+      let (mut stream, _) = host.accept().unwrap();
+      let mut greeting = [0; 5];
+      stream.read_exact(&mut greeting).unwrap();
+      stream.write_all(&greeting).unwrap();
+    });
+
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    let tunnel = Tunnel::start(&config, Arc::new(DnsCache::default())).unwrap();
+    assert_eq!(connect(&tunnel, &authority).0, "HTTP/1.1 403 Forbidden");
+
+    config.allowed_internal_hosts.push(String::from("127.0.0.1"));
+    let tunnel = Tunnel::start(&config, Arc::new(DnsCache::default())).unwrap();
+    let (status, mut reader) = connect(&tunnel, &authority);
+    assert_eq!(status, "HTTP/1.1 200 Connection Established");
+
+    reader.get_mut().write_all(b"hello").unwrap();
+    let mut echo = String::new();
+    reader.read_to_string(&mut echo).unwrap();
+    assert_eq!(echo, "hello");
+  }
+}
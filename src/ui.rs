@@ -0,0 +1,10 @@
+use rocket::response::content::Html;
+
+/// A single page dashboard for operating the crawler from a browser. It is built into the binary
+/// and talks to the GraphQL and REST apis of the service.
+const DASHBOARD: &str = include_str!("../static/dashboard.html");
+
+#[get("/")]
+pub fn dashboard() -> Html<&'static str> {
+  Html(DASHBOARD)
+}
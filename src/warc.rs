@@ -0,0 +1,166 @@
+use url::Url;
+use std::io::{self, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use reqwest::Response;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
+use encoding_rs::{Encoding, UTF_8};
+use openssl::rand::rand_bytes;
+
+/// Writes the responses fetched by a single crawl into a WARC file, so that the crawl can also
+/// serve as an archive of the pages. Every record is compressed on its own, as is customary for
+/// `.warc.gz` files, so that tools can seek to any record.
+pub struct Warc {
+  path: PathBuf,
+  file: File,
+}
+
+impl Warc {
+  /// Creates the WARC file of given job in given directory, named after the host, the job and
+  /// the time, e.g. `example.com-3-20190720T120000Z.warc.gz`, and writes its warcinfo record.
+  pub fn create(directory: &str, host: &str, job: usize, now: DateTime<Utc>) -> io::Result<Warc> {
+    fs::create_dir_all(directory)?;
+
+    // Hosts can be IPv6 addresses, whose colons are not allowed in names of files everywhere.
+    let host: String = host.chars()
+      .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+      .collect();
+    let name = format!("{}-{}-{}.warc.gz", host, job, now.format("%Y%m%dT%H%M%SZ"));
+    let path = Path::new(directory).join(name);
+    let mut warc = Warc { file: File::create(&path)?, path };
+
+    let info = format!(
+      "software: link-crawler/{}\r\nformat: WARC File Format 1.1\r\n", env!("CARGO_PKG_VERSION"),
+    );
+    warc.write_record("warcinfo", None, "application/warc-fields", info.as_bytes(), now)?;
+
+    Ok(warc)
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Writes the response given url was loaded from with given body as a response record.
+  /// Bodies are stored the way they were decompressed by the client, therefore the headers of
+  /// the record describe the decompressed body rather than how it was transferred.
+  pub fn write_response(&mut self, url: &Url, response: &Response, body: &[u8]) -> io::Result<()> {
+    let mut block = format!("{:?} {}\r\n", response.version(), response.status()).into_bytes();
+
+    for (name, value) in response.headers() {
+      if name == CONTENT_ENCODING || name == CONTENT_LENGTH || name == TRANSFER_ENCODING {
+        continue;
+      }
+
+      block.extend_from_slice(name.as_str().as_bytes());
+      block.extend_from_slice(b": ");
+      block.extend_from_slice(value.as_bytes());
+      block.extend_from_slice(b"\r\n");
+    }
+
+    block.extend_from_slice(format!("content-length: {}\r\n\r\n", body.len()).as_bytes());
+    block.extend_from_slice(body);
+
+    let content_type = "application/http;msgtype=response";
+    self.write_record("response", Some(url), content_type, &block, Utc::now())
+  }
+
+  fn write_record(
+    &mut self,
+    kind: &str,
+    url: Option<&Url>,
+    content_type: &str,
+    block: &[u8],
+    now: DateTime<Utc>,
+  ) -> io::Result<()> {
+    let mut header = format!(
+      "WARC/1.1\r\nWARC-Type: {}\r\nWARC-Record-ID: <urn:uuid:{}>\r\nWARC-Date: {}\r\n",
+      kind, uuid()?, now.format("%Y-%m-%dT%H:%M:%SZ"),
+    );
+
+    if let Some(url) = url {
+      header.push_str(&format!("WARC-Target-URI: {}\r\n", url));
+    }
+
+    header.push_str(&format!(
+      "Content-Type: {}\r\nContent-Length: {}\r\n\r\n", content_type, block.len(),
+    ));
+
+    let mut encoder = GzEncoder::new(&mut self.file, Compression::default());
+    encoder.write_all(header.as_bytes())?;
+    encoder.write_all(block)?;
+    encoder.write_all(b"\r\n\r\n")?;
+    encoder.finish()?;
+
+    Ok(())
+  }
+}
+
+/// Decodes a body the same way the client does when reading it as text, with the charset the
+/// response declares in its Content-Type header or UTF-8.
+pub fn decode(response: &Response, body: &[u8]) -> String {
+  let charset = response.headers().get("content-type")
+    .and_then(|content_type| content_type.to_str().ok())
+    .and_then(|content_type| content_type.split(';').skip(1).find_map(|parameter| {
+      let mut parts = parameter.splitn(2, '=');
+      let name = parts.next()?.trim();
+      let value = parts.next()?.trim().trim_matches('"');
+
+      if name.eq_ignore_ascii_case("charset") { Some(value.to_string()) } else { None }
+    }));
+  let encoding = charset
+    .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+    .unwrap_or(UTF_8);
+
+  encoding.decode(body).0.into_owned()
+}
+
+/// A random UUID, identifying a record.
+fn uuid() -> io::Result<String> {
+  let mut bytes = [0; 16];
+  rand_bytes(&mut bytes).map_err(io::Error::other)?;
+  // Marks the UUID as a random one of the standard variant.
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  let hex = super::export::hex(&bytes);
+
+  Ok(format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+}
+
+#[cfg(test)]
+mod test {
+  use super::{uuid, Warc};
+  use chrono::{TimeZone, Utc};
+  use flate2::read::MultiGzDecoder;
+  use std::env;
+  use std::fs::{self, File};
+  use std::io::Read;
+
+  #[test]
+  fn test_create() {
+    let directory = env::temp_dir().join("link-crawler-test-warc");
+    let now = Utc.with_ymd_and_hms(2019, 7, 20, 12, 0, 0).unwrap();
+    let warc = Warc::create(directory.to_str().unwrap(), "[::1]", 3, now).unwrap();
+
+    assert_eq!(warc.path(), directory.join("___1_-3-20190720T120000Z.warc.gz"));
+
+    let mut content = String::new();
+    MultiGzDecoder::new(File::open(warc.path()).unwrap()).read_to_string(&mut content).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(content.starts_with("WARC/1.1\r\nWARC-Type: warcinfo\r\n"));
+    assert!(content.contains("WARC-Date: 2019-07-20T12:00:00Z\r\n"));
+    assert!(content.ends_with("format: WARC File Format 1.1\r\n\r\n\r\n"));
+  }
+
+  #[test]
+  fn test_uuid() {
+    let uuid = uuid().unwrap();
+
+    assert_eq!(uuid.len(), 36);
+    assert_eq!(&uuid[14..15], "4");
+  }
+}
@@ -0,0 +1,218 @@
+use url::Url;
+use rocket::State;
+use std::error::Error;
+use super::tunnel::Tunnel;
+use super::fetch::{self, DnsCache};
+use serde::{Deserialize, Serialize};
+use super::errors::ApiError;
+use rocket::http::Status;
+use std::sync::{Arc, Mutex};
+use super::config::Config;
+use std::collections::HashMap;
+use rocket_contrib::json::Json;
+use super::routes::DomainName;
+use reqwest::{Client, Method, Proxy, RedirectPolicy};
+
+/// Urls notified about the new urls found on domains, keyed by the domain. A domain can have a
+/// webhook before it is crawled for the first time.
+pub type Webhooks = Arc<Mutex<HashMap<String, String>>>;
+
+/// A batch of urls a crawl has just found on a domain.
+#[derive(Serialize)]
+struct Notification<'a> {
+  domain: &'a str,
+  /// The id of the job which found the urls.
+  job: usize,
+  urls: &'a [String],
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Webhook {
+  /// The url new urls are posted to.
+  url: String,
+}
+
+#[get("/<domain>/webhook")]
+pub fn webhook(webhooks: State<Webhooks>, domain: DomainName) -> Result<Json<Webhook>, ApiError> {
+  let webhooks = webhooks.lock().map_err(|_| ApiError::lock())?;
+
+  match webhooks.get(&*domain) {
+    Some(url) => Ok(Json(Webhook { url: url.clone() })),
+    None => Err(not_registered(&domain)),
+  }
+}
+
+#[put("/<domain>/webhook", format = "application/json", data = "<req>")]
+pub fn register(
+  webhooks: State<Webhooks>,
+  config: State<Config>,
+  domain: DomainName,
+  req: Json<Webhook>,
+) -> Result<Json<Webhook>, ApiError> {
+  let url = Url::parse(req.url.trim())
+    .ok()
+    .filter(|url| (url.scheme() == "http" || url.scheme() == "https") && url.has_host())
+    .ok_or_else(|| {
+      ApiError::new(Status::BadRequest, "invalid_url", "The webhook must be an http or https url")
+    })?;
+
+  // Webhooks are requested by the service itself, therefore must not point to its infrastructure.
+  if fetch::is_forbidden(&config, &url) {
+    return Err(ApiError::new(
+      Status::Forbidden, "internal_address", "The host resolves to an internal address",
+    ));
+  }
+
+  let mut webhooks = webhooks.lock().map_err(|_| ApiError::lock())?;
+  webhooks.insert(domain.to_string(), url.to_string());
+
+  Ok(Json(Webhook { url: url.to_string() }))
+}
+
+#[delete("/<domain>/webhook")]
+pub fn unregister(webhooks: State<Webhooks>, domain: DomainName) -> Result<Status, ApiError> {
+  let mut webhooks = webhooks.lock().map_err(|_| ApiError::lock())?;
+
+  match webhooks.remove(&*domain) {
+    Some(_) => Ok(Status::NoContent),
+    None => Err(not_registered(&domain)),
+  }
+}
+
+fn not_registered(domain: &str) -> ApiError {
+  let message = format!("The domain {} has no webhook", domain);
+
+  ApiError::new(Status::NotFound, "webhook_not_found", message)
+}
+
+/// Posts the notifications to the webhooks. A webhook is checked for pointing to internal
+/// addresses whenever it is notified, as its host may resolve elsewhere by then than when it was
+/// registered, and the notification is only sent to the checked addresses the same way the
+/// fetcher sends requests. Redirects of webhooks are not followed.
+pub struct Notifier {
+  webhooks: Webhooks,
+  client: Client,
+  config: Config,
+  dns: Arc<DnsCache>,
+  /// Kept alive for as long as the client uses it.
+  _tunnel: Tunnel,
+}
+
+impl Notifier {
+  /// Fails if the TLS backend can't be initialized or the tunnel can't listen.
+  pub fn new(config: &Config, webhooks: Webhooks) -> Result<Notifier, Box<dyn Error>> {
+    let dns = Arc::new(DnsCache::default());
+    let tunnel = Tunnel::start(config, Arc::clone(&dns))?;
+    let client = Client::builder()
+      .redirect(RedirectPolicy::none())
+      .proxy(Proxy::https(&tunnel.url())?)
+      .build()?;
+
+    Ok(Notifier { webhooks, client, config: config.clone(), dns, _tunnel: tunnel })
+  }
+
+  /// Posts the urls which given job has just found on given host to the webhook of the host, if
+  /// it has one.
+  pub fn notify(&self, host: &str, job: usize, urls: &[String]) {
+    // TODO: Error handling the mutex.
+    let url = match self.webhooks.lock().unwrap().get(host).map(|url| Url::parse(url)) {
+      Some(Ok(url)) => url,
+      _ => return,
+    };
+
+    let addresses = self.dns.lookup(&url);
+    if fetch::has_forbidden_address(&self.config, &url, &addresses) {
+      println!("[Crawler] Refusing to notify webhook of {} at internal url {}", host, url);
+
+      return;
+    }
+
+    let notification = Notification { domain: host, job, urls };
+    let request = match fetch::pinned_request(&self.client, Method::POST, &url, &addresses) {
+      Some(request) => request,
+      None => {
+        println!("[Crawler] Webhook of {} could not be resolved", host);
+
+        return;
+      },
+    };
+
+    match request.json(&notification).send() {
+      Ok(response) if response.status().is_success() => (),
+      Ok(response) => {
+        println!("[Crawler] Webhook of {} responded with {}", host, response.status());
+      },
+      Err(e) => println!("[Crawler] Error during notifying webhook of {}: {}", host, e),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::thread;
+  use std::net::TcpListener;
+  use std::io::{BufRead, BufReader, ErrorKind, Write};
+  use super::super::config::Config;
+  use super::{Notifier, Webhooks};
+
+  /// Registers a webhook of given url for github.com the way the API would once the host of the
+  /// webhook was checked.
+  fn webhooks(url: String) -> Webhooks {
+    let webhooks = Webhooks::default();
+    webhooks.lock().unwrap().insert(String::from("github.com"), url);
+
+    webhooks
+  }
+
+  /// Checks whether anything connected to given listener.
+  fn was_contacted(listener: &TcpListener) -> bool {
+    listener.set_nonblocking(true).unwrap();
+
+    match listener.accept() {
+      Ok(_) => true,
+      Err(e) if e.kind() == ErrorKind::WouldBlock => false,
+      Err(e) => panic!("{}", e),
+    }
+  }
+
+  #[test]
+  fn test_notify_internal_webhook() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hooks/urls", listener.local_addr().unwrap());
+
+    // The host of the webhook resolves to an internal address by the time it is notified.
+    let config = Config::from_rocket(&rocket::Config::development());
+    let notifier = Notifier::new(&config, webhooks(url)).unwrap();
+    notifier.notify("github.com", 0, &[String::from("https://github.com/a")]);
+
+    assert!(!was_contacted(&listener));
+  }
+
+  #[test]
+  fn test_notify_does_not_follow_redirects() {
+    let internal = TcpListener::bind("127.0.0.1:0").unwrap();
+    let location = format!("http://{}/", internal.local_addr().unwrap());
+
+    let webhook = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hooks/urls", webhook.local_addr().unwrap());
+    let server = thread::spawn(move || {
+      let (stream, _) = webhook.accept().unwrap();
+      let mut reader = BufReader::new(stream);
+      let mut request_line = String::new();
+      reader.read_line(&mut request_line).unwrap();
+      write!(reader.get_mut(), "HTTP/1.1 307 Temporary Redirect\r\nLocation: {}\r\n", location)
+        .unwrap();
+      reader.get_mut().write_all(b"Connection: close\r\n\r\n").unwrap();
+
+      request_line
+    });
+
+    let mut config = Config::from_rocket(&rocket::Config::development());
+    config.allowed_internal_hosts.push(String::from("127.0.0.1"));
+    let notifier = Notifier::new(&config, webhooks(url)).unwrap();
+    notifier.notify("github.com", 0, &[String::from("https://github.com/a")]);
+
+    assert!(server.join().unwrap().starts_with("POST /hooks/urls "));
+    assert!(!was_contacted(&internal));
+  }
+}
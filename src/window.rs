@@ -0,0 +1,183 @@
+use super::fetch;
+use std::cmp::Ordering;
+use chrono::{DateTime, Duration, FixedOffset, TimeZone, Timelike, Utc};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Hours of the day a domain may be crawled at, e.g. only at night when the site has little
+/// traffic. Times are in the local time of the site, given by its offset from UTC.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrawlWindow {
+  /// The window applies to this domain and its subdomains.
+  pub domain: String,
+  /// Minutes since the local midnight the window opens at.
+  pub from: u32,
+  /// Minutes since the local midnight the window closes at. Windows closing before they open
+  /// span midnight, windows closing when they open span the whole day.
+  pub to: u32,
+  pub offset: FixedOffset,
+}
+
+impl CrawlWindow {
+  /// Reads the windows from the `crawl_windows` array of the Rocket configuration. Like the
+  /// schedules, invalid windows are reported rather than skipped.
+  pub fn from_rocket(config: &rocket::Config) -> Result<Vec<CrawlWindow>, String> {
+    let entries = match config.get_slice("crawl_windows") {
+      Ok(entries) => entries,
+      Err(_) => return Ok(vec!()),
+    };
+
+    entries.iter()
+      .enumerate()
+      .map(|(index, entry)| {
+        let invalid = |message: &str| {
+          format!("The crawl window {} is invalid: {}", index + 1, message)
+        };
+        let get = |name: &str| entry.get(name).and_then(|value| value.as_str());
+
+        let domain = get("domain").ok_or_else(|| invalid("domain must be a string"))?;
+        let time = |name: &str| get(name)
+          .and_then(minutes)
+          .ok_or_else(|| invalid(&format!("{} must be a HH:MM time", name)));
+        let (from, to) = (time("from")?, time("to")?);
+        let offset = match get("utc_offset") {
+          Some(offset) => parse_offset(offset)
+            .ok_or_else(|| invalid("utc_offset must be a +HH:MM offset"))?,
+          None => FixedOffset::east_opt(0).unwrap(),
+        };
+
+        Ok(CrawlWindow {
+          domain: fetch::ascii_host(domain.trim().trim_start_matches('.')),
+          from: from % MINUTES_PER_DAY,
+          to: to % MINUTES_PER_DAY,
+          offset,
+        })
+      })
+      .collect()
+  }
+
+  fn applies_to(&self, host: &str) -> bool {
+    let host = host.to_lowercase();
+
+    host == self.domain || host.ends_with(&format!(".{}", self.domain))
+  }
+
+  fn is_open(&self, now: DateTime<Utc>) -> bool {
+    let local = now.with_timezone(&self.offset);
+    let minute = local.hour() * 60 + local.minute();
+
+    match self.from.cmp(&self.to) {
+      Ordering::Less => self.from <= minute && minute < self.to,
+      Ordering::Greater => minute >= self.from || minute < self.to,
+      Ordering::Equal => true,
+    }
+  }
+
+  /// The first time after given time the window opens at.
+  fn next_opening(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let local = now.with_timezone(&self.offset);
+    let opening = local.date_naive().and_hms_opt(self.from / 60, self.from % 60, 0)?;
+    let opening = self.offset.from_local_datetime(&opening).single()?.with_timezone(&Utc);
+
+    Some(if opening > now { opening } else { opening + Duration::days(1) })
+  }
+}
+
+/// Finds when given host may be crawled if none of its windows is open at given time. None if
+/// the host may be crawled now, including hosts without any window.
+pub fn opens_at(windows: &[CrawlWindow], host: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+  let windows: Vec<&CrawlWindow> = windows.iter()
+    .filter(|window| window.applies_to(host))
+    .collect();
+
+  if windows.is_empty() || windows.iter().any(|window| window.is_open(now)) {
+    return None;
+  }
+
+  windows.iter().filter_map(|window| window.next_opening(now)).min()
+}
+
+/// Reads a `HH:MM` time into minutes since midnight. `24:00` stands for the end of the day.
+fn minutes(time: &str) -> Option<u32> {
+  let (hours, minutes) = time.trim().split_once(':')?;
+  let (hours, minutes) = (hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?);
+
+  if minutes >= 60 || hours * 60 + minutes > MINUTES_PER_DAY {
+    return None;
+  }
+
+  Some(hours * 60 + minutes)
+}
+
+/// Reads an offset from UTC such as `+02:00` or `-05:30`.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+  let offset = offset.trim();
+  let sign = match offset.chars().next()? {
+    '+' => 1,
+    '-' => -1,
+    _ => return None,
+  };
+  let minutes = minutes(&offset[1..]).filter(|minutes| *minutes < MINUTES_PER_DAY)?;
+
+  FixedOffset::east_opt(sign * minutes as i32 * 60)
+}
+
+#[cfg(test)]
+mod test {
+  use super::{opens_at, CrawlWindow};
+  use chrono::{FixedOffset, TimeZone, Utc};
+  use rocket::config::{Config, Environment, Value};
+
+  fn windows(entries: &str) -> Result<Vec<CrawlWindow>, String> {
+    let value: Value = entries.parse().unwrap();
+    let config = Config::build(Environment::Development)
+      .extra("crawl_windows", value.get("crawl_windows").unwrap().clone())
+      .unwrap();
+
+    CrawlWindow::from_rocket(&config)
+  }
+
+  #[test]
+  fn test_from_rocket() {
+    let parsed = windows(r#"crawl_windows = [
+      { domain = "GitHub.com", from = "01:00", to = "05:30", utc_offset = "+02:00" },
+      { domain = "gitlab.com", from = "22:00", to = "24:00" },
+    ]"#).unwrap();
+    let utc = |hours| FixedOffset::east_opt(hours * 3600).unwrap();
+
+    assert_eq!(parsed, vec!(
+      CrawlWindow { domain: "github.com".into(), from: 60, to: 330, offset: utc(2) },
+      // Windows ending at midnight end at the start of the next day.
+      CrawlWindow { domain: "gitlab.com".into(), from: 1320, to: 0, offset: utc(0) },
+    ));
+    assert!(windows(r#"crawl_windows = [{ domain = "github.com", from = "1am", to = "05:00" }]"#)
+      .is_err());
+    assert!(windows(r#"crawl_windows = [{ domain = "a.com", from = "01:00", to = "05:60" }]"#)
+      .is_err());
+    assert!(windows(r#"crawl_windows = [
+      { domain = "github.com", from = "01:00", to = "05:00", utc_offset = "02:00" },
+    ]"#).is_err());
+  }
+
+  #[test]
+  fn test_opens_at() {
+    let windows = windows(r#"crawl_windows = [
+      { domain = "github.com", from = "01:00", to = "05:00", utc_offset = "+02:00" },
+      { domain = "gitlab.com", from = "22:00", to = "02:00" },
+    ]"#).unwrap();
+    let at = |hour, minute| Utc.with_ymd_and_hms(2019, 7, 20, hour, minute, 0).unwrap();
+
+    // 01:00 to 05:00 at +02:00 is 23:00 to 03:00 in UTC.
+    assert_eq!(opens_at(&windows, "github.com", at(12, 0)), Some(at(23, 0)));
+    assert_eq!(opens_at(&windows, "docs.github.com", at(23, 30)), None);
+    assert_eq!(opens_at(&windows, "github.com", at(2, 59)), None);
+    assert_eq!(opens_at(&windows, "github.com", at(3, 0)), Some(at(23, 0)));
+    // Windows spanning midnight open again the next day once they closed.
+    assert_eq!(opens_at(&windows, "gitlab.com", at(1, 0)), None);
+    assert_eq!(opens_at(&windows, "gitlab.com", at(22, 0)), None);
+    assert_eq!(opens_at(&windows, "gitlab.com", at(2, 0)), Some(at(22, 0)));
+    // Domains without a window may be crawled any time.
+    assert_eq!(opens_at(&windows, "bitbucket.org", at(12, 0)), None);
+    assert_eq!(opens_at(&windows, "notgithub.com", at(12, 0)), None);
+  }
+}